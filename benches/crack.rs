@@ -0,0 +1,117 @@
+//! Micro-benchmarks for each step of cracking.
+//!
+//! Run with `cargo bench`, or `cargo bench -- <filter>` to only run benchmarks whose name contains
+//! the filter. Inputs come from [`bench_inputs`], so every run times the same work.
+
+use one_team_pad_cipher_cracker::crack::bench::bench_inputs;
+use one_team_pad_cipher_cracker::crack::{
+    crack_with_scratch, guesses, spellcheck_with_scratch, CrackScratch, SpellChecker,
+};
+use one_team_pad_cipher_cracker::dict::{levenshtein, BytesDictionary, Dictionary};
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// How long to run a benchmark before measuring it
+const WARM_UP: Duration = Duration::from_millis(500);
+/// How long to spend measuring a benchmark, split into `SAMPLES` samples
+const MEASURE: Duration = Duration::from_secs(2);
+const SAMPLES: usize = 20;
+
+/// Time `f`, printing the fastest, median and slowest time per call over all samples.
+fn bench<T>(filter: Option<&str>, name: &str, mut f: impl FnMut() -> T) {
+    if filter.is_some_and(|filter| !name.contains(filter)) {
+        return;
+    }
+
+    // warm up, and estimate how many calls fit in one sample
+    let start = Instant::now();
+    let mut calls = 0u32;
+    while start.elapsed() < WARM_UP {
+        black_box(f());
+        calls += 1;
+    }
+    let per_call = start.elapsed() / calls;
+    let calls_per_sample = ((MEASURE / SAMPLES as u32).as_nanos() / per_call.as_nanos().max(1))
+        .clamp(1, u32::MAX as u128) as u32;
+
+    let mut samples: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..calls_per_sample {
+                black_box(f());
+            }
+            start.elapsed() / calls_per_sample
+        })
+        .collect();
+    samples.sort();
+
+    println!(
+        "{:<24} [{:>12.3?} {:>12.3?} {:>12.3?}]",
+        name,
+        samples[0],
+        samples[SAMPLES / 2],
+        samples[SAMPLES - 1]
+    );
+}
+
+fn main() {
+    // cargo passes --bench, anything else is a filter
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let filter = filter.as_deref();
+
+    let inputs = bench_inputs(0xbe9c, 200);
+    let mut scratch = CrackScratch::default();
+    let mut keylen_guesses = Vec::new();
+
+    bench(filter, "keylength_guesses", || {
+        guesses(black_box(&inputs.cipherbytes), &mut keylen_guesses)
+    });
+
+    bench(filter, "crack_keylength", || {
+        crack_with_scratch(
+            black_box(&inputs.cipherbytes),
+            inputs.key_length,
+            &inputs.baseline_freqs,
+            &mut scratch,
+        )
+    });
+
+    let word = &inputs.cracked.plaintext[..12];
+    bench(filter, "levenshtein", || {
+        levenshtein(black_box(word), black_box(&inputs.cipherbytes[..12]))
+    });
+
+    bench(filter, "best_levenshtein", || {
+        inputs.bytes_dict.best_levenshtein(black_box(word))
+    });
+
+    let mut words = include_str!("../words/words_alpha_40k.txt").to_string();
+    let large_dict = BytesDictionary::from_dict(&Dictionary::from_string(&mut words));
+    bench(filter, "best_levenshtein_40k", || {
+        large_dict.best_levenshtein(black_box(word))
+    });
+
+    bench(filter, "spellcheck", || {
+        spellcheck_with_scratch(black_box(&inputs.cracked), &inputs.bytes_dict, &mut scratch)
+    });
+
+    // a crack at the wrong keylength has hardly any dictionary words to keep, so nearly all of it
+    // is searched
+    let wrong = crack_with_scratch(
+        &inputs.cipherbytes,
+        inputs.key_length + 1,
+        &inputs.baseline_freqs,
+        &mut scratch,
+    );
+    bench(filter, "spellcheck_wrong", || {
+        spellcheck_with_scratch(black_box(&wrong), &inputs.bytes_dict, &mut scratch)
+    });
+
+    // remembering lookups from previous calls, like spellchecking the same crack with a few
+    // columns moved
+    let mut checker = SpellChecker::new(&inputs.bytes_dict);
+    bench(filter, "spellcheck_reused", || {
+        checker.spellcheck(black_box(&inputs.cracked))
+    });
+}
@@ -1,6 +1,6 @@
 use crate::ciphers::{Cipher, KeySchedule};
 use crate::rng::{FromRng, Rng};
-use crate::utils::{reduce_key, Key, NumToChar, Shift};
+use crate::utils::{Key, NumToChar, Shift, ALPHABET};
 
 use std::cell::Cell;
 use std::fmt::Debug;
@@ -38,8 +38,9 @@ pub struct Encryptor<K: KeySchedule + Debug> {
 impl<K: KeySchedule + Debug> Encryptor<K> {
     /// Create a new Encryptor configured with the given key, [`KeySchedule`], and [`Rng`].
     #[allow(dead_code)]
-    pub fn new(mut key: Key, keyschedule: K, rng: Rng) -> Self {
-        reduce_key(&mut key);
+    pub fn new(key: impl Into<Key>, keyschedule: K, rng: Rng) -> Self {
+        let mut key = key.into();
+        key.reduce();
         Self {
             key,
             keyschedule,
@@ -49,10 +50,156 @@ impl<K: KeySchedule + Debug> Encryptor<K> {
     }
 }
 
+impl<K: KeySchedule + Debug> Encryptor<K> {
+    /// The key, reduced.
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    /// Decrypt `ciphertext` of a plaintext `plaintext_length` characters long, like a recipient
+    /// told the length would. Unlike [`Cipher::decrypt`], nothing has to be encrypted first.
+    pub fn decrypt_len(&self, ciphertext: &str, plaintext_length: usize) -> String {
+        let mut plaintext = String::with_capacity(plaintext_length);
+        self.decrypt_len_into(ciphertext, plaintext_length, &mut plaintext);
+        plaintext
+    }
+
+    /// The same as [`decrypt_len`][`Self::decrypt_len`], appending to `plaintext`.
+    pub fn decrypt_len_into(
+        &self,
+        ciphertext: &str,
+        plaintext_length: usize,
+        plaintext: &mut String,
+    ) {
+        // shift every ciphertext character the key schedule picked a key symbol for back by it,
+        // and discard the random characters
+        for (cipher, next_key) in ciphertext.chars().zip(self.keystream(plaintext_length)) {
+            if let NextKey::KeyIndex(index) = next_key {
+                plaintext.push(cipher.shift(-self.shift(index)));
+            }
+        }
+    }
+
+    /// What the key schedule picks for each ciphertext character of a plaintext
+    /// `plaintext_length` characters long, in order, ending with the key symbol of the last
+    /// plaintext character.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::ciphers::schedulers::{NextKey, PeriodicRand};
+    /// # use one_team_pad_cipher_cracker::ciphers::Encryptor;
+    /// # use one_team_pad_cipher_cracker::rng::Rng;
+    /// let sched = PeriodicRand { period: 3, start: 1, overwrite: false };
+    /// let encryptor = Encryptor::new(vec![5, 7], sched, Rng::default());
+    /// let keystream: Vec<NextKey> = encryptor.keystream(3).collect();
+    /// assert_eq!(
+    ///     keystream,
+    ///     [NextKey::KeyIndex(0), NextKey::Rand, NextKey::KeyIndex(1), NextKey::KeyIndex(0)]
+    /// );
+    /// ```
+    pub fn keystream(&self, plaintext_length: usize) -> impl Iterator<Item = NextKey> + '_ {
+        let keylen = self.key.len();
+        let mut remaining = plaintext_length;
+        (0..)
+            .map(move |index| self.keyschedule.schedule(index, keylen, plaintext_length))
+            .take_while(move |next_key| {
+                // stop once every plaintext character has its key symbol
+                if remaining == 0 {
+                    return false;
+                }
+                if let NextKey::KeyIndex(_) = next_key {
+                    remaining -= 1;
+                }
+                true
+            })
+    }
+
+    /// Encrypt `chars` lazily, one ciphertext character at a time. The characters are the same
+    /// as [`Cipher::encrypt`] gives for the same plaintext, random characters included, but
+    /// nothing is stashed for decrypting, so this can go on without a decrypt in between.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::ciphers::schedulers::RepeatingKey;
+    /// # use one_team_pad_cipher_cracker::ciphers::{Cipher, Encryptor};
+    /// # use one_team_pad_cipher_cracker::rng::Rng;
+    /// let encryptor = Encryptor::new(vec![1, 2], RepeatingKey, Rng::default());
+    /// let chars: Vec<char> = "abc".chars().collect();
+    /// let ciphertext: String = encryptor.encrypt_iter(chars).collect();
+    /// assert_eq!(ciphertext, "bdd");
+    /// assert_eq!(ciphertext, encryptor.encrypt("abc"));
+    /// ```
+    pub fn encrypt_iter<'a, I>(&'a self, chars: I) -> impl Iterator<Item = char> + 'a
+    where
+        I: IntoIterator<Item = char>,
+        I::IntoIter: ExactSizeIterator + 'a,
+    {
+        let chars = chars.into_iter();
+        let ptlen = chars.len();
+        self.encrypt_chars(chars, ptlen)
+    }
+
+    /// Encrypt `chars`, a plaintext `ptlen` characters long, lazily.
+    fn encrypt_chars<'a>(
+        &'a self,
+        mut chars: impl Iterator<Item = char> + 'a,
+        ptlen: usize,
+    ) -> impl Iterator<Item = char> + 'a {
+        // clone out the rng from self (otherwise decrypting will not start from the same rng
+        // state!)
+        let mut rng = self.rng.clone();
+
+        self.keystream(ptlen)
+            .map_while(move |next_key| match next_key {
+                // apply the shift amount to the next plaintext char
+                NextKey::KeyIndex(index) => Some(chars.next()?.shift(self.shift(index))),
+                // get a random character from the alphabet
+                NextKey::Rand => Some((rng.gen_range(0..ALPHABET.len()) as u8).to_char()),
+            })
+    }
+
+    /// The shift of the key symbol at `index`. Panics if the key schedule picked an index out of
+    /// bounds of the key.
+    fn shift(&self, index: usize) -> i8 {
+        *self.key.get(index).unwrap_or_else(|| {
+            tracing::error!(
+                keyschedule = ?self.keyschedule,
+                key = ?self.key,
+                index,
+                "key schedule picked a key index out of bounds"
+            );
+            panic!(
+                "{:?} picked key index {} of a {} symbol key",
+                self.keyschedule,
+                index,
+                self.key.len()
+            );
+        })
+    }
+
+    /// Whether this encryptor could have encrypted `plaintext` into `ciphertext`, both as bytes:
+    /// every character the key schedule shifts is the plaintext character under its key symbol,
+    /// and the plaintext runs out with the ciphertext. Random characters can be anything.
+    ///
+    /// This checks a hypothesis of a key and scheduler against a crack, without the random
+    /// characters the encryptor's rng would have picked having to line up. See
+    /// [`verify`][`crate::crack::verify`] for how close a crack that doesn't fit comes. Needs the
+    /// `crack` feature.
+    #[cfg(feature = "crack")]
+    pub fn fits(&self, ciphertext: &[u8], plaintext: &[u8]) -> bool {
+        crate::crack::verify(ciphertext, &self.key, &self.keyschedule, plaintext).is_exact()
+    }
+}
+
+impl Encryptor<Box<dyn KeySchedule>> {
+    /// Create a new Encryptor like [`new`][`Self::new`], with the type of `keyschedule` erased.
+    /// Encryptors of different schedulers then have the same type, so the scheduler can be picked
+    /// at runtime.
+    pub fn boxed(key: impl Into<Key>, keyschedule: impl KeySchedule + 'static, rng: Rng) -> Self {
+        Self::new(key, Box::new(keyschedule), rng)
+    }
+}
+
 impl<K: KeySchedule + Debug> Cipher for Encryptor<K> {
     fn encrypt_into(&self, plaintext: &str, ciphertext: &mut String) {
-        // get keylen and plaintext len
-        let keylen = self.key.len();
         let ptlen = plaintext.len();
 
         // stash the plaintext length in our "side channel" and also assert that we don't encrypt
@@ -62,72 +209,17 @@ impl<K: KeySchedule + Debug> Cipher for Encryptor<K> {
             "must decrypt after encrypt"
         );
 
-        // clone out the rng from self (otherwise decrypting will not start from the same rng
-        // state!)
-        let mut rng = self.rng.clone();
-
-        // create an iterator over the plaintext
-        let mut plaintext = plaintext.chars().peekable();
-
-        // continue encryption as long as there is a plaintext character left to read
-        'encryption: while plaintext.peek().is_some() {
-            // get key index to use as shift.
-            let next_key = self.keyschedule.schedule(ciphertext.len(), keylen, ptlen);
-
-            // get the shift amount from the key, or insert a random character. A random character
-            // is only inserted when the index is out of bounds of the key.
-            let shift = match next_key {
-                NextKey::KeyIndex(index) => *self.key.get(index).unwrap_or_else(|| {
-                    dbg!(&self.keyschedule);
-                    dbg!(&self.key);
-                    panic!();
-                }),
-                NextKey::Rand => {
-                    // get a random number and wrap it to the correct range
-                    let rand = rng.next() as u8;
-                    // push the character to the ciphertext
-                    ciphertext.push(rand.to_char());
-                    continue 'encryption;
-                }
-            };
-
-            // apply the shift amount to the next plaintext char. unwrap will always succeed
-            // because we "peeked" the iterator at the beginning of the loop already.
-            let cipher_char = plaintext.next().unwrap().shift(shift);
-
-            // push the enciphered character to the cipher string
-            ciphertext.push(cipher_char);
-        }
+        ciphertext.extend(self.encrypt_chars(plaintext.chars(), ptlen));
     }
 
     fn decrypt_into(&self, ciphertext: &str, plaintext: &mut String) {
-        // get keylen
-        let keylen = self.key.len();
-
         // get plaintext length over our "side channel", replacing with None
         let ptlen = self
             .prev_plaintext_length
             .replace(None)
             .expect("encrypt must be called before decrypt");
 
-        // read every byte of ciphertext
-        'decryption: for (index, cipher) in ciphertext.chars().enumerate() {
-            // get key index to use as shift.
-            let next_key = self.keyschedule.schedule(index, keylen, ptlen);
-
-            // get the shift amount from the key, or discard the character if the character was
-            // generated randomly.
-            let shift = match next_key {
-                NextKey::KeyIndex(index) => self.key[index],
-                NextKey::Rand => continue 'decryption,
-            };
-
-            // apply the shift amount in reverse because we are decrypting not encrypting.
-            let plain_char = cipher.shift(-shift);
-
-            // push the decrypted character into plaintext string
-            plaintext.push(plain_char);
-        }
+        self.decrypt_len_into(ciphertext, ptlen, plaintext);
     }
 }
 
@@ -154,14 +246,125 @@ impl<K: KeySchedule + Debug + FromRng> FromRng for Encryptor<K> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ciphers::testing::stresstest;
+    use crate::testing::{plaintext_words, round_trip};
+    use proptest::{prop_assert_ne, proptest};
+
+    proptest! {
+        #[test]
+        fn repeating_key_round_trips(plaintext in plaintext_words(10..150)) {
+            let key = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
+            let sched = crate::ciphers::schedulers::RepeatingKey;
+
+            let encryptor = Encryptor::new(key, sched, Rng::default());
+            let ciphertext = round_trip(&encryptor, &plaintext)?;
+            prop_assert_ne!(ciphertext, plaintext);
+        }
+    }
 
     #[test]
-    fn repeating_key_stress() {
+    fn known_key_and_scheduler() {
+        use crate::ciphers::schedulers::PeriodicRand;
+
+        let sched = PeriodicRand {
+            period: 5,
+            start: 3,
+            overwrite: false,
+        };
+        let plaintext = "the quick brown fox jumps over the lazy dog";
+        let ciphertext = Encryptor::new(vec![3, 1, 4], sched, Rng::from_seed(7)).encrypt(plaintext);
+
+        // the recipient only shares the key, scheduler and plaintext length
+        let recipient = Encryptor::new(vec![3, 1, 4], sched, Rng::default());
+        assert_eq!(
+            recipient.decrypt_len(&ciphertext, plaintext.len()),
+            plaintext
+        );
+
+        // other random characters fit too, but not other plaintexts, lengths, or keys
+        #[cfg(feature = "crack")]
+        {
+            use crate::utils::str_to_bytes;
+
+            let (cipherbytes, plainbytes) = (str_to_bytes(&ciphertext), str_to_bytes(plaintext));
+            assert!(recipient.fits(&cipherbytes, &plainbytes));
+            let mut other_rand = cipherbytes.clone();
+            other_rand[3] = (other_rand[3] + 1) % 27;
+            assert!(recipient.fits(&other_rand, &plainbytes));
+            let mut other_plain = plainbytes.clone();
+            other_plain[0] = (other_plain[0] + 1) % 27;
+            assert!(!recipient.fits(&cipherbytes, &other_plain));
+            assert!(!recipient.fits(&cipherbytes, &plainbytes[1..]));
+            assert!(!recipient.fits(&cipherbytes[1..], &plainbytes));
+            assert!(!Encryptor::new(vec![3, 1, 5], sched, Rng::default())
+                .fits(&cipherbytes, &plainbytes));
+        }
+    }
+
+    #[test]
+    fn lazy() {
+        use crate::ciphers::schedulers::{LengthRotate, PeriodicRand};
+
+        let key = vec![3, 1, 4, 1, 5, 9];
+        let plaintext = "the quick brown fox jumps over the lazy dog";
+        let chars: Vec<char> = plaintext.chars().collect();
+        let sched = PeriodicRand {
+            period: 7,
+            start: 2,
+            overwrite: false,
+        };
+
+        // the same characters as encrypting all at once, random ones included, and again
+        let encryptor = Encryptor::new(key.clone(), sched, Rng::from_seed(5));
+        let lazy: String = encryptor.encrypt_iter(chars.iter().copied()).collect();
+        assert_eq!(lazy, encryptor.encrypt(plaintext));
+        let again: String = encryptor.encrypt_iter(chars.clone()).collect();
+        assert_eq!(again, lazy);
+
+        // the keystream picks a key symbol for every plaintext character, and is as long as the
+        // ciphertext
+        let keystream: Vec<NextKey> = encryptor.keystream(plaintext.len()).collect();
+        assert_eq!(keystream.len(), lazy.len());
+        let keyed = keystream
+            .iter()
+            .filter(|next_key| matches!(next_key, NextKey::KeyIndex(_)))
+            .count();
+        assert_eq!(keyed, plaintext.len());
+        assert_eq!(keystream[2], NextKey::Rand);
+        assert_eq!(encryptor.keystream(0).count(), 0);
+
+        // schedulers that depend on the plaintext length get it from the iterator
+        let encryptor = Encryptor::new(key, LengthRotate, Rng::default());
+        let lazy: String = encryptor.encrypt_iter(chars).collect();
+        assert_eq!(lazy, encryptor.encrypt(plaintext));
+    }
+
+    #[test]
+    fn boxed() {
+        use crate::ciphers::schedulers::{Aab, LengthRotate, RepeatingKey};
+
         let key = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
-        let sched = crate::ciphers::schedulers::RepeatingKey;
+        let aab = Aab {
+            num_chars: 3,
+            num_reps: 2,
+            offset: 1,
+        };
+
+        // encryptors of different schedulers, picked at runtime
+        let encryptors = [
+            Encryptor::boxed(key.clone(), RepeatingKey, Rng::default()),
+            Encryptor::boxed(key.clone(), aab, Rng::default()),
+            Encryptor::boxed(key.clone(), LengthRotate, Rng::default()),
+        ];
+        let plaintext = "the quick brown fox jumps over the lazy dog";
+        let aab_ciphertext = Encryptor::new(key.clone(), aab, Rng::default()).encrypt(plaintext);
+        for (i, encryptor) in encryptors.iter().enumerate() {
+            let ciphertext = encryptor.encrypt(plaintext);
+            assert_eq!(ciphertext == aab_ciphertext, i == 1);
+            assert_eq!(encryptor.decrypt(&ciphertext), plaintext);
+        }
 
-        let encryptor = Encryptor::new(key, sched, Rng::default());
-        stresstest(encryptor, 10000).unwrap();
+        // borrowed schedulers encrypt the same
+        let borrowed = Encryptor::new(key, &aab, Rng::default());
+        assert_eq!(borrowed.encrypt(plaintext), aab_ciphertext);
     }
 }
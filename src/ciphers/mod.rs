@@ -32,62 +32,19 @@ pub trait Cipher {
 }
 
 #[cfg(test)]
-pub mod testing {
+mod tests {
     use super::*;
-    use crate::dict::Dictionary;
-    use crate::gen::Generator;
-    use crate::rng::{FromRng, Rng};
-
-    use std::fmt::Debug;
-
-    fn test_one<T: Cipher + Debug>(cipher: &T, gen: &mut Generator) {
-        // pick number of words to generate
-        let num_words = usize::max(10, gen.rng.next() as usize % 150);
-
-        // generate plaintext, ciphertext, and then decrypt
-        let plaintext = gen.generate_words(num_words);
-        let ciphertext = cipher.encrypt(&plaintext);
-        let decrypted = cipher.decrypt(&ciphertext);
-
-        // plaintext must always differ from ciphertext
-        if plaintext == ciphertext {
-            dbg!(&cipher);
-        }
-        assert_ne!(plaintext, ciphertext);
-
-        // decrypted text must always match original plaintext
-        assert_eq!(plaintext, decrypted);
-    }
-
-    pub fn stresstest<T: Cipher + Debug>(cipher: T, cycles: usize) -> anyhow::Result<()> {
-        let mut words = std::fs::read_to_string("words/default.txt")?;
-        let dict = Dictionary::from_string(&mut words);
-        let mut gen = Generator::with_dict(&dict);
-
-        for _ in 0..cycles {
-            test_one(&cipher, &mut gen);
+    use crate::testing::{from_rng, plaintext_words, round_trip};
+    use proptest::{prop_assert_ne, proptest};
+
+    proptest! {
+        #[test]
+        fn aab_round_trips(
+            encryptor in from_rng::<Encryptor<schedulers::Aab>>(),
+            plaintext in plaintext_words(10..150),
+        ) {
+            let ciphertext = round_trip(&encryptor, &plaintext)?;
+            prop_assert_ne!(ciphertext, plaintext);
         }
-
-        Ok(())
-    }
-
-    pub fn randomized_stresstest<T: Cipher + FromRng + Debug>(cycles: usize) -> anyhow::Result<()> {
-        let mut rng = Rng::default();
-
-        let mut words = std::fs::read_to_string("words/default.txt")?;
-        let dict = Dictionary::from_string(&mut words);
-        let mut gen = Generator::with_dict(&dict);
-
-        for _ in 0..cycles {
-            let cipher = T::from_rng(&mut rng);
-            test_one(&cipher, &mut gen);
-        }
-
-        Ok(())
-    }
-
-    #[test]
-    fn aab_stress() {
-        randomized_stresstest::<Encryptor<schedulers::Aab>>(10000).unwrap();
     }
 }
@@ -18,6 +18,8 @@ impl Cipher for Rot13 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::plaintext_words;
+    use proptest::{prop_assert_ne, proptest};
 
     #[test]
     fn round_trip() {
@@ -25,7 +27,7 @@ mod tests {
 
         // assert encryption works as expected
         let plaintext = "abcdefghijklmnopqrstuvwxyz ";
-        let ciphertext = rot13.encrypt(&plaintext);
+        let ciphertext = rot13.encrypt(plaintext);
         assert_eq!(ciphertext, "nopqrstuvwxyz abcdefghijklm");
 
         // assert decryption produces the same plaintext
@@ -33,8 +35,11 @@ mod tests {
         assert_eq!(plaintext, decrypted);
     }
 
-    #[test]
-    fn stresstest() {
-        crate::ciphers::testing::stresstest(Rot13, 10000).unwrap();
+    proptest! {
+        #[test]
+        fn round_trips(plaintext in plaintext_words(10..150)) {
+            let ciphertext = crate::testing::round_trip(&Rot13, &plaintext)?;
+            prop_assert_ne!(ciphertext, plaintext);
+        }
     }
 }
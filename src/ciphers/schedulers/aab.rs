@@ -1,11 +1,16 @@
 use super::{KeySchedule, NextKey};
+use crate::rng::{FromRng, Rng, KEY_LENGTHS};
+
+use anyhow::{ensure, Result};
+use std::ops::RangeInclusive;
 
 /// This scheduler repeats the first half of the key, then runs through the whole key. The hope is
 /// to confuse keylength guessing.
 ///
 /// It is called "AAB" scheduler because if the key is "AB" then this scheduler could produce an
 /// effective key of "AAB"
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Aab {
     /// Number of characters to repeat in the key
     pub num_chars: usize,
@@ -15,19 +20,88 @@ pub struct Aab {
     pub offset: usize,
 }
 
-impl KeySchedule for Aab {
-    fn schedule(&self, index: usize, key_length: usize, _plaintext_length: usize) -> NextKey {
+impl Aab {
+    /// Create an Aab scheduler for keys of `key_len` symbols, with parameters it schedules as they
+    /// are. `num_chars` has to be at least 1, fewer than `key_len` (unless the key is a single
+    /// symbol), and fit in the key after `offset`, which has to be in the key.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::ciphers::schedulers::Aab;
+    /// let aab = Aab::new(3, 2, 1, 7).unwrap();
+    /// assert_eq!(aab.canonicalize(7), aab);
+    /// assert!(Aab::new(0, 2, 1, 7).is_err());
+    /// assert!(Aab::new(3, 2, 5, 7).is_err());
+    /// ```
+    pub fn new(num_chars: usize, num_reps: usize, offset: usize, key_len: usize) -> Result<Self> {
+        ensure!(key_len > 0, "an Aab scheduler needs a key");
+        ensure!(
+            offset < key_len,
+            "offset {} is past a {} symbol key",
+            offset,
+            key_len
+        );
+        ensure!(
+            num_chars > 0,
+            "an Aab scheduler has to repeat at least 1 character"
+        );
+        ensure!(
+            num_chars <= key_len - offset && (num_chars < key_len || key_len == 1),
+            "{} characters from offset {} don't fit in a {} symbol key",
+            num_chars,
+            offset,
+            key_len
+        );
+        Ok(Self {
+            num_chars,
+            num_reps,
+            offset,
+        })
+    }
+
+    /// The parameters this scheduler actually schedules with for keys of `key_len` symbols:
+    /// `offset` wrapped into the key, and `num_chars` wrapped into the key, at least 1, and cut
+    /// short to fit after the offset. The result is what [`new`][`Self::new`] accepts.
+    pub fn canonicalize(&self, key_len: usize) -> Self {
         // offset must fit within key
-        let offset = self.offset % key_length;
+        let offset = self.offset % key_len;
 
         // num_chars must be:
         //  * at least 1
         //  * up to key_length
         //  * no greater than key_length - offset
-        let num_chars = 1.max(self.num_chars % key_length).min(key_length - offset);
+        let num_chars = 1.max(self.num_chars % key_len).min(key_len - offset);
+
+        Self {
+            num_chars,
+            num_reps: self.num_reps,
+            offset,
+        }
+    }
+
+    /// Generate random parameters that are already canonical for every key length in
+    /// `key_lengths`, so none of them get wrapped or cut short when scheduling.
+    pub fn from_rng_for_key_lengths(rng: &mut Rng, key_lengths: RangeInclusive<usize>) -> Self {
+        let shortest = (*key_lengths.start()).max(1);
+        let offset = rng.gen_range(0..shortest);
+        let most_chars = (shortest - offset).min(shortest - 1).max(1);
+        Self {
+            num_chars: rng.gen_range(1..most_chars + 1),
+            num_reps: rng.gen_range(0..8),
+            offset,
+        }
+    }
+}
+
+impl KeySchedule for Aab {
+    fn schedule(&self, index: usize, key_length: usize, _plaintext_length: usize) -> NextKey {
+        let Self {
+            num_chars,
+            num_reps,
+            offset,
+        } = self.canonicalize(key_length);
 
         // effective key length is key_length + number of repeated chars
-        let eff_key_length = key_length + num_chars * self.num_reps;
+        let eff_key_length = key_length + num_chars * num_reps;
 
         // effective index
         let index = index % eff_key_length;
@@ -35,89 +109,129 @@ impl KeySchedule for Aab {
         let next = if index < offset {
             // before any repetition
             index
-        } else if index < offset + (self.num_reps + 1) * num_chars {
+        } else if index < offset + (num_reps + 1) * num_chars {
             // within the repetition range
             (index - offset) % num_chars + offset
         } else {
             // after repeated range
-            index - num_chars * self.num_reps
+            index - num_chars * num_reps
         };
 
         NextKey::KeyIndex(next)
     }
-}
 
-impl crate::rng::FromRng for Aab {
-    fn from_rng(rng: &mut crate::rng::Rng) -> Self {
-        Self {
-            num_chars: rng.next() as usize % 32,
-            num_reps: rng.next() as usize % 8,
-            offset: rng.next() as usize % 8,
+    fn effective_period(&self, key_length: usize, _plaintext_length: usize) -> Option<usize> {
+        // a single symbol key repeats at every character, however much of it is repeated
+        if key_length == 1 {
+            return Some(1);
         }
+        let Self {
+            num_chars,
+            num_reps,
+            ..
+        } = self.canonicalize(key_length);
+        Some(key_length + num_chars * num_reps)
+    }
+}
+
+impl FromRng for Aab {
+    /// Parameters that are canonical for the key lengths [`Key`][`crate::utils::Key`]s are
+    /// generated with.
+    fn from_rng(rng: &mut Rng) -> Self {
+        Self::from_rng_for_key_lengths(rng, KEY_LENGTHS.start..=KEY_LENGTHS.end - 1)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ciphers::schedulers::testing::assert_keystream;
 
     #[test]
     fn repetition() {
         let key = b"ABCdefg";
-        let effective_key = b"ABCABCdefg";
+        let effective_key = "ABCABCdefg";
         let aab = Aab {
             num_chars: 3,
             num_reps: 1,
             offset: 0,
         };
 
-        let mut index = 0;
-        for _ in 0..500 {
-            for expected in 0..effective_key.len() {
-                let computed = aab.schedule(index, key.len(), 1000).index_or_panic();
-                assert_eq!(effective_key[expected], key[computed]);
-                index += 1;
-            }
-        }
+        assert_keystream(&aab, key, 1000, effective_key);
     }
 
     #[test]
     fn double_repetition() {
         let key = b"ABCdefg";
-        let effective_key = b"ABCABCABCdefg";
+        let effective_key = "ABCABCABCdefg";
         let aab = Aab {
             num_chars: 3,
             num_reps: 2,
             offset: 0,
         };
 
-        let mut index = 0;
-        for _ in 0..500 {
-            for expected in 0..effective_key.len() {
-                let computed = aab.schedule(index, key.len(), 1000).index_or_panic();
-                assert_eq!(effective_key[expected], key[computed]);
-                index += 1;
-            }
-        }
+        assert_keystream(&aab, key, 1000, effective_key);
     }
 
     #[test]
     fn offset() {
         let key = b"aBCDefg";
-        let effective_key = b"aBCDBCDBCDefg";
+        let effective_key = "aBCDBCDBCDefg";
         let aab = Aab {
             num_chars: 3,
             num_reps: 2,
             offset: 1,
         };
 
-        let mut index = 0;
-        for _ in 0..500 {
-            for expected in 0..effective_key.len() {
-                let computed = aab.schedule(index, key.len(), 1000).index_or_panic();
-                assert_eq!(effective_key[expected], key[computed]);
-                index += 1;
+        assert_keystream(&aab, key, 1000, effective_key);
+    }
+
+    #[test]
+    fn canonical() {
+        let mut rng = Rng::default();
+        for _ in 0..1000 {
+            let key_len = rng.gen_range(1..25);
+            let raw = Aab {
+                num_chars: rng.gen_range(0..32),
+                num_reps: rng.gen_range(0..8),
+                offset: rng.gen_range(0..32),
+            };
+
+            // the canonical form schedules the same, is valid, and stays canonical
+            let canonical = raw.canonicalize(key_len);
+            for index in 0..200 {
+                assert_eq!(
+                    canonical.schedule(index, key_len, 1000),
+                    raw.schedule(index, key_len, 1000)
+                );
             }
+            let Aab {
+                num_chars,
+                num_reps,
+                offset,
+            } = canonical;
+            assert_eq!(
+                Aab::new(num_chars, num_reps, offset, key_len).unwrap(),
+                canonical
+            );
+            assert_eq!(canonical.canonicalize(key_len), canonical);
+
+            // anything else isn't valid
+            if raw != canonical {
+                assert!(Aab::new(raw.num_chars, raw.num_reps, raw.offset, key_len).is_err());
+            }
+        }
+        assert!(Aab::new(1, 1, 0, 0).is_err());
+        assert!(Aab::new(7, 1, 0, 7).is_err());
+        assert!(Aab::new(1, 3, 0, 1).is_ok());
+
+        // generated parameters are canonical for every key length they are generated for
+        for _ in 0..1000 {
+            let aab = Aab::from_rng(&mut rng);
+            assert!((KEY_LENGTHS.start..=KEY_LENGTHS.end - 1)
+                .all(|key_len| aab.canonicalize(key_len) == aab));
+            let aab = Aab::from_rng_for_key_lengths(&mut rng, 1..=3);
+            assert!((1..=3).all(|key_len| aab.canonicalize(key_len) == aab));
         }
     }
 }
@@ -0,0 +1,120 @@
+use super::{gcd, KeySchedule, NextKey};
+
+/// This scheduler splits the key into two halves, and interleaves them. Even indices cycle through
+/// the first half of the key, and odd indices cycle through the second half. The hope is to
+/// confuse simple period detection: when the halves differ in length, the effective key only
+/// repeats after `2 * lcm(first_half, second_half)` characters.
+///
+/// Example with key `ABCdefg` (first half `ABC`, second half `defg`):
+///
+/// ```text
+/// Effective key:     AdBeCfAgBdCeAfBgCd...
+/// ```
+///
+/// If `swap` is set, the second half is used for even indices and the first half for odd indices
+/// instead.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockInterleave {
+    /// Whether to start with the second half of the key instead of the first
+    pub swap: bool,
+}
+
+impl KeySchedule for BlockInterleave {
+    fn schedule(&self, index: usize, key_length: usize, _plaintext_length: usize) -> NextKey {
+        // a key of length 1 can't be split, so both halves are the whole key
+        if key_length < 2 {
+            return NextKey::KeyIndex(0);
+        }
+
+        // the first half gets the shorter half when the key length is odd
+        let first_len = key_length / 2;
+        let second_len = key_length - first_len;
+
+        // even or odd index decides the half, then each half is cycled through on its own
+        let use_first = index.is_multiple_of(2) != self.swap;
+        let half_index = index / 2;
+
+        let next = if use_first {
+            half_index % first_len
+        } else {
+            first_len + half_index % second_len
+        };
+
+        NextKey::KeyIndex(next)
+    }
+
+    fn effective_period(&self, key_length: usize, _plaintext_length: usize) -> Option<usize> {
+        if key_length < 2 {
+            return Some(1);
+        }
+
+        // both halves line up again after a multiple of each of their lengths, counted in pairs
+        let first_len = key_length / 2;
+        let second_len = key_length - first_len;
+        Some(2 * first_len * second_len / gcd(first_len, second_len))
+    }
+}
+
+impl crate::rng::FromRng for BlockInterleave {
+    fn from_rng(rng: &mut crate::rng::Rng) -> Self {
+        Self {
+            swap: rng.gen_bool(0.5),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::testing::assert_keystream;
+    use crate::ciphers::Encryptor;
+    use crate::testing::{from_rng, plaintext_words, round_trip};
+    use proptest::{prop_assert_ne, proptest};
+
+    #[test]
+    fn even_key() {
+        let key = b"ABCdef";
+        let effective_key = "AdBeCf";
+        let sched = BlockInterleave { swap: false };
+
+        assert_keystream(&sched, key, 1000, effective_key);
+    }
+
+    #[test]
+    fn odd_key() {
+        let key = b"ABCdefg";
+        let effective_key = "AdBeCfAgBdCeAfBgCdAeBfCg";
+        let sched = BlockInterleave { swap: false };
+
+        assert_keystream(&sched, key, 1000, effective_key);
+    }
+
+    #[test]
+    fn swapped() {
+        let key = b"ABCdefg";
+        let effective_key = "dAeBfCgAdBeCfAgBdCeAfBgC";
+        let sched = BlockInterleave { swap: true };
+
+        assert_keystream(&sched, key, 1000, effective_key);
+    }
+
+    #[test]
+    fn single_char_key() {
+        let sched = BlockInterleave { swap: false };
+        for index in 0..100 {
+            assert_eq!(sched.schedule(index, 1, 1000), NextKey::KeyIndex(0));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips(
+            encryptor in from_rng::<Encryptor<BlockInterleave>>(),
+            plaintext in plaintext_words(10..150),
+        ) {
+            let ciphertext = round_trip(&encryptor, &plaintext)?;
+            prop_assert_ne!(ciphertext, plaintext);
+        }
+    }
+}
@@ -1,30 +1,120 @@
-//This scheduler mods the plaintext length to produce a the index. THe final result is also a function of index and key_lenth in order to inject a certain amount of randomness and because we didn't have an example where all 3 variables were used.
+use super::{gcd, KeySchedule, NextKey};
+use crate::rng::{FromRng, Rng};
 
-#[derive(Debug, Clone, Copy)]
-pub struct LengthMod;
+/// This scheduler steps through the key by a stride that depends on the plaintext length `L`.
+/// The stride is `L mod t`, moved up to the next stride with no factor in common with the key
+/// length `t` (a stride of 0 is 1), so every key symbol is used once every `t` characters, in an
+/// order that changes with `L`:
+///
+/// ```text
+/// key index i = (i * stride) mod t
+/// ```
+///
+/// Example with key `ABCDEFG` (`t = 7`):
+///
+/// ```text
+/// L = 1000, stride 6:    AGFEDCBAGFEDCB...
+/// L = 1003, stride 2:    ACEGBDFACEGBDF...
+/// ```
+///
+/// The effective key length is always `t`.
+///
+/// With `legacy` set, this schedules like LengthMod did before, so ciphertexts encrypted with it
+/// still decrypt: `(L * i) mod t` up to `i = L / t`, and `L mod t` for every character after. That
+/// is neither periodic nor every key symbol, so it is never generated at random.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Option<LengthModFields>"))]
+pub struct LengthMod {
+    /// Schedule like the LengthMod from before the stride
+    pub legacy: bool,
+}
 
-use super::{KeySchedule, NextKey};
+impl LengthMod {
+    /// How far along the key each character steps, for a plaintext `plaintext_length` characters
+    /// long.
+    pub fn stride(key_length: usize, plaintext_length: usize) -> usize {
+        let mut stride = (plaintext_length % key_length).max(1);
+        while gcd(stride, key_length) != 1 {
+            stride += 1;
+        }
+        stride
+    }
+}
 
 impl KeySchedule for LengthMod {
     fn schedule(&self, index: usize, key_length: usize, plaintext_length: usize) -> NextKey {
-        let next = if plaintext_length < (index * key_length) {
-            plaintext_length % key_length
+        let next = if self.legacy {
+            if plaintext_length < (index * key_length) {
+                plaintext_length % key_length
+            } else {
+                (plaintext_length * index) % key_length
+            }
         } else {
-            (plaintext_length * index) % key_length
+            (index % key_length) * Self::stride(key_length, plaintext_length) % key_length
         };
 
         NextKey::KeyIndex(next)
     }
+
+    fn effective_period(&self, key_length: usize, plaintext_length: usize) -> Option<usize> {
+        match self.legacy {
+            true => super::simulated_period(self, key_length, plaintext_length),
+            false => Some(key_length),
+        }
+    }
+}
+
+impl FromRng for LengthMod {
+    fn from_rng(_: &mut Rng) -> Self {
+        Self { legacy: false }
+    }
+}
+
+/// LengthMod as it is serialized. The LengthMod from before the stride had no fields, and
+/// serialized as `null`, so that deserializes to a legacy one.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct LengthModFields {
+    legacy: bool,
+}
+
+#[cfg(feature = "serde")]
+impl From<Option<LengthModFields>> for LengthMod {
+    fn from(fields: Option<LengthModFields>) -> Self {
+        Self {
+            legacy: fields.is_none_or(|fields| fields.legacy),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ciphers::schedulers::testing::assert_keystream;
+    use crate::ciphers::Encryptor;
+    use crate::testing::{from_rng, plaintext_words, round_trip, schedules_in_bounds};
+    use proptest::{prop_assert, prop_assert_eq, prop_assert_ne, proptest};
+
+    #[test]
+    fn strides() {
+        let key = b"ABCDEFG";
+        let sched = LengthMod::default();
+        assert_keystream(&sched, key, 1000, "AGFEDCB");
+        assert_keystream(&sched, key, 1003, "ACEGBDF");
+        assert_keystream(&sched, key, 1001, "ABCDEFG");
+
+        // strides sharing a factor with the key length move up, past 0 too
+        assert_eq!(LengthMod::stride(6, 1000), 5);
+        assert_eq!(LengthMod::stride(6, 1002), 1);
+        assert_eq!(LengthMod::stride(1, 1000), 1);
+        assert_keystream(&sched, b"A", 1000, "A");
+    }
 
     #[test]
     // tests the first branch option, if the resulting index is 0, its working correctly
     fn first() {
-        let sched = LengthMod {};
+        let sched = LengthMod { legacy: true };
         let res = sched.schedule(12, 50, 500).index_or_panic();
         assert_eq!(res, 0);
     }
@@ -32,8 +122,57 @@ mod tests {
     #[test]
     // tests the second branch option, if the resulting index is 1, its working correctly
     fn second() {
-        let sched = LengthMod {};
+        let sched = LengthMod { legacy: true };
         let res = sched.schedule(1, 50, 501).index_or_panic();
         assert_eq!(res, 1);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialized() {
+        let sched = LengthMod::default();
+        let json = serde_json::to_string(&sched).unwrap();
+        assert_eq!(serde_json::from_str::<LengthMod>(&json).unwrap(), sched);
+
+        // LengthMods serialized before the stride were legacy ones
+        let old: LengthMod = serde_json::from_str("null").unwrap();
+        assert!(old.legacy);
+    }
+
+    proptest! {
+        #[test]
+        fn every_symbol_once_a_period(
+            key_length in 1_usize..25,
+            plaintext_length in 1_usize..2000,
+        ) {
+            let sched = LengthMod::default();
+            let mut seen: Vec<usize> = (0..key_length)
+                .map(|index| sched.schedule(index, key_length, plaintext_length).index_or_panic())
+                .collect();
+            seen.sort_unstable();
+            prop_assert_eq!(seen, (0..key_length).collect::<Vec<_>>());
+            prop_assert_eq!(sched.effective_period(key_length, plaintext_length), Some(key_length));
+            for index in 0..3 * key_length {
+                prop_assert_eq!(
+                    sched.schedule(index, key_length, plaintext_length),
+                    sched.schedule(index + key_length, key_length, plaintext_length)
+                );
+            }
+        }
+
+        #[test]
+        fn in_bounds(key_length in 1_usize..25, plaintext_length in 0_usize..500, legacy: bool) {
+            schedules_in_bounds(&LengthMod { legacy }, key_length, plaintext_length)?;
+            prop_assert!(LengthMod::stride(key_length, plaintext_length) >= 1);
+        }
+
+        #[test]
+        fn round_trips(
+            encryptor in from_rng::<Encryptor<LengthMod>>(),
+            plaintext in plaintext_words(10..150),
+        ) {
+            let ciphertext = round_trip(&encryptor, &plaintext)?;
+            prop_assert_ne!(ciphertext, plaintext);
+        }
+    }
 }
@@ -0,0 +1,85 @@
+use super::{KeySchedule, NextKey};
+
+/// This scheduler cycles through the key like [`RepeatingKey`][`super::RepeatingKey`], but where it
+/// starts and which way it goes both depend on the plaintext length `L`:
+///
+///  * the first key index used is `L mod t`
+///  * if `L` is even, the key is cycled forwards, and if `L` is odd, backwards
+///
+/// Example with key `ABCDEFG`:
+///
+/// ```text
+/// L = 1000:    GABCDEFGABCDEF...
+/// L = 1001:    AGFEDCBAGFEDCB...
+/// ```
+///
+/// Compared to [`LengthMod`][`super::LengthMod`], every one of `i`, `t` and `L` matters for every
+/// index, while the effective key length stays `t`.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LengthRotate;
+
+impl KeySchedule for LengthRotate {
+    fn schedule(&self, index: usize, key_length: usize, plaintext_length: usize) -> NextKey {
+        // starting point in the key
+        let start = plaintext_length % key_length;
+
+        // how far along the rotation we are
+        let step = index % key_length;
+
+        let next = if plaintext_length.is_multiple_of(2) {
+            // forwards
+            (start + step) % key_length
+        } else {
+            // backwards
+            (start + key_length - step) % key_length
+        };
+
+        NextKey::KeyIndex(next)
+    }
+
+    fn effective_period(&self, key_length: usize, _plaintext_length: usize) -> Option<usize> {
+        // every key index once per rotation, whichever way it goes
+        Some(key_length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::testing::assert_keystream;
+    use crate::ciphers::Encryptor;
+    use crate::testing::{plaintext_words, round_trip};
+    use proptest::{prop_assert_ne, proptest};
+
+    #[test]
+    fn even_length_forwards() {
+        let key = b"ABCDEFG";
+        let effective_key = "GABCDEF";
+        let sched = LengthRotate;
+
+        assert_keystream(&sched, key, 1000, effective_key);
+    }
+
+    #[test]
+    fn odd_length_backwards() {
+        let key = b"ABCDEFG";
+        let effective_key = "AGFEDCB";
+        let sched = LengthRotate;
+
+        assert_keystream(&sched, key, 1001, effective_key);
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips(plaintext in plaintext_words(10..150)) {
+            let encryptor = Encryptor::new(
+                vec![3, 1, 4, 1, 5, 9, 2, 6],
+                LengthRotate,
+                Default::default(),
+            );
+            let ciphertext = round_trip(&encryptor, &plaintext)?;
+            prop_assert_ne!(ciphertext, plaintext);
+        }
+    }
+}
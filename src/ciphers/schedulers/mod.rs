@@ -1,21 +1,32 @@
 //! Definition of [`KeySchedule`] and various implementations of key scheduling.
 
 mod aab;
+mod blockinterleave;
 mod lengthmod;
+mod lengthrotate;
 mod offsetreverse;
 mod periodicrand;
 mod repeatingkey;
+#[cfg(test)]
+mod testing;
 
 pub use aab::Aab;
+pub use blockinterleave::BlockInterleave;
 pub use lengthmod::LengthMod;
+pub use lengthrotate::LengthRotate;
 pub use offsetreverse::OffsetReverse;
 pub use periodicrand::PeriodicRand;
 pub use repeatingkey::RepeatingKey;
 
 use crate::rng::FromRng;
 
+use std::fmt::Debug;
+
 /// Trait for implementing key scheduling.
-pub trait KeySchedule {
+///
+/// Schedulers can be borrowed or boxed and still schedule, so a `Box<dyn KeySchedule>` can stand
+/// in for a scheduler only known at runtime.
+pub trait KeySchedule: Debug {
     /// Returns the index of the key to use when shifting plaintext into ciphertext.
     ///
     /// In the project description, this process is described as: each ciphertext symbol `c[i]` is the
@@ -26,9 +37,77 @@ pub trait KeySchedule {
     ///   * `t` is the key length
     ///   * `L` is the length of the plaintext
     fn schedule(&self, index: usize, key_length: usize, plaintext_length: usize) -> NextKey;
+
+    /// The shortest period the key indices this picks repeat at over the ciphertext of a
+    /// plaintext `plaintext_length` characters long, which is the keylength the ciphertext
+    /// actually repeats at: `key_length` for a [`RepeatingKey`], but `t + num_chars * num_reps`
+    /// for an [`Aab`]. Random characters count as the same wherever they are.
+    ///
+    /// By default this schedules the whole ciphertext and finds its period, and is `None` if the
+    /// key indices don't repeat at least twice in it. Schedulers that know their period give it
+    /// for any plaintext length.
+    fn effective_period(&self, key_length: usize, plaintext_length: usize) -> Option<usize> {
+        simulated_period(self, key_length, plaintext_length)
+    }
+}
+
+impl<K: KeySchedule + ?Sized> KeySchedule for &K {
+    fn schedule(&self, index: usize, key_length: usize, plaintext_length: usize) -> NextKey {
+        (**self).schedule(index, key_length, plaintext_length)
+    }
+
+    fn effective_period(&self, key_length: usize, plaintext_length: usize) -> Option<usize> {
+        (**self).effective_period(key_length, plaintext_length)
+    }
+}
+
+impl<K: KeySchedule + ?Sized> KeySchedule for Box<K> {
+    fn schedule(&self, index: usize, key_length: usize, plaintext_length: usize) -> NextKey {
+        (**self).schedule(index, key_length, plaintext_length)
+    }
+
+    fn effective_period(&self, key_length: usize, plaintext_length: usize) -> Option<usize> {
+        (**self).effective_period(key_length, plaintext_length)
+    }
+}
+
+/// Greatest common divisor of `a` and `b`.
+fn gcd(a: usize, b: usize) -> usize {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+/// The default [`KeySchedule::effective_period`]: the shortest period of the whole keystream, if
+/// it repeats at least twice.
+fn simulated_period<K: KeySchedule + ?Sized>(
+    sched: &K,
+    key_length: usize,
+    plaintext_length: usize,
+) -> Option<usize> {
+    let keystream = schedule_all(sched, key_length, plaintext_length);
+
+    // the longest proper prefix of the keystream that is also a suffix of it, found like the
+    // failure function of Knuth-Morris-Pratt, is what's left after the shortest period
+    let mut border = vec![0; keystream.len()];
+    for i in 1..keystream.len() {
+        let mut len = border[i - 1];
+        while len > 0 && keystream[i] != keystream[len] {
+            len = border[len - 1];
+        }
+        if keystream[i] == keystream[len] {
+            len += 1;
+        }
+        border[i] = len;
+    }
+
+    let period = keystream.len() - border.last().copied()?;
+    (2 * period <= keystream.len()).then_some(period)
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NextKey {
     KeyIndex(usize),
     Rand,
@@ -46,24 +125,144 @@ impl NextKey {
     }
 }
 
+/// Materialize the effective keystream a scheduler produces when encrypting a plaintext of
+/// `plaintext_len` characters with `key`.
+///
+/// The returned Vec has one [`NextKey`] per ciphertext character, so it is exactly as long as the
+/// resulting ciphertext: every [`NextKey::KeyIndex`] consumes one plaintext character and every
+/// [`NextKey::Rand`] is an inserted (or overwritten) random character.
+pub fn expand<K: KeySchedule + ?Sized>(
+    sched: &K,
+    key: &[i8],
+    plaintext_len: usize,
+) -> Vec<NextKey> {
+    schedule_all(sched, key.len(), plaintext_len)
+}
+
+/// Same as [`expand`], for a key of `key_length` symbols.
+fn schedule_all<K: KeySchedule + ?Sized>(
+    sched: &K,
+    key_length: usize,
+    plaintext_len: usize,
+) -> Vec<NextKey> {
+    let mut keystream = Vec::with_capacity(plaintext_len);
+    let mut plaintext_used = 0;
+
+    // same loop as encryption: keep scheduling until every plaintext character is consumed
+    while plaintext_used < plaintext_len {
+        let next = sched.schedule(keystream.len(), key_length, plaintext_len);
+        if let NextKey::KeyIndex(_) = next {
+            plaintext_used += 1;
+        }
+        keystream.push(next);
+    }
+
+    keystream
+}
+
+/// Render a keystream from [`expand`] as a String, labeling each key index with the matching
+/// symbol in `labels`, and each random character with `_`.
+///
+/// With `labels` of `b"aBCDefg"` this gives the same kind of pattern used throughout the scheduler
+/// tests, like `"aBCD_BCDBCD_efgaBC_..."`.
+pub fn render(keystream: &[NextKey], labels: &[u8]) -> String {
+    keystream
+        .iter()
+        .map(|next| match next {
+            NextKey::KeyIndex(index) => labels[*index] as char,
+            NextKey::Rand => '_',
+        })
+        .collect()
+}
+
 /// Base scheduler type that exists to randomly generate many kinds of schedulers
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RandomBaseScheduler {
     Aab(Aab),
+    BlockInterleave(BlockInterleave),
     LengthMod(LengthMod),
+    LengthRotate(LengthRotate),
     OffsetReverse(OffsetReverse),
     RepeatingKey(RepeatingKey),
 }
 
+/// The families of base schedulers, without any of their parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SchedulerKind {
+    Aab,
+    LengthMod,
+    OffsetReverse,
+    RepeatingKey,
+    BlockInterleave,
+    LengthRotate,
+}
+
+impl SchedulerKind {
+    /// All base scheduler families.
+    pub const ALL: [SchedulerKind; 6] = [
+        Self::Aab,
+        Self::LengthMod,
+        Self::OffsetReverse,
+        Self::RepeatingKey,
+        Self::BlockInterleave,
+        Self::LengthRotate,
+    ];
+
+    /// Name of the scheduler family, for printing in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Aab => "Aab",
+            Self::LengthMod => "LengthMod",
+            Self::OffsetReverse => "OffsetReverse",
+            Self::RepeatingKey => "RepeatingKey",
+            Self::BlockInterleave => "BlockInterleave",
+            Self::LengthRotate => "LengthRotate",
+        }
+    }
+
+    /// Look up a scheduler family by its [`name`][`Self::name`], ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// Generate a scheduler of this family with random parameters.
+    pub fn generate(&self, rng: &mut crate::rng::Rng) -> RandomBaseScheduler {
+        match self {
+            Self::Aab => RandomBaseScheduler::Aab(Aab::from_rng(rng)),
+            Self::LengthMod => RandomBaseScheduler::LengthMod(LengthMod::from_rng(rng)),
+            Self::OffsetReverse => RandomBaseScheduler::OffsetReverse(OffsetReverse::from_rng(rng)),
+            Self::RepeatingKey => RandomBaseScheduler::RepeatingKey(RepeatingKey),
+            Self::BlockInterleave => {
+                RandomBaseScheduler::BlockInterleave(BlockInterleave::from_rng(rng))
+            }
+            Self::LengthRotate => RandomBaseScheduler::LengthRotate(LengthRotate),
+        }
+    }
+}
+
+impl RandomBaseScheduler {
+    /// Which family of scheduler this is.
+    pub fn kind(&self) -> SchedulerKind {
+        match self {
+            Self::Aab(_) => SchedulerKind::Aab,
+            Self::BlockInterleave(_) => SchedulerKind::BlockInterleave,
+            Self::LengthMod(_) => SchedulerKind::LengthMod,
+            Self::LengthRotate(_) => SchedulerKind::LengthRotate,
+            Self::OffsetReverse(_) => SchedulerKind::OffsetReverse,
+            Self::RepeatingKey(_) => SchedulerKind::RepeatingKey,
+        }
+    }
+}
+
 impl FromRng for RandomBaseScheduler {
     fn from_rng(rng: &mut crate::rng::Rng) -> Self {
-        match rng.choose(&[1, 2, 3, 4]) {
-            1 => Self::Aab(Aab::from_rng(rng)),
-            2 => Self::LengthMod(LengthMod),
-            3 => Self::OffsetReverse(OffsetReverse::from_rng(rng)),
-            4 => Self::RepeatingKey(RepeatingKey),
-            _ => unreachable!(),
-        }
+        let kind = *rng.choose(&SchedulerKind::ALL);
+        kind.generate(rng)
     }
 }
 
@@ -71,17 +270,32 @@ impl KeySchedule for RandomBaseScheduler {
     fn schedule(&self, i: usize, k: usize, p: usize) -> NextKey {
         match self {
             Self::Aab(s) => s.schedule(i, k, p),
+            Self::BlockInterleave(s) => s.schedule(i, k, p),
             Self::LengthMod(s) => s.schedule(i, k, p),
+            Self::LengthRotate(s) => s.schedule(i, k, p),
             Self::OffsetReverse(s) => s.schedule(i, k, p),
             Self::RepeatingKey(s) => s.schedule(i, k, p),
         }
     }
+
+    fn effective_period(&self, k: usize, p: usize) -> Option<usize> {
+        match self {
+            Self::Aab(s) => s.effective_period(k, p),
+            Self::BlockInterleave(s) => s.effective_period(k, p),
+            Self::LengthMod(s) => s.effective_period(k, p),
+            Self::LengthRotate(s) => s.effective_period(k, p),
+            Self::OffsetReverse(s) => s.effective_period(k, p),
+            Self::RepeatingKey(s) => s.effective_period(k, p),
+        }
+    }
 }
 
 /// Overarching scheduler type that exists to randomly generate many kinds of schedulers. At the
 /// highest level, there are multiple levels of PeriodicRand, and at the base, any one of the
-/// normal schedulers: Aab, LengthMod, OffsetReverse, and RepeatingKey
+/// normal schedulers: Aab, BlockInterleave, LengthMod, LengthRotate, OffsetReverse, and
+/// RepeatingKey
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RandomScheduler {
     /// No PeriodicRand layer
     Zero(RandomBaseScheduler),
@@ -98,21 +312,85 @@ pub enum RandomScheduler {
     ),
 }
 
-impl FromRng for RandomScheduler {
-    fn from_rng(rng: &mut crate::rng::Rng) -> Self {
-        match rng.choose(&[0, 0, 1, 1, 1, 2, 2, 2, 3]) {
-            0 => Self::Zero(RandomBaseScheduler::from_rng(rng)),
-            1 => Self::One(
-                RandomBaseScheduler::from_rng(rng),
-                PeriodicRand::from_rng(rng),
-            ),
+/// How many [`PeriodicRand`] layers a [`RandomScheduler`] is allowed to have when generated
+/// randomly. Every level still uses periods that leave enough plaintext between random characters
+/// to have a chance at cracking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SchedulerComplexity {
+    /// Only base schedulers, no random characters
+    Easy,
+    /// Base schedulers with up to one PeriodicRand layer
+    Medium,
+    /// Base schedulers with up to three PeriodicRand layers
+    Hard,
+}
+
+impl SchedulerComplexity {
+    /// All complexity levels, from easiest to hardest.
+    pub const ALL: [SchedulerComplexity; 3] = [Self::Easy, Self::Medium, Self::Hard];
+
+    /// Name of the complexity level, for printing in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Easy => "easy",
+            Self::Medium => "medium",
+            Self::Hard => "hard",
+        }
+    }
+
+    /// Look up a complexity level by its [`name`][`Self::name`], ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|complexity| complexity.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// The choices of PeriodicRand layer counts at this complexity, weighted by how likely each is
+    /// to be chosen.
+    fn layer_choices(&self) -> &'static [(u8, f64)] {
+        match self {
+            Self::Easy => &[(0, 1.0)],
+            Self::Medium => &[(0, 2.0), (1, 3.0)],
+            Self::Hard => &[(0, 2.0), (1, 3.0), (2, 3.0), (3, 1.0)],
+        }
+    }
+}
+
+impl RandomScheduler {
+    /// Randomly generate a scheduler with no more PeriodicRand layers than `complexity` allows.
+    pub fn with_complexity(rng: &mut crate::rng::Rng, complexity: SchedulerComplexity) -> Self {
+        Self::layered(rng, complexity, RandomBaseScheduler::from_rng)
+    }
+
+    /// Same as [`with_complexity`][`Self::with_complexity`], with a base scheduler of the `kind`
+    /// family.
+    pub fn with_kind(
+        rng: &mut crate::rng::Rng,
+        complexity: SchedulerComplexity,
+        kind: SchedulerKind,
+    ) -> Self {
+        Self::layered(rng, complexity, |rng| kind.generate(rng))
+    }
+
+    /// Randomly generate PeriodicRand layers as `complexity` allows, over a base scheduler from
+    /// `base`.
+    fn layered(
+        rng: &mut crate::rng::Rng,
+        complexity: SchedulerComplexity,
+        mut base: impl FnMut(&mut crate::rng::Rng) -> RandomBaseScheduler,
+    ) -> Self {
+        match rng.choose_weighted(complexity.layer_choices()) {
+            0 => Self::Zero(base(rng)),
+            1 => Self::One(base(rng), PeriodicRand::from_rng(rng)),
             2 => {
                 // make period less frequent
                 let mut pr1 = PeriodicRand::from_rng(rng);
                 let mut pr2 = PeriodicRand::from_rng(rng);
                 pr1.period += 8;
                 pr2.period += 8;
-                Self::Two(RandomBaseScheduler::from_rng(rng), pr1, pr2)
+                Self::Two(base(rng), pr1, pr2)
             }
             3 => {
                 // make period less frequent
@@ -122,11 +400,34 @@ impl FromRng for RandomScheduler {
                 pr1.period += 16;
                 pr2.period += 16;
                 pr3.period += 16;
-                Self::Three(RandomBaseScheduler::from_rng(rng), pr1, pr2, pr3)
+                Self::Three(base(rng), pr1, pr2, pr3)
             }
             _ => unreachable!(),
         }
     }
+
+    /// The base scheduler underneath any PeriodicRand layers.
+    pub fn base(&self) -> &RandomBaseScheduler {
+        match self {
+            Self::Zero(s) | Self::One(s, ..) | Self::Two(s, ..) | Self::Three(s, ..) => s,
+        }
+    }
+
+    /// Number of PeriodicRand layers in this scheduler.
+    pub fn layers(&self) -> usize {
+        match self {
+            Self::Zero(..) => 0,
+            Self::One(..) => 1,
+            Self::Two(..) => 2,
+            Self::Three(..) => 3,
+        }
+    }
+}
+
+impl FromRng for RandomScheduler {
+    fn from_rng(rng: &mut crate::rng::Rng) -> Self {
+        Self::with_complexity(rng, SchedulerComplexity::Hard)
+    }
 }
 
 impl KeySchedule for RandomScheduler {
@@ -139,3 +440,158 @@ impl KeySchedule for RandomScheduler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_chained() {
+        let key = [0; 7];
+        let aab = Aab {
+            num_chars: 3,
+            num_reps: 2,
+            offset: 1,
+        };
+        let rand = PeriodicRand {
+            period: 7,
+            start: 4,
+            overwrite: false,
+        };
+
+        let keystream = expand(&(&rand, &aab), &key, 24);
+        assert_eq!(
+            render(&keystream, b"aBCDefg"),
+            "aBCD_BCDBCD_efgaBC_DBCDBC_De"
+        );
+
+        // every plaintext character got a key index
+        let key_indices = keystream.iter().filter(|n| **n != NextKey::Rand).count();
+        assert_eq!(key_indices, 24);
+    }
+
+    #[test]
+    fn complexity_limits_layers() {
+        let mut rng = crate::rng::Rng::default();
+
+        for complexity in SchedulerComplexity::ALL.iter() {
+            let max_layers = match complexity {
+                SchedulerComplexity::Easy => 0,
+                SchedulerComplexity::Medium => 1,
+                SchedulerComplexity::Hard => 3,
+            };
+
+            // every allowed layer count should show up, and nothing beyond it
+            let mut seen = [false; 4];
+            for _ in 0..1000 {
+                let sched = RandomScheduler::with_complexity(&mut rng, *complexity);
+                assert!(sched.layers() <= max_layers);
+                seen[sched.layers()] = true;
+            }
+            assert!(seen[..=max_layers].iter().all(|&s| s));
+        }
+    }
+
+    #[test]
+    fn effective_periods() {
+        use crate::rng::{FromRng, Rng};
+
+        // known periods are the periods of the keystreams they schedule
+        let mut rng = Rng::default();
+        for _ in 0..300 {
+            let sched = RandomBaseScheduler::from_rng(&mut rng);
+            let key_length = rng.gen_range(1..25);
+            let plaintext_length = rng.gen_range(1000..2000);
+            let known = sched.effective_period(key_length, plaintext_length);
+            let simulated = simulated_period(&sched, key_length, plaintext_length);
+            assert_eq!(
+                known, simulated,
+                "{:?} of a {} symbol key",
+                sched, key_length
+            );
+        }
+
+        let aab = Aab {
+            num_chars: 3,
+            num_reps: 2,
+            offset: 1,
+        };
+        assert_eq!(aab.effective_period(7, 1000), Some(13));
+        assert_eq!(RepeatingKey.effective_period(7, 3), Some(7));
+        let boxed: Box<dyn KeySchedule> = Box::new(aab);
+        assert_eq!(boxed.effective_period(7, 1000), Some(13));
+
+        // random characters repeat with everything else, or the keystream doesn't repeat at all
+        let rand = PeriodicRand {
+            period: 4,
+            start: 1,
+            overwrite: false,
+        };
+        assert_eq!(rand.effective_period(7, 1000), Some(28));
+        assert_eq!(rand.effective_period(7, 10), None);
+        assert_eq!((&rand, &aab).effective_period(7, 20), None);
+        assert_eq!(RepeatingKey.effective_period(7, 0), Some(7));
+        assert_eq!(simulated_period(&RepeatingKey, 7, 0), None);
+    }
+
+    #[test]
+    fn expand_empty() {
+        assert!(expand(&RepeatingKey, &[1, 2, 3], 0).is_empty());
+    }
+
+    #[test]
+    fn expand_matches_ciphertext_length() {
+        use crate::ciphers::{Cipher, Encryptor};
+        use crate::rng::{FromRng, Rng};
+
+        let mut rng = Rng::default();
+        for _ in 0..100 {
+            let key: crate::utils::Key = FromRng::from_rng(&mut rng);
+            let sched = RandomScheduler::from_rng(&mut rng);
+            let plaintext = "the quick brown fox jumps over the lazy dog";
+
+            let keystream = expand(&sched, &key, plaintext.len());
+            let encryptor = Encryptor::new(key, sched, Rng::default());
+            let ciphertext = encryptor.encrypt(plaintext);
+            encryptor.decrypt(&ciphertext);
+
+            assert_eq!(keystream.len(), ciphertext.len());
+        }
+    }
+
+    #[test]
+    fn kind_from_name() {
+        for kind in SchedulerKind::ALL.iter() {
+            assert_eq!(SchedulerKind::from_name(kind.name()), Some(*kind));
+        }
+        assert_eq!(
+            SchedulerKind::from_name("repeatingkey"),
+            Some(SchedulerKind::RepeatingKey)
+        );
+        assert_eq!(SchedulerKind::from_name("Vigenere"), None);
+
+        assert_eq!(
+            SchedulerComplexity::from_name("Medium"),
+            Some(SchedulerComplexity::Medium)
+        );
+        assert_eq!(SchedulerComplexity::from_name("extreme"), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        use crate::rng::{FromRng, Rng};
+
+        let mut rng = Rng::default();
+        for _ in 0..100 {
+            let sched = RandomScheduler::from_rng(&mut rng);
+            let json = serde_json::to_string(&sched).unwrap();
+            let back: RandomScheduler = serde_json::from_str(&json).unwrap();
+
+            // the same scheduler produces the same keystream
+            let key = [1, 2, 3, 4, 5, 6, 7];
+            let render = |s: &RandomScheduler| render(&expand(s, &key, 200), b"abcdefg");
+            assert_eq!(render(&back), render(&sched), "{}", json);
+        }
+    }
+}
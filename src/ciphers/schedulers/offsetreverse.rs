@@ -1,40 +1,51 @@
-// the goal of this is to reverse the original key until the offset and then continue with the
-// original key this leads to variable effective key length to confuse key length guessing
-//
-// For Example: ABCDEF with offset 2 would turn into FEABCDEF
+use super::{KeySchedule, NextKey};
+
+/// This scheduler runs backwards through the last `offset` characters of the key, then continues
+/// with the original key. This leads to a variable effective key length (`t + offset`) to confuse
+/// keylength guessing.
+///
+/// For example, `ABCDEF` with offset 2 would turn into `FEABCDEF`.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OffsetReverse {
-    offset: usize,
+    /// Number of characters from the end of the key to run through in reverse
+    pub offset: usize,
 }
 
-use super::{KeySchedule, NextKey};
-
 impl KeySchedule for OffsetReverse {
     fn schedule(&self, index: usize, key_length: usize, _plaintext_length: usize) -> NextKey {
         // fix the offset if it's larger than the key
         let offset = self.offset % (key_length + 1);
 
-        //get the index value of the last character for zero based array
+        // get the index value of the last character for zero based array
         let eff_key_length = key_length + offset;
         let eff_index = index % eff_key_length;
 
-        //Before the offset
+        // before the offset
         let next = if eff_index < offset {
-            //calculate the inverted index (index starting from the last character)
+            // calculate the inverted index (index starting from the last character)
             eff_key_length - eff_index - offset - 1
         } else {
-            //calculate the index adjusting for any previous offset
+            // calculate the index adjusting for any previous offset
             eff_index - offset
         };
 
         NextKey::KeyIndex(next)
     }
+
+    fn effective_period(&self, key_length: usize, _plaintext_length: usize) -> Option<usize> {
+        // a single symbol key repeats at every character, however it runs
+        match key_length {
+            1 => Some(1),
+            _ => Some(key_length + self.offset % (key_length + 1)),
+        }
+    }
 }
 
 impl crate::rng::FromRng for OffsetReverse {
     fn from_rng(rng: &mut crate::rng::Rng) -> Self {
         Self {
-            offset: rng.next() as usize % 17,
+            offset: rng.gen_range(0..17),
         }
     }
 }
@@ -42,68 +53,87 @@ impl crate::rng::FromRng for OffsetReverse {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ciphers::schedulers::expand;
+    use crate::ciphers::schedulers::testing::assert_keystream;
+    use crate::ciphers::Encryptor;
+    use crate::testing::{from_rng, plaintext_words, round_trip};
+    use proptest::{prop_assert_ne, proptest};
 
     #[test]
     fn simple() {
         let key = b"ABCDEF";
-        let effective_key = b"ABCDEF";
+        let effective_key = "ABCDEF";
         let offsetreverse = OffsetReverse { offset: 0 };
 
-        let mut index = 0;
-        println!("key len is {}", key.len());
-        println!("effective key len is {}", effective_key.len());
-        for _ in 0..500 {
-            for expected in 0..effective_key.len() {
-                let computed = offsetreverse
-                    .schedule(index, key.len(), 1000)
-                    .index_or_panic();
-                println!("{}", key[computed]);
-                println!("{}", effective_key[expected]);
-                assert_eq!(effective_key[expected], key[computed]);
-                index += 1;
-            }
-        }
+        assert_keystream(&offsetreverse, key, 1000, effective_key);
     }
+
     #[test]
     fn with_offset() {
         let key = b"ABCDEF";
-        let effective_key = b"FEDABCDEF";
+        let effective_key = "FEDABCDEF";
         let offsetreverse = OffsetReverse { offset: 3 };
 
-        let mut index = 0;
-        println!("key len is {}", key.len());
-        println!("effective key len is {}", effective_key.len());
-        for _ in 0..500 {
-            for expected in 0..effective_key.len() {
-                let computed = offsetreverse
-                    .schedule(index, key.len(), 1000)
-                    .index_or_panic();
-                println!("{}", key[computed]);
-                println!("{}", effective_key[expected]);
-                assert_eq!(effective_key[expected], key[computed]);
-                index += 1;
-            }
-        }
+        assert_keystream(&offsetreverse, key, 1000, effective_key);
     }
+
     #[test]
     fn full_reverse() {
         let key = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
-        let effective_key = b"ZYXWVUTSRQPONMLKJIHGFEDCBAABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let effective_key = "ZYXWVUTSRQPONMLKJIHGFEDCBAABCDEFGHIJKLMNOPQRSTUVWXYZ";
         let offsetreverse = OffsetReverse { offset: 26 };
 
-        let mut index = 0;
-        println!("key len is {}", key.len());
-        println!("effective key len is {}", effective_key.len());
-        for _ in 0..500 {
-            for expected in 0..effective_key.len() {
-                let computed = offsetreverse
-                    .schedule(index, key.len(), 1000)
-                    .index_or_panic();
-                println!("computed  {}", key[computed] as char);
-                println!("should be {}", effective_key[expected] as char);
-                assert_eq!(effective_key[expected], key[computed]);
-                index += 1;
+        assert_keystream(&offsetreverse, key, 1000, effective_key);
+    }
+
+    #[test]
+    fn oversized_offset_wraps() {
+        // offset wraps modulo (key_length + 1), so 7 on a 6 char key is no offset at all
+        let key = [0; 6];
+        let wrapped = expand(&OffsetReverse { offset: 7 }, &key, 60);
+        let plain = expand(&OffsetReverse { offset: 0 }, &key, 60);
+        assert_eq!(wrapped, plain);
+    }
+
+    #[test]
+    fn properties() {
+        for key_length in 1..25 {
+            let key = vec![0; key_length];
+
+            for offset in 0..=key_length {
+                let sched = OffsetReverse { offset };
+                let period = key_length + offset;
+                let keystream: Vec<usize> = expand(&sched, &key, period * 4)
+                    .iter()
+                    .map(|n| n.index_or_panic())
+                    .collect();
+
+                // the effective key repeats every key_length + offset characters
+                for (i, k) in keystream.iter().enumerate().skip(period) {
+                    assert_eq!(*k, keystream[i - period]);
+                }
+
+                // the first `offset` indices run backwards from the end of the key
+                for (i, k) in keystream.iter().take(offset).enumerate() {
+                    assert_eq!(*k, key_length - 1 - i);
+                }
+
+                // then the whole key is used in order
+                for (i, k) in keystream[offset..period].iter().enumerate() {
+                    assert_eq!(*k, i);
+                }
             }
         }
     }
+
+    proptest! {
+        #[test]
+        fn round_trips(
+            encryptor in from_rng::<Encryptor<OffsetReverse>>(),
+            plaintext in plaintext_words(10..150),
+        ) {
+            let ciphertext = round_trip(&encryptor, &plaintext)?;
+            prop_assert_ne!(ciphertext, plaintext);
+        }
+    }
 }
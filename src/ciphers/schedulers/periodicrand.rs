@@ -15,6 +15,7 @@ use super::{KeySchedule, NextKey};
 /// overwrite: false }`, then the expected output keystream is `A_BCD_EFG_ABC_DEF_GAB_CDE_FG`
 /// repeating, where `_` is some random character.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PeriodicRand {
     /// Number of characters between random chars
     pub period: usize,
@@ -26,8 +27,8 @@ pub struct PeriodicRand {
 
 impl PeriodicRand {
     /// Test if current index should be rand
-    fn random_at(&self, index: usize) -> bool {
-        index >= self.start && (index - self.start) % self.period == 0
+    pub(crate) fn random_at(&self, index: usize) -> bool {
+        index >= self.start && (index - self.start).is_multiple_of(self.period)
     }
 
     /// Calculate how many insertions have been done already
@@ -60,7 +61,7 @@ impl KeySchedule for PeriodicRand {
 // let sched = (PeriodicRand { period: 7, start: 7, overwrite: false }, Aab { .. });
 // sched.schedule(0, 12, 500);
 // ```
-impl<K: KeySchedule> KeySchedule for (&PeriodicRand, &K) {
+impl<K: KeySchedule + ?Sized> KeySchedule for (&PeriodicRand, &K) {
     fn schedule(&self, mut index: usize, key_length: usize, plaintext_length: usize) -> NextKey {
         let prand = &self.0;
         let other = &self.1;
@@ -80,15 +81,23 @@ impl<K: KeySchedule> KeySchedule for (&PeriodicRand, &K) {
     }
 }
 
+// The same, owning the scheduler underneath, so layers can be stacked on a scheduler that is only
+// known at runtime.
+impl<K: KeySchedule + ?Sized> KeySchedule for (PeriodicRand, Box<K>) {
+    fn schedule(&self, index: usize, key_length: usize, plaintext_length: usize) -> NextKey {
+        (&self.0, &*self.1).schedule(index, key_length, plaintext_length)
+    }
+}
+
 impl crate::rng::FromRng for PeriodicRand {
     fn from_rng(rng: &mut crate::rng::Rng) -> Self {
         Self {
             // make the period at least 32 so we have a chance at recovering plaintext
-            period: 32 + rng.next() as usize % 32,
+            period: rng.gen_range(32..64),
             // let start be anything up to 32
-            start: rng.next() as usize % 32,
+            start: rng.gen_range(0..32),
             // overwrite vs. insert can be random
-            overwrite: rng.next() & 1 == 0,
+            overwrite: rng.gen_bool(0.5),
         }
     }
 }
@@ -96,6 +105,7 @@ impl crate::rng::FromRng for PeriodicRand {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ciphers::schedulers::testing::assert_keystream;
 
     #[test]
     fn periodic() {
@@ -106,32 +116,7 @@ mod tests {
             overwrite: false,
         };
 
-        use NextKey::*;
-
-        #[rustfmt::skip]
-        let effective_key_indices = [KeyIndex(0), Rand,
-           KeyIndex(1), KeyIndex(2), KeyIndex(3), Rand,
-           KeyIndex(4), KeyIndex(5), KeyIndex(6), Rand,
-           KeyIndex(0), KeyIndex(1), KeyIndex(2), Rand,
-           KeyIndex(3), KeyIndex(4), KeyIndex(5), Rand,
-           KeyIndex(6), KeyIndex(0), KeyIndex(1), Rand,
-           KeyIndex(2), KeyIndex(3), KeyIndex(4), Rand,
-           KeyIndex(5), KeyIndex(6)];
-
-        let mut index = 0;
-        for _ in 0..500 {
-            for expected in 0..effective_key_indices.len() {
-                let computed = sched.schedule(index, key.len(), 1000);
-                assert_eq!(effective_key_indices[expected], computed);
-                if let KeyIndex(computed) = computed {
-                    assert_eq!(
-                        key[effective_key_indices[expected].index_or_panic()],
-                        key[computed]
-                    );
-                }
-                index += 1;
-            }
-        }
+        assert_keystream(&sched, key, 1000, "A_BCD_EFG_ABC_DEF_GAB_CDE_FG");
     }
 
     #[test]
@@ -139,7 +124,7 @@ mod tests {
         use crate::ciphers::schedulers::Aab;
 
         let key = b"aBCDefg";
-        let effective_key = b"aBCD_\
+        let effective_key = "aBCD_\
                             BCDBCD_\
                             efgaBC_\
                             DBCDBC_\
@@ -166,18 +151,11 @@ mod tests {
         };
 
         let sched = (&rand, &aab);
+        // the same layer, over a scheduler only known at runtime
+        let boxed: Box<dyn KeySchedule> = Box::new(aab);
+        let boxed = (rand, boxed);
 
-        let mut index = 0;
-        for _ in 0..500 {
-            for expected in 0..effective_key.len() {
-                let computed = sched.schedule(index, key.len(), 1000);
-                if let NextKey::KeyIndex(index) = computed {
-                    assert_eq!(effective_key[expected], key[index]);
-                } else {
-                    assert_eq!(effective_key[expected], b'_');
-                }
-                index += 1;
-            }
-        }
+        assert_keystream(&sched, key, 1000, effective_key);
+        assert_keystream(&boxed, key, 1000, effective_key);
     }
 }
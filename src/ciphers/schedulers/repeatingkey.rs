@@ -2,11 +2,12 @@
 ///
 /// Example with key `HEADCRAB` and plaintext: `RISE AND SHINE MISTER FREEMAN RISE AND SHINE`:
 ///
-/// ```
+/// ```text
 ///  Plaintext:     RISE AND SHINE MISTER FREEMAN RISE AND SHINE
 /// Shifted by:     HEADCRABHEADCRABHEADCRABHEADCRABHEADCRABHEAD
 /// ```
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RepeatingKey;
 
 use super::{KeySchedule, NextKey};
@@ -15,4 +16,8 @@ impl KeySchedule for RepeatingKey {
     fn schedule(&self, index: usize, key_length: usize, _: usize) -> NextKey {
         NextKey::KeyIndex(index % key_length)
     }
+
+    fn effective_period(&self, key_length: usize, _: usize) -> Option<usize> {
+        Some(key_length)
+    }
 }
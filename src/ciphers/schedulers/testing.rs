@@ -0,0 +1,37 @@
+//! Helpers for testing the schedulers of this module.
+
+use super::{KeySchedule, NextKey};
+
+/// Periods of the expected keystream [`assert_keystream`] checks.
+const PERIODS: usize = 500;
+
+/// Assert that `sched` picks the keystream `expected` for a plaintext `plaintext_length`
+/// characters long, over and over.
+///
+/// Each character of `expected` is the label in `key` of the key symbol picked at that index, or
+/// `_` where the scheduler inserts a random character. Labels only need to tell the key symbols
+/// apart, like `b"ABCdefg"` for a key of 7 symbols. `expected` is one period of the keystream, and
+/// is checked [`PERIODS`] times in a row.
+#[track_caller]
+pub fn assert_keystream<K: KeySchedule + ?Sized>(
+    sched: &K,
+    key: &[u8],
+    plaintext_length: usize,
+    expected: &str,
+) {
+    let period = expected.as_bytes();
+    for index in 0..period.len() * PERIODS {
+        let picked = match sched.schedule(index, key.len(), plaintext_length) {
+            NextKey::KeyIndex(key_index) => key[key_index],
+            NextKey::Rand => b'_',
+        };
+        assert_eq!(
+            picked as char,
+            period[index % period.len()] as char,
+            "{:?} at index {} of a {} character plaintext",
+            sched,
+            index,
+            plaintext_length
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Module for parsing command line arguments.
+//!
+//! Arguments are either flags with a value (`--timeout 10` or `--timeout=10`), switches without a
+//! value (`--verbose`), or positional arguments. Which flags are switches has to be declared up
+//! front, otherwise `--verbose ciphertext.txt` would be ambiguous.
+
+use anyhow::{anyhow, bail, Context};
+use std::str::FromStr;
+
+/// Parsed command line arguments.
+#[derive(Debug, Default)]
+pub struct Args {
+    /// `(name, value)` of every flag given, in order. Switches have an empty value.
+    flags: Vec<(String, String)>,
+    /// Arguments that are not flags, in order
+    pub positional: Vec<String>,
+}
+
+impl Args {
+    /// Parse the arguments of this process, skipping the program name.
+    pub fn from_env(switches: &[&str]) -> anyhow::Result<Self> {
+        Self::parse(std::env::args().skip(1), switches)
+    }
+
+    /// Parse arguments. Any flag named in `switches` does not take a value.
+    pub fn parse<I, S>(args: I, switches: &[&str]) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().map(Into::into);
+
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                None => {
+                    parsed.positional.push(arg);
+                    continue;
+                }
+            };
+
+            // --name=value
+            if let Some((name, value)) = name.split_once('=') {
+                parsed.flags.push((name.to_string(), value.to_string()));
+                continue;
+            }
+
+            // --switch
+            if switches.contains(&name) {
+                parsed.flags.push((name.to_string(), String::new()));
+                continue;
+            }
+
+            // --name value
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("flag --{} needs a value", name))?;
+            parsed.flags.push((name.to_string(), value));
+        }
+
+        Ok(parsed)
+    }
+
+    /// The value of a flag, parsed as `T`, or `None` if the flag was not given. If the flag was
+    /// given more than once, the last value wins.
+    pub fn value<T>(&self, name: &str) -> anyhow::Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        match self.flags.iter().rev().find(|(n, _)| n == name) {
+            None => Ok(None),
+            Some((_, value)) => value
+                .parse()
+                .map(Some)
+                .with_context(|| format!("invalid value for --{}: {:?}", name, value)),
+        }
+    }
+
+    /// Same as [`value`][`Self::value`], but the flag is removed, for flags every subcommand
+    /// takes that are handled before picking the subcommand.
+    pub fn take<T>(&mut self, name: &str) -> anyhow::Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let value = self.value(name)?;
+        self.flags.retain(|(n, _)| n != name);
+        Ok(value)
+    }
+
+    /// The comma separated values of a flag, each parsed as `T`, or `None` if the flag was not
+    /// given.
+    pub fn list<T>(&self, name: &str) -> anyhow::Result<Option<Vec<T>>>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let list = match self.value::<String>(name)? {
+            None => return Ok(None),
+            Some(list) => list,
+        };
+
+        list.split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("invalid value in --{}: {:?}", name, value))
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Some)
+    }
+
+    /// Fail if any flag was given that is not in `known`, to catch typos.
+    pub fn reject_unknown(&self, known: &[&str]) -> anyhow::Result<()> {
+        for (name, _) in &self.flags {
+            if !known.contains(&name.as_str()) {
+                bail!("unknown flag --{}", name);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_and_positional() {
+        let args = Args::parse(
+            vec![
+                "crack",
+                "--timeout",
+                "2.5",
+                "--verbose",
+                "--seed=7",
+                "extra",
+            ],
+            &["verbose"],
+        )
+        .unwrap();
+
+        assert_eq!(args.positional, vec!["crack", "extra"]);
+        assert_eq!(args.value::<f64>("timeout").unwrap(), Some(2.5));
+        assert_eq!(args.value::<u64>("seed").unwrap(), Some(7));
+        assert_eq!(args.value::<u64>("quiet").unwrap(), None);
+    }
+
+    #[test]
+    fn lists() {
+        let args = Args::parse(vec!["--words", "10,50, 100", "--bad=1,x"], &[]).unwrap();
+        assert_eq!(
+            args.list::<usize>("words").unwrap(),
+            Some(vec![10, 50, 100])
+        );
+        assert_eq!(args.list::<usize>("count").unwrap(), None);
+        assert!(args.list::<usize>("bad").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Args::parse(vec!["--timeout"], &[]).is_err());
+
+        let args = Args::parse(vec!["--timeout", "soon"], &[]).unwrap();
+        assert!(args.value::<f64>("timeout").is_err());
+        assert!(args.reject_unknown(&["seed"]).is_err());
+        assert!(args.reject_unknown(&["timeout"]).is_ok());
+    }
+
+    #[test]
+    fn take() {
+        let mut args =
+            Args::parse(vec!["--threads", "4", "--pin", "--threads=2"], &["pin"]).unwrap();
+        assert_eq!(args.take::<usize>("threads").unwrap(), Some(2));
+        assert_eq!(args.take::<usize>("threads").unwrap(), None);
+        assert_eq!(args.take::<String>("pin").unwrap(), Some(String::new()));
+        assert!(args.reject_unknown(&[]).is_ok());
+
+        let mut args = Args::parse(vec!["--threads", "many"], &[]).unwrap();
+        assert!(args.take::<usize>("threads").is_err());
+    }
+}
@@ -0,0 +1,224 @@
+//! Reproducible benchmark workloads for the cracker.
+//!
+//! A [`Bench`] generates plaintexts of the given lengths, encrypts each with every selected
+//! scheduler family, and cracks them one at a time. The time spent in each stage of cracking is
+//! collected into a [`BenchReport`], which gives a performance baseline to compare changes against.
+
+use crate::ciphers::schedulers::{RandomScheduler, RepeatingKey, SchedulerKind};
+use crate::ciphers::{Cipher, Encryptor};
+use crate::crack::{
+    best_crack, crack, crack_keylengths, guesses, spellcheck_all, CrackResult, DictionaryStats,
+    Frequencies,
+};
+use crate::dict::{BytesDictionary, Dictionary};
+use crate::gen::Generator;
+use crate::rng::{FromRng, Rng};
+use crate::utils::*;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Benchmark workload. Every scheduler family is run `count` times at every plaintext length.
+#[derive(Clone, Debug)]
+pub struct Bench {
+    /// Base scheduler families to encrypt with
+    pub schedulers: Vec<SchedulerKind>,
+    /// Plaintext lengths, in number of words
+    pub plaintext_words: Vec<usize>,
+    /// Number of plaintexts per scheduler and plaintext length
+    pub count: usize,
+    /// Seed for generating plaintexts, keys and schedulers, so workloads are the same every run
+    pub seed: u64,
+}
+
+impl Default for Bench {
+    fn default() -> Self {
+        Self {
+            schedulers: SchedulerKind::ALL.to_vec(),
+            plaintext_words: vec![100],
+            count: 10,
+            seed: 0xbe9c,
+        }
+    }
+}
+
+/// Time spent in each stage of cracking, summed over all ciphertexts.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StageTimes {
+    pub keylength: Duration,
+    pub crack: Duration,
+    pub spellcheck: Duration,
+}
+
+impl StageTimes {
+    pub fn total(&self) -> Duration {
+        self.keylength + self.crack + self.spellcheck
+    }
+}
+
+/// Outcome of a [`Bench`] run.
+#[derive(Clone, Debug, Default)]
+pub struct BenchReport {
+    /// Number of ciphertexts cracked
+    pub ciphertexts: usize,
+    /// Number of ciphertexts cracked back to the exact plaintext
+    pub successes: usize,
+    /// Total number of ciphertext characters cracked
+    pub chars: usize,
+    pub stages: StageTimes,
+}
+
+impl BenchReport {
+    pub fn success_rate(&self) -> f64 {
+        self.successes as f64 / self.ciphertexts.max(1) as f64
+    }
+
+    /// Ciphertext characters cracked per second, counting only time spent cracking.
+    pub fn chars_per_sec(&self) -> f64 {
+        self.chars as f64 / self.stages.total().as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let millis = |d: Duration| d.as_secs_f64() * 1000.0;
+        writeln!(
+            f,
+            "{} ciphertexts, {} chars, {:.1}% success",
+            self.ciphertexts,
+            self.chars,
+            self.success_rate() * 100.0
+        )?;
+        writeln!(f, "throughput: {:.0} chars/sec", self.chars_per_sec())?;
+        writeln!(f, "keylength:  {:>10.1} ms", millis(self.stages.keylength))?;
+        writeln!(f, "crack:      {:>10.1} ms", millis(self.stages.crack))?;
+        writeln!(f, "spellcheck: {:>10.1} ms", millis(self.stages.spellcheck))?;
+        write!(f, "total:      {:>10.1} ms", millis(self.stages.total()))
+    }
+}
+
+impl Bench {
+    /// Generate, encrypt and crack every ciphertext of the workload.
+    pub fn run(&self) -> BenchReport {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let bytes_dict = BytesDictionary::from_dict(&dict);
+        let baseline_freqs = &DictionaryStats::project().letters;
+        let mut gen = Generator::with_dict(&dict);
+
+        let mut rng = Rng::from_seed(self.seed);
+        let mut report = BenchReport::default();
+
+        for &kind in &self.schedulers {
+            for &num_words in &self.plaintext_words {
+                for _ in 0..self.count {
+                    // build this ciphertext
+                    let key = Key::from_rng(&mut rng);
+                    let sched = RandomScheduler::Zero(kind.generate(&mut rng));
+                    let encryptor = Encryptor::new(key, sched, Rng::from_rng(&mut rng));
+                    gen.rng = Rng::from_rng(&mut rng);
+                    let plaintext = gen.generate_words(num_words);
+                    let cipherbytes = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+                    // crack it, timing each stage
+                    let start = Instant::now();
+                    let mut keylen_guesses = Vec::new();
+                    guesses(&cipherbytes, &mut keylen_guesses);
+                    let guessed = Instant::now();
+                    let cracked = crack_keylengths(&cipherbytes, &keylen_guesses, baseline_freqs);
+                    let cracked_at = Instant::now();
+                    let best = best_crack(&spellcheck_all(&cracked, &bytes_dict));
+                    let spellchecked = Instant::now();
+
+                    report.stages.keylength += guessed - start;
+                    report.stages.crack += cracked_at - guessed;
+                    report.stages.spellcheck += spellchecked - cracked_at;
+                    report.ciphertexts += 1;
+                    report.chars += cipherbytes.len();
+                    report.successes += (bytes_to_str(&best.plaintext) == plaintext) as usize;
+                }
+            }
+        }
+
+        report
+    }
+}
+
+/// Inputs for benchmarking each step of cracking on its own. The same seed always gives the same
+/// inputs.
+pub struct BenchInputs {
+    pub plaintext: String,
+    /// Ciphertext of `plaintext`, encrypted with a repeating key of `key_length`
+    pub cipherbytes: Vec<u8>,
+    pub key_length: usize,
+    /// Keylength guesses for `cipherbytes`
+    pub keylen_guesses: Vec<(usize, f64)>,
+    /// Frequency crack of `cipherbytes` at the right keylength, ready to be spellchecked
+    pub cracked: CrackResult,
+    pub baseline_freqs: Frequencies,
+    pub bytes_dict: BytesDictionary,
+}
+
+/// Generate a plaintext of `plaintext_words` project dictionary words, encrypt it, and run each
+/// step of cracking once, so benchmarks can time any one step without repeating the setup.
+pub fn bench_inputs(seed: u64, plaintext_words: usize) -> BenchInputs {
+    let mut words = include_str!("../../words/default.txt").to_string();
+    let dict = Dictionary::from_string(&mut words);
+    let bytes_dict = BytesDictionary::from_dict(&dict);
+    let baseline_freqs = DictionaryStats::project().letters.clone();
+
+    let mut rng = Rng::from_seed(seed);
+    let key = Key::from_rng(&mut rng);
+    let key_length = key.len();
+    let encryptor = Encryptor::new(key, RepeatingKey, Rng::from_rng(&mut rng));
+    let mut gen = Generator::with_dict(&dict);
+    gen.rng = Rng::from_rng(&mut rng);
+    let plaintext = gen.generate_words(plaintext_words);
+    let cipherbytes = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+    let mut keylen_guesses = Vec::new();
+    guesses(&cipherbytes, &mut keylen_guesses);
+    let cracked = crack(&cipherbytes, key_length, &baseline_freqs);
+
+    BenchInputs {
+        plaintext,
+        cipherbytes,
+        key_length,
+        keylen_guesses,
+        cracked,
+        baseline_freqs,
+        bytes_dict,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_bench() {
+        let bench = Bench {
+            schedulers: vec![SchedulerKind::RepeatingKey, SchedulerKind::Aab],
+            plaintext_words: vec![20, 40],
+            count: 2,
+            seed: 3,
+        };
+        let report = bench.run();
+
+        assert_eq!(report.ciphertexts, 2 * 2 * 2);
+        assert!(report.chars > 0);
+        assert!(report.chars_per_sec() > 0.0);
+        assert!(report.to_string().contains("chars/sec"));
+    }
+
+    #[test]
+    fn inputs_are_repeatable() {
+        let a = bench_inputs(11, 30);
+        let b = bench_inputs(11, 30);
+        assert_eq!(a.plaintext, b.plaintext);
+        assert_eq!(a.cipherbytes, b.cipherbytes);
+        assert_eq!(a.cracked.plaintext, b.cracked.plaintext);
+        assert_eq!(a.cipherbytes.len(), a.plaintext.len());
+        assert_ne!(bench_inputs(12, 30).plaintext, a.plaintext);
+    }
+}
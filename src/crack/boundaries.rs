@@ -0,0 +1,182 @@
+//! Module for fixing column shifts that put spaces where dictionary words can't have them.
+//!
+//! Frequency analysis picks each column's shift on its own, and a column with few characters can
+//! pick a shift that looks fine by frequency but turns letters into spaces (or spaces into
+//! letters) all the way down the column. That shows up as two spaces in a row, or a word longer
+//! than any in the dictionary, so a runner-up shift of that column usually fits better.
+
+use super::{CrackResult, DictionaryStats};
+use crate::utils::{Shift, ALPHABET};
+
+/// Runner-up shifts of each column to try, besides the one it was cracked with.
+const CANDIDATES: usize = 3;
+
+/// Passes over every column before giving up on finding a better shift.
+const MAX_PASSES: usize = 3;
+
+/// Re-pick the shift of each column of a crack at `keylength`, penalizing impossible space
+/// patterns with [`DictionaryStats::word_boundary_penalty`].
+///
+/// Every column tries its best few shifts by letter frequency, keeping one if the frequency score
+/// plus the boundary penalty of the whole plaintext goes down. The confidence is scaled by how
+/// much the combined score changed.
+pub fn fix_word_boundaries(
+    ciphertext: &[u8],
+    keylength: usize,
+    cracked: &CrackResult,
+    stats: &DictionaryStats,
+) -> CrackResult {
+    if keylength == 0 || cracked.plaintext.len() != ciphertext.len() {
+        // not a crack of this ciphertext at this keylength
+        return cracked.clone();
+    }
+
+    let scores = column_scores(ciphertext, keylength, stats);
+    let candidates = best_shifts(&scores, CANDIDATES + 1);
+    let mut shifts = shifts_of(ciphertext, keylength, &cracked.plaintext);
+
+    let mut plaintext = cracked.plaintext.clone();
+    let total = |shifts: &[i8], plaintext: &[u8]| {
+        let frequency: f64 = shifts
+            .iter()
+            .zip(&scores)
+            .map(|(&shift, column)| column[shift as usize])
+            .sum();
+        frequency + stats.word_boundary_penalty(plaintext)
+    };
+    let original = total(&shifts, &plaintext);
+    let mut best = original;
+
+    for _ in 0..MAX_PASSES {
+        let mut improved = false;
+        for column in 0..keylength.min(ciphertext.len()) {
+            for &candidate in &candidates[column] {
+                let kept = shifts[column];
+                if candidate == kept {
+                    continue;
+                }
+
+                set_column(ciphertext, keylength, column, candidate, &mut plaintext);
+                shifts[column] = candidate;
+                let score = total(&shifts, &plaintext);
+                if score < best {
+                    best = score;
+                    improved = true;
+                } else {
+                    set_column(ciphertext, keylength, column, kept, &mut plaintext);
+                    shifts[column] = kept;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    CrackResult {
+        plaintext,
+        confidence: match original {
+            original if original > 0.0 => cracked.confidence * best / original,
+            _ => cracked.confidence,
+        },
+        timed_out: cracked.timed_out,
+    }
+}
+
+/// Frequency score of every shift of every column, lower is better.
+pub(super) fn column_scores(
+    ciphertext: &[u8],
+    keylength: usize,
+    stats: &DictionaryStats,
+) -> Vec<Vec<f64>> {
+    let mut histograms = vec![[0_u32; 27]; keylength];
+    for (index, &c) in ciphertext.iter().enumerate() {
+        histograms[index % keylength][c as usize] += 1;
+    }
+    histograms
+        .iter()
+        .map(|histogram| {
+            let total: u32 = histogram.iter().sum();
+            (0..ALPHABET.len() as u8)
+                .map(|shift| match total {
+                    0 => 0.0,
+                    _ => stats.letters.compare_shifted(histogram, total, shift) as f64,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The `count` best shifts of each column by `scores`, best first.
+pub(super) fn best_shifts(scores: &[Vec<f64>], count: usize) -> Vec<Vec<i8>> {
+    scores
+        .iter()
+        .map(|column| {
+            let mut shifts: Vec<i8> = (0..column.len() as i8).collect();
+            shifts.sort_by(|&a, &b| column[a as usize].total_cmp(&column[b as usize]));
+            shifts.truncate(count);
+            shifts
+        })
+        .collect()
+}
+
+/// The shift that decrypts each column of `ciphertext` to `plaintext`.
+pub(super) fn shifts_of(ciphertext: &[u8], keylength: usize, plaintext: &[u8]) -> Vec<i8> {
+    let len = ALPHABET.len() as i8;
+    (0..keylength)
+        .map(
+            |column| match (ciphertext.get(column), plaintext.get(column)) {
+                (Some(&c), Some(&p)) => (p as i8 - c as i8).rem_euclid(len),
+                _ => 0,
+            },
+        )
+        .collect()
+}
+
+/// Decrypt one column of the ciphertext with `shift`.
+pub(super) fn set_column(
+    ciphertext: &[u8],
+    keylength: usize,
+    column: usize,
+    shift: i8,
+    plaintext: &mut [u8],
+) {
+    for index in (column..ciphertext.len()).step_by(keylength) {
+        plaintext[index] = ciphertext[index].shift(shift);
+    }
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::prelude::*;
+    use crate::utils::str_to_bytes;
+
+    #[test]
+    fn fixes_wrong_column() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let stats = DictionaryStats::from_dict(&dict);
+        let plaintext = Generator::with_dict(&dict).generate_words(40);
+        let key = vec![3, 1, 4, 1, 5, 9];
+        let ciphertext =
+            Encryptor::new(key.clone(), RepeatingKey, Rng::default()).encrypt(&plaintext);
+        let ciphertext = str_to_bytes(&ciphertext);
+        let plainbytes = str_to_bytes(&plaintext);
+
+        // the right crack, but with one column off by a few shifts
+        let mut wrong = plainbytes.clone();
+        set_column(&ciphertext, key.len(), 2, -key[2] + 7, &mut wrong);
+        let cracked = CrackResult::new(wrong, 1.0);
+
+        let fixed = fix_word_boundaries(&ciphertext, key.len(), &cracked, &stats);
+        assert_eq!(fixed.plaintext, plainbytes);
+        assert!(fixed.confidence < cracked.confidence);
+
+        // and a right crack is left alone
+        let right = CrackResult::new(plainbytes.clone(), 1.0);
+        let fixed = fix_word_boundaries(&ciphertext, key.len(), &right, &stats);
+        assert_eq!(fixed.plaintext, plainbytes);
+    }
+}
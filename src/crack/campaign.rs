@@ -0,0 +1,822 @@
+//! Experiment campaigns that sweep over scheduler types, key lengths, plaintext lengths, and rand
+//! periods. Every combination of parameters is a "cell" of the campaign. Each cell gets cracked a
+//! number of times, and the success rate and timing of each cell is collected into a
+//! [`CampaignReport`] that can be saved as CSV or JSON.
+//!
+//! A long campaign can save a [`Checkpoint`] of the trials it has finished as it goes, and pick up
+//! from it after being interrupted. Every trial's seed comes from the campaign's seed in a fixed
+//! order, so the trials left to run are the same ones the interrupted campaign would have run.
+
+use crate::ciphers::schedulers::{PeriodicRand, RandomScheduler, SchedulerKind};
+use crate::ciphers::{Cipher, Encryptor};
+use crate::crack::threads;
+use crate::crack::worker::{crack_test2, ResultsChannel, ResultsStats, WhenFull};
+use crate::crack::{guesses, DictionaryStats, SegmentationWeights};
+use crate::dict::{normalized_levenshtein, BytesDictionary, Dictionary};
+use crate::gen::{Generator, MessageLength, MessageShape};
+use crate::rng::{FromRng, Rng};
+use crate::utils::*;
+
+use anyhow::{bail, Context};
+use crossbeam_channel::unbounded;
+use std::borrow::Cow;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How often [`Campaign::run_checkpointed`] saves its checkpoint.
+pub const CHECKPOINT_EVERY: Duration = Duration::from_secs(30);
+
+/// Parameters to sweep over. Every combination of the values is run `trials` times.
+#[derive(Clone, Debug)]
+pub struct Campaign {
+    /// Base scheduler families to test
+    pub schedulers: Vec<SchedulerKind>,
+    /// Key lengths to test
+    pub key_lengths: Vec<usize>,
+    /// Plaintext lengths to test, in number of words. Each trial's plaintext is within a quarter
+    /// of its cell's word count, rather than always exactly that long.
+    pub plaintext_words: Vec<usize>,
+    /// Words and spacing of the plaintexts. The length comes from each cell's `plaintext_words`.
+    pub message_shape: MessageShape,
+    /// Periods of a [`PeriodicRand`] layer on top of the base scheduler. `None` means no random
+    /// characters at all.
+    pub rand_periods: Vec<Option<usize>>,
+    /// Number of trials to run per cell
+    pub trials: usize,
+    /// Seed for all randomness in the campaign, so campaigns can be repeated exactly
+    pub seed: u64,
+    /// Number of worker threads to crack with
+    pub num_workers: usize,
+    /// Generate and crack with this many words of the project dictionary, sampled with the
+    /// campaign's seed, for quicker sweeps. `None` uses every word.
+    pub dictionary_words: Option<NonZeroUsize>,
+    /// How many finished trials wait for the campaign to count and checkpoint them. Workers wait
+    /// for room beyond that, since a dropped trial would only be left out of the report and run
+    /// again on resume.
+    pub results_capacity: usize,
+}
+
+impl Default for Campaign {
+    fn default() -> Self {
+        Self {
+            schedulers: SchedulerKind::ALL.to_vec(),
+            key_lengths: vec![5, 9, 13, 17, 21],
+            plaintext_words: vec![50, 100, 200],
+            rand_periods: vec![None, Some(48)],
+            message_shape: MessageShape::default(),
+            trials: 10,
+            seed: 0x5eed,
+            num_workers: threads::threads(),
+            dictionary_words: None,
+            results_capacity: ResultsChannel::default().capacity,
+        }
+    }
+}
+
+/// One combination of campaign parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub scheduler: SchedulerKind,
+    pub key_length: usize,
+    pub plaintext_words: usize,
+    pub rand_period: Option<usize>,
+}
+
+/// Results collected over all trials of one [`Cell`].
+#[derive(Clone, Debug)]
+pub struct CellResult {
+    pub cell: Cell,
+    /// Number of trials run
+    pub trials: usize,
+    /// Number of trials where the plaintext was recovered exactly
+    pub successes: usize,
+    /// Sum of levenshtein distance divided by plaintext length over all trials
+    pub total_error: f64,
+    /// Total time spent cracking over all trials
+    pub total_time: Duration,
+}
+
+impl CellResult {
+    pub fn success_rate(&self) -> f64 {
+        self.successes as f64 / self.trials.max(1) as f64
+    }
+
+    pub fn mean_error(&self) -> f64 {
+        self.total_error / self.trials.max(1) as f64
+    }
+
+    pub fn mean_millis(&self) -> f64 {
+        self.total_time.as_secs_f64() * 1000.0 / self.trials.max(1) as f64
+    }
+
+    /// Count one more trial of the cell.
+    fn add(&mut self, outcome: &TrialOutcome) {
+        self.trials += 1;
+        self.successes += outcome.success as usize;
+        self.total_error += outcome.error;
+        self.total_time += outcome.time;
+    }
+}
+
+/// The outcome of a whole campaign, one entry per cell.
+#[derive(Clone, Debug)]
+pub struct CampaignReport {
+    pub cells: Vec<CellResult>,
+    /// What happened to the results of the trials this run cracked
+    pub results: ResultsStats,
+}
+
+/// Outcome of cracking a single trial.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TrialOutcome {
+    success: bool,
+    error: f64,
+    time: Duration,
+}
+
+/// The trials a campaign has finished, to resume it from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    /// Seed of the campaign
+    seed: u64,
+    /// Every cell of the campaign, in order
+    cells: Vec<Cell>,
+    /// Number of trials per cell
+    trials: usize,
+    /// Number of dictionary words the campaign sampled, if it did
+    dictionary_words: Option<NonZeroUsize>,
+    /// Every finished trial, by its place in the order trials are queued, and its outcome
+    finished: Vec<(usize, TrialOutcome)>,
+}
+
+impl Checkpoint {
+    const HEADER: &'static str = "campaign checkpoint v2";
+    /// Most trials a checkpoint can cover, over all its cells, so a malformed one can't make
+    /// [`from_text`][`Self::from_text`] allocate without bound.
+    const MAX_TRIALS: usize = 1 << 26;
+
+    /// A checkpoint of `campaign` before any trials have run.
+    pub fn new(campaign: &Campaign) -> Self {
+        Self {
+            seed: campaign.seed,
+            cells: campaign.cells(),
+            trials: campaign.trials,
+            dictionary_words: campaign.dictionary_words,
+            finished: Vec::new(),
+        }
+    }
+
+    /// Number of trials finished.
+    pub fn finished(&self) -> usize {
+        self.finished.len()
+    }
+
+    /// Whether this is a checkpoint of `campaign`, which has the same seed, cells, trials and
+    /// dictionary words.
+    pub fn is_of(&self, campaign: &Campaign) -> bool {
+        let Self {
+            seed,
+            cells,
+            trials,
+            dictionary_words,
+            ..
+        } = Self::new(campaign);
+        (self.seed, &self.cells, self.trials, self.dictionary_words)
+            == (seed, &cells, trials, dictionary_words)
+    }
+
+    /// Render the checkpoint as text: a header, the campaign it is of, a line per cell, and a line
+    /// per finished trial with its place, whether it succeeded, its error and its time in
+    /// nanoseconds.
+    pub fn to_text(&self) -> String {
+        let dictionary_words = match self.dictionary_words {
+            Some(n) => n.to_string(),
+            None => "all".to_string(),
+        };
+        let mut text = format!(
+            "{}\nseed {} trials {} words {}\n",
+            Self::HEADER,
+            self.seed,
+            self.trials,
+            dictionary_words
+        );
+        for cell in &self.cells {
+            let rand_period = match cell.rand_period {
+                Some(period) => period.to_string(),
+                None => "none".to_string(),
+            };
+            text.push_str(&format!(
+                "cell {} {} {} {}\n",
+                cell.scheduler.name(),
+                cell.key_length,
+                cell.plaintext_words,
+                rand_period
+            ));
+        }
+        for (trial, outcome) in &self.finished {
+            text.push_str(&format!(
+                "{} {} {} {}\n",
+                trial,
+                outcome.success as u8,
+                outcome.error,
+                outcome.time.as_nanos()
+            ));
+        }
+        text
+    }
+
+    /// Parse a checkpoint from [`to_text`][`Self::to_text`].
+    pub fn from_text(text: &str) -> anyhow::Result<Self> {
+        let mut lines = (1..).zip(text.lines()).peekable();
+        if lines.next().map(|(_, line)| line) != Some(Self::HEADER) {
+            bail!("not a campaign checkpoint");
+        }
+
+        let campaign = lines.next().map(|(_, line)| line).unwrap_or_default();
+        let fields: Vec<&str> = campaign.split_whitespace().collect();
+        let (seed, trials, dictionary_words) = match fields[..] {
+            ["seed", seed, "trials", trials, "words", words] => (seed, trials, words),
+            _ => bail!("invalid campaign line {:?}", campaign),
+        };
+        let mut checkpoint = Self {
+            seed: seed.parse()?,
+            cells: Vec::new(),
+            trials: trials.parse()?,
+            dictionary_words: match dictionary_words {
+                "all" => None,
+                words => Some(words.parse()?),
+            },
+            finished: Vec::new(),
+        };
+
+        while let Some((number, line)) = lines.next_if(|(_, line)| line.starts_with("cell ")) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match fields[..] {
+                ["cell", scheduler, key_length, plaintext_words, rand_period] => {
+                    (|| -> anyhow::Result<_> {
+                        Ok(Cell {
+                            scheduler: SchedulerKind::from_name(scheduler)
+                                .with_context(|| format!("no scheduler {:?}", scheduler))?,
+                            key_length: key_length.parse()?,
+                            plaintext_words: plaintext_words.parse()?,
+                            rand_period: match rand_period {
+                                "none" => None,
+                                period => Some(period.parse()?),
+                            },
+                        })
+                    })()
+                }
+                _ => Err(anyhow::anyhow!("expected 5 fields")),
+            };
+            checkpoint
+                .cells
+                .push(parsed.with_context(|| format!("invalid line {}", number))?);
+        }
+
+        let total = match checkpoint.cells.len().checked_mul(checkpoint.trials) {
+            Some(total) if total <= Self::MAX_TRIALS => total,
+            _ => bail!(
+                "{} cells of {} trials is more than {} trials",
+                checkpoint.cells.len(),
+                checkpoint.trials,
+                Self::MAX_TRIALS
+            ),
+        };
+        let mut seen = vec![false; total];
+        for (number, line) in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match fields[..] {
+                [trial, success, error, nanos] => (|| -> anyhow::Result<_> {
+                    let outcome = TrialOutcome {
+                        success: success.parse::<u8>()? != 0,
+                        error: error.parse()?,
+                        time: Duration::from_nanos(nanos.parse()?),
+                    };
+                    Ok((trial.parse()?, outcome))
+                })(),
+                _ => Err(anyhow::anyhow!("expected 4 fields")),
+            };
+            let (trial, outcome) = parsed.with_context(|| format!("invalid line {}", number))?;
+            match seen.get_mut(trial) {
+                None => bail!("line {}: no trial {} in the campaign", number, trial),
+                Some(true) => bail!("line {}: trial {} finished twice", number, trial),
+                Some(seen) => *seen = true,
+            }
+            checkpoint.finished.push((trial, outcome));
+        }
+
+        Ok(checkpoint)
+    }
+
+    /// Read a checkpoint from a file written by [`write`][`Self::write`].
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        Self::from_text(&text).with_context(|| format!("invalid checkpoint {}", path.display()))
+    }
+
+    /// Write the checkpoint to a file, replacing it all at once, so an interruption while writing
+    /// leaves the last checkpoint whole.
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        std::fs::write(&partial, self.to_text())
+            .with_context(|| format!("could not write {}", path.display()))?;
+        std::fs::rename(&partial, path)
+            .with_context(|| format!("could not write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+impl Campaign {
+    /// Every combination of parameters, in a fixed order.
+    pub fn cells(&self) -> Vec<Cell> {
+        let mut cells = Vec::new();
+        for &scheduler in &self.schedulers {
+            for &key_length in &self.key_lengths {
+                for &plaintext_words in &self.plaintext_words {
+                    for &rand_period in &self.rand_periods {
+                        cells.push(Cell {
+                            scheduler,
+                            key_length,
+                            plaintext_words,
+                            rand_period,
+                        });
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    /// Run every trial of every cell across the worker threads, and collect the results.
+    pub fn run(&self) -> CampaignReport {
+        let (report, _) = self
+            .run_from(Checkpoint::new(self), &mut |_| Ok(()))
+            .expect("nothing is saved");
+        report
+    }
+
+    /// Same as [`run`][`Self::run`], skipping the trials already finished in `resume`, and saving
+    /// a checkpoint of every finished trial to `path` every [`CHECKPOINT_EVERY`] and at the end.
+    /// Fails if `resume` is a checkpoint of another campaign, or the checkpoint can't be saved.
+    pub fn run_checkpointed(
+        &self,
+        resume: Option<Checkpoint>,
+        path: &Path,
+    ) -> anyhow::Result<CampaignReport> {
+        let checkpoint = match resume {
+            Some(checkpoint) if !checkpoint.is_of(self) => {
+                bail!("the checkpoint is of a campaign with another seed, cells, trials or dictionary")
+            }
+            Some(checkpoint) => checkpoint,
+            None => Checkpoint::new(self),
+        };
+
+        let mut saved = Instant::now();
+        let (report, checkpoint) = self.run_from(checkpoint, &mut |checkpoint| {
+            if saved.elapsed() < CHECKPOINT_EVERY {
+                return Ok(());
+            }
+            saved = Instant::now();
+            checkpoint.write(path)
+        })?;
+        checkpoint.write(path)?;
+        Ok(report)
+    }
+
+    /// Run the trials not finished in `checkpoint`, adding each one to it as it finishes and
+    /// passing it to `save`. Returns the report of every trial, and the checkpoint of them all.
+    fn run_from(
+        &self,
+        mut checkpoint: Checkpoint,
+        save: &mut dyn FnMut(&Checkpoint) -> anyhow::Result<()>,
+    ) -> anyhow::Result<(CampaignReport, Checkpoint)> {
+        let cells = self.cells();
+
+        let mut report = CampaignReport {
+            cells: cells
+                .iter()
+                .map(|&cell| CellResult {
+                    cell,
+                    trials: 0,
+                    successes: 0,
+                    total_error: 0.0,
+                    total_time: Duration::default(),
+                })
+                .collect(),
+            results: ResultsStats::default(),
+        };
+        let mut finished = vec![false; cells.len() * self.trials];
+        for (trial, outcome) in &checkpoint.finished {
+            finished[*trial] = true;
+            report.cells[trial / self.trials].add(outcome);
+        }
+
+        // queue up every trial along with its own seed, so results don't depend on which worker
+        // happens to pick up which trial. Finished trials still take their seed, so the rest get
+        // the same seeds they would have without a checkpoint
+        let (jobs_in, jobs_out) = unbounded();
+        let mut rng = Rng::from_seed(self.seed);
+        for (cell_index, cell) in cells.iter().enumerate() {
+            for n in 0..self.trials {
+                let trial = cell_index * self.trials + n;
+                let seed = rng.next();
+                if !finished[trial] {
+                    jobs_in.send((trial, *cell, seed)).unwrap();
+                }
+            }
+        }
+        drop(jobs_in);
+
+        // every worker generates and cracks with the same words. The project dictionary's stats
+        // are computed once for the whole process, and a sample's once for the whole campaign
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let (dict, stats) = match self.dictionary_words {
+            Some(n) => {
+                let dict = dict.sample(n, &mut Rng::from_seed(self.seed));
+                let stats = DictionaryStats::from_dict(&dict);
+                (dict, Cow::Owned(stats))
+            }
+            None => (dict, Cow::Borrowed(DictionaryStats::project())),
+        };
+        let (dict, stats) = (&dict, &*stats);
+
+        // finished trials wait for the campaign in a bounded channel, so a slow checkpoint doesn't
+        // pile them up in memory
+        let results = ResultsChannel {
+            capacity: self.results_capacity,
+            when_full: WhenFull::Block,
+        };
+        let (results_in, results_out, counters) = results.open();
+        let saving = std::thread::scope(|scope| {
+            for index in 0..self.num_workers.max(1) {
+                let jobs = jobs_out.clone();
+                let results = results_in.clone();
+                let shape = &self.message_shape;
+                scope.spawn(move || {
+                    threads::pin_current(index);
+                    let bytes_dict = BytesDictionary::from_dict(dict);
+                    let mut gen = Generator::with_dict(dict);
+
+                    // the jobs channel is closed once drained, ending this loop. Once the
+                    // campaign stops collecting results, the rest of the jobs are dropped
+                    for (trial, cell, seed) in jobs.iter() {
+                        let outcome = run_trial(cell, seed, shape, &mut gen, stats, &bytes_dict);
+                        if !results.send((trial, outcome)) {
+                            return;
+                        }
+                    }
+                });
+            }
+            drop(results_in);
+
+            // the results channel is closed once every worker exits
+            let mut saving = Ok(());
+            for (trial, outcome) in results_out.iter() {
+                report.cells[trial / self.trials].add(&outcome);
+                checkpoint.finished.push((trial, outcome));
+                saving = save(&checkpoint);
+                if saving.is_err() {
+                    break;
+                }
+            }
+            drop(results_out);
+            saving
+        });
+        saving?;
+        report.results = counters.stats();
+
+        Ok((report, checkpoint))
+    }
+}
+
+/// Generate a key of exactly `key_length`, with at least one non-zero shift.
+fn key_of_length(rng: &mut Rng, key_length: usize) -> Key {
+    loop {
+        let mut key: Key = (0..key_length)
+            .map(|_| rng.gen_range(0..ALPHABET.len()) as i8)
+            .collect();
+        key.reduce();
+        if key.iter().any(|k| k != &0) {
+            return key;
+        }
+    }
+}
+
+/// Build the scheduler for a cell with random parameters.
+fn scheduler_for(cell: &Cell, rng: &mut Rng) -> RandomScheduler {
+    let base = cell.scheduler.generate(rng);
+    match cell.rand_period {
+        None => RandomScheduler::Zero(base),
+        Some(period) => RandomScheduler::One(
+            base,
+            PeriodicRand {
+                period,
+                start: rng.gen_range(0..period),
+                overwrite: rng.gen_bool(0.5),
+            },
+        ),
+    }
+}
+
+fn run_trial(
+    cell: Cell,
+    seed: u64,
+    shape: &MessageShape,
+    gen: &mut Generator,
+    stats: &DictionaryStats,
+    bytes_dict: &BytesDictionary,
+) -> TrialOutcome {
+    let mut rng = Rng::from_seed(seed);
+
+    // build the encryptor and plaintext for this trial
+    let key = key_of_length(&mut rng, cell.key_length);
+    let sched = scheduler_for(&cell, &mut rng);
+    let encryptor = Encryptor::new(key, sched, Rng::from_rng(&mut rng));
+    gen.rng = Rng::from_rng(&mut rng);
+    let spread = cell.plaintext_words / 4;
+    let shape = MessageShape {
+        length: MessageLength::Words(
+            cell.plaintext_words - spread..cell.plaintext_words + spread + 1,
+        ),
+        ..shape.clone()
+    };
+    let plaintext = gen.generate_message(&shape);
+    let cipherbytes = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+    // crack it, timing only the cracking
+    let start = Instant::now();
+    let mut keylen_guesses = Vec::new();
+    guesses(&cipherbytes, &mut keylen_guesses);
+    let (best, _) = crack_test2(
+        &cipherbytes,
+        &keylen_guesses,
+        stats,
+        bytes_dict,
+        SegmentationWeights::default(),
+    );
+    let time = start.elapsed();
+
+    let plainbytes = str_to_bytes(&plaintext);
+    let error = normalized_levenshtein(&best.plaintext, &plainbytes);
+
+    TrialOutcome {
+        success: best.plaintext == plainbytes,
+        error,
+        time,
+    }
+}
+
+impl CampaignReport {
+    const CSV_HEADER: &'static str = "scheduler,key_length,plaintext_words,rand_period,trials,\
+                                      successes,success_rate,mean_error,mean_millis";
+
+    /// Render the report as CSV with a header row. Cells without random characters have an empty
+    /// rand_period.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(Self::CSV_HEADER);
+        csv.push('\n');
+
+        for r in &self.cells {
+            let rand_period = r.cell.rand_period.map(|p| p.to_string());
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{:.4},{:.4},{:.3}\n",
+                r.cell.scheduler.name(),
+                r.cell.key_length,
+                r.cell.plaintext_words,
+                rand_period.unwrap_or_default(),
+                r.trials,
+                r.successes,
+                r.success_rate(),
+                r.mean_error(),
+                r.mean_millis(),
+            ));
+        }
+
+        csv
+    }
+
+    /// Render the report as a JSON array with one object per cell. Cells without random
+    /// characters have a `null` rand_period.
+    pub fn to_json(&self) -> String {
+        let cells: Vec<String> = self
+            .cells
+            .iter()
+            .map(|r| {
+                let rand_period = r.cell.rand_period.map(|p| p.to_string());
+                format!(
+                    "{{\"scheduler\":\"{}\",\"key_length\":{},\"plaintext_words\":{},\
+                     \"rand_period\":{},\"trials\":{},\"successes\":{},\"success_rate\":{:.4},\
+                     \"mean_error\":{:.4},\"mean_millis\":{:.3}}}",
+                    r.cell.scheduler.name(),
+                    r.cell.key_length,
+                    r.cell.plaintext_words,
+                    rand_period.as_deref().unwrap_or("null"),
+                    r.trials,
+                    r.successes,
+                    r.success_rate(),
+                    r.mean_error(),
+                    r.mean_millis(),
+                )
+            })
+            .collect();
+
+        format!("[\n  {}\n]\n", cells.join(",\n  "))
+    }
+
+    /// Write the report to a file, as JSON if the file ends in `.json` and as CSV otherwise.
+    pub fn write(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let contents = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(),
+            _ => self.to_csv(),
+        };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiny_campaign() -> Campaign {
+        Campaign {
+            schedulers: vec![SchedulerKind::RepeatingKey, SchedulerKind::Aab],
+            key_lengths: vec![5],
+            plaintext_words: vec![30],
+            rand_periods: vec![None],
+            message_shape: MessageShape::default(),
+            trials: 1,
+            seed: 7,
+            num_workers: 2,
+            dictionary_words: None,
+            results_capacity: ResultsChannel::default().capacity,
+        }
+    }
+
+    #[test]
+    fn cells_cover_every_combination() {
+        let campaign = Campaign::default();
+        let cells = campaign.cells();
+        assert_eq!(cells.len(), 6 * 5 * 3 * 2);
+        assert_eq!(cells[0].scheduler, SchedulerKind::Aab);
+        assert_eq!(cells[1].rand_period, Some(48));
+    }
+
+    #[test]
+    fn tiny_report() {
+        let report = tiny_campaign().run();
+        assert_eq!(report.cells.len(), 2);
+        assert!(report.cells.iter().all(|r| r.trials == 1));
+
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("scheduler,key_length"));
+        assert!(lines[1].starts_with("RepeatingKey,5,30,,1,"));
+
+        let json = report.to_json();
+        assert!(json.contains("\"scheduler\":\"Aab\""));
+        assert!(json.contains("\"rand_period\":null"));
+    }
+
+    #[test]
+    fn repeatable() {
+        let a = tiny_campaign().run();
+        let b = tiny_campaign().run();
+        for (a, b) in a.cells.iter().zip(b.cells.iter()) {
+            assert_eq!(a.successes, b.successes);
+            assert_eq!(a.total_error, b.total_error);
+        }
+    }
+
+    #[test]
+    fn sampled_dictionary() {
+        let campaign = Campaign {
+            dictionary_words: NonZeroUsize::new(12),
+            trials: 2,
+            ..tiny_campaign()
+        };
+        let a = campaign.run();
+        let b = campaign.run();
+        for (a, b) in a.cells.iter().zip(b.cells.iter()) {
+            assert_eq!(a.trials, 2);
+            assert_eq!(a.successes, b.successes);
+            assert_eq!(a.total_error, b.total_error);
+        }
+    }
+
+    #[test]
+    fn bounded_results() {
+        // workers wait for room behind a single result, and every trial is still counted
+        let campaign = Campaign {
+            trials: 3,
+            results_capacity: 1,
+            ..tiny_campaign()
+        };
+        let report = campaign.run();
+        assert!(report.cells.iter().all(|r| r.trials == 3));
+        assert_eq!(report.results.sent, 6);
+        assert_eq!(report.results.dropped, 0);
+    }
+
+    #[test]
+    fn checkpoint_text() {
+        let mut checkpoint = Checkpoint::new(&tiny_campaign());
+        checkpoint.finished.push((
+            1,
+            TrialOutcome {
+                success: false,
+                error: 0.1 + 0.2,
+                time: Duration::from_nanos(123_456_789),
+            },
+        ));
+        let text = checkpoint.to_text();
+        assert_eq!(
+            text,
+            "campaign checkpoint v2\nseed 7 trials 1 words all\n\
+             cell RepeatingKey 5 30 none\ncell Aab 5 30 none\n\
+             1 0 0.30000000000000004 123456789\n"
+        );
+        assert_eq!(Checkpoint::from_text(&text).unwrap(), checkpoint);
+
+        let sampled = Campaign {
+            dictionary_words: NonZeroUsize::new(12),
+            rand_periods: vec![Some(48)],
+            ..tiny_campaign()
+        };
+        let sampled = Checkpoint::new(&sampled);
+        assert_eq!(Checkpoint::from_text(&sampled.to_text()).unwrap(), sampled);
+
+        assert!(Checkpoint::from_text("seed 7 trials 1 words all\n").is_err());
+        let past_the_end = text.replace("\n1 0", "\n2 0");
+        assert!(Checkpoint::from_text(&past_the_end).is_err());
+        assert!(Checkpoint::from_text(&format!("{}1 0 0.5\n", text)).is_err());
+        let twice = format!("{}1 1 0 5\n", text);
+        let err = Checkpoint::from_text(&twice).unwrap_err();
+        assert_eq!(err.to_string(), "line 6: trial 1 finished twice");
+        let huge = text.replace("trials 1 ", &format!("trials {} ", usize::MAX));
+        assert!(Checkpoint::from_text(&huge).is_err());
+        let huge = text.replace("trials 1 ", &format!("trials {} ", Checkpoint::MAX_TRIALS));
+        assert!(Checkpoint::from_text(&huge).is_err());
+    }
+
+    #[test]
+    fn resume() {
+        let campaign = Campaign {
+            trials: 2,
+            ..tiny_campaign()
+        };
+        let path = std::env::temp_dir().join(format!("campaign-{}.checkpoint", std::process::id()));
+        let full = campaign.run_checkpointed(None, &path).unwrap();
+        let checkpoint = Checkpoint::read(&path).unwrap();
+        assert_eq!(checkpoint.finished(), 4);
+
+        // resuming from half the trials runs the other half again, the same as the first time
+        let mut half = checkpoint.clone();
+        half.finished.retain(|(trial, _)| trial % 2 == 0);
+        let resumed = campaign.run_checkpointed(Some(half), &path).unwrap();
+        for (a, b) in full.cells.iter().zip(&resumed.cells) {
+            assert_eq!(a.trials, b.trials);
+            assert_eq!(a.successes, b.successes);
+            assert!((a.mean_error() - b.mean_error()).abs() < 1e-12);
+        }
+        assert_eq!(Checkpoint::read(&path).unwrap().finished(), 4);
+
+        // a checkpoint of another campaign is turned away
+        let other = Campaign {
+            seed: 8,
+            ..campaign.clone()
+        };
+        assert!(other
+            .run_checkpointed(Some(checkpoint.clone()), &path)
+            .is_err());
+        let other = Campaign {
+            dictionary_words: NonZeroUsize::new(12),
+            ..campaign.clone()
+        };
+        assert!(other
+            .run_checkpointed(Some(checkpoint.clone()), &path)
+            .is_err());
+        let other = Campaign {
+            key_lengths: vec![6],
+            ..campaign.clone()
+        };
+        assert!(other.run_checkpointed(Some(checkpoint), &path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// full default sweep, writing the report out for inspection
+    #[test]
+    #[ignore]
+    fn default_campaign() {
+        let report = Campaign::default().run();
+        report
+            .write(std::path::Path::new("campaign_report.csv"))
+            .unwrap();
+        println!("{}", report.to_csv());
+    }
+}
@@ -0,0 +1,168 @@
+//! Module for telling which known plaintext was encrypted, without cracking.
+//!
+//! Two ciphertext characters shifted by the same key symbol differ by exactly as much as their
+//! plaintext characters do, whatever the key. Whatever the scheduler, characters some distance
+//! apart are often shifted by the same key symbol, so the right known plaintext shows the same
+//! differences as the ciphertext at some distance, far more often than chance (1 in 27).
+//!
+//! Random characters inserted by the scheduler push the plaintext along, so the ciphertext is
+//! compared in short windows, each lined up with the plaintext a few characters back, where the
+//! random characters so far would have pushed it.
+
+use crate::utils::ALPHABET;
+
+/// Characters in each window of ciphertext.
+const WINDOW: usize = 32;
+
+/// Longest distance between two characters shifted by the same key symbol that is looked for.
+const MAX_DISTANCE: usize = 48;
+
+/// Random characters that can be inserted between two characters compared.
+const MAX_INSERTED: usize = 2;
+
+/// How far a window may be pushed along from where the random characters would be, if they were
+/// spread out evenly.
+const DRIFT_SLACK: usize = 3;
+
+/// Scores at or above this are taken to be the known plaintext. The right known plaintext scores
+/// 0.3 or more under every scheduler, even with three layers of random characters.
+pub const KNOWN_SCORE: f64 = 0.3;
+
+/// Scores at or below this are taken to be no known plaintext at all. Unrelated plaintexts score
+/// about 0.2.
+pub const UNKNOWN_SCORE: f64 = 0.25;
+
+/// What [`classify_known`] made of a ciphertext.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Classification {
+    /// The known plaintext at this index, with its score
+    Known(usize, f64),
+    /// None of the known plaintexts
+    Unknown,
+    /// Too close to tell, so crack it to find out
+    Unsure,
+}
+
+/// Decide which of `known_plaintexts` was encrypted into `ciphertext`, if any, by the
+/// [`known_score`] of each.
+pub fn classify_known<'a>(
+    ciphertext: &[u8],
+    known_plaintexts: impl IntoIterator<Item = &'a [u8]>,
+) -> Classification {
+    let best = known_plaintexts
+        .into_iter()
+        .map(|plaintext| known_score(ciphertext, plaintext))
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b));
+    match best {
+        Some((index, score)) if score >= KNOWN_SCORE => Classification::Known(index, score),
+        Some((_, score)) if score > UNKNOWN_SCORE => Classification::Unsure,
+        _ => Classification::Unknown,
+    }
+}
+
+/// How well the differences between ciphertext characters match those of `plaintext`, up to 1.0
+/// when every window matches at some distance. `plaintext` longer than the ciphertext, or too
+/// short to compare, scores 0.0.
+///
+/// Each window of ciphertext scores the largest fraction of its characters whose difference with
+/// the character some distance on matches the plaintext, lined up a little back for random
+/// characters, and the median window is the score.
+pub fn known_score(ciphertext: &[u8], plaintext: &[u8]) -> f64 {
+    let (n, m) = (ciphertext.len(), plaintext.len());
+    if n < m || m <= MAX_DISTANCE {
+        // random characters are only ever added, and too short to tell
+        return 0.0;
+    }
+    let inserted = n - m;
+    let len = ALPHABET.len();
+    let diff = |text: &[u8], a: usize, b: usize| (text[b] as usize + len - text[a] as usize) % len;
+
+    // lines each window up with the plaintext as if the random characters were spread out evenly,
+    // give or take a few
+
+    let mut windows: Vec<f64> = (0..n - MAX_DISTANCE)
+        .step_by(WINDOW)
+        .map(|start| {
+            let end = (start + WINDOW).min(n - MAX_DISTANCE);
+            let even = start * inserted / n;
+            let drifts = even.saturating_sub(DRIFT_SLACK)..=(even + DRIFT_SLACK).min(inserted);
+
+            let mut best = 0;
+            for drift in drifts {
+                for distance in 1..=MAX_DISTANCE {
+                    for shorter in 0..=MAX_INSERTED.min(distance - 1) {
+                        let matches = (start..end)
+                            .filter(|&i| {
+                                let j = i.saturating_sub(drift);
+                                let k = j + distance - shorter;
+                                i >= drift
+                                    && k < m
+                                    && diff(ciphertext, i, i + distance) == diff(plaintext, j, k)
+                            })
+                            .count();
+                        best = best.max(matches);
+                    }
+                }
+            }
+            best as f64 / (end - start) as f64
+        })
+        .collect();
+
+    windows.sort_by(|a, b| a.total_cmp(b));
+    windows.get(windows.len() / 2).copied().unwrap_or(0.0)
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::{BlockInterleave, PeriodicRand, RepeatingKey};
+    use crate::prelude::*;
+    use crate::utils::str_to_bytes;
+
+    #[test]
+    fn classifies() {
+        let known = include_str!("../../words/test1_plaintext.txt");
+        let known: Vec<Vec<u8>> = known.lines().map(str_to_bytes).collect();
+        let classify = |ciphertext: &str| {
+            classify_known(&str_to_bytes(ciphertext), known.iter().map(Vec::as_slice))
+        };
+        let plaintext = include_str!("../../words/test1_plaintext.txt")
+            .lines()
+            .nth(3)
+            .unwrap();
+        let key = vec![5, 20, 11, 3, 17, 8, 26, 1, 14, 9, 2, 23, 6];
+
+        let encryptor = Encryptor::new(key.clone(), RepeatingKey, Rng::default());
+        assert!(matches!(
+            classify(&encryptor.encrypt(plaintext)),
+            Classification::Known(3, _)
+        ));
+
+        // random characters inserted, and a key that doesn't simply repeat
+        let sched = (
+            &PeriodicRand {
+                period: 40,
+                start: 7,
+                overwrite: false,
+            },
+            &BlockInterleave { swap: false },
+        );
+        let encryptor = Encryptor::new(key.clone(), sched, Rng::default());
+        assert!(matches!(
+            classify(&encryptor.encrypt(plaintext)),
+            Classification::Known(3, _)
+        ));
+
+        // dictionary words are none of them
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let words = Generator::with_dict(&dict).generate_exact_len(500);
+        let encryptor = Encryptor::new(key, RepeatingKey, Rng::default());
+        assert_eq!(
+            classify(&encryptor.encrypt(&words)),
+            Classification::Unknown
+        );
+        assert_eq!(classify("too short"), Classification::Unknown);
+    }
+}
@@ -0,0 +1,274 @@
+//! Module for looking at the frequency attack one column at a time.
+//!
+//! At a keylength, every column of the ciphertext is shifted back by whichever shift makes its
+//! character frequencies fit the baseline best. A column with plenty of characters has one shift
+//! that fits far better than the rest, but a short column, or one mixed up by random characters,
+//! can have a runner-up that fits almost as well, and then the attack is only guessing. A
+//! [`ColumnDiagnostics`] shows each column's histogram with its best and runner-up shifts, as
+//! ASCII bars or as CSV, so those columns stand out.
+
+use crate::crack::Frequencies;
+use crate::utils::{NumToChar, ALPHABET};
+
+use std::fmt;
+
+/// How much worse than the best shift the runner-up has to fit for a column to be sure of its
+/// shift, in the scores of [`Frequencies::compare_shifted`].
+pub const UNSURE_MARGIN: f32 = 0.05;
+
+/// The frequency attack at one keylength, column by column, from [`ColumnDiagnostics::new`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnDiagnostics {
+    pub keylength: usize,
+    /// One per key symbol, in key order
+    pub columns: Vec<ColumnDiagnostic>,
+}
+
+/// The frequency attack on one column of the ciphertext.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnDiagnostic {
+    /// Count of each ciphertext character in the column, `'a'` first and `' '` last
+    pub histogram: [u32; 27],
+    /// Shift back that fits the baseline best, as the cracker picks it
+    pub best_shift: u8,
+    /// Score of [`best_shift`][`Self::best_shift`], lower is closer
+    pub best_score: f32,
+    /// Shift back that fits the baseline second best
+    pub runner_up_shift: u8,
+    /// Score of [`runner_up_shift`][`Self::runner_up_shift`]
+    pub runner_up_score: f32,
+}
+
+impl ColumnDiagnostic {
+    /// Number of ciphertext characters in the column.
+    pub fn characters(&self) -> u32 {
+        self.histogram.iter().sum()
+    }
+
+    /// How much worse the runner-up shift fits than the best one.
+    pub fn margin(&self) -> f32 {
+        self.runner_up_score - self.best_score
+    }
+
+    /// Whether the runner-up shift fits within [`UNSURE_MARGIN`] of the best one, or the column
+    /// is empty.
+    pub fn is_unsure(&self) -> bool {
+        self.characters() == 0 || self.margin() < UNSURE_MARGIN
+    }
+
+    /// The key symbol the best shift undoes, as the [`Encryptor`][`crate::ciphers::Encryptor`]
+    /// shifts plaintext by.
+    pub fn key(&self) -> i8 {
+        (-(self.best_shift as i8)).rem_euclid(ALPHABET.len() as i8)
+    }
+}
+
+impl ColumnDiagnostics {
+    const CSV_HEADER: &'static str = "column,characters,key,best_shift,best_score,\
+                                      runner_up_shift,runner_up_score,margin,unsure";
+
+    /// Score every shift of every column of `ciphertext` at `keylength` against `baseline`, like
+    /// [`crack`][`crate::crack::crack`] does.
+    pub fn new(ciphertext: &[u8], keylength: usize, baseline: &Frequencies) -> Self {
+        let keylength = keylength.max(1);
+        let mut histograms = vec![[0_u32; 27]; keylength];
+        for (index, &c) in ciphertext.iter().enumerate() {
+            histograms[index % keylength][c as usize] += 1;
+        }
+
+        let columns = histograms
+            .into_iter()
+            .map(|histogram| {
+                let total = histogram.iter().sum();
+                let scores: Vec<f32> = (0..ALPHABET.len() as u8)
+                    .map(|shift| baseline.compare_shifted(&histogram, total, shift))
+                    .collect();
+
+                // the first of equally good shifts, as the cracker keeps it
+                let best = (0..scores.len())
+                    .min_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+                    .unwrap_or(0);
+                let runner_up = (0..scores.len())
+                    .filter(|&shift| shift != best)
+                    .min_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+                    .unwrap_or(best);
+
+                ColumnDiagnostic {
+                    histogram,
+                    best_shift: best as u8,
+                    best_score: scores[best],
+                    runner_up_shift: runner_up as u8,
+                    runner_up_score: scores[runner_up],
+                }
+            })
+            .collect();
+
+        Self { keylength, columns }
+    }
+
+    /// Indices of the columns the attack is [unsure][`ColumnDiagnostic::is_unsure`] about.
+    pub fn unsure_columns(&self) -> Vec<usize> {
+        (0..self.columns.len())
+            .filter(|&column| self.columns[column].is_unsure())
+            .collect()
+    }
+
+    /// Render the columns as CSV with a header row, one row per column, followed by the count of
+    /// each character in the column (`a` to `z`, then `space`).
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(Self::CSV_HEADER);
+        for c in ALPHABET.chars() {
+            match c {
+                ' ' => csv.push_str(",space"),
+                c => csv.push_str(&format!(",{}", c)),
+            }
+        }
+        csv.push('\n');
+
+        for (index, column) in self.columns.iter().enumerate() {
+            csv.push_str(&format!(
+                "{},{},{},{},{:.4},{},{:.4},{:.4},{}",
+                index,
+                column.characters(),
+                column.key(),
+                column.best_shift,
+                column.best_score,
+                column.runner_up_shift,
+                column.runner_up_score,
+                column.margin(),
+                column.is_unsure(),
+            ));
+            for count in &column.histogram {
+                csv.push_str(&format!(",{}", count));
+            }
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
+impl fmt::Display for ColumnDiagnostics {
+    /// A summary line per column, then its histogram as bars `HEIGHT` rows tall over the
+    /// alphabet, with the space shown as `_`. Columns the attack is unsure about are marked with
+    /// a `?`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const HEIGHT: u32 = 4;
+
+        let unsure = self.unsure_columns();
+        writeln!(
+            f,
+            "columns at keylength {}: {} unsure {:?}",
+            self.keylength,
+            unsure.len(),
+            unsure
+        )?;
+
+        let labels: String = ALPHABET
+            .chars()
+            .map(|c| if c == ' ' { '_' } else { c })
+            .collect();
+        for (index, column) in self.columns.iter().enumerate() {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{} column {:>2}: {} characters, key {} ({}), shift {} scores {:.3}, \
+                 runner-up {} scores {:.3}",
+                if column.is_unsure() { '?' } else { ' ' },
+                index,
+                column.characters(),
+                column.key(),
+                (column.key() as u8).to_char(),
+                column.best_shift,
+                column.best_score,
+                column.runner_up_shift,
+                column.runner_up_score,
+            )?;
+
+            // scale the tallest bar to the full height, rounding any count up to at least a bit
+            let tallest = column.histogram.iter().copied().max().unwrap_or(0).max(1);
+            for row in (0..HEIGHT).rev() {
+                let bars: String = column
+                    .histogram
+                    .iter()
+                    .map(|&count| match (count * HEIGHT).div_ceil(tallest) > row {
+                        true => '#',
+                        false => ' ',
+                    })
+                    .collect();
+                writeln!(f, "{:>14}{}", "", bars.trim_end())?;
+            }
+            writeln!(f, "{:>14}{}", "", labels)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::ciphers::{Cipher, Encryptor};
+    use crate::crack::{crack, DictionaryStats};
+    use crate::rng::Rng;
+    use crate::utils::str_to_bytes;
+
+    #[test]
+    fn matches_crack() {
+        let plaintext = "it was the best of times it was the worst of times ".repeat(10);
+        let key = vec![3, 1, 4, 1, 5];
+        let encryptor = Encryptor::new(key.clone(), RepeatingKey, Rng::default());
+        let ciphertext = str_to_bytes(&encryptor.encrypt(&plaintext));
+        let baseline = &DictionaryStats::project().letters;
+
+        let diagnostics = ColumnDiagnostics::new(&ciphertext, key.len(), baseline);
+        let found: Vec<i8> = diagnostics.columns.iter().map(|c| c.key()).collect();
+        assert_eq!(found, key);
+        assert!(diagnostics.unsure_columns().is_empty());
+
+        // the best shifts are the ones the cracker uses
+        let cracked = crack(&ciphertext, key.len(), baseline);
+        assert_eq!(cracked.plaintext, str_to_bytes(&plaintext));
+        let total: f32 = diagnostics.columns.iter().map(|c| c.best_score).sum();
+        assert!((total as f64 - cracked.confidence).abs() < 1e-3);
+    }
+
+    #[test]
+    fn short_columns_are_unsure() {
+        let ciphertext = str_to_bytes("the cat");
+        let diagnostics =
+            ColumnDiagnostics::new(&ciphertext, 10, &DictionaryStats::project().letters);
+        assert_eq!(diagnostics.columns.len(), 10);
+        // the last three columns have no characters at all
+        for column in &diagnostics.columns[7..] {
+            assert_eq!(column.characters(), 0);
+            assert!(column.is_unsure());
+        }
+        for column in &diagnostics.columns {
+            assert!(column.margin() >= 0.0);
+        }
+    }
+
+    #[test]
+    fn csv_and_bars() {
+        let ciphertext = str_to_bytes("aab aab");
+        let diagnostics =
+            ColumnDiagnostics::new(&ciphertext, 2, &DictionaryStats::project().letters);
+
+        let csv = diagnostics.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("column,characters,key,"));
+        assert!(lines[0].ends_with(",y,z,space"));
+        // column 0 has `a`, `b`, `a` and `b`
+        assert!(lines[1].starts_with("0,4,"));
+        assert!(lines[1].ends_with(&format!(",2,2{}", ",0".repeat(25))));
+
+        let text = diagnostics.to_string();
+        assert!(text.starts_with("columns at keylength 2: "), "{}", text);
+        assert!(text.contains("column  1: 3 characters"), "{}", text);
+        assert!(text.contains("##"), "{}", text);
+        assert!(text.contains("abcdefghijklmnopqrstuvwxyz_"), "{}", text);
+    }
+}
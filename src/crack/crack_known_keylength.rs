@@ -8,7 +8,8 @@
 //! We have access to the dictionary of plaintext words, so calculate character frequency using the
 //! dictionary.
 
-use super::CrackResult;
+use super::rank::cmp_ranked;
+use super::{CrackResult, CrackScratch};
 use crate::utils::Shift;
 use crate::{
     dict::Dictionary,
@@ -16,7 +17,8 @@ use crate::{
 };
 
 /// Frequency distribution
-#[derive(Clone)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Frequencies {
     /// values[0]  => frequency of 'a'
     /// values[1]  => frequency of 'b'
@@ -26,6 +28,10 @@ pub struct Frequencies {
     values: [f32; 27],
 }
 
+/// Count added to every character by [`Frequencies::from_bytes_smoothed`] when smoothing the
+/// frequencies of a short block, as in Laplace's rule of succession.
+pub const LAPLACE_SMOOTHING: f32 = 1.0;
+
 impl Frequencies {
     ///  Generate the baseline character frequency from the given dictionary.
     pub fn from_dict(dict: &Dictionary) -> Self {
@@ -43,20 +49,31 @@ impl Frequencies {
         // for space, every word is followed by a space, so we can just count words
         values[26] = dict.words.len() as f32;
 
-        // divide each letter count by the total to get a fraction
-        let total: f32 = values.iter().sum();
-        for v in values.iter_mut() {
-            *v /= total;
-        }
-
-        // return Frequencies
-        Self { values }
+        Self::from_counts(values, 0.0)
     }
 
     ///  Calculate character frequency from a slice of bytes, &[u8], where 0 is 'a', 1 is 'b', etc.
-    ///  and 26 is ' '.
+    ///  and 26 is ' '. Empty bytes have no frequencies to speak of, so every character gets the
+    ///  same one.
     pub fn from_bytes(bytes: &[u8]) -> Self {
-        let mut values = [0.0; 27];
+        Self::from_bytes_smoothed(bytes, 0.0)
+    }
+
+    /// Same as [`from_bytes`][`Self::from_bytes`], but failing on empty bytes instead of guessing
+    /// every character is as frequent.
+    pub fn try_from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.is_empty() {
+            anyhow::bail!("no characters to count frequencies of");
+        }
+        Ok(Self::from_bytes(bytes))
+    }
+
+    /// Same as [`from_bytes`][`Self::from_bytes`], but counting every character `alpha` more
+    /// times than it appears (Laplace smoothing, with [`LAPLACE_SMOOTHING`]). Characters a short
+    /// block happens not to have still get some frequency, so [`chi_squared`][`Self::chi_squared`]
+    /// and [`log_likelihood`][`Self::log_likelihood`] against it stay finite.
+    pub fn from_bytes_smoothed(bytes: &[u8], alpha: f32) -> Self {
+        let mut counts = [0.0; 27];
 
         // the byte values are assumed to already be "nice" and in the range 0-26. Rust will crash
         // safely if this is not the case.
@@ -64,18 +81,69 @@ impl Frequencies {
         // the utils::str_to_bytes function should be used early on when using bytes instead of
         // chars so this is ok.
         for b in bytes {
-            values[*b as usize] += 1.0;
+            counts[*b as usize] += 1.0;
+        }
+
+        Self::from_counts(counts, alpha)
+    }
+
+    /// Frequencies from the count of each character plus `alpha`, or all the same if there is
+    /// nothing to count.
+    fn from_counts(mut values: [f32; 27], alpha: f32) -> Self {
+        for v in values.iter_mut() {
+            *v += alpha;
         }
 
-        // divide by total number of bytes
+        // divide each count by the total to get a fraction
+        let total: f32 = values.iter().sum();
         for v in values.iter_mut() {
-            *v /= bytes.len() as f32;
+            *v = match total > 0.0 {
+                true => *v / total,
+                false => 1.0 / 27.0,
+            };
         }
 
         // return Frequencies
         Self { values }
     }
 
+    /// The frequency of each character, `'a'` first and `' '` last.
+    pub fn values(&self) -> &[f32; 27] {
+        &self.values
+    }
+
+    /// Pearson's chi-squared statistic of a histogram of `total` characters against these
+    /// frequencies, as if every character in it were shifted by `shift` first, like
+    /// [`compare_shifted`][`Self::compare_shifted`]. Lower means closer. Characters these
+    /// frequencies never expect make it infinite, unless they are
+    /// [smoothed][`Self::from_bytes_smoothed`].
+    pub fn chi_squared(&self, histogram: &[u32; 27], total: u32, shift: u8) -> f32 {
+        let len = self.values.len();
+        let shift = shift as usize % len;
+
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(plain, &baseline)| {
+                let observed = histogram[(plain + len - shift) % len] as f32;
+                let expected = baseline * total as f32;
+                if observed == 0.0 && expected == 0.0 {
+                    // never expected, and never seen
+                    return 0.0;
+                }
+                (observed - expected) * (observed - expected) / expected
+            })
+            .sum()
+    }
+
+    /// Log-likelihood of `bytes` being drawn from these frequencies. Higher means closer, and
+    /// characters these frequencies never expect make it negative infinity, unless they are
+    /// [smoothed][`Self::from_bytes_smoothed`].
+    pub fn log_likelihood(&self, bytes: &[u8]) -> f32 {
+        bytes.iter().map(|&b| self.values[b as usize].ln()).sum()
+    }
+
+    #[allow(clippy::should_implement_trait)] // this never fails, unlike FromStr::from_str
     pub fn from_str(s: &str) -> Self {
         Self::from_bytes(str_to_bytes(s).as_slice())
     }
@@ -91,104 +159,553 @@ impl Frequencies {
 
         sum_of_differences
     }
+
+    /// The frequencies of the same text with every character shifted by `shift`, by rotating the
+    /// frequency values instead of shifting and recounting the text.
+    pub fn shifted(&self, shift: i8) -> Self {
+        let mut values = self.values;
+        let shift = shift.rem_euclid(values.len() as i8) as usize;
+        values.rotate_right(shift);
+        Self { values }
+    }
+
+    /// Compare against a histogram of `total` characters, as if every character in it were
+    /// shifted by `shift` first. This scores the same as [`compare`][`Self::compare`] with the
+    /// frequencies of the shifted characters, without shifting any characters. An empty histogram
+    /// scores as if every frequency were 0.0.
+    pub fn compare_shifted(&self, histogram: &[u32; 27], total: u32, shift: u8) -> f32 {
+        let len = self.values.len();
+        let shift = shift as usize % len;
+
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(plain, baseline)| {
+                // the ciphertext character that shifts into this plaintext character
+                let cipher = (plain + len - shift) % len;
+                let other = histogram[cipher] as f32 / total.max(1) as f32;
+                (other - baseline).abs()
+            })
+            .sum()
+    }
 }
 
-/// Return the best (smallest confidence value) CrackResult from a list of many
+/// Return the best (smallest confidence value) CrackResult from a list of many, by
+/// [`CrackResult::cmp_confidence`]. Equally confident cracks order by plaintext, so the same one
+/// wins whatever order the list is in.
+///
+/// Panics if the list is empty.
 pub fn best_crack(crackresults: &[CrackResult]) -> CrackResult {
     assert!(!crackresults.is_empty());
     crackresults
         .iter()
-        .min_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap()) // have to unwrap because floats can be NaN (but should not happen to us)
+        .min_by(|a, b| {
+            a.cmp_confidence(b)
+                .then_with(|| a.plaintext.cmp(&b.plaintext))
+        })
         .unwrap() // only could be None if iterator is empty
         .clone()
 }
 
-/// Slice ciphertext into chunks of every (keylength) character
-pub fn slice(ciphertext: &[u8], keylength: usize) -> Vec<Vec<u8>> {
-    let mut ct_blocks = vec![Vec::new(); keylength];
+/// Same as [`best_crack`], for cracks paired with the keylength each was cracked at. Equally
+/// confident cracks order by keylength, shortest first, then by plaintext, like they
+/// [`rank`][`crate::crack::rank`].
+///
+/// Panics if the list is empty.
+pub fn best_crack_at_keylengths(crackresults: &[(usize, CrackResult)]) -> CrackResult {
+    assert!(!crackresults.is_empty());
+    crackresults
+        .iter()
+        .min_by(|a, b| cmp_ranked(a, b))
+        .map(|(_, res)| res.clone())
+        .unwrap() // only could be None if iterator is empty
+}
 
-    // pass over ciphertext, dumping the char into the right bucket as we go
-    for (ct_index, ct_char) in ciphertext.iter().enumerate() {
-        // find which bucket to use
-        let bucket = ct_index % keylength;
+/// Crack the ciphertext at every keylength guess, returning one result per guess in the same order
+/// as `keylen_guesses`. Each result's confidence is scaled by the confidence of its keylength
+/// guess.
+///
+/// With the `parallel` feature, the keylengths are cracked concurrently on the rayon thread pool.
+pub fn crack_keylengths(
+    ciphertext: &[u8],
+    keylen_guesses: &[(usize, f64)],
+    baseline: &Frequencies,
+) -> Vec<CrackResult> {
+    let crack_one = |scratch: &mut CrackScratch, (keylen, keylen_confidence): &(usize, f64)| {
+        let mut res = crack_with_scratch(ciphertext, *keylen, baseline, scratch);
+        res.confidence *= keylen_confidence;
+        res
+    };
+
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        keylen_guesses
+            .par_iter()
+            .map_init(CrackScratch::default, crack_one)
+            .collect()
+    }
 
-        // push the char into that bucket
-        ct_blocks[bucket].push(*ct_char);
+    #[cfg(not(feature = "parallel"))]
+    {
+        let mut scratch = CrackScratch::default();
+        keylen_guesses
+            .iter()
+            .map(|guess| crack_one(&mut scratch, guess))
+            .collect()
     }
+}
+
+/// Crack the ciphertext at every keylength guess concurrently, and reduce to the best result.
+pub fn crack_all_keylengths_par(
+    ciphertext: &[u8],
+    keylen_guesses: &[(usize, f64)],
+    baseline: &Frequencies,
+) -> CrackResult {
+    let cracked = crack_keylengths(ciphertext, keylen_guesses, baseline);
+    let keylens = keylen_guesses.iter().map(|&(keylen, _)| keylen);
+    best_crack_at_keylengths(&keylens.zip(cracked).collect::<Vec<_>>())
+}
+
+/// Crack the ciphertext based on the given keylength
+///
+/// Each column of the ciphertext (every `keylength`th character) is reduced to a histogram in a
+/// single strided pass. Every shift of a column is then scored by rotating its histogram, so no
+/// candidate plaintexts are built until the best shift of each column is known.
+pub fn crack(ciphertext: &[u8], keylength: usize, baseline: &Frequencies) -> CrackResult {
+    crack_with_scratch(
+        ciphertext,
+        keylength,
+        baseline,
+        &mut CrackScratch::default(),
+    )
+}
 
-    ct_blocks
+/// Same as [`crack`], reusing the buffers in `scratch`.
+pub fn crack_with_scratch(
+    ciphertext: &[u8],
+    keylength: usize,
+    baseline: &Frequencies,
+    scratch: &mut CrackScratch,
+) -> CrackResult {
+    crack_columns(ciphertext, keylength, baseline, &[], scratch)
 }
 
-/// Unslice the highest confidence plaintext into a normal string
-pub fn unslice(pt_blocks: Vec<Vec<u8>>, keylength: usize) -> Vec<u8> {
-    // get a single string ready
-    let mut unsliced = Vec::with_capacity(pt_blocks[0].len() * keylength);
-
-    // the first block will be the longest, so we iterate over indexes of that
-    for i in 0..pt_blocks[0].len() {
-        // for every index, go through each block and pull out the character there.
-        // if there is no character there, just continue
-        for block in pt_blocks.iter() {
-            if let Some(c) = block.get(i) {
-                unsliced.push(*c);
+/// Crack the ciphertext based on the given keylength, with some of the key already known.
+///
+/// `pinned[column]` is the key symbol of a column, as the [`Encryptor`][`crate::ciphers::Encryptor`]
+/// shifts plaintext by, or `None` to find the column's shift by frequency analysis like
+/// [`crack`] does. Columns past the end of `pinned` are searched too. Pinned columns still add how
+/// well they fit the baseline to the confidence, so results stay comparable with [`crack`].
+pub fn crack_pinned(
+    ciphertext: &[u8],
+    keylength: usize,
+    baseline: &Frequencies,
+    pinned: &[Option<i8>],
+) -> CrackResult {
+    crack_columns(
+        ciphertext,
+        keylength,
+        baseline,
+        pinned,
+        &mut CrackScratch::default(),
+    )
+}
+
+/// The key symbols at `keylength` implied by known plaintext, to pass to [`crack_pinned`].
+///
+/// Each fragment is a position in the plaintext and the plaintext bytes starting there. Every
+/// byte fixes the key symbol of its column, assuming no random characters come before it. Fails
+/// if a fragment runs past the end of the ciphertext, or if two bytes fix different symbols for
+/// the same column, which rules out this keylength.
+pub fn key_from_fragments(
+    ciphertext: &[u8],
+    keylength: usize,
+    fragments: &[(usize, Vec<u8>)],
+) -> anyhow::Result<Vec<Option<i8>>> {
+    let mut key = vec![None; keylength];
+    for (start, bytes) in fragments {
+        if start + bytes.len() > ciphertext.len() {
+            anyhow::bail!(
+                "fragment at {} runs past the end of the {} character ciphertext",
+                start,
+                ciphertext.len()
+            );
+        }
+
+        for (position, &plain) in (*start..).zip(bytes) {
+            let column = position % keylength;
+            let shift = (ciphertext[position] as i8 - plain as i8).rem_euclid(ALPHABET.len() as i8);
+            match key[column] {
+                Some(pinned) if pinned != shift => {
+                    anyhow::bail!("key[{}] would be both {} and {}", column, pinned, shift)
+                }
+                _ => key[column] = Some(shift),
             }
         }
     }
+    Ok(key)
+}
+
+fn crack_columns(
+    ciphertext: &[u8],
+    keylength: usize,
+    baseline: &Frequencies,
+    pinned: &[Option<i8>],
+    scratch: &mut CrackScratch,
+) -> CrackResult {
+    // count the characters in each column
+    let histograms = &mut scratch.histograms;
+    histograms.clear();
+    histograms.resize(keylength, [0; 27]);
+    for (index, &c) in ciphertext.iter().enumerate() {
+        histograms[index % keylength][c as usize] += 1;
+    }
+
+    let shifts = &mut scratch.shifts;
+    let total_confidence = column_shifts(histograms, baseline, pinned, shifts);
+
+    // shift every character by the best shift of its column
+    let plaintext = ciphertext
+        .iter()
+        .enumerate()
+        .map(|(index, c)| c.shift(shifts[index % keylength]))
+        .collect();
 
-    unsliced
+    CrackResult::new(plaintext, total_confidence)
 }
 
-/// Crack a single block of ciphertext as if it were shifted with a key of length 1
-fn crack_block(cipherblock: &[u8], baseline: &Frequencies) -> CrackResult {
-    // vector to hold each individual shift attempt
-    let mut crack_results: Vec<CrackResult> = Vec::with_capacity(27);
+/// The shift back of each column from its `histograms`, fit to `baseline` like [`crack_pinned`]
+/// finds them, into `shifts`. Returns the confidence summed over the columns.
+pub(crate) fn column_shifts(
+    histograms: &[[u32; 27]],
+    baseline: &Frequencies,
+    pinned: &[Option<i8>],
+    shifts: &mut Vec<i8>,
+) -> f64 {
+    // find the best shift for each column. confidence overall is sum of each column's confidence
+    shifts.clear();
+    let mut total_confidence = 0.0;
+
+    for (column, histogram) in histograms.iter().enumerate() {
+        let total: u32 = histogram.iter().sum();
+        if total == 0 {
+            // no characters in this column, the ciphertext is shorter than the keylength
+            shifts.push(0);
+            continue;
+        }
 
-    // try each shift in the alphabet (0 shift == 27 shift)
-    for shift in 0..ALPHABET.len() as i8 {
-        // make the plaintext
-        let plaintext: Vec<u8> = cipherblock.iter().map(|&n| n.shift(shift)).collect();
+        // a pinned key symbol shifts forward, so its column shifts back by it
+        if let Some(key) = pinned.get(column).copied().flatten() {
+            let shift = (-key).rem_euclid(ALPHABET.len() as i8);
+            shifts.push(shift);
+            total_confidence += baseline.compare_shifted(histogram, total, shift as u8) as f64;
+            continue;
+        }
 
-        // calculate the confidence to baseline
-        let confidence =
-            Frequencies::compare(baseline, &Frequencies::from_bytes(&plaintext)) as f64;
+        // try each shift in the alphabet (0 shift == 27 shift), keeping the first best one
+        let mut best = (0, f32::MAX);
+        for shift in 0..ALPHABET.len() as u8 {
+            let confidence = baseline.compare_shifted(histogram, total, shift);
+            if confidence < best.1 {
+                best = (shift, confidence);
+            }
+        }
 
-        // push the result
-        crack_results.push(CrackResult {
-            plaintext,
-            confidence,
-        });
+        shifts.push(best.0 as i8);
+        total_confidence += best.1 as f64;
     }
 
-    // return the best result
-    best_crack(&crack_results)
+    total_confidence
 }
 
-/// Crack the ciphertext based on the given keylength
-pub fn crack(ciphertext: &[u8], keylength: usize, baseline: &Frequencies) -> CrackResult {
-    // slice up the ciphertext based on keylength
-    let ct_blocks = slice(ciphertext, keylength);
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::crack::guesses;
+    use crate::prelude::*;
+    use crate::utils::bytes_to_str;
+
+    /// crack by building every shifted column and comparing its frequencies
+    fn materialized_crack(ciphertext: &[u8], keylength: usize, baseline: &Frequencies) -> Vec<u8> {
+        let shifts: Vec<i8> = (0..keylength)
+            .map(|column| {
+                let block: Vec<u8> = ciphertext
+                    .iter()
+                    .skip(column)
+                    .step_by(keylength)
+                    .copied()
+                    .collect();
+                (0..27)
+                    .min_by(|&a, &b| {
+                        let score = |shift: i8| {
+                            let shifted: Vec<u8> = block.iter().map(|c| c.shift(shift)).collect();
+                            baseline.compare(&Frequencies::from_bytes(&shifted))
+                        };
+                        score(a).partial_cmp(&score(b)).unwrap()
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        ciphertext
+            .iter()
+            .enumerate()
+            .map(|(i, c)| c.shift(shifts[i % keylength]))
+            .collect()
+    }
+
+    #[test]
+    fn matches_materialized_crack() {
+        let mut words = std::fs::read_to_string("words/default.txt").unwrap();
+        let dict = Dictionary::from_string(&mut words);
+        let baseline = Frequencies::from_dict(&dict);
+
+        let plaintext = Generator::with_dict(&dict).generate_words(60);
+        let encryptor = Encryptor::new(vec![2, 7, 1, 8, 2, 8], RepeatingKey, Rng::default());
+        let cipherbytes = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+        for keylength in 1..20 {
+            assert_eq!(
+                crack(&cipherbytes, keylength, &baseline).plaintext,
+                materialized_crack(&cipherbytes, keylength, &baseline),
+            );
+        }
+        assert_eq!(
+            bytes_to_str(&crack(&cipherbytes, 6, &baseline).plaintext),
+            plaintext
+        );
+    }
+
+    #[test]
+    fn shifted_matches_shifted_bytes() {
+        let bytes = str_to_bytes("the quick brown fox jumps over the lazy dog");
+        let baseline = Frequencies::from_str("hello world");
 
-    // vector to store crackresults. we will get one result from each index of the keylength so we
-    // allocate for that number of items up front.
-    let mut crack_results: Vec<CrackResult> = Vec::with_capacity(keylength);
+        let mut histogram = [0; 27];
+        for &b in &bytes {
+            histogram[b as usize] += 1;
+        }
 
-    // crack each ct_block as if it were single key shift
-    for block in ct_blocks {
-        crack_results.push(crack_block(&block, baseline));
+        for shift in -30..30 {
+            let shifted: Vec<u8> = bytes.iter().map(|b| b.shift(shift)).collect();
+            let expected = Frequencies::from_bytes(&shifted);
+            assert_eq!(
+                Frequencies::from_bytes(&bytes).shifted(shift).values,
+                expected.values
+            );
+
+            let rotation = shift.rem_euclid(27) as u8;
+            assert_eq!(
+                baseline.compare_shifted(&histogram, bytes.len() as u32, rotation),
+                baseline.compare(&expected)
+            );
+        }
     }
 
-    // de-interleave the plaintext chunks back into one contiguous plaintext
-    let pt_chunks: Vec<Vec<u8>> = crack_results
-        .iter()
-        .map(|cr| cr.plaintext.clone())
-        .collect();
-    let plaintext: Vec<u8> = unslice(pt_chunks, keylength);
+    #[test]
+    fn smoothing() {
+        // nothing to count
+        assert!(Frequencies::try_from_bytes(&[]).is_err());
+        let empty = Frequencies::from_bytes(&[]);
+        assert!(empty.values().iter().all(|&v| v == 1.0 / 27.0));
+        let histogram = [0; 27];
+        assert!(!empty.compare_shifted(&histogram, 0, 3).is_nan());
+
+        // a short block without most letters
+        let bytes = str_to_bytes("aab");
+        let raw = Frequencies::try_from_bytes(&bytes).unwrap();
+        let smoothed = Frequencies::from_bytes_smoothed(&bytes, LAPLACE_SMOOTHING);
+        assert_eq!(raw.values()[2], 0.0);
+        assert_eq!(smoothed.values()[0], 3.0 / 30.0);
+        assert_eq!(smoothed.values()[2], 1.0 / 30.0);
+        assert!((smoothed.values().iter().sum::<f32>() - 1.0).abs() < 1e-6);
+
+        let other = str_to_bytes("abc");
+        assert_eq!(raw.log_likelihood(&other), f32::NEG_INFINITY);
+        assert!(smoothed.log_likelihood(&other).is_finite());
+        assert!(smoothed.log_likelihood(&bytes) > smoothed.log_likelihood(&other));
+
+        let mut histogram = [0; 27];
+        for &b in &other {
+            histogram[b as usize] += 1;
+        }
+        assert_eq!(raw.chi_squared(&histogram, 3, 0), f32::INFINITY);
+        assert!(smoothed.chi_squared(&histogram, 3, 0).is_finite());
+
+        // a block is closest to its own frequencies, unshifted
+        let mut histogram = [0; 27];
+        for &b in &bytes {
+            histogram[b as usize] += 1;
+        }
+        let unshifted = smoothed.chi_squared(&histogram, 3, 0);
+        assert!((1..27).all(|shift| smoothed.chi_squared(&histogram, 3, shift) > unshifted));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let freqs = Frequencies::from_str("hello world");
+        let json = serde_json::to_string(&freqs).unwrap();
+        let back: Frequencies = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.values, freqs.values);
+
+        let result = CrackResult::new(str_to_bytes("hello world"), 0.25);
+        let json = serde_json::to_string(&result).unwrap();
+        let back: CrackResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.plaintext, result.plaintext);
+        assert_eq!(back.confidence, result.confidence);
+        assert!(!back.timed_out);
+    }
 
-    // confidence overall is sum of each individual confidence
-    let total_confidence = crack_results.iter().map(|cr| cr.confidence).sum();
+    #[test]
+    fn reused_scratch() {
+        let mut words = std::fs::read_to_string("words/default.txt").unwrap();
+        let dict = Dictionary::from_string(&mut words);
+        let baseline = Frequencies::from_dict(&dict);
+        let plaintext = Generator::with_dict(&dict).generate_words(40);
+        let encryptor = Encryptor::new(vec![5, 3, 1, 20], RepeatingKey, Rng::default());
+        let cipherbytes = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+        // leftovers from longer and shorter keylengths don't leak into the next crack
+        let mut scratch = CrackScratch::default();
+        for &keylength in &[30, 4, 17, 1, 4] {
+            let reused = crack_with_scratch(&cipherbytes, keylength, &baseline, &mut scratch);
+            let fresh = crack(&cipherbytes, keylength, &baseline);
+            assert_eq!(reused.plaintext, fresh.plaintext);
+            assert_eq!(reused.confidence, fresh.confidence);
+        }
+    }
 
-    CrackResult {
-        plaintext,
-        confidence: total_confidence,
+    #[test]
+    fn pinned_columns() {
+        let mut words = std::fs::read_to_string("words/default.txt").unwrap();
+        let dict = Dictionary::from_string(&mut words);
+        let baseline = Frequencies::from_dict(&dict);
+        let plaintext = Generator::with_dict(&dict).generate_words(3);
+        let key = vec![3, 9, 14, 20, 25, 1, 7, 11];
+        let encryptor = Encryptor::new(key.clone(), RepeatingKey, Rng::default());
+        let cipherbytes = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+        // nothing pinned is the same as a plain crack
+        let unpinned = crack(&cipherbytes, key.len(), &baseline);
+        let pinned = crack_pinned(&cipherbytes, key.len(), &baseline, &[]);
+        assert_eq!(pinned.plaintext, unpinned.plaintext);
+        assert_eq!(pinned.confidence, unpinned.confidence);
+
+        // with the whole key pinned, even a short ciphertext decrypts exactly
+        let whole: Vec<Option<i8>> = key.iter().map(|&k| Some(k)).collect();
+        let pinned = crack_pinned(&cipherbytes, key.len(), &baseline, &whole);
+        assert_eq!(bytes_to_str(&pinned.plaintext), plaintext);
+
+        // a pinned column decrypts with its key symbol, whatever fits the baseline best
+        let mut one = vec![None; key.len()];
+        one[2] = Some(0);
+        let pinned = crack_pinned(&cipherbytes, key.len(), &baseline, &one);
+        assert_eq!(pinned.plaintext[2], cipherbytes[2]);
+        assert_eq!(pinned.plaintext[10], cipherbytes[10]);
+    }
+
+    #[test]
+    fn fragments() {
+        let plaintext = "the quick brown fox jumps over the lazy dog";
+        let key = vec![4, 0, 19, 26, 2];
+        let encryptor = Encryptor::new(key.clone(), RepeatingKey, Rng::default());
+        let cipherbytes = str_to_bytes(&encryptor.encrypt(plaintext));
+
+        // "quick" covers every column once
+        let fragments = [(4, str_to_bytes("quick"))];
+        let pinned = key_from_fragments(&cipherbytes, key.len(), &fragments).unwrap();
+        assert_eq!(pinned, key.iter().map(|&k| Some(k)).collect::<Vec<_>>());
+        let baseline = Frequencies::from_str("hello world");
+        let res = crack_pinned(&cipherbytes, key.len(), &baseline, &pinned);
+        assert_eq!(bytes_to_str(&res.plaintext), plaintext);
+
+        // only the columns a fragment covers are pinned
+        let fragments = [(0, str_to_bytes("th")), (42, str_to_bytes("g"))];
+        let pinned = key_from_fragments(&cipherbytes, key.len(), &fragments).unwrap();
+        assert_eq!(pinned, [Some(4), Some(0), Some(19), None, None]);
+
+        // a wrong keylength contradicts itself, and fragments must fit the ciphertext
+        let fragments = [(4, str_to_bytes("quick brown"))];
+        assert!(key_from_fragments(&cipherbytes, 3, &fragments).is_err());
+        let fragments = [(40, str_to_bytes("dogs"))];
+        assert!(key_from_fragments(&cipherbytes, key.len(), &fragments).is_err());
+    }
+
+    #[test]
+    fn keylength_longer_than_ciphertext() {
+        let baseline = Frequencies::from_str("hello world");
+        let res = crack(&[1, 2, 3], 5, &baseline);
+        assert_eq!(res.plaintext.len(), 3);
+        assert!(res.confidence.is_finite());
+    }
+
+    #[test]
+    fn all_keylengths_match_sequential() {
+        let mut words = std::fs::read_to_string("words/default.txt").unwrap();
+        let dict = Dictionary::from_string(&mut words);
+        let baseline = Frequencies::from_dict(&dict);
+
+        let plaintext = Generator::with_dict(&dict).generate_words(100);
+        let encryptor = Encryptor::new(vec![4, 8, 15, 16, 23, 42], RepeatingKey, Rng::default());
+        let cipherbytes = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+        let mut keylen_guesses = Vec::new();
+        guesses(&cipherbytes, &mut keylen_guesses);
+
+        // one result per guess, in the same order as cracking one by one
+        let all = crack_keylengths(&cipherbytes, &keylen_guesses, &baseline);
+        assert_eq!(all.len(), keylen_guesses.len());
+        for (res, (keylen, keylen_confidence)) in all.iter().zip(keylen_guesses.iter()) {
+            let single = crack(&cipherbytes, *keylen, &baseline);
+            assert_eq!(res.plaintext, single.plaintext);
+            assert_eq!(res.confidence, single.confidence * keylen_confidence);
+        }
+
+        // the best of them is the same as picking the best sequentially
+        let best = crack_all_keylengths_par(&cipherbytes, &keylen_guesses, &baseline);
+        assert_eq!(best.plaintext, best_crack(&all).plaintext);
+        assert_eq!(bytes_to_str(&best.plaintext).len(), plaintext.len());
+    }
+
+    #[test]
+    fn best_crack_ties() {
+        let results = [
+            CrackResult::new(vec![3, 1], 0.5),
+            CrackResult::new(vec![9], f64::NAN),
+            CrackResult::new(vec![2, 7], 0.5),
+            CrackResult::new(vec![4], f64::INFINITY),
+            CrackResult::new(vec![2, 5], 0.5),
+        ];
+
+        // NaN is never best, and ties go to the lexically first plaintext in any order
+        for rotation in 0..results.len() {
+            let mut shuffled = results.to_vec();
+            shuffled.rotate_left(rotation);
+            assert_eq!(best_crack(&shuffled).plaintext, vec![2, 5]);
+            shuffled.reverse();
+            assert_eq!(best_crack(&shuffled).plaintext, vec![2, 5]);
+        }
+        let nan = CrackResult::new(vec![], f64::NAN);
+        assert_eq!(
+            best_crack(&[nan.clone(), results[3].clone()]).plaintext,
+            vec![4]
+        );
+        assert!(best_crack(&[nan]).confidence.is_nan());
+
+        // with keylengths, ties go to the shorter keylength before the plaintext
+        let at_keylengths = [
+            (7, results[4].clone()),
+            (3, results[1].clone()),
+            (5, results[0].clone()),
+            (5, results[2].clone()),
+        ];
+        for rotation in 0..at_keylengths.len() {
+            let mut shuffled = at_keylengths.to_vec();
+            shuffled.rotate_left(rotation);
+            assert_eq!(best_crack_at_keylengths(&shuffled).plaintext, vec![2, 7]);
+        }
     }
 }
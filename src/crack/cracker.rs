@@ -1,82 +1,460 @@
-use strsim::levenshtein;
-
-use crate::crack::{best_crack, crack, guesses, spellcheck, Frequencies};
-use crate::dict::{BytesDictionary, Dictionary};
+use crate::crack::classify::{classify_known, Classification};
+use crate::crack::keylength::MIN_KEYLENGTH;
+use crate::crack::pipeline::{normalize, OnPartial, Partial, Pipeline, PipelineContext};
+use crate::crack::strategy::Dispatch;
+use crate::crack::threads;
+use crate::crack::{
+    crack_with_scratch, CrackOptions, CrackResult, CrackScratch, DictionaryStats, Frequencies,
+    MAX_GUESSED_KEYLENGTH,
+};
+use crate::dict::{levenshtein_within, BytesDictionary, Dictionary};
 use crate::utils::*;
 
-pub fn crack_single_ciphertext(ciphertext: &str) -> String {
-    // SETUP
-    let mut words = include_str!("../../words/default.txt").to_string();
-    let dict = Dictionary::from_string(&mut words);
-    let bytes_dict = BytesDictionary::from_dict(&dict);
-    let baseline_freqs = Frequencies::from_dict(&dict);
-
-    // Get strings for Test 1
-    let test1_str = include_str!("../../words/test1_plaintext.txt");
-    let test1_known_plaintexts: Vec<(String, Frequencies)> = test1_str
-        .lines()
-        .map(|s| {
-            let string = s.to_string();
-            let freqs = Frequencies::from_str(s);
-            (string, freqs)
-        })
-        .collect();
-
-    let mut keylen_guesses = Vec::new();
-    let mut crack_results = Vec::new();
-    let mut spell_checked = Vec::new();
-
-    // get bytes for the given ciphertext
-    let cipherbytes = str_to_bytes(&ciphertext);
-
-    // KEYLENGTH GUESSING
-    guesses(&cipherbytes, &mut keylen_guesses);
-
-    // ===============   TEST 1   ===================== //
-
-    let mut best_test1_score = f32::MAX;
-    let mut test1_guessed_pt = "";
-
-    for (known_pt, freqs) in test1_known_plaintexts.iter() {
-        let mut best_score = f32::MAX;
-
-        for crack in (3..120_usize).map(|keylen| crack(&cipherbytes, keylen, &freqs)) {
-            let crackstr = bytes_to_str(&crack.plaintext);
-            let score = levenshtein(&crackstr, &known_pt) as f32 / known_pt.len() as f32;
-
-            // update the best score for this plaintext
-            if score < best_score {
-                best_score = score;
-            }
+use std::time::{Duration, Instant};
+use tracing::{debug, debug_span, info, info_span};
+
+/// Test 1 scores below this are taken to be a known plaintext. A crack unrelated to every known
+/// plaintext scores about 0.75 or more.
+pub(super) const TEST1_THRESHOLD: f64 = 0.7;
+
+/// Everything needed to crack ciphertexts: the dictionary for Test 2, the known plaintexts for
+/// Test 1, and optionally a deadline to have an answer by.
+pub struct Cracker {
+    pub(super) bytes_dict: BytesDictionary,
+    pub(super) stats: DictionaryStats,
+    /// Candidate plaintexts for Test 1
+    known_plaintexts: KnownPlaintexts,
+    /// Stages Test 2 ciphertexts are cracked in
+    pipeline: Pipeline,
+    /// Time by which cracking must return, even if not every strategy was tried
+    deadline: Option<Instant>,
+    /// Whether to crack with the pipeline a [`Dispatch`] picks for each ciphertext instead of
+    /// `pipeline`
+    adaptive: bool,
+}
+
+impl Default for Cracker {
+    /// A Cracker using the project's Test 2 dictionary and Test 1 plaintexts.
+    fn default() -> Self {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let known_plaintexts = include_str!("../../words/test1_plaintext.txt");
+        Self::new(&dict, known_plaintexts.lines())
+    }
+}
+
+impl Cracker {
+    /// Create a Cracker for ciphertexts that are either one of the `known_plaintexts` (Test 1), or
+    /// made of words from `dict` (Test 2).
+    pub fn new<'a>(dict: &Dictionary, known_plaintexts: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            bytes_dict: BytesDictionary::from_dict(dict),
+            stats: DictionaryStats::from_dict(dict),
+            known_plaintexts: KnownPlaintexts::new(known_plaintexts),
+            pipeline: Pipeline::default(),
+            deadline: None,
+            adaptive: false,
+        }
+    }
+
+    /// Crack ciphertexts that aren't a known plaintext with `pipeline` instead of every stage.
+    pub fn with_pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Crack each ciphertext that isn't a known plaintext with the [`Strategy`] its length calls
+    /// for, as [`dispatch`][`Self::dispatch`] picks it, instead of the Cracker's pipeline.
+    ///
+    /// [`Strategy`]: crate::crack::strategy::Strategy
+    pub fn with_adaptive_strategy(mut self) -> Self {
+        self.adaptive = true;
+        self
+    }
+
+    /// Which strategy an [adaptive][`Self::with_adaptive_strategy`] Cracker cracks `ciphertext`
+    /// with, after checking its known plaintexts.
+    pub fn dispatch(&self, ciphertext: &str) -> Dispatch {
+        Dispatch::new(ciphertext, self.known_plaintexts.len())
+    }
+
+    /// The pipeline to crack `ciphertext` with: the one its dispatch picks if the Cracker is
+    /// adaptive, or the Cracker's own.
+    fn pipeline_for(&self, ciphertext: &str) -> Pipeline {
+        if !self.adaptive {
+            return self.pipeline.clone();
+        }
+        let dispatch = self.dispatch(ciphertext);
+        info!(
+            strategy = dispatch.strategy.name(),
+            len = dispatch.length,
+            "picked a strategy"
+        );
+        dispatch.strategy.pipeline()
+    }
+
+    /// Return the best result found so far once `deadline` passes.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Return the best result found so far once `timeout` has passed, counting from now. A
+    /// timeout too long to count to is no deadline at all.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        match deadline_after(timeout) {
+            Some(deadline) => self.with_deadline(deadline),
+            None => self,
+        }
+    }
+
+    /// Crack the ciphertext, first checking if it was one of the known plaintexts, then cracking
+    /// it as dictionary words.
+    ///
+    /// If the deadline passes, the best result found so far is returned with
+    /// [`timed_out`][`CrackResult::timed_out`] set.
+    pub fn crack(&self, ciphertext: &str) -> CrackResult {
+        self.crack_with_deadline(ciphertext, self.deadline)
+    }
+
+    /// Same as [`crack`][`Self::crack`], with `deadline` instead of the Cracker's own. This lets
+    /// one Cracker be shared by cracks that each have their own time limit.
+    pub fn crack_with_deadline(&self, ciphertext: &str, deadline: Option<Instant>) -> CrackResult {
+        self.crack_in(
+            ciphertext,
+            deadline,
+            &self.pipeline_for(ciphertext),
+            TEST1_THRESHOLD,
+            MAX_GUESSED_KEYLENGTH,
+            None,
+        )
+    }
+
+    /// Same as [`crack_with_deadline`][`Self::crack_with_deadline`], calling `on_partial` each
+    /// time a stage of the pipeline finishes a keylength candidate, with the best crack so far.
+    /// Known plaintexts (Test 1) don't go through the pipeline, so they aren't reported.
+    pub fn crack_with_deadline_partials(
+        &self,
+        ciphertext: &str,
+        deadline: Option<Instant>,
+        on_partial: impl Fn(&Partial) + Sync,
+    ) -> CrackResult {
+        self.crack_in(
+            ciphertext,
+            deadline,
+            &self.pipeline_for(ciphertext),
+            TEST1_THRESHOLD,
+            MAX_GUESSED_KEYLENGTH,
+            Some(&on_partial),
+        )
+    }
+
+    /// Same as [`crack`][`Self::crack`], configured by `opts` instead: its timeout (counting from
+    /// now) instead of the Cracker's deadline, if it has one, its known plaintext threshold and
+    /// keylengths, its spellchecking weights, and its number of threads with the `parallel`
+    /// feature.
+    pub fn crack_with(&self, ciphertext: &str, opts: &CrackOptions) -> CrackResult {
+        self.crack_with_options(ciphertext, opts, None)
+    }
+
+    /// Same as [`crack_with`][`Self::crack_with`], calling `on_partial` like
+    /// [`crack_with_deadline_partials`][`Self::crack_with_deadline_partials`] does.
+    pub fn crack_with_partials(
+        &self,
+        ciphertext: &str,
+        opts: &CrackOptions,
+        on_partial: impl Fn(&Partial) + Sync,
+    ) -> CrackResult {
+        self.crack_with_options(ciphertext, opts, Some(&on_partial))
+    }
+
+    fn crack_with_options(
+        &self,
+        ciphertext: &str,
+        opts: &CrackOptions,
+        on_partial: Option<OnPartial>,
+    ) -> CrackResult {
+        let deadline = match opts.timeout {
+            Some(timeout) => deadline_after(timeout),
+            None => self.deadline,
+        };
+        let pipeline = self.pipeline_for(ciphertext).with_options(opts);
+        let crack = || {
+            let threshold = opts.known_plaintext_threshold;
+            let max_keylength = opts.keylength_scoring.max_keylength;
+            self.crack_in(
+                ciphertext,
+                deadline,
+                &pipeline,
+                threshold,
+                max_keylength,
+                on_partial,
+            )
+        };
+
+        threads::install(opts.num_workers, crack)
+    }
+
+    /// Crack the ciphertext with `pipeline` until `deadline`, taking it to be a known plaintext if
+    /// a crack at a keylength up to `max_keylength` scores under `threshold` against it. The
+    /// pipeline reports its partial results to `on_partial`.
+    fn crack_in(
+        &self,
+        ciphertext: &str,
+        deadline: Option<Instant>,
+        pipeline: &Pipeline,
+        threshold: f64,
+        max_keylength: usize,
+        on_partial: Option<OnPartial>,
+    ) -> CrackResult {
+        // get bytes for the given ciphertext
+        let cipherbytes = normalize(ciphertext);
+        let _span = info_span!("crack", len = cipherbytes.len()).entered();
+
+        // ===============   TEST 1   ===================== //
+        let mut scratch = CrackScratch::default();
+        let test1 = self.known_plaintexts.find(
+            &cipherbytes,
+            threshold,
+            max_keylength,
+            deadline,
+            &mut scratch,
+        );
+        if let Some(result) = test1 {
+            return result;
         }
 
-        if best_score < best_test1_score {
-            best_test1_score = best_score;
-            test1_guessed_pt = known_pt;
+        // ===============   TEST 2   ===================== //
+        let _test2_span = debug_span!("test2").entered();
+
+        // crack, refine and spellcheck each keylength candidate until running out of time
+        let ctx = PipelineContext {
+            stats: &self.stats,
+            bytes_dict: &self.bytes_dict,
+            deadline,
+            on_partial,
+        };
+        let best = pipeline.run(&ctx, ciphertext);
+        if best.timed_out {
+            info!("out of time");
         }
+
+        best
+    }
+}
+
+/// The known plaintexts of Test 1, as bytes along with their character frequencies. Both
+/// [`Cracker`] and the worker pool's trials check for them this way.
+pub(super) struct KnownPlaintexts(Vec<(Vec<u8>, Frequencies)>);
+
+impl KnownPlaintexts {
+    pub(super) fn new<'a>(plaintexts: impl IntoIterator<Item = &'a str>) -> Self {
+        let plaintexts = plaintexts
+            .into_iter()
+            .map(|s| {
+                let bytes = normalize(s);
+                let freqs = Frequencies::from_bytes(&bytes);
+                (bytes, freqs)
+            })
+            .collect();
+        Self(plaintexts)
     }
 
-    if best_test1_score < 0.8 {
+    /// The project's Test 1 plaintexts.
+    #[cfg(feature = "workers")]
+    pub(super) fn project() -> Self {
+        Self::new(include_str!("../../words/test1_plaintext.txt").lines())
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The known plaintext at `index`.
+    pub(super) fn get(&self, index: usize) -> &[u8] {
+        &self.0[index].0
+    }
+
+    /// Test 1: the known plaintext `cipherbytes` is an encryption of, if it's clearly one of them,
+    /// or if a crack at a keylength up to `max_keylength` scores under `threshold` against it.
+    /// Returns `None` if it's none of them, or `deadline` passes before one scores well enough.
+    pub(super) fn find(
+        &self,
+        cipherbytes: &[u8],
+        threshold: f64,
+        max_keylength: usize,
+        deadline: Option<Instant>,
+        scratch: &mut CrackScratch,
+    ) -> Option<CrackResult> {
+        let out_of_time = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        let _span = debug_span!("test1").entered();
+
+        // tell the known plaintexts apart without cracking, when it's clear
+        let known = self.0.iter().map(|(pt, _)| pt.as_slice());
+        let classification = classify_known(cipherbytes, known);
+        debug!(?classification, "classified");
+        if let Classification::Known(index, score) = classification {
+            info!(score, "matched a known plaintext");
+            let mut result = CrackResult::new(self.get(index).to_vec(), 1.0 - score);
+            result.timed_out = out_of_time();
+            return Some(result);
+        }
+
+        // only scores under the threshold matter, so they bound the edit distances worth finishing
+        let mut best_test1_score = threshold;
+        let mut test1_guessed_pt = None;
+
+        // otherwise crack at every keylength with the frequencies of each known plaintext, unless
+        // it's clearly none of them
+        let unsure = classification == Classification::Unsure;
+        'test1: for (known_pt, freqs) in self.0.iter().filter(|_| unsure) {
+            for keylen in MIN_KEYLENGTH..=max_keylength {
+                if out_of_time() {
+                    debug!(keylen, "out of time");
+                    break 'test1;
+                }
+
+                let crack = crack_with_scratch(cipherbytes, keylen, freqs, scratch);
+                let len = crack.plaintext.len().max(known_pt.len());
+                let max = (best_test1_score * len as f64) as usize;
+                let row = &mut scratch.levenshtein_row;
+
+                // update the best score overall
+                if let Some(distance) = levenshtein_within(&crack.plaintext, known_pt, max, row) {
+                    let score = distance as f64 / len as f64;
+                    if score < best_test1_score {
+                        best_test1_score = score;
+                        test1_guessed_pt = Some(known_pt);
+                    }
+                }
+            }
+        }
+
         // it was probably test1, return plaintext
-        return test1_guessed_pt.to_string();
+        let known_pt = test1_guessed_pt?;
+        info!(score = best_test1_score, "matched a known plaintext");
+        let mut result = CrackResult::new(known_pt.clone(), best_test1_score);
+        result.timed_out = out_of_time();
+        Some(result)
     }
+}
+
+/// Crack a single ciphertext with the project dictionaries, returning the plaintext guess. This is
+/// [`Cracker::crack`] with the default Cracker, so every stage of the default
+/// [`Pipeline`] runs for ciphertexts that aren't a known plaintext.
+pub fn crack_single_ciphertext(ciphertext: &str) -> String {
+    bytes_to_str(&Cracker::default().crack(ciphertext).plaintext)
+}
+
+/// The deadline `timeout` from now, or `None` if that's too far off for an [`Instant`], which no
+/// crack would reach anyway.
+pub(crate) fn deadline_after(timeout: Duration) -> Option<Instant> {
+    Instant::now().checked_add(timeout)
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::prelude::*;
+
+    fn ciphertext() -> (String, String) {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let plaintext = Generator::with_dict(&dict).generate_words(80);
+        let encryptor = Encryptor::new(vec![1, 5, 9, 13, 17], RepeatingKey, Rng::default());
+        (plaintext.clone(), encryptor.encrypt(&plaintext))
+    }
+
+    #[test]
+    fn no_deadline() {
+        let (plaintext, ciphertext) = ciphertext();
+        let result = Cracker::default().crack(&ciphertext);
+        assert!(!result.timed_out);
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
+    }
+
+    #[test]
+    fn deadline_already_passed() {
+        let (plaintext, ciphertext) = ciphertext();
+        let cracker = Cracker::default().with_deadline(Instant::now());
+        let result = cracker.crack(&ciphertext);
+
+        // still get something back, with the right length, but flagged as timed out
+        assert!(result.timed_out);
+        assert_eq!(result.plaintext.len(), plaintext.len());
+    }
+
+    #[test]
+    fn options() {
+        let (plaintext, ciphertext) = ciphertext();
+        let cracker = Cracker::default();
 
-    // ===============   TEST 2   ===================== //
+        // one thread, and only the best few keylength guesses
+        let opts = CrackOptions::default()
+            .with_workers(1)
+            .with_max_keylengths(3);
+        let result = cracker.crack_with(&ciphertext, &opts);
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
+        assert!(!result.timed_out);
 
-    // CRACKING SLICES
-    for (keylen, keylen_confidence) in keylen_guesses.iter() {
-        let mut res = crack(&cipherbytes, *keylen, &baseline_freqs);
-        res.confidence *= keylen_confidence;
-        crack_results.push(res);
+        // a timeout that has already passed, though the cracker has no deadline
+        let result = cracker.crack_with(&ciphertext, &opts.clone().with_timeout(Duration::ZERO));
+        assert!(result.timed_out);
+        assert_eq!(result.plaintext.len(), plaintext.len());
+
+        // a timeout too long to count to is no deadline
+        let result = cracker.crack_with(&ciphertext, &opts.with_timeout(Duration::MAX));
+        assert!(!result.timed_out);
+        let result = cracker.with_timeout(Duration::MAX).crack(&ciphertext);
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
     }
 
-    // SPELL CHECKING
-    for crack in &crack_results {
-        spell_checked.push(spellcheck(crack, &bytes_dict));
+    #[test]
+    fn known_fragments() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let plaintext = Generator::with_dict(&dict).generate_words(8);
+        let key = vec![9, 18, 2, 7, 11];
+        let encryptor = Encryptor::new(key.clone(), RepeatingKey, Rng::default());
+        let ciphertext = encryptor.encrypt(&plaintext);
+        // without spellchecking, which would correct a wrong crib away again
+        let pipeline = Pipeline::from_names(["normalize", "keylength", "crack", "rank"]).unwrap();
+        let cracker = Cracker::default().with_pipeline(pipeline);
+
+        // the crack follows the key a crib covering all of it fixes, right or wrong
+        let with_crib = |crib: &str| {
+            let opts = CrackOptions {
+                known_fragments: vec![(0, str_to_bytes(&crib[..key.len()]))],
+                ..CrackOptions::default().with_workers(1)
+            };
+            bytes_to_str(&cracker.crack_with(&ciphertext, &opts).plaintext)
+        };
+        assert_eq!(with_crib(&plaintext), plaintext);
+        let wrong = "hermeneutics footfalls";
+        assert!(with_crib(wrong).starts_with(&wrong[..key.len()]));
     }
 
-    let best_after_spellcheck = best_crack(&spell_checked);
+    #[cfg(feature = "serde")]
+    #[test]
+    fn options_file() {
+        // options left out keep their defaults
+        let opts: CrackOptions = serde_json::from_str(r#"{"max_keylengths": 4}"#).unwrap();
+        assert_eq!(opts.max_keylengths, Some(4));
+        assert_eq!(opts.known_plaintext_threshold, TEST1_THRESHOLD);
+        assert!(opts.timeout.is_none());
+    }
 
-    // return the plaintext guess
-    return bytes_to_str(&best_after_spellcheck.plaintext);
+    #[test]
+    fn known_plaintext() {
+        let known = include_str!("../../words/test1_plaintext.txt")
+            .lines()
+            .nth(2)
+            .unwrap();
+        let encryptor = Encryptor::new(vec![3, 1, 4, 1, 5, 9, 2, 6], RepeatingKey, Rng::default());
+        let ciphertext = encryptor.encrypt(known);
+
+        let result = Cracker::default().crack(&ciphertext);
+        assert_eq!(bytes_to_str(&result.plaintext), known);
+    }
 }
@@ -0,0 +1,129 @@
+//! Evaluation datasets of encrypted trials, for sharing between teammates and for checking that
+//! cracking accuracy doesn't regress over time.
+//!
+//! A dataset is NDJSON, one [`DatasetEntry`] per line: a [`TrialRecord`] (plaintext, ciphertext,
+//! key and scheduler) along with the [`SchedulerComplexity`] its scheduler was generated at.
+//! [`evaluate_dataset`][`crate::crack::eval::evaluate_dataset`] measures how accurately the
+//! cracker does on one.
+
+use crate::ciphers::schedulers::{RandomScheduler, SchedulerComplexity};
+use crate::crack::replay::{load_lines, read_lines, Recorder};
+use crate::crack::worker::{TrialCracker, TrialRecord};
+use crate::dict::Dictionary;
+use crate::gen::Generator;
+use crate::rng::Rng;
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// What goes into a dataset. Every complexity level gets `count` entries.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Dataset {
+    pub count: usize,
+    pub complexities: Vec<SchedulerComplexity>,
+    /// Seed for all randomness in the dataset, so it can be built again exactly
+    pub seed: u64,
+}
+
+impl Default for Dataset {
+    fn default() -> Self {
+        Self {
+            count: 100,
+            complexities: SchedulerComplexity::ALL.to_vec(),
+            seed: 0xda7a,
+        }
+    }
+}
+
+/// One trial of a dataset.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct DatasetEntry {
+    /// Complexity the scheduler was generated at
+    pub complexity: SchedulerComplexity,
+    #[serde(flatten)]
+    pub trial: TrialRecord,
+}
+
+impl Dataset {
+    /// Generate every entry, numbered from 0 in order of complexity.
+    pub fn build(&self) -> Vec<DatasetEntry> {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let mut gen = Generator::with_dict(&dict);
+        let cracker = TrialCracker::project();
+
+        let mut rng = Rng::from_seed(self.seed);
+        let mut entries = Vec::with_capacity(self.count * self.complexities.len());
+        for &complexity in &self.complexities {
+            for _ in 0..self.count {
+                let id = entries.len() as u64;
+                let sched = RandomScheduler::with_complexity(&mut rng, complexity);
+                let trial = cracker.generate(id, rng.next(), sched, &mut gen);
+                entries.push(DatasetEntry { complexity, trial });
+            }
+        }
+        entries
+    }
+
+    /// Generate every entry and write them as NDJSON.
+    pub fn write(&self, out: impl Write) -> anyhow::Result<()> {
+        let mut recorder = Recorder::new(out);
+        for entry in self.build() {
+            recorder.record(&entry)?;
+        }
+        recorder.finish()?;
+        Ok(())
+    }
+}
+
+/// Read every entry of a dataset from NDJSON, skipping blank lines.
+pub fn read(input: impl BufRead) -> anyhow::Result<Vec<DatasetEntry>> {
+    read_lines(input, "dataset entry")
+}
+
+/// Read every entry of a dataset file.
+pub fn load(path: &Path) -> anyhow::Result<Vec<DatasetEntry>> {
+    load_lines(path, "dataset entry")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_write_read() {
+        let dataset = Dataset {
+            count: 2,
+            complexities: vec![SchedulerComplexity::Easy, SchedulerComplexity::Hard],
+            seed: 9,
+        };
+
+        let mut out = Vec::new();
+        dataset.write(&mut out).unwrap();
+        let entries = read(out.as_slice()).unwrap();
+        assert_eq!(entries.len(), 4);
+
+        // numbered in order, grouped by complexity, and flattened into one object per line
+        let ids: Vec<u64> = entries.iter().map(|e| e.trial.id).collect();
+        assert_eq!(ids, [0, 1, 2, 3]);
+        assert_eq!(entries[1].complexity, SchedulerComplexity::Easy);
+        assert_eq!(entries[2].complexity, SchedulerComplexity::Hard);
+        assert!(entries
+            .iter()
+            .take(2)
+            .all(|e| e.trial.scheduler.layers() == 0));
+        let first_line = std::str::from_utf8(&out).unwrap().lines().next().unwrap();
+        assert!(first_line.starts_with("{\"complexity\":\"Easy\",\"id\":0,"));
+
+        // the same seed builds the same dataset
+        let again = dataset.build();
+        for (a, b) in entries.iter().zip(again.iter()) {
+            assert_eq!(a.trial.ciphertext, b.trial.ciphertext);
+            assert_eq!(a.trial.key, b.trial.key);
+        }
+
+        let report = crate::crack::eval::evaluate_dataset(entries);
+        assert_eq!(report.overall.trials, 4);
+        assert_eq!(report.per_complexity["hard"].trials, 2);
+    }
+}
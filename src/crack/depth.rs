@@ -0,0 +1,264 @@
+//! Module for reading two messages in depth: ciphertexts of the same length, encrypted under the
+//! same keystream.
+//!
+//! Whatever the keystream is, even one that never repeats, it cancels out of the difference of
+//! two ciphertexts under it, leaving the difference of their plaintexts. Both plaintexts are then
+//! decoded together into dictionary words, like [`decode_words`][`crate::crack::decode_words`]
+//! decodes one, with a shortest path over the positions between words. Whichever plaintext is
+//! decoded less far places its next word, and its characters have to differ from the characters
+//! of the other plaintext's word by the difference of the ciphertexts.
+
+use crate::dict::{substitution_cost, BytesDictionary};
+use crate::utils::{Shift, ALPHABET};
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Most positions between words the search visits before giving up. Dictionaries of a few dozen
+/// words visit far fewer for messages of a few hundred characters.
+const MAX_VISITED: usize = 1 << 21;
+
+/// Plaintexts of two messages in depth, from [`read_depth`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepthCrack {
+    /// Plaintext of the first ciphertext
+    pub first: Vec<u8>,
+    /// Plaintext of the second ciphertext
+    pub second: Vec<u8>,
+    /// Fraction of the characters whose plaintexts don't differ by the difference of the
+    /// ciphertexts, like the confidence of a [`CrackResult`][`crate::crack::CrackResult`]. 0.0
+    /// when both plaintexts are dictionary words that encrypt to the ciphertexts under one
+    /// keystream.
+    pub confidence: f64,
+}
+
+impl DepthCrack {
+    /// The keystream that encrypts the first plaintext to `first`, the first ciphertext.
+    pub fn keystream(&self, first: &[u8]) -> Vec<i8> {
+        first
+            .iter()
+            .zip(&self.first)
+            .map(|(&c, &p)| (c as i8 - p as i8).rem_euclid(ALPHABET.len() as i8))
+            .collect()
+    }
+}
+
+/// The difference of two ciphertexts under the same keystream, which is the difference of their
+/// plaintexts: each byte of `first` shifted back by the byte of `second` at the same position.
+///
+/// ```
+/// # use one_team_pad_cipher_cracker::crack::depth::difference;
+/// # use one_team_pad_cipher_cracker::utils::{str_to_bytes, Shift};
+/// let keystream = [3, 25, 11, 0, 7];
+/// let encrypt = |text: &str| -> Vec<u8> {
+///     str_to_bytes(text).iter().zip(&keystream).map(|(p, &k)| p.shift(k)).collect()
+/// };
+/// let plaintexts = difference(&str_to_bytes("hello"), &str_to_bytes("world"));
+/// assert_eq!(difference(&encrypt("hello"), &encrypt("world")), plaintexts);
+/// ```
+pub fn difference(first: &[u8], second: &[u8]) -> Vec<u8> {
+    first
+        .iter()
+        .zip(second)
+        .map(|(&a, &b)| a.shift(-(b as i8)))
+        .collect()
+}
+
+/// How far both plaintexts are decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Frontier {
+    /// Both plaintexts are decoded up to here
+    lag: usize,
+    /// Whether the second plaintext is the one decoded past `lag`
+    second_leads: bool,
+    /// The word one plaintext is decoded into past `lag`, and where `lag` is in it. `None` when
+    /// both are decoded up to `lag`.
+    overhang: Option<(usize, usize)>,
+}
+
+/// Decode two ciphertexts of the same length, encrypted under the same keystream, into the two
+/// sequences of dictionary words whose differences come closest to the difference of the
+/// ciphertexts, where characters a small shift apart come closer (see [`substitution_cost`]).
+/// The last word of each may be cut short, like a plaintext truncated to a length.
+///
+/// `None` if the ciphertexts aren't the same length, or no sequences of words fit them within
+/// the search's limits.
+pub fn read_depth(first: &[u8], second: &[u8], dict: &BytesDictionary) -> Option<DepthCrack> {
+    if first.len() != second.len() {
+        return None;
+    }
+    let n = first.len();
+    let diff = difference(first, second);
+
+    // cheapest cost to each frontier, and the frontier and word it was reached from
+    let mut best: HashMap<Frontier, (usize, Option<(Frontier, usize)>)> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    let start = Frontier {
+        lag: 0,
+        second_leads: false,
+        overhang: None,
+    };
+    best.insert(start, (0, None));
+    queue.push(Reverse((0, start)));
+
+    let mut visited = 0;
+    let goal = loop {
+        let Reverse((cost, frontier)) = queue.pop()?;
+        if best[&frontier].0 < cost {
+            // already reached more cheaply
+            continue;
+        }
+        if frontier.lag == n && frontier.overhang.is_none() {
+            break frontier;
+        }
+        visited += 1;
+        if visited > MAX_VISITED {
+            return None;
+        }
+
+        for (index, word) in dict.words().enumerate() {
+            let (next, extra) = place(frontier, index, word, &diff, dict);
+            let total = cost + extra;
+            if best.get(&next).is_none_or(|&(known, _)| total < known) {
+                best.insert(next, (total, Some((frontier, index))));
+                queue.push(Reverse((total, next)));
+            }
+        }
+    };
+
+    // write every word on the way to the goal into the plaintext it was placed in
+    let mut plaintexts = [vec![0; n], vec![0; n]];
+    let mut frontier = goal;
+    while let Some((previous, index)) = best[&frontier].1 {
+        let placer = placing(previous);
+        let word = dict.word(index);
+        let end = (previous.lag + word.len()).min(n);
+        plaintexts[placer][previous.lag..end].copy_from_slice(&word[..end - previous.lag]);
+        frontier = previous;
+    }
+
+    let [first, second] = plaintexts;
+    let mismatched = difference(&first, &second)
+        .iter()
+        .zip(&diff)
+        .filter(|(a, b)| a != b)
+        .count();
+    Some(DepthCrack {
+        first,
+        second,
+        confidence: mismatched as f64 / n.max(1) as f64,
+    })
+}
+
+/// Which plaintext places the next word at `frontier`: the one decoded less far, or the first if
+/// neither is.
+fn placing(frontier: Frontier) -> usize {
+    match frontier.overhang {
+        Some(_) if !frontier.second_leads => 1,
+        _ => 0,
+    }
+}
+
+/// The frontier after placing word `index` of `dict` at `frontier`, and the cost of how far its
+/// characters are from what the other plaintext's overhang makes them.
+fn place(
+    frontier: Frontier,
+    index: usize,
+    word: &[u8],
+    diff: &[u8],
+    dict: &BytesDictionary,
+) -> (Frontier, usize) {
+    let Frontier { lag, overhang, .. } = frontier;
+    let n = diff.len();
+    let overhang = match overhang {
+        Some((leader, offset)) => {
+            let leader = dict.word(leader);
+            &leader[offset..leader.len().min(offset + n - lag)]
+        }
+        None => &[],
+    };
+    let placed = &word[..word.len().min(n - lag)];
+
+    // the first plaintext is the second shifted forward by the difference
+    let placer = placing(frontier);
+    let cost = overhang
+        .iter()
+        .zip(placed)
+        .zip(&diff[lag..])
+        .map(|((&other, &own), &d)| {
+            let expected = match placer {
+                0 => other.shift(d as i8),
+                _ => other.shift(-(d as i8)),
+            };
+            substitution_cost(expected, own)
+        })
+        .sum();
+
+    let next = if placed.len() < overhang.len() {
+        Frontier {
+            lag: lag + placed.len(),
+            overhang: frontier
+                .overhang
+                .map(|(leader, offset)| (leader, offset + placed.len())),
+            ..frontier
+        }
+    } else if placed.len() == overhang.len() {
+        Frontier {
+            lag: lag + placed.len(),
+            second_leads: false,
+            overhang: None,
+        }
+    } else {
+        Frontier {
+            lag: lag + overhang.len(),
+            second_leads: placer == 1,
+            overhang: Some((index, overhang.len())),
+        }
+    };
+    (next, cost)
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::utils::{bytes_to_str, str_to_bytes};
+
+    #[test]
+    fn reads_depth() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let bytes_dict = BytesDictionary::from_dict(&dict);
+        let mut gen = Generator::with_dict(&dict);
+        let plaintexts = [gen.generate_exact_len(120), gen.generate_exact_len(120)];
+
+        // a keystream that never repeats, which no frequency analysis can crack
+        let mut rng = Rng::default();
+        let keystream: Vec<i8> = (0..120).map(|_| rng.gen_range(0..27) as i8).collect();
+        let encrypt = |plaintext: &str| -> Vec<u8> {
+            str_to_bytes(plaintext)
+                .iter()
+                .zip(&keystream)
+                .map(|(p, &k)| p.shift(k))
+                .collect()
+        };
+        let ciphertexts = [encrypt(&plaintexts[0]), encrypt(&plaintexts[1])];
+
+        let cracked = read_depth(&ciphertexts[0], &ciphertexts[1], &bytes_dict).unwrap();
+        assert_eq!(bytes_to_str(&cracked.first), plaintexts[0]);
+        assert_eq!(bytes_to_str(&cracked.second), plaintexts[1]);
+        assert_eq!(cracked.confidence, 0.0);
+        assert_eq!(cracked.keystream(&ciphertexts[0]), keystream);
+
+        // a garbled character is decoded back into its words, and counts against the confidence
+        let mut garbled = ciphertexts[1].clone();
+        garbled[40] = garbled[40].shift(9);
+        let cracked = read_depth(&ciphertexts[0], &garbled, &bytes_dict).unwrap();
+        assert_eq!(bytes_to_str(&cracked.first), plaintexts[0]);
+        assert_eq!(bytes_to_str(&cracked.second), plaintexts[1]);
+        assert_eq!(cracked.confidence, 1.0 / 120.0);
+
+        // messages of different lengths aren't in depth
+        assert!(read_depth(&ciphertexts[0], &ciphertexts[1][1..], &bytes_dict).is_none());
+    }
+}
@@ -0,0 +1,295 @@
+//! Splitting the crack of a single ciphertext into independent tasks.
+//!
+//! Every keylength guess is its own task: run the [`Pipeline`] stages that work on one candidate at
+//! a time, from cracking through spellchecking, on that keylength alone. Tasks are fanned out to
+//! the workers of a [`WorkerPool`], in between any trials it is cracking, and the candidates are
+//! fanned back in, then verified and ranked together. This pays off for very long ciphertexts,
+//! where each task takes a long time on its own. Once a keylength cracks cleanly, queued guesses
+//! that are multiples of it are skipped, since they would only crack the same key repeated.
+
+use crate::crack::cracker::deadline_after;
+use crate::crack::pipeline::{Candidate, Pipeline, PipelineContext, PipelineState, Rank, Verify};
+use crate::crack::worker::WorkerPool;
+use crate::crack::{
+    guesses_with, key_from_fragments, CrackOptions, CrackResult, CrackedPeriods, DictionaryStats,
+};
+use crate::dict::{BytesDictionary, Dictionary};
+use crate::utils::str_to_bytes;
+
+use crossbeam_channel::bounded;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use tracing::{debug, info_span};
+
+/// Crack a ciphertext made of words from the project dictionary, spreading the keylength guesses
+/// across a [`WorkerPool`] of `opts.num_workers` threads. Ciphertexts too short to guess a
+/// keylength of crack to an [empty][`CrackResult::empty`] result. Once `opts.timeout` passes, the
+/// best crack so far is returned with [`timed_out`][`CrackResult::timed_out`] set.
+pub fn crack_parallel(ciphertext: &str, opts: &CrackOptions) -> CrackResult {
+    let (bytes_dict, stats) = project_dictionary();
+    let pool = WorkerPool::spawn(opts.num_workers.max(1));
+    let cipherbytes = str_to_bytes(ciphertext);
+    let result = crack_parallel_with(&pool, &cipherbytes, bytes_dict.clone(), stats.clone(), opts);
+    if let Err(panic) = pool.join() {
+        std::panic::resume_unwind(panic);
+    }
+    result
+}
+
+/// The project dictionary and its statistics, built the first time they are needed.
+fn project_dictionary() -> &'static (Arc<BytesDictionary>, Arc<DictionaryStats>) {
+    static PROJECT: OnceLock<(Arc<BytesDictionary>, Arc<DictionaryStats>)> = OnceLock::new();
+    PROJECT.get_or_init(|| {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let stats = DictionaryStats::project().clone();
+        (Arc::new(BytesDictionary::from_dict(&dict)), Arc::new(stats))
+    })
+}
+
+/// Same as [`crack_parallel`], but with the given dictionary and its statistics, on the workers of
+/// `pool` however many there are. A pool that was already shut down leaves the tasks to run on the
+/// calling thread.
+pub fn crack_parallel_with(
+    pool: &WorkerPool,
+    cipherbytes: &[u8],
+    bytes_dict: Arc<BytesDictionary>,
+    stats: Arc<DictionaryStats>,
+    opts: &CrackOptions,
+) -> CrackResult {
+    let _span = info_span!("crack_parallel", len = cipherbytes.len()).entered();
+
+    // KEYLENGTH GUESSING
+    let mut keylen_guesses = Vec::new();
+    guesses_with(cipherbytes, &mut keylen_guesses, &opts.keylength_scoring);
+    if let Some(max) = opts.max_keylengths {
+        keylen_guesses.truncate(max.max(1));
+    }
+    if keylen_guesses.is_empty() {
+        debug!("too short to guess a keylength");
+        return CrackResult::empty();
+    }
+
+    // only the keylengths known plaintext doesn't contradict. If it contradicts all of them,
+    // random characters probably moved it, so crack every keylength without it
+    let fragments = &opts.known_fragments;
+    let mut tasks: Vec<_> = keylen_guesses
+        .iter()
+        .filter(|&&(keylen, _)| key_from_fragments(cipherbytes, keylen, fragments).is_ok())
+        .copied()
+        .collect();
+    if tasks.is_empty() {
+        debug!("known fragments contradict every keylength, cracking without them");
+        tasks = keylen_guesses;
+    }
+
+    // each task runs the stages that take one candidate at a time, and the rest run on them all
+    let shared = Arc::new(Shared {
+        cipherbytes: cipherbytes.to_vec(),
+        bytes_dict,
+        stats,
+        per_candidate: Pipeline::default()
+            .with_options(opts)
+            .without("normalize")
+            .without("keylength")
+            .without("verify")
+            .without("rank"),
+        periods: CrackedPeriods::default(),
+        deadline: opts.timeout.and_then(deadline_after),
+        timed_out: AtomicBool::new(false),
+    });
+
+    // fan out: queue up one task per keylength guess, each sending back its candidate. There is
+    // room for all of them, so no worker waits on the fan in
+    let (results_in, results_out) = bounded(tasks.len());
+    for (task, guess) in tasks.into_iter().enumerate() {
+        let shared = Arc::clone(&shared);
+        let results = results_in.clone();
+        let run = move || {
+            if let Some(candidate) = shared.crack(guess) {
+                results.send((task, candidate)).unwrap();
+            }
+        };
+        if let Err(run) = pool.execute(run) {
+            run();
+        }
+    }
+    drop(results_in);
+
+    // fan in: the results channel is closed once every task is done, so verify and rank the
+    // candidates in the order of the guesses
+    let mut candidates: Vec<_> = results_out.iter().collect();
+    candidates.sort_by_key(|&(task, _)| task);
+    let mut state = PipelineState::with_guesses(cipherbytes, &[]);
+    state.candidates = candidates
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .collect();
+    Pipeline::empty()
+        .then(Verify::default())
+        .then(Rank)
+        .run_state(&shared.context(), &mut state);
+    let mut best = state.best.unwrap_or_else(CrackResult::empty);
+    best.timed_out |= shared.timed_out.load(Ordering::Relaxed);
+    best
+}
+
+/// What every task of one [`crack_parallel_with`] shares.
+struct Shared {
+    cipherbytes: Vec<u8>,
+    bytes_dict: Arc<BytesDictionary>,
+    stats: Arc<DictionaryStats>,
+    /// Stages each task runs on its candidate
+    per_candidate: Pipeline,
+    /// Keylengths that cracked cleanly so far
+    periods: CrackedPeriods,
+    /// Time by which every task must be done, leaving its candidate as it is
+    deadline: Option<Instant>,
+    /// Whether a task ran out of time
+    timed_out: AtomicBool,
+}
+
+impl Shared {
+    fn context(&self) -> PipelineContext<'_> {
+        PipelineContext {
+            stats: &self.stats,
+            bytes_dict: &self.bytes_dict,
+            deadline: self.deadline,
+            on_partial: None,
+        }
+    }
+
+    /// The candidate for one keylength guess, run through every per candidate stage, or `None`
+    /// if the keylength is a multiple of one that already cracked cleanly.
+    fn crack(&self, guess: (usize, f64)) -> Option<Candidate> {
+        let keylen = guess.0;
+        if self.periods.covers(keylen) {
+            debug!(keylen, "multiple of a cleanly cracked keylength, skipping");
+            return None;
+        }
+        let mut state = PipelineState::with_guesses(&self.cipherbytes, &[guess]);
+        self.per_candidate.run_state(&self.context(), &mut state);
+        if state.timed_out {
+            self.timed_out.store(true, Ordering::Relaxed);
+        }
+        let candidate = state.candidates.pop()?;
+        if let (Some(res), Some(checked)) = (&candidate.crack, &candidate.checked) {
+            let clean = checked.plaintext == res.plaintext;
+            self.periods
+                .record(&self.cipherbytes, keylen, &res.plaintext, clean);
+        }
+        Some(candidate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::{Aab, RepeatingKey};
+    use crate::prelude::*;
+    use crate::utils::bytes_to_str;
+
+    #[test]
+    fn matches_plaintext() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let plaintext = Generator::with_dict(&dict).generate_words(150);
+
+        let aab = Aab {
+            num_chars: 2,
+            num_reps: 1,
+            offset: 3,
+        };
+        let encryptor = Encryptor::new(vec![9, 18, 2, 7, 11, 0, 5], aab, Rng::default());
+        let ciphertext = encryptor.encrypt(&plaintext);
+
+        let opts = CrackOptions {
+            num_workers: 3,
+            max_keylengths: Some(10),
+            ..Default::default()
+        };
+        let result = crack_parallel(&ciphertext, &opts);
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
+        assert!(!result.timed_out);
+
+        // a timeout that has already passed still gives a crack back, flagged as timed out
+        let opts = opts.with_timeout(std::time::Duration::ZERO);
+        let result = crack_parallel(&ciphertext, &opts);
+        assert!(result.timed_out);
+        assert_eq!(result.plaintext.len(), plaintext.len());
+    }
+
+    #[test]
+    fn known_fragments() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let plaintext = Generator::with_dict(&dict).generate_words(8);
+        let key = vec![9, 18, 2, 7, 11, 0, 5, 21, 13, 3, 17];
+        let encryptor = Encryptor::new(key.clone(), RepeatingKey, Rng::default());
+        let ciphertext = encryptor.encrypt(&plaintext);
+
+        // a crib covering the whole key cracks a ciphertext too short for frequencies alone
+        let crib = str_to_bytes(&plaintext[..key.len()]);
+        let opts = CrackOptions {
+            num_workers: 2,
+            known_fragments: vec![(0, crib)],
+            ..Default::default()
+        };
+        let result = crack_parallel(&ciphertext, &opts);
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
+
+        // a crib that contradicts every keylength is ignored, instead of leaving nothing to crack
+        let opts = CrackOptions {
+            num_workers: 2,
+            known_fragments: vec![(0, vec![0; ciphertext.len()]), (1, vec![1])],
+            ..Default::default()
+        };
+        let result = crack_parallel(&ciphertext, &opts);
+        assert!(!result.plaintext.is_empty());
+    }
+
+    #[test]
+    fn short_text() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let mut plaintext = Generator::with_dict(&dict).generate_words(20);
+        plaintext.truncate(60);
+        let plaintext = plaintext[..plaintext.rfind(' ').unwrap()].to_string();
+        // ten characters a column, so the keylength guess of 6 searches whole keys
+        let key = vec![4, 20, 11, 0, 17, 9];
+        let encryptor = Encryptor::new(key, RepeatingKey, Rng::default());
+        let ciphertext = encryptor.encrypt(&plaintext);
+
+        let opts = CrackOptions {
+            num_workers: 2,
+            ..Default::default()
+        };
+        let result = crack_parallel(&ciphertext, &opts);
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
+    }
+
+    #[test]
+    fn shut_down_pool() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let plaintext = Generator::with_dict(&dict).generate_words(100);
+        let encryptor = Encryptor::new(vec![6, 2, 19, 8, 0], RepeatingKey, Rng::default());
+        let cipherbytes = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+        // the tasks run on the calling thread instead
+        let mut pool = WorkerPool::spawn(1);
+        pool.shutdown();
+        let (bytes_dict, stats) = project_dictionary();
+        let opts = CrackOptions::default().with_max_keylengths(4);
+        let result = crack_parallel_with(
+            &pool,
+            &cipherbytes,
+            bytes_dict.clone(),
+            stats.clone(),
+            &opts,
+        );
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
+        pool.join().expect("worker panicked");
+    }
+}
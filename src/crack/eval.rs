@@ -0,0 +1,344 @@
+//! Accuracy metrics over labelled trials, such as a dataset from [`crate::crack::dataset`].
+//!
+//! An [`Evaluator`] cracks each trial and tallies how it went into an [`EvalReport`], overall and
+//! grouped by scheduler family and complexity. With the `serde` feature the report serializes to
+//! JSON, so accuracy can be compared between versions of the cracker.
+
+use crate::ciphers::schedulers::SchedulerComplexity;
+use crate::ciphers::KeySchedule;
+use crate::crack::worker::{TrialCracker, TrialRecord, TrialResult};
+use crate::crack::SegmentationWeights;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Accuracy over a group of trials.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvalTally {
+    pub trials: usize,
+    /// Trials where the plaintext was recovered exactly
+    pub exact_matches: usize,
+    pub exact_match_rate: f64,
+    /// Mean levenshtein distance between the cracked and real plaintext, divided by the length
+    /// of the real plaintext
+    pub mean_error: f64,
+    /// Trials where the keylength the base scheduler repeats the key at (see
+    /// [`KeySchedule::effective_period`]), or a multiple of it, was one of the top keylength
+    /// guesses
+    pub keylength_hits: usize,
+    pub keylength_hit_rate: f64,
+    /// Trials where the key recovered from the crack equals the real key, up to reduction
+    pub key_matches: usize,
+    pub key_match_rate: f64,
+    /// Trials where the cracker guessed the wrong test
+    pub wrong_test: usize,
+    /// Mean number of words spellchecking corrected to get each crack
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mean_corrections: f64,
+}
+
+impl EvalTally {
+    fn add(&mut self, result: &TrialResult, keylength_hit: bool) {
+        self.trials += 1;
+        self.exact_matches += result.success as usize;
+        self.keylength_hits += keylength_hit as usize;
+        self.key_matches += (result.recovered_key.as_ref() == Some(&result.trial.key)) as usize;
+        self.wrong_test += (result.guessed_test != result.trial.actual_test()) as usize;
+
+        let n = self.trials as f64;
+        self.exact_match_rate = self.exact_matches as f64 / n;
+        self.keylength_hit_rate = self.keylength_hits as f64 / n;
+        self.key_match_rate = self.key_matches as f64 / n;
+        self.mean_error += (result.error - self.mean_error) / n;
+        self.mean_corrections += (result.corrections as f64 - self.mean_corrections) / n;
+    }
+}
+
+impl fmt::Display for EvalTally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>6} trials {:>6.1}% exact  mean error {:.4}  keylength {:>5.1}%  key {:>5.1}%  wrong test {}  corrections {:.1}",
+            self.trials,
+            self.exact_match_rate * 100.0,
+            self.mean_error,
+            self.keylength_hit_rate * 100.0,
+            self.key_match_rate * 100.0,
+            self.wrong_test,
+            self.mean_corrections,
+        )
+    }
+}
+
+/// Accuracy over every evaluated trial.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvalReport {
+    /// How many of the best keylength guesses count as a hit
+    pub keylength_top: usize,
+    pub overall: EvalTally,
+    /// Grouped by base scheduler family name
+    pub per_scheduler: BTreeMap<String, EvalTally>,
+    /// Grouped by complexity name, for trials that have one
+    pub per_complexity: BTreeMap<String, EvalTally>,
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<16} {}", "overall", self.overall)?;
+        for (name, tally) in &self.per_scheduler {
+            writeln!(f, "{:<16} {}", name, tally)?;
+        }
+        for (name, tally) in &self.per_complexity {
+            writeln!(f, "{:<16} {}", name, tally)?;
+        }
+        Ok(())
+    }
+}
+
+/// Cracks trials and collects an [`EvalReport`].
+pub struct Evaluator {
+    cracker: TrialCracker,
+    report: EvalReport,
+}
+
+impl Evaluator {
+    /// Keylength guesses are a hit when the keylength the key repeats at, or a multiple of it, is
+    /// one of this many best guesses.
+    pub const KEYLENGTH_TOP: usize = 15;
+
+    /// An Evaluator cracking with the project's dictionary and Test 1 plaintexts.
+    pub fn project() -> Self {
+        Self {
+            cracker: TrialCracker::project(),
+            report: EvalReport {
+                keylength_top: Self::KEYLENGTH_TOP,
+                ..EvalReport::default()
+            },
+        }
+    }
+
+    /// Spellcheck Test 2 cracks weighing words by `weights`.
+    pub fn with_weights(mut self, weights: SegmentationWeights) -> Self {
+        self.cracker = self.cracker.with_weights(weights);
+        self
+    }
+
+    /// Crack one trial and add it to the report, under `complexity` too if it has one.
+    pub fn add(
+        &mut self,
+        trial: TrialRecord,
+        complexity: Option<SchedulerComplexity>,
+    ) -> TrialResult {
+        // the keylength the ciphertext repeats at, under the random characters
+        let key_length = trial.key.len();
+        let period = trial
+            .scheduler
+            .base()
+            .effective_period(key_length, trial.plaintext.len())
+            .unwrap_or(key_length);
+        let result = self.cracker.crack(trial);
+        let keylength_hit = self
+            .cracker
+            .keylen_guesses()
+            .iter()
+            .take(self.report.keylength_top)
+            .any(|&(guess, _)| guess % period == 0);
+
+        let report = &mut self.report;
+        report.overall.add(&result, keylength_hit);
+        let kind = result.trial.scheduler.base().kind();
+        report
+            .per_scheduler
+            .entry(kind.name().to_string())
+            .or_default()
+            .add(&result, keylength_hit);
+        if let Some(complexity) = complexity {
+            report
+                .per_complexity
+                .entry(complexity.name().to_string())
+                .or_default()
+                .add(&result, keylength_hit);
+        }
+
+        result
+    }
+
+    /// The report so far.
+    pub fn report(&self) -> &EvalReport {
+        &self.report
+    }
+
+    pub fn finish(self) -> EvalReport {
+        self.report
+    }
+}
+
+/// Crack every entry of a dataset and report the accuracy.
+#[cfg(feature = "serde")]
+pub fn evaluate_dataset(entries: Vec<crate::crack::dataset::DatasetEntry>) -> EvalReport {
+    let mut evaluator = Evaluator::project();
+    for entry in entries {
+        evaluator.add(entry.trial, Some(entry.complexity));
+    }
+    evaluator.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RandomScheduler;
+    use crate::prelude::*;
+
+    /// Generate and evaluate `trials` trials of each complexity.
+    fn evaluate(complexities: &[SchedulerComplexity], trials: usize) -> EvalReport {
+        evaluate_with(Evaluator::project(), complexities, trials)
+    }
+
+    /// Same as [`evaluate`], cracking with `evaluator`.
+    fn evaluate_with(
+        mut evaluator: Evaluator,
+        complexities: &[SchedulerComplexity],
+        trials: usize,
+    ) -> EvalReport {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let mut gen = Generator::with_dict(&dict);
+        let generator = TrialCracker::project();
+
+        let mut rng = Rng::from_seed(0xe7a1);
+        for &complexity in complexities {
+            for id in 0..trials as u64 {
+                let sched = RandomScheduler::with_complexity(&mut rng, complexity);
+                let trial = generator.generate(id, rng.next(), sched, &mut gen);
+                evaluator.add(trial, Some(complexity));
+            }
+        }
+        evaluator.finish()
+    }
+
+    #[test]
+    fn tallies() {
+        let report = evaluate(&[SchedulerComplexity::Easy, SchedulerComplexity::Hard], 2);
+        assert_eq!(report.keylength_top, Evaluator::KEYLENGTH_TOP);
+        assert_eq!(report.overall.trials, 4);
+        assert_eq!(report.per_complexity.len(), 2);
+        assert_eq!(report.per_complexity["easy"].trials, 2);
+        assert_eq!(
+            report
+                .per_scheduler
+                .values()
+                .map(|t| t.trials)
+                .sum::<usize>(),
+            4
+        );
+
+        let overall = &report.overall;
+        assert!(overall.exact_matches <= overall.trials);
+        assert_eq!(
+            overall.exact_match_rate,
+            overall.exact_matches as f64 / overall.trials as f64
+        );
+        assert!((0.0..=1.0).contains(&overall.keylength_hit_rate));
+
+        let summary = report.to_string();
+        assert!(summary.contains("overall") && summary.contains("easy"));
+    }
+
+    #[test]
+    fn running_mean() {
+        let mut tally = EvalTally::default();
+        let mut result = TrialResult {
+            trial: TrialRecord {
+                id: 0,
+                seed: 0,
+                key: Key::from(vec![1; 5]),
+                scheduler: RandomScheduler::from_rng(&mut Rng::default()),
+                known_plaintext: None,
+                plaintext: String::new(),
+                ciphertext: String::new(),
+            },
+            guessed_test: 1,
+            cracked: String::new(),
+            error: 0.5,
+            success: false,
+            recovered_key: None,
+            corrections: 3,
+        };
+        tally.add(&result, true);
+        result.error = 0.0;
+        result.success = true;
+        result.guessed_test = 2;
+        // a key recovered at twice the real keylength still matches
+        result.recovered_key = Some(Key::from(vec![1; 10]));
+        result.corrections = 0;
+        tally.add(&result, false);
+
+        assert_eq!(tally.mean_error, 0.25);
+        assert_eq!(tally.exact_match_rate, 0.5);
+        assert_eq!(tally.keylength_hit_rate, 0.5);
+        assert_eq!(tally.key_match_rate, 0.5);
+        assert_eq!(tally.wrong_test, 1);
+        assert_eq!(tally.mean_corrections, 1.5);
+    }
+
+    /// crack success rates for each level of scheduler complexity
+    #[test]
+    #[ignore]
+    fn complexity_experiments() {
+        let report = evaluate(&SchedulerComplexity::ALL, 32);
+        println!("{}", report);
+
+        // without any random characters, the majority of cracks should succeed
+        assert!(
+            report.per_complexity["easy"].exact_match_rate > 0.5,
+            "too many failures on easy schedulers"
+        );
+    }
+
+    /// crack success rates for each way of weighing spellchecked words, which picked the
+    /// [default][`SegmentationWeights::default`] weights
+    #[test]
+    #[ignore]
+    fn segmentation_experiments() {
+        let default = SegmentationWeights::default();
+        let mut experiments = vec![default];
+        for &length_weight in &[0.5, 1.5, 2.0] {
+            experiments.push(SegmentationWeights {
+                length_weight,
+                ..default
+            });
+        }
+        for &edit_weight in &[0.5, 1.5, 2.0] {
+            experiments.push(SegmentationWeights {
+                edit_weight,
+                ..default
+            });
+        }
+        for &freq_weight in &[0.0, 0.3, 1.0] {
+            experiments.push(SegmentationWeights {
+                freq_weight,
+                ..default
+            });
+        }
+
+        let complexities = [SchedulerComplexity::Easy, SchedulerComplexity::Medium];
+        let mut best_rate = 0.0;
+        let mut default_rate = 0.0;
+        for weights in experiments {
+            let evaluator = Evaluator::project().with_weights(weights);
+            let overall = evaluate_with(evaluator, &complexities, 32).overall;
+            println!("{:?}\n  {}", weights, overall);
+            if weights == default {
+                default_rate = overall.exact_match_rate;
+            }
+            best_rate = f64::max(best_rate, overall.exact_match_rate);
+        }
+
+        assert_eq!(
+            default_rate, best_rate,
+            "the default weights aren't the best"
+        );
+    }
+}
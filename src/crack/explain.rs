@@ -0,0 +1,592 @@
+//! Annotating a crack, to see how a plaintext guess lines up with its ciphertext.
+//!
+//! An [`Explanation`] works backwards from a ciphertext and a plaintext guess: it fits a repeating
+//! key, lines the plaintext up with the ciphertext, and reads off the shift used for every
+//! character. From those shifts it recovers the key, finds the ciphertext characters that don't
+//! fit the key (random characters, most likely), and guesses what kind of scheduler would produce
+//! them, then checks the guess by re-encrypting the plaintext with it. This is meant for
+//! debugging wrong cracks, so it describes whatever plaintext it is given, right or wrong.
+
+use crate::ciphers::schedulers::{PeriodicRand, RepeatingKey};
+use crate::ciphers::KeySchedule;
+use crate::crack::{verify, VerificationReport};
+use crate::utils::{Key, NumToChar, ALPHABET, MAX_KEYLENGTH};
+
+use std::fmt;
+
+/// A crack, annotated with the key and schedule that would turn the plaintext into the
+/// ciphertext.
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    pub ciphertext: Vec<u8>,
+    pub plaintext: Vec<u8>,
+    /// Length of the repeating key that fits best
+    pub keylength: usize,
+    /// Whether the key repeats along ciphertext or plaintext positions
+    pub columns: KeyColumns,
+    /// Shift of each key symbol, shortened to its smallest repeating period
+    pub key: Key,
+    /// For every ciphertext character, the shift that turns its plaintext character into it, or
+    /// `None` if no plaintext character lines up with it
+    pub keystream: Vec<Option<i8>>,
+    /// For every ciphertext character, the index of the plaintext character it lines up with
+    pub alignment: Vec<Option<usize>>,
+    /// Ciphertext positions that no plaintext character lines up with, probably random characters
+    pub rand_positions: Vec<usize>,
+    /// Ciphertext positions that line up with a plaintext character, but with a shift other than
+    /// the key's
+    pub off_key_positions: Vec<usize>,
+    /// What kind of scheduler the key and random characters look like
+    pub scheduler: SchedulerHypothesis,
+    /// How well the plaintext re-encrypts to the ciphertext under the key and scheduler, or under
+    /// a repeating key if the scheduler is irregular
+    pub verification: VerificationReport,
+}
+
+/// Which positions a repeating key is indexed by. They differ once random characters are added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyColumns {
+    /// The key symbol is picked by ciphertext position, so random characters overwrite key symbols
+    Ciphertext,
+    /// The key symbol is picked by plaintext position, so random characters are inserted between
+    /// key symbols
+    Plaintext,
+}
+
+/// A guess at the scheduler that encrypted a ciphertext, from the pattern of characters that
+/// don't fit the repeating key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchedulerHypothesis {
+    /// Every character fits the key, repeating from the start
+    RepeatingKey,
+    /// Characters that don't fit the key come every `period` characters, from `start` on, like
+    /// [`PeriodicRand`][`crate::ciphers::schedulers::PeriodicRand`]
+    PeriodicRand {
+        period: usize,
+        start: usize,
+        overwrite: bool,
+    },
+    /// Characters that don't fit the key have no fixed period
+    Irregular,
+}
+
+impl SchedulerHypothesis {
+    /// The scheduler this guesses, or `None` if it is [`Irregular`][`Self::Irregular`].
+    pub fn scheduler(self) -> Option<Box<dyn KeySchedule>> {
+        match self {
+            Self::RepeatingKey => Some(Box::new(RepeatingKey)),
+            Self::PeriodicRand {
+                period,
+                start,
+                overwrite,
+            } => Some(Box::new(PeriodicRand {
+                period,
+                start,
+                overwrite,
+            })),
+            Self::Irregular => None,
+        }
+    }
+}
+
+impl fmt::Display for SchedulerHypothesis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::RepeatingKey => write!(f, "RepeatingKey"),
+            Self::PeriodicRand {
+                period,
+                start,
+                overwrite,
+            } => write!(
+                f,
+                "PeriodicRand, period {} from {}, {}",
+                period,
+                start,
+                if *overwrite {
+                    "overwriting"
+                } else {
+                    "inserting"
+                }
+            ),
+            Self::Irregular => write!(f, "irregular, not a repeating key"),
+        }
+    }
+}
+
+impl Explanation {
+    /// Explain how `plaintext` could have been encrypted into `ciphertext`. Both are bytes as
+    /// given by [`str_to_bytes`][`crate::utils::str_to_bytes`].
+    ///
+    /// Every keylength up to 24 is tried, with the key repeating along the ciphertext and along
+    /// the plaintext. The key that lines the plaintext up with the ciphertext in the fewest edits
+    /// is kept, preferring shorter keys.
+    pub fn new(ciphertext: &[u8], plaintext: &[u8]) -> Self {
+        let keylengths = 1..=MAX_KEYLENGTH.min(ciphertext.len().max(1));
+        Self::with_keylengths(ciphertext, plaintext, keylengths)
+    }
+
+    /// Explain how `plaintext` could have been encrypted into `ciphertext` with a key of
+    /// `keylength` symbols, like the keylength it was cracked at.
+    pub fn at_keylength(ciphertext: &[u8], plaintext: &[u8], keylength: usize) -> Self {
+        Self::with_keylengths(ciphertext, plaintext, keylength..=keylength)
+    }
+
+    fn with_keylengths(
+        ciphertext: &[u8],
+        plaintext: &[u8],
+        keylengths: impl IntoIterator<Item = usize>,
+    ) -> Self {
+        let (n, m) = (ciphertext.len(), plaintext.len());
+        let shift = |i: usize, j: usize| {
+            (ciphertext[i] as i8 - plaintext[j] as i8).rem_euclid(ALPHABET.len() as i8)
+        };
+
+        let mut best: Option<(u32, usize, KeyColumns, Vec<i8>)> = None;
+        for keylength in keylengths {
+            for &columns in &[KeyColumns::Ciphertext, KeyColumns::Plaintext] {
+                let key = estimate_key(ciphertext, plaintext, keylength, columns);
+                let (a, b) = keyed(ciphertext, plaintext, &key, columns);
+                let cost = edit_cost(n, m, |i, j| a[i] == b[j]);
+                if best
+                    .as_ref()
+                    .is_none_or(|(best_cost, ..)| cost < *best_cost)
+                {
+                    best = Some((cost, keylength, columns, key));
+                }
+            }
+        }
+        let (_, keylength, columns, full_key) = best.expect("at least one keylength is tried");
+
+        let (a, b) = keyed(ciphertext, plaintext, &full_key, columns);
+        let alignment = align(n, m, |i, j| a[i] == b[j]);
+        let keystream: Vec<Option<i8>> = alignment
+            .iter()
+            .enumerate()
+            .map(|(i, aligned)| aligned.map(|j| shift(i, j)))
+            .collect();
+
+        let mut rand_positions = Vec::new();
+        let mut off_key_positions = Vec::new();
+        for (i, aligned) in alignment.iter().enumerate() {
+            match aligned {
+                None => rand_positions.push(i),
+                Some(j) if a[i] != b[*j] => off_key_positions.push(i),
+                Some(_) => {}
+            }
+        }
+
+        let scheduler = hypothesize(&rand_positions, &off_key_positions, columns);
+        let key = Key::from(full_key).canonical();
+        let sched = scheduler
+            .scheduler()
+            .unwrap_or_else(|| Box::new(RepeatingKey));
+        let verification = verify(ciphertext, &key, &*sched, plaintext);
+
+        Self {
+            ciphertext: ciphertext.to_vec(),
+            plaintext: plaintext.to_vec(),
+            keylength,
+            columns,
+            key,
+            keystream,
+            alignment,
+            rand_positions,
+            off_key_positions,
+            scheduler,
+            verification,
+        }
+    }
+
+    /// The key as letters, where a shift of 0 is `a` and a shift of 26 is a space.
+    pub fn key_letters(&self) -> String {
+        self.key.as_letters()
+    }
+}
+
+impl KeyColumns {
+    /// The position that picks the key symbol, out of ciphertext position `i` lined up with
+    /// plaintext position `j`.
+    fn pick(self, i: usize, j: usize) -> usize {
+        match self {
+            Self::Ciphertext => i,
+            Self::Plaintext => j,
+        }
+    }
+}
+
+impl fmt::Display for Explanation {
+    /// The summary, then the ciphertext, keystream and plaintext side by side. The width of the
+    /// side by side rows can be set with the formatting width, as in `{:80}`, and defaults to 64.
+    ///
+    /// Random characters are `_` in the keystream and plaintext rows. Under the rows, `r` marks a
+    /// random character and `!` a character with a shift other than the key's.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = f.width().unwrap_or(64).max(1);
+
+        writeln!(
+            f,
+            "keylength: {} (repeating along the {})",
+            self.keylength,
+            match self.columns {
+                KeyColumns::Ciphertext => "ciphertext",
+                KeyColumns::Plaintext => "plaintext",
+            }
+        )?;
+        writeln!(f, "key:       {:#} {:?}", self.key, self.key_letters())?;
+        writeln!(
+            f,
+            "rand:      {} positions {:?}",
+            self.rand_positions.len(),
+            self.rand_positions
+        )?;
+        writeln!(
+            f,
+            "off key:   {} positions {:?}",
+            self.off_key_positions.len(),
+            self.off_key_positions
+        )?;
+        writeln!(f, "scheduler: {}", self.scheduler)?;
+        writeln!(
+            f,
+            "verified:  {:.1}% of {} characters re-encrypt, {} random",
+            self.verification.match_fraction() * 100.0,
+            self.verification.checked + self.verification.leftover,
+            self.verification.wildcards
+        )?;
+
+        let cipher: Vec<char> = self.ciphertext.iter().map(|c| c.to_char()).collect();
+        let key: Vec<char> = self
+            .keystream
+            .iter()
+            .map(|shift| shift.map_or('_', |shift| (shift as u8).to_char()))
+            .collect();
+        let plain: Vec<char> = self
+            .alignment
+            .iter()
+            .map(|j| j.map_or('_', |j| self.plaintext[j].to_char()))
+            .collect();
+        let marks: Vec<char> = (0..self.ciphertext.len())
+            .map(|i| {
+                if self.rand_positions.contains(&i) {
+                    'r'
+                } else if self.off_key_positions.contains(&i) {
+                    '!'
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+
+        for start in (0..cipher.len()).step_by(width) {
+            let end = (start + width).min(cipher.len());
+            let row = |chars: &[char]| chars[start..end].iter().collect::<String>();
+            writeln!(f)?;
+            writeln!(f, "{:>6} cipher {}", start, row(&cipher))?;
+            writeln!(f, "{:>6} key    {}", "", row(&key))?;
+            writeln!(f, "{:>6} plain  {}", "", row(&plain))?;
+            let marks = format!("{:>6}        {}", "", row(&marks));
+            writeln!(f, "{}", marks.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The most common shift of each key column, over every way the plaintext could be offset into
+/// the ciphertext by inserted characters. Each plaintext character has only one true offset, but
+/// that offset gives the same shift for the whole column, so it stands out from the rest.
+fn estimate_key(
+    ciphertext: &[u8],
+    plaintext: &[u8],
+    keylength: usize,
+    columns: KeyColumns,
+) -> Vec<i8> {
+    let mut counts = vec![[0_u32; 27]; keylength];
+    for offset in 0..=ciphertext.len().saturating_sub(plaintext.len()) {
+        for (j, (&p, &c)) in plaintext.iter().zip(&ciphertext[offset..]).enumerate() {
+            let shift = (c as i8 - p as i8).rem_euclid(ALPHABET.len() as i8);
+            counts[columns.pick(j + offset, j) % keylength][shift as usize] += 1;
+        }
+    }
+
+    counts
+        .iter()
+        .map(|column| {
+            // the first of equally common shifts
+            let best = (0..column.len()).max_by_key(|&shift| (column[shift], !shift));
+            best.unwrap_or(0) as i8
+        })
+        .collect()
+}
+
+/// The ciphertext and plaintext, one of them shifted by the repeating `key` along `columns`, so
+/// that ciphertext `i` lines up with plaintext `j` under the key exactly when `a[i] == b[j]`.
+/// Lining up compares every pair of positions, so this leaves it only comparing bytes.
+fn keyed(
+    ciphertext: &[u8],
+    plaintext: &[u8],
+    key: &[i8],
+    columns: KeyColumns,
+) -> (Vec<u8>, Vec<u8>) {
+    let shifted = |text: &[u8], sign: i8| -> Vec<u8> {
+        text.iter()
+            .enumerate()
+            .map(|(k, &c)| {
+                (c as i8 + sign * key[k % key.len()]).rem_euclid(ALPHABET.len() as i8) as u8
+            })
+            .collect()
+    };
+    match columns {
+        KeyColumns::Ciphertext => (shifted(ciphertext, -1), plaintext.to_vec()),
+        KeyColumns::Plaintext => (ciphertext.to_vec(), shifted(plaintext, 1)),
+    }
+}
+
+/// Number of edits to line up `n` ciphertext characters with `m` plaintext characters, where
+/// `matches(i, j)` says whether ciphertext `i` and plaintext `j` line up without an edit.
+fn edit_cost(n: usize, m: usize, matches: impl Fn(usize, usize) -> bool) -> u32 {
+    let mut prev: Vec<u32> = (0..=m as u32).collect();
+    let mut row = vec![0; m + 1];
+    for i in 1..=n {
+        row[0] = i as u32;
+        for j in 1..=m {
+            let cost = !matches(i - 1, j - 1) as u32;
+            row[j] = (prev[j - 1] + cost).min(prev[j] + 1).min(row[j - 1] + 1);
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+    prev[m]
+}
+
+/// Line up `n` ciphertext characters with `m` plaintext characters along a shortest edit path,
+/// returning the plaintext index each ciphertext character lines up with, or `None` for
+/// ciphertext characters that are left out.
+pub(super) fn align(
+    n: usize,
+    m: usize,
+    matches: impl Fn(usize, usize) -> bool,
+) -> Vec<Option<usize>> {
+    let cols = m + 1;
+    let mut table = vec![0_u32; (n + 1) * cols];
+    for (j, cell) in table.iter_mut().enumerate().take(cols) {
+        *cell = j as u32;
+    }
+    for i in 1..=n {
+        table[i * cols] = i as u32;
+        for j in 1..=m {
+            let cost = !matches(i - 1, j - 1) as u32;
+            table[i * cols + j] = (table[(i - 1) * cols + j - 1] + cost)
+                .min(table[(i - 1) * cols + j] + 1)
+                .min(table[i * cols + j - 1] + 1);
+        }
+    }
+
+    // walk back from the end, preferring to line characters up over leaving them out
+    let mut alignment = vec![None; n];
+    let (mut i, mut j) = (n, m);
+    while i > 0 {
+        let here = table[i * cols + j];
+        if j > 0 && here == table[(i - 1) * cols + j - 1] + !matches(i - 1, j - 1) as u32 {
+            alignment[i - 1] = Some(j - 1);
+            i -= 1;
+            j -= 1;
+        } else if here == table[(i - 1) * cols + j] + 1 {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+
+    alignment
+}
+
+/// Guess the scheduler from the positions that don't fit the repeating key.
+///
+/// A random character can happen to fit the key, and then lines up with a plaintext character in
+/// its neighbor's place, so a few positions may be off by one. The period is the most common gap
+/// between positions, and the pattern only has to hold for three quarters of them.
+fn hypothesize(
+    rand_positions: &[usize],
+    off_key_positions: &[usize],
+    columns: KeyColumns,
+) -> SchedulerHypothesis {
+    let mut misfits: Vec<usize> = rand_positions
+        .iter()
+        .chain(off_key_positions)
+        .copied()
+        .collect();
+    misfits.sort_unstable();
+
+    if misfits.is_empty() {
+        return SchedulerHypothesis::RepeatingKey;
+    }
+
+    // the most common gap, then the most common position within it, preferring the smallest
+    let most_common = |values: &mut dyn Iterator<Item = usize>| {
+        let mut counts = std::collections::BTreeMap::new();
+        for value in values {
+            *counts.entry(value).or_insert(0) += 1;
+        }
+        counts
+            .into_iter()
+            .max_by_key(|&(value, count)| (count, std::cmp::Reverse(value)))
+            .map(|(value, _)| value)
+    };
+    let period = most_common(&mut misfits.windows(2).map(|pair| pair[1] - pair[0]));
+    let period = match period {
+        Some(period) if period > 1 => period,
+        _ => return SchedulerHypothesis::Irregular,
+    };
+    let residue = most_common(&mut misfits.iter().map(|pos| pos % period)).unwrap_or(0);
+
+    let fitting = misfits
+        .iter()
+        .filter(|&&pos| pos % period == residue)
+        .count();
+    if fitting * 4 < misfits.len() * 3 {
+        return SchedulerHypothesis::Irregular;
+    }
+
+    SchedulerHypothesis::PeriodicRand {
+        period,
+        start: misfits
+            .iter()
+            .copied()
+            .find(|pos| pos % period == residue)
+            .unwrap_or(residue),
+        overwrite: columns == KeyColumns::Ciphertext,
+    }
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::utils::str_to_bytes;
+
+    fn plaintext() -> String {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        Generator::with_dict(&dict).generate_words(120)
+    }
+
+    #[test]
+    fn repeating_key() {
+        let plaintext = plaintext();
+        let key = vec![1, 5, 9, 13, 17];
+        let encryptor = Encryptor::new(key.clone(), RepeatingKey, Rng::default());
+        let ciphertext = encryptor.encrypt(&plaintext);
+
+        let explanation = Explanation::new(&str_to_bytes(&ciphertext), &str_to_bytes(&plaintext));
+        assert_eq!(explanation.keylength, key.len());
+        assert_eq!(explanation.key.0, key);
+        assert_eq!(explanation.key_letters(), "bfjnr");
+        assert!(explanation.rand_positions.is_empty());
+        assert_eq!(explanation.scheduler, SchedulerHypothesis::RepeatingKey);
+        assert!(explanation.verification.is_exact());
+
+        let text = format!("{:40}", explanation);
+        assert!(text.contains("scheduler: RepeatingKey"), "{}", text);
+        assert!(
+            text.contains(&format!(
+                "verified:  100.0% of {} characters re-encrypt, 0 random",
+                plaintext.len()
+            )),
+            "{}",
+            text
+        );
+        assert!(text.contains(&format!("cipher {}", &ciphertext[..40])));
+        assert!(text.contains(&format!("plain  {}", &plaintext[..40])));
+    }
+
+    #[test]
+    fn periodic_rand() {
+        let plaintext = plaintext();
+        let key = vec![10, 10, 12, 1, 2, 3, 4];
+
+        for &overwrite in &[false, true] {
+            let sched = PeriodicRand {
+                period: 18,
+                start: 5,
+                overwrite,
+            };
+            let encryptor = Encryptor::new(key.clone(), sched, Rng::default());
+            let ciphertext = encryptor.encrypt(&plaintext);
+
+            let explanation =
+                Explanation::new(&str_to_bytes(&ciphertext), &str_to_bytes(&plaintext));
+            // a random character that happens to fit the key can trade places with its neighbor
+            let inserted: Vec<usize> = (5..ciphertext.len()).step_by(18).collect();
+            assert_eq!(explanation.key.0, key);
+            assert_eq!(explanation.rand_positions.len(), inserted.len());
+            for (found, inserted) in explanation.rand_positions.iter().zip(&inserted) {
+                assert!(found.abs_diff(*inserted) <= 1, "{} {}", found, inserted);
+            }
+            assert_eq!(
+                explanation.scheduler,
+                SchedulerHypothesis::PeriodicRand {
+                    period: 18,
+                    start: 5,
+                    overwrite,
+                }
+            );
+            assert!(explanation.verification.is_exact());
+            let at_keylength = Explanation::at_keylength(
+                &str_to_bytes(&ciphertext),
+                &str_to_bytes(&plaintext),
+                key.len(),
+            );
+            assert_eq!(at_keylength.scheduler, explanation.scheduler);
+            assert_eq!(at_keylength.verification, explanation.verification);
+        }
+    }
+
+    #[test]
+    fn wrong_crack() {
+        let plaintext = plaintext();
+        let encryptor = Encryptor::new(vec![1, 5, 9, 13, 17], RepeatingKey, Rng::default());
+        let ciphertext = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+        // one wrong character is off the key, so it doesn't re-encrypt to the ciphertext
+        let mut crack = str_to_bytes(&plaintext);
+        crack[10] = (crack[10] + 1) % 27;
+        let explanation = Explanation::new(&ciphertext, &crack);
+        assert_eq!(explanation.key.0, [1, 5, 9, 13, 17]);
+        assert_eq!(explanation.off_key_positions, [10]);
+        assert_eq!(explanation.scheduler, SchedulerHypothesis::Irregular);
+        assert_eq!(
+            explanation.verification.checked - explanation.verification.matches,
+            1
+        );
+    }
+
+    #[test]
+    fn alignment() {
+        let (a, b) = (b"abxcd", b"abcd");
+        let alignment = align(a.len(), b.len(), |i, j| a[i] == b[j]);
+        assert_eq!(alignment, [Some(0), Some(1), None, Some(2), Some(3)]);
+        assert_eq!(edit_cost(a.len(), b.len(), |i, j| a[i] == b[j]), 1);
+        assert_eq!(align(3, 0, |_, _| true), [None, None, None]);
+    }
+
+    #[test]
+    fn hypotheses() {
+        let columns = KeyColumns::Plaintext;
+        assert_eq!(
+            hypothesize(&[], &[], columns),
+            SchedulerHypothesis::RepeatingKey
+        );
+        assert_eq!(
+            hypothesize(&[3, 13, 22, 33, 43], &[], columns),
+            SchedulerHypothesis::PeriodicRand {
+                period: 10,
+                start: 3,
+                overwrite: false
+            }
+        );
+        assert_eq!(
+            hypothesize(&[3, 5], &[12, 30, 31], columns),
+            SchedulerHypothesis::Irregular
+        );
+    }
+}
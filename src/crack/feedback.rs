@@ -0,0 +1,165 @@
+//! Module for feeding spellcheck corrections back into the key.
+//!
+//! A column cracked with the wrong shift decrypts every one of its characters wrong by the same
+//! amount, so spellchecking corrects characters all the way down that column, and by the same
+//! shift. Tallying the corrections by column finds the suspect key symbols and the shift that
+//! fixes them. Moving those columns and spellchecking again fixes the characters spellchecking
+//! couldn't, like the ones in words it corrected to the wrong word.
+
+use super::boundaries::{set_column, shifts_of};
+use super::{Correction, CrackResult, DictionaryStats, SegmentationWeights, SpellChecker};
+use crate::dict::BytesDictionary;
+use crate::utils::ALPHABET;
+
+/// Most rounds of moving columns and spellchecking again.
+const MAX_ROUNDS: usize = 3;
+
+/// Fewest corrections agreeing on a column's shift before it is moved.
+const MIN_VOTES: usize = 2;
+
+/// Fraction of a column's characters that have to be corrected by the same shift before it is
+/// moved.
+const MIN_VOTE_FRACTION: f64 = 0.2;
+
+/// Most characters spellchecking can change, as a fraction, for the crack to be worth fixing.
+/// Cracks at wrong keylengths need most of their characters corrected, and moving their columns
+/// won't help.
+const MAX_CHANGED: f64 = 0.5;
+
+/// Spellcheck a crack at `keylength`, moving the columns that spellchecking corrected by the same
+/// shift over and over, and spellchecking again, for a few rounds while fewer characters need
+/// correcting.
+///
+/// Returns the crack with its columns moved (with the confidence of `cracked`) and the
+/// [`Correction`] that spellchecked it, like a [`SpellChecker`] with `stats` and `weights` would
+/// have given.
+pub fn spellcheck_with_feedback(
+    ciphertext: &[u8],
+    keylength: usize,
+    cracked: &CrackResult,
+    dict: &BytesDictionary,
+    stats: &DictionaryStats,
+    weights: SegmentationWeights,
+) -> (CrackResult, Correction) {
+    // the same slices come up again in every round
+    let mut checker = SpellChecker::new(dict)
+        .with_stats(stats)
+        .with_weights(weights);
+    let mut correction = checker.correct(cracked);
+    let mut crack = cracked.clone();
+    if keylength == 0 || cracked.plaintext.len() != ciphertext.len() {
+        // not a crack of this ciphertext at this keylength
+        return (crack, correction);
+    }
+    let changed = correction.checked.confidence;
+    if changed == 0.0 || changed > MAX_CHANGED {
+        // nothing to fix, or too much
+        return (crack, correction);
+    }
+
+    let len = ALPHABET.len();
+    for _ in 0..MAX_ROUNDS {
+        // corrections of each column, by the shift that would make them
+        let mut votes = vec![vec![0_usize; len]; keylength];
+        let Correction {
+            checked, alignment, ..
+        } = &correction;
+        for (&corrected, aligned) in checked.plaintext.iter().zip(alignment) {
+            if let Some(&index) = aligned.as_ref() {
+                let delta = (corrected as usize + len - crack.plaintext[index] as usize) % len;
+                if delta != 0 {
+                    votes[index % keylength][delta] += 1;
+                }
+            }
+        }
+
+        let mut shifts = shifts_of(ciphertext, keylength, &crack.plaintext);
+        let mut plaintext = crack.plaintext.clone();
+        let mut moved = false;
+        for (column, column_votes) in votes.iter().enumerate() {
+            let column_len = (ciphertext.len() + keylength - 1 - column) / keylength;
+            let (delta, &count) = column_votes
+                .iter()
+                .enumerate()
+                .max_by_key(|&(_, count)| count)
+                .expect("the alphabet isn't empty");
+            if count >= MIN_VOTES && count as f64 >= MIN_VOTE_FRACTION * column_len as f64 {
+                shifts[column] = ((shifts[column] as usize + delta) % len) as i8;
+                set_column(
+                    ciphertext,
+                    keylength,
+                    column,
+                    shifts[column],
+                    &mut plaintext,
+                );
+                moved = true;
+            }
+        }
+        if !moved {
+            break;
+        }
+
+        let trial = CrackResult {
+            plaintext,
+            ..crack.clone()
+        };
+        let trial_correction = checker.correct(&trial);
+        if trial_correction.checked.confidence >= correction.checked.confidence {
+            break;
+        }
+        crack = trial;
+        correction = trial_correction;
+    }
+
+    (crack, correction)
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::crack::spellcheck;
+    use crate::prelude::*;
+    use crate::utils::str_to_bytes;
+
+    #[test]
+    fn moves_wrong_columns() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let bytes_dict = BytesDictionary::from_dict(&dict);
+        let stats = DictionaryStats::from_dict(&dict);
+        let plaintext = Generator::with_dict(&dict).generate_words(50);
+        let key = vec![3, 14, 15, 9, 26, 5, 3, 5, 8];
+        let ciphertext =
+            Encryptor::new(key.clone(), RepeatingKey, Rng::default()).encrypt(&plaintext);
+        let ciphertext = str_to_bytes(&ciphertext);
+        let plainbytes = str_to_bytes(&plaintext);
+
+        // the right crack, but with two columns far off
+        let mut wrong = plainbytes.clone();
+        set_column(&ciphertext, key.len(), 2, -key[2] + 9, &mut wrong);
+        set_column(&ciphertext, key.len(), 7, -key[7] - 5, &mut wrong);
+        let cracked = CrackResult::new(wrong, 1.0);
+        let weights = SegmentationWeights::default();
+
+        let (crack, correction) = spellcheck_with_feedback(
+            &ciphertext,
+            key.len(),
+            &cracked,
+            &bytes_dict,
+            &stats,
+            weights,
+        );
+        assert_eq!(crack.plaintext, plainbytes);
+        assert_eq!(correction.checked.plaintext, plainbytes);
+        assert!(correction.checked.confidence < spellcheck(&cracked, &bytes_dict).confidence);
+        assert!(correction.corrections.is_empty());
+
+        // a right crack is left alone
+        let right = CrackResult::new(plainbytes.clone(), 1.0);
+        let (crack, correction) =
+            spellcheck_with_feedback(&ciphertext, key.len(), &right, &bytes_dict, &stats, weights);
+        assert_eq!(crack.plaintext, plainbytes);
+        assert_eq!(correction.checked.confidence, 0.0);
+    }
+}
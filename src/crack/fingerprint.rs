@@ -0,0 +1,94 @@
+//! Module for matching known plaintexts to a ciphertext by fingerprint, without the key.
+//!
+//! Under a key that repeats every `keylength` characters, every character of a column is shifted
+//! by the same key symbol, so the difference between each character and the one `keylength` on is
+//! the same in the ciphertext as in its plaintext. That sequence of differences, the fingerprint,
+//! doesn't change whatever shift each column is given.
+//!
+//! Fingerprints only line up while the scheduler inserts no random characters. For ciphertexts
+//! that have them, see [`classify`][`super::classify`].
+
+use crate::utils::{ALPHABET, MAX_KEYLENGTH};
+
+/// The difference between each character of `text` and the one `keylength` on, which is the same
+/// for any text shifted column by column at `keylength`.
+///
+/// ```
+/// # use one_team_pad_cipher_cracker::crack::fingerprint::fingerprint;
+/// // "abc abc" and "bdd bdd" differ column by column at keylength 4
+/// assert_eq!(fingerprint(&[0, 1, 2, 26, 0, 1, 2], 4), fingerprint(&[1, 3, 3, 0, 1, 3, 3], 4));
+/// ```
+pub fn fingerprint(text: &[u8], keylength: usize) -> Vec<u8> {
+    let len = ALPHABET.len();
+    text.iter()
+        .zip(text.iter().skip(keylength))
+        .map(|(&a, &b)| ((b as usize + len - a as usize) % len) as u8)
+        .collect()
+}
+
+/// Score each of `candidates` against `ciphertext` by the fraction of their fingerprints that
+/// agree, at whichever keylength up to [`MAX_KEYLENGTH`] agrees the most, over the length both
+/// share. Returns the index of each candidate with its score, best first.
+///
+/// The plaintext of the ciphertext scores 1.0, and unrelated plaintexts about 1 in 27. Candidates
+/// too short to fingerprint score 0.0.
+pub fn match_known<'a>(
+    ciphertext: &[u8],
+    candidates: impl IntoIterator<Item = &'a [u8]>,
+) -> Vec<(usize, f64)> {
+    let mut scores: Vec<(usize, f64)> = candidates
+        .into_iter()
+        .map(|candidate| {
+            let len = ciphertext.len().min(candidate.len());
+            let (ciphertext, candidate) = (&ciphertext[..len], &candidate[..len]);
+            (1..=MAX_KEYLENGTH)
+                .filter(|&keylength| keylength < len)
+                .map(|keylength| {
+                    let agree = fingerprint(ciphertext, keylength)
+                        .iter()
+                        .zip(&fingerprint(candidate, keylength))
+                        .filter(|(a, b)| a == b)
+                        .count();
+                    agree as f64 / (len - keylength) as f64
+                })
+                .fold(0.0, f64::max)
+        })
+        .enumerate()
+        .collect();
+    scores.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::{LengthRotate, RepeatingKey};
+    use crate::ciphers::{Cipher, Encryptor};
+    use crate::rng::Rng;
+    use crate::utils::{bytes_to_str, str_to_bytes};
+
+    #[test]
+    fn matches_known() {
+        let known = include_str!("../../words/test1_plaintext.txt");
+        let known: Vec<Vec<u8>> = known.lines().map(str_to_bytes).collect();
+        let key = vec![9, 0, 17, 4, 22, 13, 1, 6, 25, 11, 3, 8, 19, 14, 2, 7];
+
+        for (index, plaintext) in known.iter().enumerate() {
+            let plaintext = bytes_to_str(plaintext);
+            let encryptor = Encryptor::new(key.clone(), LengthRotate, Rng::default());
+            let ciphertext = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+            let scores = match_known(&ciphertext, known.iter().map(Vec::as_slice));
+            assert_eq!(scores.len(), known.len());
+            assert_eq!(scores[0], (index, 1.0));
+            assert!(scores[1].1 < 0.2);
+        }
+
+        // a truncated plaintext still matches over the length it has
+        let plaintext = bytes_to_str(&known[2]);
+        let encryptor = Encryptor::new(key, RepeatingKey, Rng::default());
+        let ciphertext = str_to_bytes(&encryptor.encrypt(&plaintext));
+        let scores = match_known(&ciphertext, [&known[2][..100], &[0, 1][..]]);
+        assert_eq!(scores, vec![(0, 1.0), (1, 0.0)]);
+    }
+}
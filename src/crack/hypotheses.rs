@@ -0,0 +1,418 @@
+//! Module for choosing which trials a [`WorkerPool`][`crate::crack::worker::WorkerPool`] cracks.
+//!
+//! Submitting random schedulers one after another spends trials on whatever families and key
+//! lengths the rng happens to favour. A [`HypothesisScheduler`] instead treats every scheduler
+//! family at every band of key lengths as a [`Hypothesis`] and picks which one the next trial
+//! tests: either each in turn ([`Policy::RoundRobin`]), or, like a multi-armed bandit, mostly the
+//! ones that have been cracking while still trying the rest now and then ([`Policy::Bandit`]).
+//!
+//! A [`HypothesisFeed`] shares one scheduler between the thread submitting trials and the thread
+//! receiving their results, and keeps only a few trials in flight at a time, so the bandit learns
+//! from results before it picks much more.
+
+use crate::ciphers::schedulers::{RandomScheduler, SchedulerComplexity, SchedulerKind};
+use crate::crack::worker::{Submitter, Tally, TrialResult};
+use crate::rng::{Rng, KEY_LENGTHS};
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// How long a [`HypothesisFeed`] waits for a result before giving up on it. Results dropped by a
+/// full results channel never come back.
+pub const LOST_RESULT_AFTER: Duration = Duration::from_secs(5);
+
+/// Bands of key lengths, splitting up [`KEY_LENGTHS`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeylengthBand {
+    /// 5 to 10 shifts
+    Short,
+    /// 11 to 16 shifts
+    Medium,
+    /// 17 to 23 shifts
+    Long,
+}
+
+impl KeylengthBand {
+    /// All bands, from shortest to longest.
+    pub const ALL: [KeylengthBand; 3] = [Self::Short, Self::Medium, Self::Long];
+
+    /// Key lengths in the band.
+    pub fn lengths(&self) -> Range<usize> {
+        match self {
+            Self::Short => KEY_LENGTHS.start..11,
+            Self::Medium => 11..17,
+            Self::Long => 17..KEY_LENGTHS.end,
+        }
+    }
+
+    /// The band `length` falls in. Lengths outside of [`KEY_LENGTHS`] fall in the closest band.
+    pub fn of(length: usize) -> Self {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|band| length < band.lengths().end)
+            .unwrap_or(Self::Long)
+    }
+
+    /// Name of the band, for printing in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Short => "short",
+            Self::Medium => "medium",
+            Self::Long => "long",
+        }
+    }
+}
+
+/// A guess at what makes trials crackable: a scheduler family, and a band of key lengths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Hypothesis {
+    pub kind: SchedulerKind,
+    pub keylengths: KeylengthBand,
+}
+
+impl Hypothesis {
+    /// Every hypothesis, with neighbours of different families and every family in one band
+    /// before the next band.
+    pub fn all() -> Vec<Self> {
+        KeylengthBand::ALL
+            .iter()
+            .flat_map(|&keylengths| {
+                SchedulerKind::ALL
+                    .iter()
+                    .map(move |&kind| Self { kind, keylengths })
+            })
+            .collect()
+    }
+
+    /// The hypothesis a trial tested.
+    pub fn of(result: &TrialResult) -> Self {
+        Self {
+            kind: result.trial.scheduler.base().kind(),
+            keylengths: KeylengthBand::of(result.trial.key.len()),
+        }
+    }
+}
+
+/// How a [`HypothesisScheduler`] picks the next hypothesis to test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// Every hypothesis in turn, whatever the results
+    RoundRobin,
+    /// The hypothesis with the best upper confidence bound on its success rate (UCB1): its
+    /// success rate so far, plus `exploration` times a bonus that shrinks the more it is tested.
+    /// A larger `exploration` tries the less successful hypotheses more often.
+    Bandit { exploration: f64 },
+}
+
+impl Policy {
+    /// A bandit with the usual UCB1 exploration, the square root of 2.
+    pub fn bandit() -> Self {
+        Self::Bandit {
+            exploration: std::f64::consts::SQRT_2,
+        }
+    }
+
+    /// Look up a policy by name: `round-robin` or `bandit`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "round-robin" => Some(Self::RoundRobin),
+            "bandit" => Some(Self::bandit()),
+            _ => None,
+        }
+    }
+}
+
+/// Results of one hypothesis so far.
+#[derive(Clone, Debug, Default)]
+pub struct Arm {
+    pub tally: Tally,
+    /// Trials submitted that haven't come back yet
+    pub in_flight: usize,
+}
+
+/// Picks the hypothesis each trial tests, and generates a scheduler for it.
+#[derive(Clone, Debug)]
+pub struct HypothesisScheduler {
+    policy: Policy,
+    complexity: SchedulerComplexity,
+    rng: Rng,
+    /// Every hypothesis, and how it has done so far
+    arms: Vec<(Hypothesis, Arm)>,
+    /// Index of the hypothesis round-robin picks next
+    next: usize,
+}
+
+impl HypothesisScheduler {
+    /// A scheduler picking by `policy`, generating schedulers as complex as `complexity` allows
+    /// from `seed`.
+    pub fn new(policy: Policy, complexity: SchedulerComplexity, seed: u64) -> Self {
+        Self {
+            policy,
+            complexity,
+            rng: Rng::from_seed(seed),
+            arms: Hypothesis::all()
+                .into_iter()
+                .map(|hypothesis| (hypothesis, Arm::default()))
+                .collect(),
+            next: 0,
+        }
+    }
+
+    /// Pick the next hypothesis to test and generate a scheduler for it. The trial counts as in
+    /// flight until its result is [`record`][`Self::record`]ed.
+    pub fn pick(&mut self) -> (Hypothesis, RandomScheduler) {
+        let index = match self.policy {
+            Policy::RoundRobin => {
+                let index = self.next;
+                self.next = (index + 1) % self.arms.len();
+                index
+            }
+            Policy::Bandit { exploration } => self.upper_bound_index(exploration),
+        };
+
+        let (hypothesis, arm) = &mut self.arms[index];
+        arm.in_flight += 1;
+        let sched = RandomScheduler::with_kind(&mut self.rng, self.complexity, hypothesis.kind);
+        (*hypothesis, sched)
+    }
+
+    /// Index of the hypothesis with the best UCB1 bound. Trials in flight count as tested, so
+    /// trials picked before their results come back spread out over the hypotheses. Ties go to
+    /// the hypothesis first in order, and untested ones go before any other.
+    fn upper_bound_index(&self, exploration: f64) -> usize {
+        let tested = |arm: &Arm| arm.tally.trials + arm.in_flight;
+        let total: usize = self.arms.iter().map(|(_, arm)| tested(arm)).sum();
+        let bound = |arm: &Arm| match tested(arm) {
+            0 => f64::INFINITY,
+            n => {
+                let bonus = ((total as f64).ln() / n as f64).sqrt();
+                let rate = arm.tally.successes as f64 / n as f64;
+                rate + exploration * bonus
+            }
+        };
+
+        let mut best = 0;
+        for index in 1..self.arms.len() {
+            if bound(&self.arms[index].1) > bound(&self.arms[best].1) {
+                best = index;
+            }
+        }
+        best
+    }
+
+    /// Take in the result of a trial, which is no longer in flight.
+    pub fn record(&mut self, result: &TrialResult) {
+        let hypothesis = Hypothesis::of(result);
+        if let Some((_, arm)) = self.arms.iter_mut().find(|(h, _)| *h == hypothesis) {
+            arm.in_flight = arm.in_flight.saturating_sub(1);
+            arm.tally.add(result);
+        }
+    }
+
+    /// Trials submitted that haven't come back yet, over every hypothesis.
+    pub fn in_flight(&self) -> usize {
+        self.arms.iter().map(|(_, arm)| arm.in_flight).sum()
+    }
+
+    /// Every hypothesis and how it has done so far.
+    pub fn arms(&self) -> &[(Hypothesis, Arm)] {
+        &self.arms
+    }
+}
+
+impl fmt::Display for HypothesisScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (hypothesis, arm) in &self.arms {
+            if arm.tally.trials > 0 {
+                writeln!(
+                    f,
+                    "{:<16} {:<7} {}",
+                    hypothesis.kind.name(),
+                    hypothesis.keylengths.name(),
+                    arm.tally
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A [`HypothesisScheduler`] shared by the thread submitting trials and the thread receiving
+/// their results.
+pub struct HypothesisFeed {
+    scheduler: Mutex<HypothesisScheduler>,
+    /// Notified each time a result is recorded
+    recorded: Condvar,
+    /// Most trials to have in flight at once
+    max_in_flight: usize,
+}
+
+impl HypothesisFeed {
+    /// Share `scheduler`, with at most `max_in_flight` trials in flight.
+    pub fn new(scheduler: HypothesisScheduler, max_in_flight: usize) -> Self {
+        Self {
+            scheduler: Mutex::new(scheduler),
+            recorded: Condvar::new(),
+            max_in_flight: max_in_flight.max(1),
+        }
+    }
+
+    /// Submit `trials` trials to `submitter`, each as soon as fewer than the most trials are in
+    /// flight, or no result came back for [`LOST_RESULT_AFTER`]. Returns early if the pool was
+    /// shut down.
+    pub fn submit(&self, submitter: &Submitter, trials: usize) {
+        for _ in 0..trials {
+            let (hypothesis, sched) = {
+                let mut scheduler = self.scheduler();
+                while scheduler.in_flight() >= self.max_in_flight {
+                    let (guard, wait) = self
+                        .recorded
+                        .wait_timeout(scheduler, LOST_RESULT_AFTER)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    scheduler = guard;
+                    if wait.timed_out() {
+                        break;
+                    }
+                }
+                scheduler.pick()
+            };
+
+            let lengths = hypothesis.keylengths.lengths();
+            if submitter.submit_with_key_lengths(sched, lengths).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Take in the result of a trial, letting another one be submitted.
+    pub fn record(&self, result: &TrialResult) {
+        self.scheduler().record(result);
+        self.recorded.notify_all();
+    }
+
+    /// The shared scheduler, to look at how the hypotheses have done.
+    pub fn scheduler(&self) -> MutexGuard<'_, HypothesisScheduler> {
+        self.scheduler
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crack::worker::{TrialRecord, WorkerPool};
+    use crate::utils::Key;
+
+    use std::sync::Arc;
+
+    /// a result of `hypothesis`, which cracked if `success`
+    fn result(hypothesis: Hypothesis, success: bool) -> TrialResult {
+        let mut rng = Rng::from_seed(1);
+        let scheduler =
+            RandomScheduler::with_kind(&mut rng, SchedulerComplexity::Easy, hypothesis.kind);
+        TrialResult {
+            trial: TrialRecord {
+                id: 0,
+                seed: 1,
+                key: Key::from(vec![1; hypothesis.keylengths.lengths().start]),
+                scheduler,
+                known_plaintext: None,
+                plaintext: String::new(),
+                ciphertext: String::new(),
+            },
+            guessed_test: 2,
+            cracked: String::new(),
+            error: 0.0,
+            success,
+            recovered_key: None,
+            corrections: 0,
+        }
+    }
+
+    #[test]
+    fn bands() {
+        for length in KEY_LENGTHS {
+            assert!(KeylengthBand::of(length).lengths().contains(&length));
+        }
+        assert_eq!(KeylengthBand::of(100), KeylengthBand::Long);
+        assert_eq!(Hypothesis::all().len(), 18);
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut scheduler =
+            HypothesisScheduler::new(Policy::RoundRobin, SchedulerComplexity::Medium, 3);
+        let picked: Vec<Hypothesis> = (0..36).map(|_| scheduler.pick().0).collect();
+        assert_eq!(picked[..18], Hypothesis::all()[..]);
+        assert_eq!(picked[18..], Hypothesis::all()[..]);
+        // neighbours are different families
+        assert!(picked.windows(2).all(|pair| pair[0].kind != pair[1].kind));
+
+        let (hypothesis, sched) = scheduler.pick();
+        assert_eq!(sched.base().kind(), hypothesis.kind);
+        assert_eq!(scheduler.in_flight(), 37);
+    }
+
+    #[test]
+    fn bandit_favours_successes() {
+        let mut scheduler =
+            HypothesisScheduler::new(Policy::bandit(), SchedulerComplexity::Easy, 3);
+        let all = Hypothesis::all();
+        let winner = all[4];
+
+        // every hypothesis is tried once before any twice
+        let first: Vec<Hypothesis> = (0..all.len()).map(|_| scheduler.pick().0).collect();
+        assert_eq!(first, all);
+
+        // only one hypothesis cracks, so it gets tested most
+        for hypothesis in first {
+            scheduler.record(&result(hypothesis, hypothesis == winner));
+        }
+        for _ in 0..1000 {
+            let (hypothesis, _) = scheduler.pick();
+            scheduler.record(&result(hypothesis, hypothesis == winner));
+        }
+        assert_eq!(scheduler.in_flight(), 0);
+
+        let trials = |h: Hypothesis| {
+            let (_, arm) = scheduler.arms().iter().find(|(a, _)| *a == h).unwrap();
+            arm.tally.trials
+        };
+        for &hypothesis in &all {
+            assert!(trials(hypothesis) >= 1);
+            if hypothesis != winner {
+                assert!(trials(winner) > 5 * trials(hypothesis));
+            }
+        }
+        assert!(scheduler.to_string().contains("short"));
+    }
+
+    #[test]
+    fn feed_pool() {
+        let scheduler = HypothesisScheduler::new(Policy::RoundRobin, SchedulerComplexity::Easy, 5);
+        let feed = Arc::new(HypothesisFeed::new(scheduler, 2));
+        let mut pool = WorkerPool::spawn_with_seed(2, 5);
+
+        let recording = Arc::clone(&feed);
+        let aggregator = pool.spawn_aggregator(move |result| {
+            recording.record(result);
+            Ok(())
+        });
+        feed.submit(&pool.sender().unwrap(), 4);
+        pool.shutdown();
+
+        assert_eq!(aggregator.join().unwrap().unwrap().overall.trials, 4);
+        pool.join().unwrap();
+
+        let scheduler = feed.scheduler();
+        assert_eq!(scheduler.in_flight(), 0);
+        // the trials tested the first four hypotheses, one each
+        for (index, (hypothesis, arm)) in scheduler.arms().iter().enumerate() {
+            assert_eq!(arm.tally.trials, (index < 4) as usize, "{:?}", hypothesis);
+        }
+    }
+}
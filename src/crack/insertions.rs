@@ -0,0 +1,162 @@
+//! Module for guessing the periods of ciphertexts with random characters inserted every so often.
+//!
+//! A [`PeriodicRand`] that inserts random characters, rather than overwriting key symbols with
+//! them, pushes every character after an insertion one key symbol along. Characters a multiple of
+//! the keylength apart then only line up until the next insertion, so keylength guesses from
+//! coincidences (or Hamming distances between chunks) blur.
+//!
+//! Instead, every combination of a keylength, an insertion period and the start of the insertions
+//! on a grid is scored together. The characters the insertions would be are skipped, and the rest
+//! scored by how significantly characters a multiple of the keylength apart coincide, like
+//! [`significance`][`crate::crack::significance`] scores a keylength. The right combination skips
+//! exactly the inserted characters, leaving a ciphertext under a plainly repeating key.
+
+use super::keylength::{coincidence_z_score, p_value, MIN_KEYLENGTH};
+use crate::ciphers::schedulers::PeriodicRand;
+use crate::utils::MAX_KEYLENGTH;
+
+use std::ops::{Range, RangeInclusive};
+
+/// Combinations of keylengths and insertions [`guess_periods`] tries.
+#[derive(Clone, Debug)]
+pub struct InsertionGrid {
+    /// Keylengths to try
+    pub keylengths: RangeInclusive<usize>,
+    /// Numbers of characters between insertions to try
+    pub periods: RangeInclusive<usize>,
+    /// Positions of the first insertion to try
+    pub starts: Range<usize>,
+}
+
+impl Default for InsertionGrid {
+    /// The project's keylengths, and the insertions a random [`PeriodicRand`] makes.
+    fn default() -> Self {
+        Self {
+            keylengths: MIN_KEYLENGTH..=MAX_KEYLENGTH,
+            periods: 32..=63,
+            starts: 0..32,
+        }
+    }
+}
+
+/// A keylength, and the random characters inserted on top of the key repeating at it.
+#[derive(Clone, Copy, Debug)]
+pub struct PeriodHypothesis {
+    pub keylength: usize,
+    /// Random characters inserted like this scheduler would (never overwriting), or `None` for a
+    /// key repeating without any
+    pub insertions: Option<PeriodicRand>,
+    /// How unlikely the characters left after skipping the insertions are to coincide as often as
+    /// they do if they were shuffled, like [`significance`][`crate::crack::significance`]. Lower
+    /// is better.
+    pub significance: f64,
+}
+
+impl PeriodHypothesis {
+    /// `ciphertext` without the characters this hypothesis says were inserted, encrypted under a
+    /// key repeating at the keylength if the hypothesis is right.
+    pub fn strip(&self, ciphertext: &[u8]) -> Vec<u8> {
+        match &self.insertions {
+            Some(insertions) => ciphertext
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| !insertions.random_at(index))
+                .map(|(_, &c)| c)
+                .collect(),
+            None => ciphertext.to_vec(),
+        }
+    }
+}
+
+/// Score every combination of a keylength and insertions on `grid`, and of a keylength without
+/// insertions, best first.
+///
+/// Starts at or past the end of `ciphertext` insert nothing, so they aren't tried. Combinations
+/// that score the same keep the order they are tried in: without insertions first, then by
+/// period and start.
+pub fn guess_periods(ciphertext: &[u8], grid: &InsertionGrid) -> Vec<PeriodHypothesis> {
+    let without = std::iter::once(None);
+    let with = grid.periods.clone().flat_map(|period| {
+        grid.starts
+            .clone()
+            .filter(|&start| start < ciphertext.len())
+            .map(move |start| {
+                Some(PeriodicRand {
+                    period: period.max(1),
+                    start,
+                    overwrite: false,
+                })
+            })
+    });
+
+    let mut scored = Vec::new();
+    for insertions in without.chain(with) {
+        let stripped = PeriodHypothesis {
+            keylength: 0,
+            insertions,
+            significance: 1.0,
+        }
+        .strip(ciphertext);
+        for keylength in grid.keylengths.clone() {
+            let z = coincidence_z_score(&stripped, keylength);
+            scored.push((keylength, insertions, z));
+        }
+    }
+
+    // the p-values of very significant hypotheses round to 0.0, so rank by z-score
+    scored.sort_by(|(.., a), (.., b)| b.total_cmp(a));
+    scored
+        .into_iter()
+        .map(|(keylength, insertions, z)| PeriodHypothesis {
+            keylength,
+            insertions,
+            significance: p_value(z),
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::crack::{crack, DictionaryStats};
+    use crate::prelude::*;
+    use crate::utils::{bytes_to_str, str_to_bytes};
+
+    #[test]
+    fn insertions() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let plaintext = Generator::with_dict(&dict).generate_exact_len(500);
+        let key = vec![4, 20, 11, 0, 17, 9, 2, 25, 13, 6, 19];
+
+        let sched = PeriodicRand {
+            period: 41,
+            start: 13,
+            overwrite: false,
+        };
+        let encryptor = Encryptor::new(key.clone(), sched, Rng::default());
+        let ciphertext = str_to_bytes(&encryptor.encrypt(&plaintext));
+
+        // the keylength, period and start all come out on top
+        let guesses = guess_periods(&ciphertext, &InsertionGrid::default());
+        let best = guesses[0];
+        assert_eq!(best.keylength, key.len());
+        let insertions = best.insertions.unwrap();
+        assert_eq!((insertions.period, insertions.start), (41, 13));
+        assert!(best.significance < 1e-6);
+
+        // and stripping the insertions leaves a plain repeating key to crack
+        let stripped = best.strip(&ciphertext);
+        assert_eq!(stripped.len(), plaintext.len());
+        let cracked = crack(&stripped, key.len(), &DictionaryStats::project().letters);
+        assert_eq!(bytes_to_str(&cracked.plaintext), plaintext);
+
+        // a key repeating without insertions is best guessed without any
+        let encryptor = Encryptor::new(key.clone(), RepeatingKey, Rng::default());
+        let ciphertext = str_to_bytes(&encryptor.encrypt(&plaintext));
+        let best = guess_periods(&ciphertext, &InsertionGrid::default())[0];
+        assert_eq!(best.keylength, key.len());
+        assert!(best.insertions.is_none());
+    }
+}
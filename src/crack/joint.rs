@@ -0,0 +1,168 @@
+//! Module for re-picking the shifts of neighboring columns together.
+//!
+//! Frequency analysis picks each column's shift on its own, so when two neighboring columns are
+//! both a little off, fixing either one alone doesn't look any better, and the pair stays wrong.
+//! Every character of a column sits next to a character of each neighboring column, so trying
+//! shifts of a few neighbors at once and scoring the pairs of characters they make catches those.
+
+use super::boundaries::{best_shifts, column_scores, set_column, shifts_of};
+use super::{CrackResult, DictionaryStats};
+
+/// Best shifts of each column by letter frequency to try, besides the one it was cracked with.
+const CANDIDATES: usize = 4;
+
+/// Most neighboring columns re-picked at once.
+const MAX_WIDTH: usize = 3;
+
+/// Passes over every window of columns before giving up on finding better shifts.
+const MAX_PASSES: usize = 3;
+
+/// Re-pick the shifts of every pair and triple of neighboring columns of a crack at `keylength`
+/// together, by the [`bigram_score`][`DictionaryStats::bigram_score`] of the characters they
+/// touch.
+///
+/// Each column only tries its best few shifts by letter frequency, so the search stays small. The
+/// confidence is scaled by how much the bigram score of the whole plaintext changed.
+pub fn refine_adjacent_columns(
+    ciphertext: &[u8],
+    keylength: usize,
+    cracked: &CrackResult,
+    stats: &DictionaryStats,
+) -> CrackResult {
+    let columns = keylength.min(ciphertext.len());
+    if columns < 2 || cracked.plaintext.len() != ciphertext.len() {
+        // no neighbors, or not a crack of this ciphertext
+        return cracked.clone();
+    }
+
+    let mut shifts = shifts_of(ciphertext, keylength, &cracked.plaintext);
+    let candidates = best_shifts(&column_scores(ciphertext, keylength, stats), CANDIDATES);
+    let mut plaintext = cracked.plaintext.clone();
+
+    for _ in 0..MAX_PASSES {
+        let mut improved = false;
+        for width in 2..=MAX_WIDTH.min(columns) {
+            // the last column neighbors the first, unless the window already covers every column
+            let starts = if width == columns { 1 } else { columns };
+            for start in 0..starts {
+                let window: Vec<usize> = (start..start + width).map(|c| c % columns).collect();
+                improved |= refine_window(
+                    ciphertext,
+                    keylength,
+                    &window,
+                    &candidates,
+                    &mut shifts,
+                    &mut plaintext,
+                    stats,
+                );
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    let original = stats.bigram_score(&cracked.plaintext);
+    let refined = stats.bigram_score(&plaintext);
+    CrackResult {
+        plaintext,
+        confidence: match original {
+            original if original > 0.0 => cracked.confidence * refined / original,
+            _ => cracked.confidence,
+        },
+        timed_out: cracked.timed_out,
+    }
+}
+
+/// Try every combination of candidate shifts for the columns of `window`, keeping the one whose
+/// characters make the likeliest pairs. Returns whether the shifts changed.
+fn refine_window(
+    ciphertext: &[u8],
+    keylength: usize,
+    window: &[usize],
+    candidates: &[Vec<i8>],
+    shifts: &mut [i8],
+    plaintext: &mut [u8],
+    stats: &DictionaryStats,
+) -> bool {
+    // only pairs with a character in the window change with its shifts
+    let in_window = |index: usize| window.contains(&(index % keylength));
+    let pairs: Vec<usize> = (0..plaintext.len().saturating_sub(1))
+        .filter(|&index| in_window(index) || in_window(index + 1))
+        .collect();
+    let score = |plaintext: &[u8]| -> f64 {
+        pairs
+            .iter()
+            .map(|&index| stats.bigram_surprise(plaintext[index], plaintext[index + 1]))
+            .sum()
+    };
+
+    // each column's current shift, then its candidates
+    let options: Vec<Vec<i8>> = window
+        .iter()
+        .map(|&column| {
+            let mut options = vec![shifts[column]];
+            options.extend(candidates[column].iter().filter(|&&s| s != shifts[column]));
+            options
+        })
+        .collect();
+
+    let kept: Vec<i8> = window.iter().map(|&column| shifts[column]).collect();
+    let mut best = (score(plaintext), kept.clone());
+    let mut choice = vec![0; window.len()];
+    // count through every combination of options, like an odometer
+    while let Some(digit) = choice
+        .iter()
+        .zip(&options)
+        .position(|(&chosen, options)| chosen + 1 < options.len())
+    {
+        choice[digit] += 1;
+        choice[..digit].iter_mut().for_each(|chosen| *chosen = 0);
+
+        let trial: Vec<i8> = choice.iter().zip(&options).map(|(&c, o)| o[c]).collect();
+        for (&column, &shift) in window.iter().zip(&trial) {
+            set_column(ciphertext, keylength, column, shift, plaintext);
+        }
+        let trial_score = score(plaintext);
+        if trial_score < best.0 {
+            best = (trial_score, trial);
+        }
+    }
+
+    for (&column, &shift) in window.iter().zip(&best.1) {
+        set_column(ciphertext, keylength, column, shift, plaintext);
+        shifts[column] = shift;
+    }
+    best.1 != kept
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::prelude::*;
+    use crate::utils::str_to_bytes;
+
+    #[test]
+    fn fixes_neighboring_columns() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let stats = DictionaryStats::from_dict(&dict);
+        let plaintext = Generator::with_dict(&dict).generate_words(60);
+        let key = vec![2, 7, 1, 8, 2, 8, 1];
+        let ciphertext =
+            Encryptor::new(key.clone(), RepeatingKey, Rng::default()).encrypt(&plaintext);
+        let ciphertext = str_to_bytes(&ciphertext);
+        let plainbytes = str_to_bytes(&plaintext);
+
+        // the right crack, but with the last and first columns both a little off
+        let mut wrong = plainbytes.clone();
+        set_column(&ciphertext, key.len(), 6, -key[6] + 1, &mut wrong);
+        set_column(&ciphertext, key.len(), 0, -key[0] - 1, &mut wrong);
+        let cracked = CrackResult::new(wrong, 1.0);
+
+        let refined = refine_adjacent_columns(&ciphertext, key.len(), &cracked, &stats);
+        assert_eq!(refined.plaintext, plainbytes);
+        assert!(refined.confidence < cracked.confidence);
+    }
+}
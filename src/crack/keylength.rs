@@ -1,17 +1,171 @@
-/// Guess the keylength based on the technique shown in
-/// [cryptopals](https://cryptopals.com/sets/1/challenges/6). It is yet to be tested on these shift
-/// based ciphers, but this implementation worked against the linked cryptopals challenge based on
-/// multi-byte xor.
+use crate::utils::{Key, MAX_KEYLENGTH};
+
+use std::convert::TryInto;
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
+
+/// Shortest keylength guessed. Keys of 1 and 2 repeat every 3 and 4 characters too, so cracking
+/// those multiples recovers them.
+pub(crate) const MIN_KEYLENGTH: usize = 3;
+
+/// Longest keylength guessed unless [`KeylengthScoring`] says otherwise.
+pub const MAX_GUESSED_KEYLENGTH: usize = 120;
+
+/// Fewest whole chunks a keylength has to split the ciphertext into to be scored. With fewer, the
+/// distances between chunks are mostly noise.
+const MIN_CHUNKS: usize = 4;
+
+/// Fewest characters of every message together each column has to hold for [`guesses_pooled`] to
+/// guess a keylength. With fewer, spellchecking fits words to almost any shifts of the columns.
+const MIN_POOLED_COLUMN_CHARS: usize = 32;
+
+/// Guess the keylength, scoring each by how significantly characters a multiple of it apart
+/// coincide (see [`KeylengthMethod`]). Guesses are sorted best first.
+///
+/// Only keylengths in [`keylength_range`] are guessed. `keysizes` is left empty for ciphertexts
+/// too short to guess any keylength of.
 #[allow(dead_code)]
 pub fn guesses(ciphertext: &[u8], keysizes: &mut Vec<(usize, f64)>) {
-    let keysize_lo: usize = 3;
-    let keysize_hi: usize = 120.min(ciphertext.len() / 4);
+    guesses_with(ciphertext, keysizes, &KeylengthScoring::default())
+}
 
+/// How each keylength is scored when guessing keylengths.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct KeylengthScoring {
+    /// Compare at most this many pairs of chunks per keylength for
+    /// [`Detrended`][`KeylengthMethod::Detrended`] scores, estimating the score from a sample of
+    /// nearby chunks on long ciphertexts. `None` always compares every pair of chunks.
+    pub max_chunk_pairs: Option<usize>,
+    /// Longest keylength guessed. Shorter ciphertexts guess only shorter keylengths, see
+    /// [`keylength_range`].
+    pub max_keylength: usize,
+    /// What each keylength's score means
+    pub method: KeylengthMethod,
+}
+
+/// How [`guesses_with`] scores keylengths. Lower scores are better either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeylengthMethod {
+    /// Hamming distance between chunks, with the trend over keylengths fit by linear regression
+    /// taken out. Scores are 1.0 or more, and only mean something next to the other scores of
+    /// the same ciphertext.
+    Detrended,
+    /// How unlikely the ciphertext is to have as many characters a multiple of the keylength
+    /// apart coincide if it were shuffled, as a one-sided p-value (see [`significance`]). Scores
+    /// are between 0.0 and 1.0, and mean the same for every ciphertext.
+    #[default]
+    Significance,
+}
+
+impl Default for KeylengthScoring {
+    fn default() -> Self {
+        Self {
+            max_chunk_pairs: Some(1 << 16),
+            max_keylength: MAX_GUESSED_KEYLENGTH,
+            method: KeylengthMethod::default(),
+        }
+    }
+}
+
+impl KeylengthScoring {
+    /// Only guess keylengths up to the project's longest key, [`MAX_KEYLENGTH`]. Schedulers that
+    /// don't simply repeat the key can repeat it over a longer period, so this can miss those.
+    pub fn project() -> Self {
+        Self {
+            max_keylength: MAX_KEYLENGTH,
+            ..Self::default()
+        }
+    }
+}
+
+/// Keylengths worth guessing for a ciphertext of `len` characters, up to `max_keylength`: each has
+/// to split the ciphertext into enough whole chunks to compare. Empty when the ciphertext is too
+/// short for any.
+///
+/// ```
+/// # use one_team_pad_cipher_cracker::crack::keylength_range;
+/// assert_eq!(keylength_range(60, 120), 3..=15);
+/// assert_eq!(keylength_range(1000, 24), 3..=24);
+/// assert!(keylength_range(10, 120).is_empty());
+/// ```
+pub fn keylength_range(len: usize, max_keylength: usize) -> RangeInclusive<usize> {
+    MIN_KEYLENGTH..=max_keylength.min(len / MIN_CHUNKS)
+}
+
+/// Same as [`guesses`], but scoring keylengths as configured by `scoring`.
+pub fn guesses_with(
+    ciphertext: &[u8],
+    keysizes: &mut Vec<(usize, f64)>,
+    scoring: &KeylengthScoring,
+) {
     // clear previous keysizes
     keysizes.clear();
 
-    for keysize in keysize_lo..keysize_hi {
-        let score = hamming_distance_between_chunks(ciphertext, keysize);
+    let range = keylength_range(ciphertext.len(), scoring.max_keylength);
+    match scoring.method {
+        KeylengthMethod::Significance => {
+            let mut scored: Vec<_> = range
+                .map(|keysize| (keysize, coincidence_z_score(ciphertext, keysize)))
+                .collect();
+            // the p-values of very significant keylengths round to 0.0, so rank by z-score
+            scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+            keysizes.extend(scored.into_iter().map(|(keysize, z)| (keysize, p_value(z))));
+        }
+        KeylengthMethod::Detrended => detrended(ciphertext, range, keysizes, scoring),
+    }
+}
+
+/// Guess the keylength of messages encrypted under the same key and scheduler, each starting the
+/// key schedule at its first character, by how significantly characters a multiple of it apart
+/// coincide within and across the messages. Guesses are sorted best first, and scored like
+/// [`KeylengthMethod::Significance`] whatever `scoring.method` is.
+///
+/// Every keylength up to `scoring.max_keylength` that the longest message has characters that
+/// far apart in, and that leaves enough characters of all the messages in each column, is
+/// guessed. Pooled messages guess keylengths too long for any one of them this way.
+pub fn guesses_pooled(
+    ciphertexts: &[&[u8]],
+    keysizes: &mut Vec<(usize, f64)>,
+    scoring: &KeylengthScoring,
+) {
+    keysizes.clear();
+
+    let longest = ciphertexts.iter().map(|ciphertext| ciphertext.len()).max();
+    let total: usize = ciphertexts.iter().map(|ciphertext| ciphertext.len()).sum();
+    let range = MIN_KEYLENGTH
+        ..=scoring
+            .max_keylength
+            .min(longest.unwrap_or(0).saturating_sub(1))
+            .min(total / MIN_POOLED_COLUMN_CHARS);
+    let mut scored: Vec<_> = range
+        .map(|keysize| (keysize, pooled_z_score(ciphertexts, keysize)))
+        .collect();
+    scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    keysizes.extend(scored.into_iter().map(|(keysize, z)| (keysize, p_value(z))));
+}
+
+/// Score `range` of keylengths by [`KeylengthMethod::Detrended`] into `keysizes`, best first.
+fn detrended(
+    ciphertext: &[u8],
+    range: RangeInclusive<usize>,
+    keysizes: &mut Vec<(usize, f64)>,
+    scoring: &KeylengthScoring,
+) {
+    if range.clone().count() < 2 {
+        // too short to fit a line through the scores
+        return;
+    }
+
+    for keysize in range {
+        let score = match scoring.max_chunk_pairs {
+            Some(max_pairs) => {
+                sampled_hamming_distance_between_chunks(ciphertext, keysize, max_pairs)
+            }
+            None => hamming_distance_between_chunks(ciphertext, keysize),
+        };
         keysizes.push((keysize, score));
     }
 
@@ -32,7 +186,7 @@ pub fn guesses(ciphertext: &[u8], keysizes: &mut Vec<(usize, f64)>) {
     let min = *keysizes
         .iter()
         .map(|(_, y)| y)
-        .min_by(|a, b| a.partial_cmp(&b).unwrap())
+        .min_by(|a, b| a.partial_cmp(b).unwrap())
         .unwrap();
     for (_, y) in keysizes.iter_mut() {
         *y += min.abs() + 1.0;
@@ -42,6 +196,131 @@ pub fn guesses(ciphertext: &[u8], keysizes: &mut Vec<(usize, f64)>) {
     keysizes.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
 }
 
+/// Farthest apart characters are compared by [`significance`], unless the keylength is longer.
+/// Every multiple of the keylength up to it is compared, so a keylength is more significant than
+/// its multiples, which only repeat it. Random characters inserted by the scheduler shift
+/// characters further apart out of line.
+const SIGNIFICANCE_DISTANCE: usize = 120;
+
+/// How unlikely `ciphertext` is to have as many characters a multiple of `keylength` apart
+/// coincide if it were shuffled, as a one-sided p-value. At the right keylength, coinciding
+/// characters were shifted by the same key symbol, so they coincide as often as plaintext
+/// characters do, far more than shuffled ones.
+///
+/// The shuffled baseline is exact rather than sampled: two positions of a shuffled ciphertext hold
+/// the same character with the chance of drawing two of the same from its character counts.
+///
+/// ```
+/// # use one_team_pad_cipher_cracker::crack::significance;
+/// let ciphertext: Vec<u8> = b"the quick brown fox".repeat(5).iter().map(|b| b % 27).collect();
+/// assert!(significance(&ciphertext, 19) < 1e-6);
+/// assert!(significance(&ciphertext, 7) > 0.1);
+/// ```
+pub fn significance(ciphertext: &[u8], keylength: usize) -> f64 {
+    p_value(coincidence_z_score(ciphertext, keylength))
+}
+
+/// Standard deviations more characters a multiple of `keylength` apart coincide than in the
+/// shuffled ciphertext.
+pub(crate) fn coincidence_z_score(ciphertext: &[u8], keylength: usize) -> f64 {
+    pooled_z_score(&[ciphertext], keylength)
+}
+
+/// Same as [`coincidence_z_score`], but over messages that all start the key schedule at their
+/// first character. Characters a multiple of `keylength` apart coincide within each message, and
+/// across every two messages, as often as plaintext characters do at the right keylength.
+fn pooled_z_score(ciphertexts: &[&[u8]], keylength: usize) -> f64 {
+    let n: usize = ciphertexts.iter().map(|ciphertext| ciphertext.len()).sum();
+    let mut counts = [0_usize; 256];
+    for &c in ciphertexts.iter().copied().flatten() {
+        counts[c as usize] += 1;
+    }
+    if n < 2 || keylength == 0 {
+        return 0.0;
+    }
+    let same: usize = counts
+        .iter()
+        .map(|&count| count * count.saturating_sub(1))
+        .sum();
+    let expected = same as f64 / (n * (n - 1)) as f64;
+
+    let longest = ciphertexts.iter().map(|ciphertext| ciphertext.len()).max();
+    let farthest = SIGNIFICANCE_DISTANCE
+        .max(keylength)
+        .min(longest.unwrap_or(0).saturating_sub(1));
+    let mut pairs = 0;
+    let mut coincidences = 0;
+    for distance in (keylength..=farthest).step_by(keylength) {
+        for (a, b) in ciphertexts
+            .iter()
+            .flat_map(|a| ciphertexts.iter().map(move |b| (a, b)))
+        {
+            // `a` ahead of `b`, so pairs across two messages are counted both ways
+            let ahead = b.get(distance..).unwrap_or_default();
+            pairs += a.len().min(ahead.len());
+            coincidences += a.iter().zip(ahead).filter(|(a, b)| a == b).count();
+        }
+    }
+
+    let deviation = (pairs as f64 * expected * (1.0 - expected)).sqrt();
+    if deviation == 0.0 {
+        return 0.0;
+    }
+    (coincidences as f64 - pairs as f64 * expected) / deviation
+}
+
+/// Chance of a standard normal being at least `z`, from the complementary error function as
+/// approximated in Numerical Recipes (`erfcc`, fractional error under 1.2e-7).
+pub(crate) fn p_value(z: f64) -> f64 {
+    let x = z / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.5 * x.abs());
+    let poly = -x * x - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let erfc = t * poly.exp();
+    let erfc = if x >= 0.0 { erfc } else { 2.0 - erfc };
+    erfc / 2.0
+}
+
+/// Keylengths whose cracks came out clean, shared by concurrent cracks of one ciphertext so
+/// keylength guesses equivalent to one of them can be skipped.
+///
+/// A key cracked at a multiple of its real length repeats itself, so cracking every multiple of a
+/// keylength that already cracked cleanly only redoes the same work.
+#[derive(Debug, Default)]
+pub struct CrackedPeriods {
+    periods: Mutex<Vec<usize>>,
+}
+
+impl CrackedPeriods {
+    /// Record the crack of `plaintext` at `keylength`, clean if spellchecking didn't change it, by
+    /// the shortest period of its key.
+    pub fn record(&self, ciphertext: &[u8], keylength: usize, plaintext: &[u8], clean: bool) {
+        if !clean || plaintext.len() != ciphertext.len() || keylength == 0 {
+            return;
+        }
+        let period = Key::between(ciphertext, plaintext, keylength).minimal_period();
+        let mut periods = self.periods.lock().unwrap();
+        if !periods.contains(&period) {
+            periods.push(period);
+        }
+    }
+
+    /// Whether `keylength` is a multiple of a keylength that already cracked cleanly.
+    pub fn covers(&self, keylength: usize) -> bool {
+        self.periods
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|&period| keylength.is_multiple_of(period))
+    }
+}
+
 /// Take 4 chunks of size `chunksize` and calculate a normalized score of the Hamming distance
 /// between each chunk.
 pub fn hamming_distance_between_chunks(input: &[u8], chunksize: usize) -> f64 {
@@ -56,25 +335,72 @@ pub fn hamming_distance_between_chunks(input: &[u8], chunksize: usize) -> f64 {
     distance as f64 / chunks.len() as f64
 }
 
+/// Same score as [`hamming_distance_between_chunks`], but comparing at most about `max_pairs`
+/// pairs of chunks.
+///
+/// When there are too many pairs, each chunk is only compared with the chunks closely following
+/// it, and the average distance of those pairs stands in for the average of every pair.
+pub fn sampled_hamming_distance_between_chunks(
+    input: &[u8],
+    chunksize: usize,
+    max_pairs: usize,
+) -> f64 {
+    let chunks: Vec<&[u8]> = input.chunks_exact(chunksize).collect();
+    let n = chunks.len();
+    let all_pairs = n * n.saturating_sub(1) / 2;
+    if all_pairs <= max_pairs {
+        return hamming_distance_between_chunks(input, chunksize);
+    }
+
+    // compare every chunk with the next `window` chunks
+    let window = (max_pairs / n).max(1);
+    let mut distance = 0;
+    let mut pairs = 0;
+    for ii in 0..n {
+        for jj in ii + 1..n.min(ii + 1 + window) {
+            distance += hamming_distance(chunks[ii], chunks[jj]);
+            pairs += 1;
+        }
+    }
+
+    // scale the sampled average up to the total over all pairs, to match the unsampled score
+    let mean = distance as f64 / pairs as f64;
+    mean * all_pairs as f64 / n as f64
+}
+
 /// Calculate the bitwise Hamming distance between two `u8` slices
 pub fn hamming_distance(a: &[u8], b: &[u8]) -> u32 {
     assert_eq!(a.len(), b.len(), "lengths must be equal");
 
-    a.iter()
-        .zip(b.iter())
-        // XOR leaves a 1 where the bits differ. Then counting the ones in the u8 gives the hamming
-        // distance for that one byte
+    // XOR leaves a 1 where the bits differ, so counting the ones gives the hamming distance. Do
+    // this 8 bytes at a time, then finish off the bytes that don't fill a u64
+    let a_words = a.chunks_exact(8);
+    let b_words = b.chunks_exact(8);
+    let tail: u32 = a_words
+        .remainder()
+        .iter()
+        .zip(b_words.remainder())
         .map(|(a, b)| (a ^ b).count_ones())
-        // add all the single byte hamming distances
-        .sum()
+        .sum();
+
+    a_words
+        .zip(b_words)
+        .map(|(a, b)| {
+            let a = u64::from_ne_bytes(a.try_into().unwrap());
+            let b = u64::from_ne_bytes(b.try_into().unwrap());
+            (a ^ b).count_ones()
+        })
+        .sum::<u32>()
+        + tail
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "gen"))]
 mod tests {
     use super::*;
     use crate::ciphers::{Cipher, Encryptor, KeySchedule};
     use crate::rng::FromRng;
     use crate::rng::Rng;
+    use crate::utils::{str_to_bytes, Shift};
 
     // import schedulers we need
     use crate::ciphers::schedulers::{PeriodicRand, RepeatingKey};
@@ -106,23 +432,29 @@ mod tests {
         // get ciphertext into bytes
         let ciphertext = crate::utils::str_to_bytes(&ciphertext);
 
-        // calculate guesses
-        let mut keysizes = Vec::new();
-        guesses(&ciphertext, &mut keysizes);
+        // calculate guesses, both ways
+        for method in [KeylengthMethod::Significance, KeylengthMethod::Detrended] {
+            let scoring = KeylengthScoring {
+                method,
+                ..Default::default()
+            };
+            let mut keysizes = Vec::new();
+            guesses_with(&ciphertext, &mut keysizes, &scoring);
+
+            // count how many integer multiples (including exact matches) of the expected keylength
+            // are in the top results
+            let integer_multiples = keysizes
+                .iter()
+                .map(|(guess, _score)| guess)
+                // only look at the top 5 guesses
+                .take(5)
+                // filter where the expected keylength is a factor of the guess
+                .filter(|&&guess| guess == expected_keylen)
+                // count how many are left
+                .count();
 
-        // count how many integer multiples (including exact matches) of the expected keylength are
-        // in the top results
-        let integer_multiples = keysizes
-            .iter()
-            .map(|(guess, _score)| guess)
-            // only look at the top 5 guesses
-            .take(5)
-            // filter where the expected keylength is a factor of the guess
-            .filter(|&&guess| guess == expected_keylen)
-            // count how many are left
-            .count();
-
-        assert!(integer_multiples > 0, "keylength not in top 5");
+            assert!(integer_multiples > 0, "keylength not in top 5 {:?}", method);
+        }
     }
 
     #[test]
@@ -180,6 +512,64 @@ mod tests {
         expected_keylen_rank(keylen, inserted_rand, expected_keylen);
     }
 
+    #[test]
+    fn short_ciphertexts() {
+        let mut rng = Rng::default();
+        let ciphertext: Vec<u8> = (0..60).map(|_| rng.gen_range(0..27) as u8).collect();
+
+        // only keylengths leaving enough chunks to compare
+        let mut keysizes = Vec::new();
+        guesses(&ciphertext, &mut keysizes);
+        assert_eq!(keysizes.len(), 13);
+        assert!(keysizes.iter().all(|&(keysize, _)| keysize <= 15));
+
+        let project = KeylengthScoring::project();
+        guesses_with(&ciphertext.repeat(10), &mut keysizes, &project);
+        assert!(keysizes
+            .iter()
+            .all(|&(keysize, _)| keysize <= MAX_KEYLENGTH));
+
+        // nothing to guess from
+        guesses(&ciphertext[..11], &mut keysizes);
+        assert!(keysizes.is_empty());
+    }
+
+    #[test]
+    fn hamming_distance_wide() {
+        let mut rng = Rng::default();
+        for len in 0..40 {
+            let mut a = vec![0; len];
+            let mut b = vec![0; len];
+            rng.fill_bytes(&mut a);
+            rng.fill_bytes(&mut b);
+            let bytewise: u32 = a.iter().zip(&b).map(|(a, b)| (a ^ b).count_ones()).sum();
+            assert_eq!(hamming_distance(&a, &b), bytewise);
+        }
+        assert_eq!(hamming_distance(b"this is a test", b"wokka wokka!!!"), 37);
+    }
+
+    #[test]
+    fn sampled_chunks() {
+        let mut rng = Rng::default();
+        let input: Vec<u8> = (0..2000).map(|_| rng.gen_range(0..27) as u8).collect();
+
+        // with room for every pair, sampling gives the exact score
+        let exact = hamming_distance_between_chunks(&input, 10);
+        assert_eq!(
+            sampled_hamming_distance_between_chunks(&input, 10, 1 << 20),
+            exact
+        );
+
+        // otherwise the estimate is close for random input
+        let sampled = sampled_hamming_distance_between_chunks(&input, 10, 1000);
+        assert!(
+            (sampled - exact).abs() / exact < 0.05,
+            "{} vs {}",
+            sampled,
+            exact
+        );
+    }
+
     /// stress testing keylength guessing
     #[test]
     #[ignore]
@@ -191,8 +581,7 @@ mod tests {
         let dict = crate::dict::Dictionary::from_string(&mut words);
         let mut gen = crate::gen::Generator::with_dict(&dict);
 
-        // reusable Vecs for ciphertext, key, plaintext
-        let mut plaintext = String::new();
+        // reusable Vecs for ciphertext, key
         let mut ciphertext = String::new();
         let mut key = Vec::new();
         let mut keysizes = Vec::new();
@@ -203,18 +592,15 @@ mod tests {
         // total number of "failures" where the correct keylength was not in the top 15 results
         let mut failures = 0;
 
-        for _ in 0..RUNS {
+        for plaintext in gen.messages(120).take(RUNS) {
             // choose a keylength between 8 and 32
-            let keylen = rng.next() % 30 + 8;
+            let keylen = rng.gen_range(8..38);
 
             // build the key
             for _ in 0..keylen {
                 key.push(rng.next() as u8 as i8);
             }
 
-            // build the plaintext
-            gen.generate_words_into(120, &mut plaintext);
-
             // create the encryptor
             // TODO: generate a random scheduler
             let enc_rng = FromRng::from_rng(&mut rng);
@@ -227,15 +613,15 @@ mod tests {
             // get keylength guesses
             guesses(&ct_bytes, &mut keysizes);
 
-            // count how many integer multiples (including exact matches) of the expected keylength are
-            // in the top results
+            // count how many integer multiples (including exact matches) of the expected keylength
+            // are in the top results
             let guessed = keysizes
                 .iter()
                 .map(|(guess, _score)| guess)
                 // only look at the top 15 guesses
                 .take(15)
                 // filter where the expected keylength is a factor of the guess
-                .any(|&guess| guess == keylen as usize);
+                .any(|&guess| guess == keylen);
 
             if !guessed {
                 failures += 1;
@@ -243,7 +629,6 @@ mod tests {
 
             // clear the buffers but keep allocation around
             ciphertext.clear();
-            plaintext.clear();
             key.clear();
         }
 
@@ -254,4 +639,23 @@ mod tests {
             "too many failures when guessing keylength"
         );
     }
+
+    #[test]
+    fn periods() {
+        // a key of 3 cracked at 6, which makes 6, 9, 12... redundant
+        let key = [4, 0, 25, 4, 0, 25];
+        let plaintext = str_to_bytes("the quick brown fox");
+        let ciphertext: Vec<u8> = plaintext
+            .iter()
+            .enumerate()
+            .map(|(i, p)| p.shift(key[i % key.len()]))
+            .collect();
+        let periods = CrackedPeriods::default();
+        periods.record(&ciphertext, 6, &plaintext, false);
+        assert!(!periods.covers(9));
+        periods.record(&ciphertext, 6, &plaintext, true);
+        assert!(periods.covers(3));
+        assert!(periods.covers(9));
+        assert!(!periods.covers(8));
+    }
 }
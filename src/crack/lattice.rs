@@ -0,0 +1,184 @@
+//! Module for decoding a crack straight into dictionary words.
+//!
+//! Spellchecking corrects a crack one word at a time, from the left, so one badly cracked word can
+//! throw off where every word after it starts. Instead, this lines up every dictionary word at
+//! every position of the crack, and finds the sequence of words covering the whole crack that
+//! differs from it the least, with a shortest path over the positions between words (a lattice).
+//! The words only have to line up with the ciphertext character for character, which they do
+//! under the repeating key schedulers of Test 2.
+//!
+//! The words decoded under a slightly wrong key still fix most characters of each wrong column, so
+//! the key is read back off the words, and the crack decoded again under it, for a few rounds.
+
+use super::boundaries::{set_column, shifts_of};
+use super::CrackResult;
+use crate::dict::{substitution_cost, BytesDictionary};
+use crate::utils::ALPHABET;
+
+/// Most rounds of reading the key off the decoded words and decoding again.
+const MAX_ROUNDS: usize = 3;
+
+/// Decode a crack at `keylength` into the dictionary words that differ from it the least, where
+/// characters a small shift apart differ less (see [`substitution_cost`]). The last word may be
+/// cut short, like a plaintext truncated to a length.
+///
+/// The confidence is the fraction of characters that differ from the crack under the key read off
+/// the words, so words a repeating key can't encrypt to the ciphertext are less confident. Like
+/// the confidence of a spellchecked crack, it is 0.0 when nothing needed correcting. `None` if no
+/// sequence of words is as long as the crack.
+pub fn decode_words(
+    ciphertext: &[u8],
+    keylength: usize,
+    cracked: &CrackResult,
+    dict: &BytesDictionary,
+) -> Option<CrackResult> {
+    if keylength == 0 || cracked.plaintext.len() != ciphertext.len() {
+        // not a crack of this ciphertext at this keylength
+        return None;
+    }
+
+    let mut crack = cracked.plaintext.clone();
+    let mut decoded = lattice(&crack, dict)?;
+    for _ in 0..MAX_ROUNDS {
+        // the key that encrypts the decoded words to the most characters of each column
+        let len = ALPHABET.len();
+        let mut votes = vec![vec![0_usize; len]; keylength];
+        for (index, (&c, &p)) in ciphertext.iter().zip(&decoded).enumerate() {
+            votes[index % keylength][(p as usize + len - c as usize) % len] += 1;
+        }
+        let shifts = shifts_of(ciphertext, keylength, &crack);
+        let mut moved = crack.clone();
+        for (column, column_votes) in votes.iter().enumerate() {
+            let best = (0..len)
+                .max_by_key(|&shift| column_votes[shift])
+                .unwrap_or(0) as i8;
+            if best != shifts[column] {
+                set_column(ciphertext, keylength, column, best, &mut moved);
+            }
+        }
+        if moved == crack {
+            break;
+        }
+
+        crack = moved;
+        decoded = lattice(&crack, dict)?;
+    }
+
+    let changed = decoded.iter().zip(&crack).filter(|(a, b)| a != b).count();
+    Some(CrackResult {
+        plaintext: decoded,
+        confidence: changed as f64 / crack.len().max(1) as f64,
+        timed_out: cracked.timed_out,
+    })
+}
+
+/// The better of `checked`, a spellchecked version of `crack`, and the words `crack` decodes to,
+/// by confidence. Decoding is skipped when spellchecking changed nothing.
+pub fn prefer_decoded(
+    ciphertext: &[u8],
+    keylength: usize,
+    crack: &CrackResult,
+    checked: CrackResult,
+    dict: &BytesDictionary,
+) -> CrackResult {
+    if checked.confidence == 0.0 {
+        return checked;
+    }
+    match decode_words(ciphertext, keylength, crack, dict) {
+        Some(decoded) if decoded.confidence < checked.confidence => decoded,
+        _ => checked,
+    }
+}
+
+/// The sequence of dictionary words, joined by spaces, exactly as long as `crack` and differing
+/// from it the least.
+fn lattice(crack: &[u8], dict: &BytesDictionary) -> Option<Vec<u8>> {
+    let n = crack.len();
+
+    // cheapest cost of covering the crack up to each position with whole words, and the start and
+    // word of the last word on the way there
+    let mut cost = vec![usize::MAX; n + 1];
+    let mut back = vec![(0, 0); n + 1];
+    cost[0] = 0;
+
+    for start in 0..n {
+        if cost[start] == usize::MAX {
+            continue;
+        }
+        for (index, word) in dict.words().enumerate() {
+            // dictionary words end in a space, and the last one may be cut short
+            let end = (start + word.len()).min(n);
+            let total = cost[start]
+                + crack[start..end]
+                    .iter()
+                    .zip(word)
+                    .map(|(&c, &w)| substitution_cost(c, w))
+                    .sum::<usize>();
+            if total < cost[end] {
+                cost[end] = total;
+                back[end] = (start, index);
+            }
+        }
+    }
+    if cost[n] == usize::MAX {
+        return None;
+    }
+
+    let mut decoded = vec![0; n];
+    let mut end = n;
+    while end > 0 {
+        let (start, index) = back[end];
+        decoded[start..end].copy_from_slice(&dict.word(index)[..end - start]);
+        end = start;
+    }
+    Some(decoded)
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::crack::spellcheck;
+    use crate::prelude::*;
+    use crate::utils::{bytes_to_str, str_to_bytes};
+
+    #[test]
+    fn decodes_words() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let bytes_dict = BytesDictionary::from_dict(&dict);
+
+        // cut off in the middle of a word
+        let plaintext = Generator::with_dict(&dict).generate_words(40);
+        let plaintext = plaintext[..plaintext.len() - 3].to_string();
+        let key = vec![21, 4, 0, 13, 7, 19, 2, 11, 16, 25, 6];
+        let ciphertext =
+            Encryptor::new(key.clone(), RepeatingKey, Rng::default()).encrypt(&plaintext);
+        let ciphertext = str_to_bytes(&ciphertext);
+        let plainbytes = str_to_bytes(&plaintext);
+
+        // the right crack, but with a third of the columns off
+        let mut wrong = plainbytes.clone();
+        for (column, off) in [(1, 5), (4, -3), (6, 1), (9, 12)] {
+            set_column(
+                &ciphertext,
+                key.len(),
+                column,
+                -key[column] + off,
+                &mut wrong,
+            );
+        }
+        let cracked = CrackResult::new(wrong, 1.0);
+
+        let decoded = decode_words(&ciphertext, key.len(), &cracked, &bytes_dict).unwrap();
+        assert_eq!(bytes_to_str(&decoded.plaintext), plaintext);
+        assert_eq!(decoded.confidence, 0.0);
+        assert_ne!(spellcheck(&cracked, &bytes_dict).plaintext, plainbytes);
+
+        // a right crack decodes to itself
+        let right = CrackResult::new(plainbytes.clone(), 1.0);
+        let decoded = decode_words(&ciphertext, key.len(), &right, &bytes_dict).unwrap();
+        assert_eq!(decoded.plaintext, plainbytes);
+        assert_eq!(decoded.confidence, 0.0);
+    }
+}
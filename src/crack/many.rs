@@ -0,0 +1,275 @@
+//! Cracking several ciphertexts encrypted under the same key and scheduler at once.
+//!
+//! Every message starts the key schedule over at its first character, so the characters at the
+//! same position of every message were shifted by the same key symbol. Lined up at their first
+//! characters, the messages pool into columns holding the characters of all of them, with far
+//! stronger frequencies than any one message has on its own. Keylengths are guessed from the
+//! pooled messages too (see [`guesses_pooled`]), so messages each too short to crack are cracked
+//! together.
+
+use super::crack_known_keylength::column_shifts;
+use crate::crack::{
+    guesses_pooled, key_from_fragments, prefer_decoded, spellcheck_with_feedback, CrackOptions,
+    CrackResult, CrackedPeriods, DictionaryStats,
+};
+use crate::dict::{BytesDictionary, Dictionary};
+use crate::utils::{Key, Shift, ALPHABET};
+
+use crossbeam_channel::unbounded;
+use tracing::{debug, info_span};
+
+/// Cracks of messages sharing a key, from [`crack_many`].
+#[derive(Clone, Debug)]
+pub struct ManyCrack {
+    /// Guessed plaintext of each message, in the order the ciphertexts were given
+    pub plaintexts: Vec<Vec<u8>>,
+    /// Key shared by the messages, that the columns were shifted back by at the keylength they
+    /// cracked best at, cut down to its shortest period
+    pub key: Key,
+    /// Fraction of the characters of every message spellchecking changed, like the confidence of
+    /// a [`CrackResult`]. Lower is better.
+    pub confidence: f64,
+}
+
+impl ManyCrack {
+    /// No plaintext for any of `messages`, less confident than any crack.
+    fn empty(messages: usize) -> Self {
+        Self {
+            plaintexts: vec![Vec::new(); messages],
+            key: Key::default(),
+            confidence: f64::INFINITY,
+        }
+    }
+}
+
+/// Crack ciphertexts made of words from the project dictionary, all encrypted under the same key
+/// and scheduler, spreading the keylength guesses across `opts.num_workers` threads. Messages too
+/// short to guess a keylength of together crack to no plaintexts and infinite confidence.
+///
+/// `opts.known_fragments` are positions in the first ciphertext.
+pub fn crack_many(ciphertexts: &[Vec<u8>], opts: &CrackOptions) -> ManyCrack {
+    let mut words = include_str!("../../words/default.txt").to_string();
+    let dict = Dictionary::from_string(&mut words);
+    let bytes_dict = BytesDictionary::from_dict(&dict);
+    let stats = DictionaryStats::project();
+
+    crack_many_with(ciphertexts, &bytes_dict, stats, opts)
+}
+
+/// Same as [`crack_many`], but with the given dictionary and its statistics.
+pub fn crack_many_with(
+    ciphertexts: &[Vec<u8>],
+    bytes_dict: &BytesDictionary,
+    stats: &DictionaryStats,
+    opts: &CrackOptions,
+) -> ManyCrack {
+    let messages: Vec<&[u8]> = ciphertexts.iter().map(Vec::as_slice).collect();
+    let total: usize = messages.iter().map(|message| message.len()).sum();
+    let _span = info_span!("crack_many", messages = messages.len(), total).entered();
+
+    // KEYLENGTH GUESSING, over every message at once
+    let mut keylen_guesses = Vec::new();
+    guesses_pooled(&messages, &mut keylen_guesses, &opts.keylength_scoring);
+    if let Some(max) = opts.max_keylengths {
+        keylen_guesses.truncate(max.max(1));
+    }
+    if keylen_guesses.is_empty() {
+        debug!("too short to guess a keylength");
+        return ManyCrack::empty(messages.len());
+    }
+
+    // known plaintext pins key symbols like it does for a single ciphertext, and is ignored if it
+    // contradicts every keylength
+    let first = messages.first().copied().unwrap_or_default();
+    let mut tasks: Vec<_> = keylen_guesses
+        .iter()
+        .filter_map(|&(keylen, _)| {
+            let pinned = key_from_fragments(first, keylen, &opts.known_fragments).ok()?;
+            Some((keylen, pinned))
+        })
+        .collect();
+    if tasks.is_empty() {
+        debug!("known fragments contradict every keylength, cracking without them");
+        tasks = keylen_guesses
+            .iter()
+            .map(|&(keylen, _)| (keylen, Vec::new()))
+            .collect();
+    }
+
+    // fan out: queue up one task per keylength guess
+    let (tasks_in, tasks_out) = unbounded();
+    for task in tasks {
+        tasks_in.send(task).unwrap();
+    }
+    drop(tasks_in);
+
+    let (results_in, results_out) = unbounded();
+    let periods = &CrackedPeriods::default();
+    let messages = &messages;
+    // cleanly cracked periods are recorded by the longest message, which cracks most reliably
+    let longest = (0..messages.len()).max_by_key(|&index| messages[index].len());
+
+    std::thread::scope(|scope| {
+        for _ in 0..opts.num_workers.max(1) {
+            let tasks = tasks_out.clone();
+            let results = results_in.clone();
+            scope.spawn(move || {
+                for (keylen, pinned) in tasks.iter() {
+                    if periods.covers(keylen) {
+                        debug!(keylen, "multiple of a cleanly cracked keylength, skipping");
+                        continue;
+                    }
+
+                    // count the characters of every message in each column
+                    let mut histograms = vec![[0_u32; 27]; keylen];
+                    for message in messages.iter() {
+                        for (index, &c) in message.iter().enumerate() {
+                            histograms[index % keylen][c as usize] += 1;
+                        }
+                    }
+                    let mut shifts = Vec::with_capacity(keylen);
+                    let confidence =
+                        column_shifts(&histograms, &stats.letters, &pinned, &mut shifts);
+
+                    // spellcheck each message on its own, with the columns shifted the same
+                    let mut changed = 0.0;
+                    let mut plaintexts = Vec::with_capacity(messages.len());
+                    for &message in messages.iter() {
+                        let plaintext = message
+                            .iter()
+                            .enumerate()
+                            .map(|(index, c)| c.shift(shifts[index % keylen]))
+                            .collect();
+                        let res = CrackResult::new(plaintext, confidence);
+                        let (res, correction) = spellcheck_with_feedback(
+                            message,
+                            keylen,
+                            &res,
+                            bytes_dict,
+                            stats,
+                            opts.segmentation_weights,
+                        );
+                        let checked = correction.checked;
+                        let checked = prefer_decoded(message, keylen, &res, checked, bytes_dict);
+                        changed += checked.confidence * message.len() as f64;
+                        plaintexts.push(checked.plaintext);
+                    }
+
+                    let confidence = changed / total.max(1) as f64;
+                    if let Some(index) = longest {
+                        let clean = confidence == 0.0;
+                        periods.record(messages[index], keylen, &plaintexts[index], clean);
+                    }
+                    let key: Key = shifts
+                        .iter()
+                        .map(|shift| (-shift).rem_euclid(ALPHABET.len() as i8))
+                        .collect();
+                    results.send((keylen, key, plaintexts, confidence)).unwrap();
+                }
+            });
+        }
+    });
+    drop(results_in);
+
+    // fan in: the crack changing the fewest characters wins, the better keylength guess on ties
+    let order = |keylen: usize| keylen_guesses.iter().position(|&(k, _)| k == keylen);
+    let best = results_out
+        .iter()
+        .min_by(|(a_len, _, _, a), (b_len, _, _, b)| {
+            a.total_cmp(b)
+                .then_with(|| order(*a_len).cmp(&order(*b_len)))
+        });
+    match best {
+        Some((_, key, plaintexts, confidence)) => ManyCrack {
+            key: Key(key.0[..key.minimal_period()].to_vec()),
+            plaintexts,
+            confidence,
+        },
+        None => ManyCrack::empty(messages.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::{PeriodicRand, RepeatingKey};
+    use crate::ciphers::{Cipher, Encryptor, KeySchedule};
+    use crate::crack::distributed::crack_parallel;
+    use crate::gen::Generator;
+    use crate::rng::Rng;
+    use crate::utils::{bytes_to_str, str_to_bytes};
+
+    /// encrypt one of several messages, going on with the random characters of the last
+    fn encrypt<K: KeySchedule + std::fmt::Debug>(
+        encryptor: &Encryptor<K>,
+        plaintext: &str,
+    ) -> Vec<u8> {
+        let ciphertext = encryptor.encrypt(plaintext);
+        encryptor.decrypt(&ciphertext);
+        str_to_bytes(&ciphertext)
+    }
+
+    #[test]
+    fn pooled_messages() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let mut gen = Generator::with_dict(&dict);
+        let plaintexts: Vec<String> = (0..16).map(|_| gen.generate_words(4)).collect();
+
+        let key = vec![4, 20, 11, 0, 17, 9, 2, 25, 13, 6, 19, 8, 1];
+        let encryptor = Encryptor::new(key.clone(), RepeatingKey, Rng::default());
+        let ciphertexts: Vec<Vec<u8>> = plaintexts
+            .iter()
+            .map(|plaintext| encrypt(&encryptor, plaintext))
+            .collect();
+        let opts = CrackOptions {
+            num_workers: 2,
+            ..Default::default()
+        };
+
+        // each message alone is too short to guess the keylength of
+        let alone = crack_parallel(&bytes_to_str(&ciphertexts[0]), &opts);
+        assert_ne!(bytes_to_str(&alone.plaintext), plaintexts[0]);
+
+        // pooled, every message cracks, and so does the key they share
+        let cracked = crack_many(&ciphertexts, &opts);
+        let cracked_plaintexts: Vec<String> =
+            cracked.plaintexts.iter().map(|p| bytes_to_str(p)).collect();
+        assert_eq!(cracked_plaintexts, plaintexts);
+        assert_eq!(cracked.key, key.into_iter().collect::<Key>());
+        assert_eq!(cracked.confidence, 0.0);
+
+        // random characters land at the same positions of every message, so those line up too
+        let sched = PeriodicRand {
+            period: 18,
+            start: 5,
+            overwrite: true,
+        };
+        let plaintexts: Vec<String> = (0..12).map(|_| gen.generate_words(12)).collect();
+        let key = [3, 14, 22, 7, 0, 18];
+        let encryptor = Encryptor::new(key.to_vec(), sched, Rng::default());
+        let ciphertexts: Vec<Vec<u8>> = plaintexts
+            .iter()
+            .map(|plaintext| encrypt(&encryptor, plaintext))
+            .collect();
+        let cracked = crack_many(&ciphertexts, &opts);
+        assert_eq!(cracked.plaintexts.len(), plaintexts.len());
+        let exact = cracked
+            .plaintexts
+            .iter()
+            .zip(&plaintexts)
+            .filter(|(cracked, plaintext)| bytes_to_str(cracked) == **plaintext)
+            .count();
+        assert!(exact >= plaintexts.len() / 2, "{} exact", exact);
+        // the column of random characters has no key symbol to crack
+        assert_eq!(cracked.key.len(), 18);
+        assert!((0..18)
+            .filter(|&column| column != 5)
+            .all(|column| cracked.key[column] == key[column % key.len()]));
+
+        // nothing to guess a keylength of
+        let cracked = crack_many(&[vec![1, 2], vec![]], &opts);
+        assert_eq!(cracked.plaintexts, vec![Vec::<u8>::new(); 2]);
+        assert!(cracked.confidence.is_infinite());
+    }
+}
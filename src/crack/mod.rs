@@ -3,34 +3,129 @@
 //! This module holds all code needed for cracking ciphertexts specifically encrypted using the
 //! project encryption model: [`Encryptor`][`crate::ciphers::Encryptor`]
 
+#[cfg(feature = "gen")]
+pub mod bench;
+mod boundaries;
+#[cfg(feature = "workers")]
+pub mod campaign;
+pub mod classify;
+pub mod columns;
 mod crack_known_keylength;
+#[cfg(all(feature = "serde", feature = "workers"))]
+pub mod dataset;
+pub mod depth;
+#[cfg(feature = "workers")]
+pub mod distributed;
+#[cfg(feature = "workers")]
+pub mod eval;
+pub mod explain;
+mod feedback;
+pub mod fingerprint;
+#[cfg(feature = "workers")]
+pub mod hypotheses;
+mod insertions;
+mod joint;
 mod keylength;
+mod lattice;
+#[cfg(feature = "workers")]
+mod many;
+pub mod ngram;
+mod options;
+pub mod pipeline;
+pub mod randomness;
+mod rank;
+pub mod refine;
+pub mod render;
+#[cfg(all(feature = "serde", feature = "workers"))]
+pub mod replay;
+mod scratch;
+#[cfg(all(feature = "serde", feature = "workers"))]
+pub mod server;
+mod short;
 mod spellcheck;
+mod stats;
+pub mod strategy;
+pub mod threads;
+mod verify;
+#[cfg(feature = "workers")]
 pub mod worker;
 
-pub use crack_known_keylength::{best_crack, crack, Frequencies};
-pub use keylength::guesses;
-pub use spellcheck::spellcheck;
+pub use boundaries::fix_word_boundaries;
+pub use crack_known_keylength::{
+    best_crack, best_crack_at_keylengths, crack, crack_all_keylengths_par, crack_keylengths,
+    crack_pinned, crack_with_scratch, key_from_fragments, Frequencies, LAPLACE_SMOOTHING,
+};
+pub use feedback::spellcheck_with_feedback;
+pub use insertions::{guess_periods, InsertionGrid, PeriodHypothesis};
+pub use joint::refine_adjacent_columns;
+pub use keylength::{
+    guesses, guesses_pooled, guesses_with, keylength_range, significance, CrackedPeriods,
+    KeylengthMethod, KeylengthScoring, MAX_GUESSED_KEYLENGTH,
+};
+pub use lattice::{decode_words, prefer_decoded};
+#[cfg(feature = "workers")]
+pub use many::{crack_many, crack_many_with, ManyCrack};
+pub use options::CrackOptions;
+pub use rank::{rank, RankedResults};
+pub use scratch::CrackScratch;
+pub use short::{crack_short, Tetragrams, MAX_SHORT_KEYLENGTH};
+pub use spellcheck::{
+    spellcheck, spellcheck_aligned, spellcheck_aligned_with_scratch, spellcheck_aligned_with_stats,
+    spellcheck_all, spellcheck_with_scratch, Correction, SegmentationWeights, SpellChecker,
+    WordCorrection, SPELLCHECK_CACHE,
+};
+pub use stats::{DictionaryStats, IMPOSSIBLE_BOUNDARY, WORD_LENGTH_WEIGHT};
+pub use verify::{verify, VerificationReport};
 
 mod cracker;
-pub use cracker::crack_single_ciphertext;
+pub use cracker::{crack_single_ciphertext, Cracker};
+
+use std::cmp::Ordering;
 
 /// Every cracking strategy produces some plaintext along with a confidence value. If we run two
 /// different strategies, both are successful (returning `Some(CrackResult)`), but the plaintexts
 /// don't match, we could try to guess the correct one based on the confidence value.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CrackResult {
     /// Guessed plaintext.
     pub plaintext: Vec<u8>,
     /// Confidence value associated with the plaintext on a scale of 0-100. Lower values correspond
     /// to **most confident** with 0.0 being the absolute most confident.
     ///
-    /// An example way to calculate confidence would be to take the number of characters in words
-    /// that needed to be "spell corrected" to a valid word in the dictionary, divided by the
-    /// length of plaintext. This would
+    /// [`spellcheck`] calculates it as the number of characters that needed to be "spell
+    /// corrected" to valid words in the dictionary, divided by the length of plaintext.
     pub confidence: f64,
+    /// Whether cracking ran out of time before every strategy was tried. A timed out result is
+    /// still the best one found before the deadline, but may be far from the real plaintext.
+    pub timed_out: bool,
+}
+
+impl CrackResult {
+    /// Create a CrackResult that did not run out of time.
+    pub fn new(plaintext: Vec<u8>, confidence: f64) -> Self {
+        Self {
+            plaintext,
+            confidence,
+            timed_out: false,
+        }
+    }
+
+    /// No plaintext at all, less confident than any crack. Ciphertexts too short to guess a
+    /// keylength of crack to this.
+    pub fn empty() -> Self {
+        Self::new(Vec::new(), f64::INFINITY)
+    }
+
+    /// Order of the confidences of two cracks, most confident first. NaN, from a crack too
+    /// degenerate to score, orders after every number, even infinity.
+    pub fn cmp_confidence(&self, other: &Self) -> Ordering {
+        let (a, b) = (self.confidence, other.confidence);
+        a.is_nan().cmp(&b.is_nan()).then_with(|| a.total_cmp(&b))
+    }
 }
 
+#[cfg(feature = "gen")]
 #[test]
 fn end_to_end() {
     use crate::ciphers::schedulers::*;
@@ -63,46 +158,19 @@ fn end_to_end() {
     println!("encrypted:\n{}\n", ciphertext);
     println!("plaintext:\n{}\n", plaintext);
 
-    let cipherbytes = str_to_bytes(&ciphertext);
-
-    //
-    // KEYLENGTH GUESSING
-    //
-
-    let mut keylen_guesses = Vec::new();
-    guesses(&cipherbytes, &mut keylen_guesses);
-
-    //
-    // CRACKING SLICES
-    //
-
-    let mut crack_results = Vec::new();
-
-    let baseline_freqs = Frequencies::from_dict(&dict);
-
-    for (keylen, _) in keylen_guesses {
-        let res = crack(&cipherbytes, keylen, &baseline_freqs);
-        crack_results.push(res);
-    }
-
-    let best = best_crack(&crack_results);
-    println!(
-        "best crack result from known keylength:\n{}\n",
-        bytes_to_str(&best.plaintext)
-    );
-
     //
-    // SPELL CHECKING
+    // CRACKING, every stage from keylength guessing to spell checking
     //
 
-    let mut spell_checked = Vec::new();
     let bytesdict = BytesDictionary::from_dict(&dict);
-
-    for crack in crack_results {
-        spell_checked.push(spellcheck(&crack, &bytesdict));
-    }
-
-    let best = best_crack(&spell_checked);
+    let stats = DictionaryStats::from_dict(&dict);
+    let ctx = pipeline::PipelineContext {
+        stats: &stats,
+        bytes_dict: &bytesdict,
+        deadline: None,
+        on_partial: None,
+    };
+    let best = pipeline::Pipeline::default().run(&ctx, &ciphertext);
 
     println!(
         "best crack result after spell check:\n{}\n",
@@ -0,0 +1,210 @@
+//! Module for [`NgramModel`], how likely every run of a few characters is.
+//!
+//! Each n-gram is indexed by its characters as a number in base of the alphabet's size, so one
+//! model works for any order up to quadgrams and any alphabet, not just the project's 27
+//! characters. [`Tetragrams`][`super::Tetragrams`] are built on it. Letter
+//! [`Frequencies`] and the bigrams of [`DictionaryStats`][`super::DictionaryStats`] keep their
+//! own fixed size tables for now, since column cracking and spellchecking look them up in their
+//! innermost loops; a unigram model can still be made from frequencies to compare against.
+
+use super::Frequencies;
+use crate::utils::ALPHABET;
+
+use anyhow::{bail, Result};
+
+/// How likely every n-gram of an alphabet is, smoothed so none are impossible unless asked.
+#[derive(Clone, Debug)]
+pub struct NgramModel {
+    /// Characters in each n-gram
+    order: usize,
+    /// Characters in the alphabet, numbered from 0
+    alphabet: usize,
+    /// Probability of each n-gram, indexed by its characters in base `alphabet`
+    probabilities: Box<[f32]>,
+    /// `-ln` of each probability
+    surprise: Box<[f32]>,
+}
+
+impl NgramModel {
+    /// Longest n-grams modelled. Quadgrams of the project's alphabet already take half a million
+    /// entries.
+    pub const MAX_ORDER: usize = 4;
+
+    /// The model of `counts` of every n-gram of `order` characters from an alphabet of `alphabet`
+    /// characters, with `alpha` added to every count first (Laplace smoothing). Without any counts
+    /// or smoothing, every n-gram is as likely.
+    ///
+    /// Fails if the order is 0 or more than [`MAX_ORDER`][`Self::MAX_ORDER`], the alphabet is
+    /// empty, or there isn't exactly one count for every n-gram.
+    pub fn from_counts(order: usize, alphabet: usize, counts: &[f64], alpha: f64) -> Result<Self> {
+        if order == 0 || order > Self::MAX_ORDER {
+            bail!("n-grams of {} characters aren't modelled", order);
+        }
+        if alphabet == 0 {
+            bail!("the alphabet has no characters");
+        }
+        let ngrams = alphabet.pow(order as u32);
+        if counts.len() != ngrams {
+            bail!("{} counts for {} n-grams", counts.len(), ngrams);
+        }
+
+        let total: f64 = counts.iter().sum::<f64>() + alpha * ngrams as f64;
+        let probabilities: Box<[f32]> = counts
+            .iter()
+            .map(|count| match total > 0.0 {
+                true => ((count + alpha) / total) as f32,
+                false => 1.0 / ngrams as f32,
+            })
+            .collect();
+        let surprise = counts
+            .iter()
+            .map(|count| match total > 0.0 {
+                true => -((count + alpha) / total).ln() as f32,
+                false => (ngrams as f32).ln(),
+            })
+            .collect();
+
+        Ok(Self {
+            order,
+            alphabet,
+            probabilities,
+            surprise,
+        })
+    }
+
+    /// The model of every run of `order` characters in `text`, with `alpha` added to every count.
+    /// Fails like [`from_counts`][`Self::from_counts`], or if `text` has a character outside the
+    /// alphabet.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::crack::ngram::NgramModel;
+    /// let model = NgramModel::from_text(2, 3, &[0, 1, 0, 1, 2], 0.0).unwrap();
+    /// assert_eq!(model.probability(&[0, 1]), 0.5);
+    /// assert_eq!(model.probability(&[2, 2]), 0.0);
+    /// ```
+    pub fn from_text(order: usize, alphabet: usize, text: &[u8], alpha: f64) -> Result<Self> {
+        if let Some(&c) = text.iter().find(|&&c| c as usize >= alphabet) {
+            bail!("{} is outside an alphabet of {} characters", c, alphabet);
+        }
+        let mut counts = vec![0.0; alphabet.checked_pow(order as u32).unwrap_or(0)];
+        if order > 0 && order <= Self::MAX_ORDER {
+            for ngram in text.windows(order) {
+                counts[index_in(alphabet, ngram)] += 1.0;
+            }
+        }
+        Self::from_counts(order, alphabet, &counts, alpha)
+    }
+
+    /// Characters in each n-gram.
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Characters in the alphabet.
+    pub fn alphabet(&self) -> usize {
+        self.alphabet
+    }
+
+    /// Probability of `ngram`, which has to be [`order`][`Self::order`] characters long.
+    pub fn probability(&self, ngram: &[u8]) -> f32 {
+        self.probabilities[self.index(ngram)]
+    }
+
+    /// `-ln` of the probability of `ngram`, infinite for n-grams that never happen.
+    pub fn surprise(&self, ngram: &[u8]) -> f32 {
+        self.surprise[self.index(ngram)]
+    }
+
+    /// How unlike the model `text` is, by the surprise of every run of
+    /// [`order`][`Self::order`] characters (its negative log-likelihood). Lower is better.
+    pub fn score(&self, text: &[u8]) -> f64 {
+        text.windows(self.order)
+            .map(|ngram| self.surprise(ngram) as f64)
+            .sum()
+    }
+
+    /// Sum of the differences between the probabilities of every n-gram, like
+    /// [`Frequencies::compare`]. Lower means closer. Models of a different order or alphabet are
+    /// as far apart as can be.
+    pub fn compare(&self, other: &Self) -> f32 {
+        if (self.order, self.alphabet) != (other.order, other.alphabet) {
+            return f32::INFINITY;
+        }
+        self.probabilities
+            .iter()
+            .zip(other.probabilities.iter())
+            .map(|(a, b)| (a - b).abs())
+            .sum()
+    }
+
+    fn index(&self, ngram: &[u8]) -> usize {
+        debug_assert_eq!(ngram.len(), self.order);
+        index_in(self.alphabet, ngram)
+    }
+}
+
+impl From<&Frequencies> for NgramModel {
+    /// The unigram model of the project alphabet with `freqs` as its probabilities.
+    fn from(freqs: &Frequencies) -> Self {
+        let counts: Vec<f64> = freqs.values().iter().map(|&v| v as f64).collect();
+        Self::from_counts(1, ALPHABET.len(), &counts, 0.0)
+            .expect("one frequency for every character")
+    }
+}
+
+/// Index of `ngram` among every n-gram of its length, as a number in base `alphabet`.
+fn index_in(alphabet: usize, ngram: &[u8]) -> usize {
+    ngram
+        .iter()
+        .fold(0, |index, &c| index * alphabet + c as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::str_to_bytes;
+
+    #[test]
+    fn orders() {
+        let text = str_to_bytes("the cat sat on the mat");
+        let len = ALPHABET.len();
+
+        // unigrams match the frequencies
+        let freqs = Frequencies::from_bytes(&text);
+        let unigrams = NgramModel::from_text(1, len, &text, 0.0).unwrap();
+        assert_eq!(unigrams.compare(&NgramModel::from(&freqs)), 0.0);
+
+        for order in 1..=NgramModel::MAX_ORDER {
+            let model = NgramModel::from_text(order, len, &text, 1.0).unwrap();
+            let total: f64 = model.probabilities.iter().map(|&p| p as f64).sum();
+            assert!(
+                (total - 1.0).abs() < 1e-3,
+                "order {} sums to {}",
+                order,
+                total
+            );
+
+            // smoothed, so nothing is impossible, but the text is likelier than its reverse
+            let reversed: Vec<u8> = text.iter().rev().copied().collect();
+            assert!(model.score(&reversed).is_finite());
+            if order > 1 {
+                assert!(model.score(&text) < model.score(&reversed));
+            }
+        }
+
+        // a smaller alphabet
+        let binary = NgramModel::from_text(3, 2, &[0, 1, 1, 0, 1, 1, 0], 0.0).unwrap();
+        assert_eq!(binary.probability(&[0, 1, 1]), 0.4);
+        assert_eq!(binary.surprise(&[0, 0, 0]), f32::INFINITY);
+        assert_eq!(binary.compare(&unigrams), f32::INFINITY);
+
+        assert!(NgramModel::from_text(0, len, &text, 0.0).is_err());
+        assert!(NgramModel::from_text(5, len, &text, 0.0).is_err());
+        assert!(NgramModel::from_text(2, 3, &text, 0.0).is_err());
+        assert!(NgramModel::from_counts(2, 3, &[1.0; 8], 0.0).is_err());
+
+        // nothing counted is uniform
+        let empty = NgramModel::from_text(2, 3, &[], 0.0).unwrap();
+        assert_eq!(empty.probability(&[2, 1]), 1.0 / 9.0);
+    }
+}
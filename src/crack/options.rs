@@ -0,0 +1,108 @@
+//! Module for [`CrackOptions`].
+
+use super::cracker::TEST1_THRESHOLD;
+use crate::crack::{threads, KeylengthScoring, SegmentationWeights};
+
+use std::time::Duration;
+
+/// Tunable settings for cracking a ciphertext, with
+/// [`Cracker::crack_with`][`crate::crack::Cracker::crack_with`] or
+/// [`crack_parallel`][`crate::crack::distributed::crack_parallel`].
+///
+/// `crack_parallel` only cracks dictionary words, so it doesn't check for known plaintexts.
+/// Options left out of a serialized `CrackOptions` keep their defaults.
+///
+/// ```
+/// # use one_team_pad_cipher_cracker::crack::CrackOptions;
+/// # use std::time::Duration;
+/// let opts = CrackOptions::default()
+///     .with_workers(2)
+///     .with_max_keylengths(10)
+///     .with_timeout(Duration::from_secs(5));
+/// assert_eq!(opts.num_workers, 2);
+/// assert_eq!(opts.max_keylengths, Some(10));
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CrackOptions {
+    /// Number of threads to split cracking across
+    pub num_workers: usize,
+    /// Only crack this many of the best keylength guesses. `None` cracks every guess.
+    pub max_keylengths: Option<usize>,
+    /// How keylength guesses are scored, and the longest keylength guessed
+    pub keylength_scoring: KeylengthScoring,
+    /// Plaintext known ahead of time, as positions and the bytes starting there. Keylengths the
+    /// fragments contradict are skipped, and the key symbols they fix are not searched for.
+    pub known_fragments: Vec<(usize, Vec<u8>)>,
+    /// Keylengths of at most [`MAX_SHORT_KEYLENGTH`][`crate::crack::MAX_SHORT_KEYLENGTH`] that
+    /// leave fewer ciphertext characters than this in each column are cracked with
+    /// [`crack_short`][`crate::crack::crack_short`] instead of column frequencies. `0` never does.
+    pub short_text_chars_per_column: usize,
+    /// How spellchecking weighs the words it splits cracks into
+    pub segmentation_weights: SegmentationWeights,
+    /// Cracks closer to a known plaintext than this, as the fraction of characters that differ,
+    /// stop cracking and give the known plaintext (Test 1)
+    pub known_plaintext_threshold: f64,
+    /// Time to return the best crack found so far after. `None` cracks until done.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for CrackOptions {
+    fn default() -> Self {
+        Self {
+            num_workers: threads::threads(),
+            max_keylengths: None,
+            keylength_scoring: KeylengthScoring::default(),
+            known_fragments: Vec::new(),
+            short_text_chars_per_column: 16,
+            segmentation_weights: SegmentationWeights::default(),
+            known_plaintext_threshold: TEST1_THRESHOLD,
+            timeout: None,
+        }
+    }
+}
+
+impl CrackOptions {
+    /// Split cracking across `num_workers` threads.
+    pub fn with_workers(mut self, num_workers: usize) -> Self {
+        self.num_workers = num_workers;
+        self
+    }
+
+    /// Only crack the `max` best keylength guesses.
+    pub fn with_max_keylengths(mut self, max: usize) -> Self {
+        self.max_keylengths = Some(max);
+        self
+    }
+
+    /// Score keylength guesses with `scoring`.
+    pub fn with_keylength_scoring(mut self, scoring: KeylengthScoring) -> Self {
+        self.keylength_scoring = scoring;
+        self
+    }
+
+    /// Crack knowing the plaintext at these positions.
+    pub fn with_known_fragments(mut self, fragments: Vec<(usize, Vec<u8>)>) -> Self {
+        self.known_fragments = fragments;
+        self
+    }
+
+    /// Spellcheck with `weights`.
+    pub fn with_segmentation_weights(mut self, weights: SegmentationWeights) -> Self {
+        self.segmentation_weights = weights;
+        self
+    }
+
+    /// Take cracks closer than `threshold` to a known plaintext to be it.
+    pub fn with_known_plaintext_threshold(mut self, threshold: f64) -> Self {
+        self.known_plaintext_threshold = threshold;
+        self
+    }
+
+    /// Return the best crack found so far once `timeout` has passed.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
@@ -0,0 +1,811 @@
+//! Module for [`Pipeline`], the stages a ciphertext of dictionary words (Test 2) is cracked in.
+//!
+//! The default pipeline runs every stage in order:
+//!
+//! 1. `normalize`: turn the ciphertext text into bytes ([`Normalize`])
+//! 2. `keylength`: guess keylengths, one candidate each ([`Keylengths`])
+//! 3. `crack`: crack each candidate column by column ([`ColumnCrack`])
+//! 4. `boundaries` and `columns`: refine each crack ([`WordBoundaries`], [`AdjacentColumns`])
+//! 5. `spellcheck`: spellcheck each crack, or decode it into words ([`Spellcheck`])
+//! 6. `verify`: check each spellchecked crack by re-encrypting it ([`Verify`])
+//! 7. `rank`: pick the best candidate ([`Rank`])
+//!
+//! Every stage implements [`Stage`], so a pipeline can leave stages out, replace them, or run them
+//! in another order, either built up in code or from a list of stage names. The `short` stage
+//! ([`ShortKeySearch`]) isn't in the default pipeline, and stands in for `keylength` and `crack`
+//! on ciphertexts too short to guess a keylength of. Neither is the `crack` stage
+//! [`PinnedCrack`], which stands in for [`ColumnCrack`] when some of the plaintext is known.
+//!
+//! Stages that work through the candidates one by one report a [`Partial`] each time they finish
+//! one, if the [`PipelineContext`] has somewhere to send it, so a slow crack can show its best
+//! crack so far instead of nothing until the end.
+
+use crate::crack::explain::Explanation;
+use crate::crack::keylength::MIN_KEYLENGTH;
+use crate::crack::{
+    crack, crack_pinned, crack_short, fix_word_boundaries, guesses_with, key_from_fragments,
+    prefer_decoded, rank, refine_adjacent_columns, spellcheck_with_feedback, CrackOptions,
+    CrackResult, CrackedPeriods, DictionaryStats, KeylengthScoring, SegmentationWeights,
+    Tetragrams, VerificationReport, WordCorrection, MAX_SHORT_KEYLENGTH,
+};
+use crate::dict::BytesDictionary;
+use crate::utils::{bytes_to_str, CharToNum};
+
+use anyhow::{anyhow, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tracing::{debug, debug_span, trace};
+
+/// Everything the stages of a [`Pipeline`] crack with.
+pub struct PipelineContext<'a> {
+    pub stats: &'a DictionaryStats,
+    pub bytes_dict: &'a BytesDictionary,
+    /// Time by which the pipeline must return. Stages leave the candidates they didn't get to as
+    /// they are once it passes.
+    pub deadline: Option<Instant>,
+    /// Called each time a stage finishes a candidate, from whichever thread finished it
+    pub on_partial: Option<OnPartial<'a>>,
+}
+
+/// Where a [`Pipeline`] reports a [`Partial`] each time a stage finishes a candidate.
+pub type OnPartial<'a> = &'a (dyn Fn(&Partial) + Sync);
+
+/// A candidate a stage has just finished, and the best crack so far.
+#[derive(Clone)]
+pub struct Partial {
+    /// Name of the stage that finished the candidate
+    pub stage: &'static str,
+    /// The candidate, as the stage left it
+    pub candidate: Candidate,
+    /// How many candidates the stage has finished, this one included
+    pub finished: usize,
+    /// How many candidates the stage has to finish
+    pub candidates: usize,
+    /// The best crack so far and its keylength, picked like [`Rank`] would pick it now
+    pub best: Option<(usize, CrackResult)>,
+    /// Whether the best crack changed when this candidate finished
+    pub improved: bool,
+}
+
+impl PipelineContext<'_> {
+    fn out_of_time(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// One keylength guess, and its crack so far.
+#[derive(Clone)]
+pub struct Candidate {
+    pub keylength: usize,
+    /// Confidence of the keylength guess, lower is better
+    pub keylength_confidence: f64,
+    /// Crack at this keylength, refined by every stage so far. `None` until it is cracked.
+    pub crack: Option<CrackResult>,
+    /// Spellchecked version of the crack. `None` until it is spellchecked.
+    pub checked: Option<CrackResult>,
+    /// Words spellchecking changed in the crack. Empty until it is spellchecked.
+    pub corrections: Vec<WordCorrection>,
+    /// How well the spellchecked crack re-encrypts to the ciphertext. `None` until it is
+    /// verified.
+    pub verification: Option<VerificationReport>,
+}
+
+impl Candidate {
+    /// A keylength guess that isn't cracked yet.
+    pub fn new(keylength: usize, keylength_confidence: f64) -> Self {
+        Self {
+            keylength,
+            keylength_confidence,
+            crack: None,
+            checked: None,
+            corrections: Vec::new(),
+            verification: None,
+        }
+    }
+}
+
+/// What the stages of a [`Pipeline`] pass along to each other.
+#[derive(Clone, Default)]
+pub struct PipelineState {
+    /// Ciphertext as it was given
+    pub text: String,
+    /// Ciphertext as bytes, once normalized
+    pub ciphertext: Vec<u8>,
+    /// Every keylength guess, best first
+    pub candidates: Vec<Candidate>,
+    /// Best crack, once ranked
+    pub best: Option<CrackResult>,
+    /// Whether a stage ran out of time before getting to every candidate
+    pub timed_out: bool,
+}
+
+impl PipelineState {
+    /// State for cracking `text`, which still needs normalizing.
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// State for cracking ciphertext that is already bytes, with a candidate for each of
+    /// `keylen_guesses`.
+    pub fn with_guesses(ciphertext: &[u8], keylen_guesses: &[(usize, f64)]) -> Self {
+        Self {
+            text: bytes_to_str(ciphertext),
+            ciphertext: ciphertext.to_vec(),
+            candidates: keylen_guesses
+                .iter()
+                .map(|&(keylength, confidence)| Candidate::new(keylength, confidence))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Run `f` on every candidate, in parallel with the `parallel` feature, until the deadline
+    /// passes. Each candidate `f` finishes is reported to the context as a [`Partial`] of `stage`.
+    fn each_candidate<F>(&mut self, ctx: &PipelineContext, stage: &'static str, f: F)
+    where
+        F: Fn(&[u8], &mut Candidate) + Send + Sync,
+    {
+        let out_of_time = AtomicBool::new(false);
+        let ciphertext = &self.ciphertext;
+        let candidates = self.candidates.len();
+        // every candidate as of its last report, and the best crack among them
+        let reported = ctx.on_partial.map(|on_partial| {
+            let best = best_of(self.candidates.iter());
+            (on_partial, Mutex::new((self.candidates.clone(), 0, best)))
+        });
+        let run = |(i, candidate): (usize, &mut Candidate)| {
+            if ctx.out_of_time() {
+                out_of_time.store(true, Ordering::Relaxed);
+                return;
+            }
+            f(ciphertext, candidate);
+
+            if let Some((on_partial, reported)) = &reported {
+                // reporting under the lock keeps the reports in order
+                let mut reported = reported.lock().unwrap_or_else(|err| err.into_inner());
+                let (latest, finished, best) = &mut *reported;
+                latest[i] = candidate.clone();
+                *finished += 1;
+                let now = best_of(latest.iter());
+                let improved = match (&now, &*best) {
+                    (Some((a_len, a)), Some((b_len, b))) => {
+                        a_len != b_len || a.plaintext != b.plaintext || a.confidence != b.confidence
+                    }
+                    (now, before) => now.is_some() != before.is_some(),
+                };
+                *best = now;
+                on_partial(&Partial {
+                    stage,
+                    candidate: candidate.clone(),
+                    finished: *finished,
+                    candidates,
+                    best: best.clone(),
+                    improved,
+                });
+            }
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            self.candidates.par_iter_mut().enumerate().for_each(run);
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        self.candidates.iter_mut().enumerate().for_each(run);
+
+        self.timed_out |= out_of_time.into_inner();
+    }
+}
+
+/// The crack [`Rank`] picks out of `candidates` and its keylength: the most confident spellchecked
+/// crack, or the most confident crack if none are spellchecked, or `None` if none are cracked.
+fn best_of<'a>(
+    candidates: impl Iterator<Item = &'a Candidate> + Clone,
+) -> Option<(usize, CrackResult)> {
+    let checked = rank(
+        candidates
+            .clone()
+            .filter_map(|candidate| Some((candidate.keylength, candidate.checked.clone()?))),
+    );
+    let ranked = match checked.is_empty() {
+        false => checked,
+        true => rank(
+            candidates
+                .filter_map(|candidate| Some((candidate.keylength, candidate.crack.clone()?))),
+        ),
+    };
+    ranked.iter().next().cloned()
+}
+
+/// One step of a [`Pipeline`].
+pub trait Stage: Send + Sync {
+    /// Name the stage is configured by
+    fn name(&self) -> &'static str;
+
+    /// Take `state` one step further.
+    fn run(&self, ctx: &PipelineContext, state: &mut PipelineState);
+}
+
+/// Turns the ciphertext text into bytes, lowercasing it and dropping characters outside the
+/// alphabet, like a trailing newline. Every pipeline cracking text starts with it.
+#[derive(Clone)]
+pub struct Normalize;
+
+impl Stage for Normalize {
+    fn name(&self) -> &'static str {
+        "normalize"
+    }
+
+    fn run(&self, _ctx: &PipelineContext, state: &mut PipelineState) {
+        state.ciphertext = normalize(&state.text);
+    }
+}
+
+/// `text` as bytes, lowercased and without the characters outside the alphabet.
+pub fn normalize(text: &str) -> Vec<u8> {
+    text.chars()
+        .filter_map(|c| c.to_ascii_lowercase().try_to_num())
+        .collect()
+}
+
+/// Guesses keylengths, replacing the candidates with one for each guess.
+#[derive(Clone, Default)]
+pub struct Keylengths {
+    pub scoring: KeylengthScoring,
+    /// Only keep this many of the best guesses. `None` keeps every guess.
+    pub max: Option<usize>,
+}
+
+impl Stage for Keylengths {
+    fn name(&self) -> &'static str {
+        "keylength"
+    }
+
+    fn run(&self, _ctx: &PipelineContext, state: &mut PipelineState) {
+        let mut keylen_guesses = Vec::new();
+        guesses_with(&state.ciphertext, &mut keylen_guesses, &self.scoring);
+        if let Some(max) = self.max {
+            keylen_guesses.truncate(max.max(1));
+        }
+        debug!(best = ?&keylen_guesses[..keylen_guesses.len().min(5)], "keylength guesses");
+
+        state.candidates = keylen_guesses
+            .iter()
+            .map(|&(keylength, confidence)| Candidate::new(keylength, confidence))
+            .collect();
+        state.best = None;
+    }
+}
+
+/// Cracks every candidate column by column with the dictionary's letter frequencies, scaling the
+/// confidence by the keylength guess's.
+#[derive(Clone)]
+pub struct ColumnCrack;
+
+impl Stage for ColumnCrack {
+    fn name(&self) -> &'static str {
+        "crack"
+    }
+
+    fn run(&self, ctx: &PipelineContext, state: &mut PipelineState) {
+        state.each_candidate(ctx, self.name(), |ciphertext, candidate| {
+            let mut res = crack(ciphertext, candidate.keylength, &ctx.stats.letters);
+            res.confidence *= candidate.keylength_confidence;
+            candidate.crack = Some(res);
+        });
+    }
+}
+
+/// Cracks every candidate like [`ColumnCrack`], keeping the key symbols that `fragments` of known
+/// plaintext fix at its keylength, and searching whole keys with [`crack_short`] for keylengths of
+/// at most [`MAX_SHORT_KEYLENGTH`] that leave fewer than `short_text_chars_per_column` characters
+/// in each column. Fragments that contradict a keylength are left out at that keylength. The
+/// tetragrams are counted from the dictionary the first time a short keylength is cracked.
+#[derive(Clone, Default)]
+pub struct PinnedCrack {
+    /// Plaintext known ahead of time, as positions and the bytes starting there
+    pub fragments: Vec<(usize, Vec<u8>)>,
+    /// `0` never searches whole keys
+    pub short_text_chars_per_column: usize,
+    tetragrams: Arc<OnceLock<Tetragrams>>,
+}
+
+impl PinnedCrack {
+    /// Crack with the known fragments and short text setting of `opts`.
+    pub fn new(opts: &CrackOptions) -> Self {
+        Self {
+            fragments: opts.known_fragments.clone(),
+            short_text_chars_per_column: opts.short_text_chars_per_column,
+            tetragrams: Arc::default(),
+        }
+    }
+}
+
+impl Stage for PinnedCrack {
+    fn name(&self) -> &'static str {
+        "crack"
+    }
+
+    fn run(&self, ctx: &PipelineContext, state: &mut PipelineState) {
+        state.each_candidate(ctx, self.name(), |ciphertext, candidate| {
+            let keylen = candidate.keylength;
+            let pinned =
+                key_from_fragments(ciphertext, keylen, &self.fragments).unwrap_or_default();
+            let letters = &ctx.stats.letters;
+            let is_short = keylen <= MAX_SHORT_KEYLENGTH
+                && ciphertext.len() < keylen * self.short_text_chars_per_column;
+            let mut res = if is_short {
+                let tetragrams = self
+                    .tetragrams
+                    .get_or_init(|| Tetragrams::from_bytes_dict(ctx.bytes_dict));
+                crack_short(ciphertext, keylen, letters, tetragrams, &pinned)
+            } else {
+                crack_pinned(ciphertext, keylen, letters, &pinned)
+            };
+            res.confidence *= candidate.keylength_confidence;
+            candidate.crack = Some(res);
+        });
+    }
+}
+
+/// Cracks ciphertexts too short for keylength guessing or column frequencies: a candidate for every
+/// keylength up to [`MAX_SHORT_KEYLENGTH`], each cracked whole with [`crack_short`]. Every crack
+/// is as confident as the tetragram score of its plaintext per character, so keylengths compare
+/// by how much their plaintexts look like words. The tetragrams are counted from the dictionary
+/// the first time the stage runs.
+#[derive(Clone, Default)]
+pub struct ShortKeySearch {
+    tetragrams: Arc<OnceLock<Tetragrams>>,
+}
+
+impl Stage for ShortKeySearch {
+    fn name(&self) -> &'static str {
+        "short"
+    }
+
+    fn run(&self, ctx: &PipelineContext, state: &mut PipelineState) {
+        let tetragrams = self
+            .tetragrams
+            .get_or_init(|| Tetragrams::from_bytes_dict(ctx.bytes_dict));
+
+        state.candidates = (MIN_KEYLENGTH..=MAX_SHORT_KEYLENGTH)
+            .take_while(|&keylength| keylength <= state.ciphertext.len())
+            .map(|keylength| Candidate::new(keylength, 1.0))
+            .collect();
+        state.best = None;
+
+        state.each_candidate(ctx, self.name(), |ciphertext, candidate| {
+            let letters = &ctx.stats.letters;
+            let mut res = crack_short(ciphertext, candidate.keylength, letters, tetragrams, &[]);
+            res.confidence = tetragrams.score(&res.plaintext) / res.plaintext.len().max(1) as f64;
+            candidate.crack = Some(res);
+        });
+    }
+}
+
+/// Fixes the word boundaries of every crack with [`fix_word_boundaries`].
+#[derive(Clone)]
+pub struct WordBoundaries;
+
+impl Stage for WordBoundaries {
+    fn name(&self) -> &'static str {
+        "boundaries"
+    }
+
+    fn run(&self, ctx: &PipelineContext, state: &mut PipelineState) {
+        state.each_candidate(ctx, self.name(), |ciphertext, candidate| {
+            if let Some(res) = &candidate.crack {
+                let res = fix_word_boundaries(ciphertext, candidate.keylength, res, ctx.stats);
+                candidate.crack = Some(res);
+            }
+        });
+    }
+}
+
+/// Fixes neighboring columns of every crack together with [`refine_adjacent_columns`].
+#[derive(Clone)]
+pub struct AdjacentColumns;
+
+impl Stage for AdjacentColumns {
+    fn name(&self) -> &'static str {
+        "columns"
+    }
+
+    fn run(&self, ctx: &PipelineContext, state: &mut PipelineState) {
+        state.each_candidate(ctx, self.name(), |ciphertext, candidate| {
+            if let Some(res) = &candidate.crack {
+                let res = refine_adjacent_columns(ciphertext, candidate.keylength, res, ctx.stats);
+                candidate.crack = Some(res);
+            }
+        });
+    }
+}
+
+/// Spellchecks every crack, feeding corrections back into the key, or decodes it into words if
+/// that needs fewer corrections. Once a keylength cracks cleanly, its multiples are skipped, since
+/// they would only crack the same key repeated.
+#[derive(Clone, Default)]
+pub struct Spellcheck {
+    pub weights: SegmentationWeights,
+}
+
+impl Stage for Spellcheck {
+    fn name(&self) -> &'static str {
+        "spellcheck"
+    }
+
+    fn run(&self, ctx: &PipelineContext, state: &mut PipelineState) {
+        let periods = CrackedPeriods::default();
+        state.each_candidate(ctx, self.name(), |ciphertext, candidate| {
+            let keylen = candidate.keylength;
+            if periods.covers(keylen) {
+                trace!(keylen, "multiple of a cleanly cracked keylength, skipping");
+                return;
+            }
+            if let Some(res) = &candidate.crack {
+                let (res, correction) = spellcheck_with_feedback(
+                    ciphertext,
+                    keylen,
+                    res,
+                    ctx.bytes_dict,
+                    ctx.stats,
+                    self.weights,
+                );
+                let checked =
+                    prefer_decoded(ciphertext, keylen, &res, correction.checked, ctx.bytes_dict);
+                let clean = checked.plaintext == res.plaintext;
+                periods.record(ciphertext, keylen, &res.plaintext, clean);
+                trace!(
+                    keylen,
+                    confidence = checked.confidence,
+                    "cracked and spellchecked"
+                );
+                candidate.crack = Some(res);
+                candidate.checked = Some(checked);
+                candidate.corrections = correction.corrections;
+            }
+        });
+    }
+}
+
+/// Re-encrypts spellchecked cracks with the key and scheduler they look encrypted with at their
+/// keylength. When one of them re-encrypts to at least `threshold` of the ciphertext, the
+/// ciphertext was probably encrypted with a repeating key, and every verified crack is made less
+/// confident by how much less of it re-encrypts than the best, times `weight`. Spellchecking can
+/// bend a wrong crack into words no key would decrypt to, and those then rank below the crack that
+/// fits. Other schedulers don't repeat the key, so even their right cracks don't re-encrypt, and
+/// their cracks are left as they are.
+///
+/// Verifying a crack takes longer than spellchecking it, so the most confident cracks are verified
+/// first, and the rest are left unverified once even re-encrypting exactly couldn't make them win.
+#[derive(Clone)]
+pub struct Verify {
+    pub threshold: f64,
+    pub weight: f64,
+}
+
+impl Default for Verify {
+    /// A character that doesn't re-encrypt counts as much as one spellchecking changed.
+    fn default() -> Self {
+        Self {
+            threshold: 0.9,
+            weight: 1.0,
+        }
+    }
+}
+
+impl Stage for Verify {
+    fn name(&self) -> &'static str {
+        "verify"
+    }
+
+    fn run(&self, ctx: &PipelineContext, state: &mut PipelineState) {
+        let mut order: Vec<(f64, usize)> = state
+            .candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, candidate)| Some((candidate.checked.as_ref()?.confidence, i)))
+            .collect();
+        order.sort_by(|(a, i), (b, j)| a.total_cmp(b).then(i.cmp(j)));
+
+        // cracks rank by confidence less the weighted fraction that re-encrypts
+        let mut best_score = f64::INFINITY;
+        let mut best_fraction: f64 = 0.0;
+        for (confidence, i) in order {
+            if confidence - self.weight >= best_score {
+                break;
+            }
+            if ctx.out_of_time() {
+                state.timed_out = true;
+                break;
+            }
+            let candidate = &mut state.candidates[i];
+            let keylen = candidate.keylength;
+            let explanation = match &candidate.checked {
+                Some(checked) => {
+                    Explanation::at_keylength(&state.ciphertext, &checked.plaintext, keylen)
+                }
+                None => continue,
+            };
+            let fraction = explanation.verification.match_fraction();
+            trace!(keylen, fraction, scheduler = %explanation.scheduler, "verified");
+            best_score = best_score.min(confidence - self.weight * fraction);
+            best_fraction = best_fraction.max(fraction);
+            candidate.verification = Some(explanation.verification);
+        }
+
+        if best_fraction < self.threshold {
+            return;
+        }
+        for candidate in state.candidates.iter_mut() {
+            let fraction = candidate.verification.map(|v| v.match_fraction());
+            if let (Some(fraction), Some(checked)) = (fraction, &mut candidate.checked) {
+                checked.confidence += (best_fraction - fraction) * self.weight;
+            }
+        }
+    }
+}
+
+/// Picks the most confident spellchecked crack, the one at the shorter keylength on ties (see
+/// [`rank`]). If none were spellchecked, it picks the most confident crack,
+/// and if none were cracked, it cracks the best keylength guess without
+/// refining it. Without any keylength guesses, the crack is [empty][`CrackResult::empty`].
+#[derive(Clone)]
+pub struct Rank;
+
+impl Stage for Rank {
+    fn name(&self) -> &'static str {
+        "rank"
+    }
+
+    fn run(&self, ctx: &PipelineContext, state: &mut PipelineState) {
+        let mut best = if let Some((_, best)) = best_of(state.candidates.iter()) {
+            best
+        } else if let Some(candidate) = state.candidates.first() {
+            // out of time before anything was cracked, and cracking is fast
+            crack(&state.ciphertext, candidate.keylength, &ctx.stats.letters)
+        } else {
+            // too short to guess a keylength, so nothing to crack
+            CrackResult::empty()
+        };
+        best.timed_out = state.timed_out;
+        debug!(confidence = best.confidence, "best dictionary crack");
+        state.best = Some(best);
+    }
+}
+
+/// The stages of cracking a ciphertext of dictionary words, run one after another over every
+/// keylength candidate. Clones share their stages.
+#[derive(Clone)]
+pub struct Pipeline {
+    stages: Vec<Arc<dyn Stage>>,
+}
+
+impl Default for Pipeline {
+    /// Every stage, in the order the module lists them.
+    fn default() -> Self {
+        Self::empty()
+            .then(Normalize)
+            .then(Keylengths::default())
+            .then(ColumnCrack)
+            .then(WordBoundaries)
+            .then(AdjacentColumns)
+            .then(Spellcheck::default())
+            .then(Verify::default())
+            .then(Rank)
+    }
+}
+
+impl Pipeline {
+    /// A pipeline without any stages.
+    pub fn empty() -> Self {
+        Self { stages: Vec::new() }
+    }
+
+    /// The pipeline running the default stage of each name, in the order given.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::crack::pipeline::Pipeline;
+    /// let pipeline = Pipeline::from_names(["normalize", "keylength", "crack", "rank"]).unwrap();
+    /// assert_eq!(pipeline.names(), ["normalize", "keylength", "crack", "rank"]);
+    /// assert!(Pipeline::from_names(["normalise"]).is_err());
+    /// ```
+    pub fn from_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let mut pipeline = Self::empty();
+        for name in names {
+            pipeline.stages.push(match name {
+                "normalize" => Arc::new(Normalize),
+                "keylength" => Arc::new(Keylengths::default()),
+                "crack" => Arc::new(ColumnCrack),
+                "short" => Arc::new(ShortKeySearch::default()),
+                "boundaries" => Arc::new(WordBoundaries),
+                "columns" => Arc::new(AdjacentColumns),
+                "spellcheck" => Arc::new(Spellcheck::default()),
+                "verify" => Arc::new(Verify::default()),
+                "rank" => Arc::new(Rank),
+                _ => return Err(anyhow!("no pipeline stage is named {:?}", name)),
+            });
+        }
+        Ok(pipeline)
+    }
+
+    /// Run `stage` after every other stage.
+    pub fn then(mut self, stage: impl Stage + 'static) -> Self {
+        self.stages.push(Arc::new(stage));
+        self
+    }
+
+    /// Leave out every stage named `name`.
+    pub fn without(mut self, name: &str) -> Self {
+        self.stages.retain(|stage| stage.name() != name);
+        self
+    }
+
+    /// Run `stage` instead of every stage with the same name.
+    pub fn replace(mut self, stage: impl Stage + Clone + 'static) -> Self {
+        for existing in self.stages.iter_mut() {
+            if existing.name() == stage.name() {
+                *existing = Arc::new(stage.clone());
+            }
+        }
+        self
+    }
+
+    /// Configure the `keylength`, `crack` and `spellcheck` stages as `opts` says, like
+    /// [`replace`][`Self::replace`] with stages built from them. The `crack` stage becomes a
+    /// [`PinnedCrack`], for the known fragments and short text setting.
+    pub fn with_options(self, opts: &CrackOptions) -> Self {
+        self.replace(Keylengths {
+            scoring: opts.keylength_scoring,
+            max: opts.max_keylengths,
+        })
+        .replace(PinnedCrack::new(opts))
+        .replace(Spellcheck {
+            weights: opts.segmentation_weights,
+        })
+    }
+
+    /// Names of the stages, in the order they run.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.stages.iter().map(|stage| stage.name()).collect()
+    }
+
+    /// Crack `ciphertext`, returning the best crack found, or an empty one without a `rank` stage.
+    pub fn run(&self, ctx: &PipelineContext, ciphertext: &str) -> CrackResult {
+        let mut state = PipelineState::new(ciphertext);
+        self.run_state(ctx, &mut state);
+        let timed_out = state.timed_out;
+        state.best.unwrap_or(CrackResult {
+            timed_out,
+            ..CrackResult::empty()
+        })
+    }
+
+    /// Run every stage on `state`, in order.
+    pub fn run_state(&self, ctx: &PipelineContext, state: &mut PipelineState) {
+        for stage in &self.stages {
+            debug_span!("stage", name = stage.name()).in_scope(|| stage.run(ctx, state));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::prelude::*;
+
+    #[test]
+    fn stages() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let bytes_dict = BytesDictionary::from_dict(&dict);
+        let stats = DictionaryStats::from_dict(&dict);
+        let ctx = PipelineContext {
+            stats: &stats,
+            bytes_dict: &bytes_dict,
+            deadline: None,
+            on_partial: None,
+        };
+
+        let plaintext = Generator::with_dict(&dict).generate_words(60);
+        let encryptor = Encryptor::new(vec![7, 1, 22, 15, 3, 10], RepeatingKey, Rng::default());
+        let ciphertext = encryptor.encrypt(&plaintext);
+
+        // upper case and a trailing newline are normalized away
+        let text = format!("{}\n", ciphertext.to_uppercase());
+        let result = Pipeline::default().run(&ctx, &text);
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
+        assert!(!result.timed_out);
+
+        // the right crack re-encrypts exactly, so verifying it doesn't make it less confident
+        let unverified = Pipeline::default().without("verify").run(&ctx, &text);
+        assert_eq!(result.confidence, unverified.confidence);
+
+        // without spellchecking, the ranked crack is one of the unchecked cracks
+        let pipeline = Pipeline::default().without("spellcheck");
+        assert!(!pipeline.names().contains(&"spellcheck"));
+        let mut state = PipelineState::new(&ciphertext);
+        pipeline.run_state(&ctx, &mut state);
+        assert!(state.candidates.iter().all(|c| c.checked.is_none()));
+        assert_eq!(state.ciphertext.len(), plaintext.len());
+        assert!(state.best.is_some());
+
+        // a replaced stage keeps its place
+        let pipeline = Pipeline::default().replace(Keylengths {
+            max: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(pipeline.names(), Pipeline::default().names());
+        let mut state = PipelineState::new(&ciphertext);
+        pipeline.run_state(&ctx, &mut state);
+        assert_eq!(state.candidates.len(), 2);
+
+        // out of time before cracking anything still returns a crack of the right length
+        let ctx = PipelineContext {
+            deadline: Some(Instant::now()),
+            ..ctx
+        };
+        let result = Pipeline::default().run(&ctx, &ciphertext);
+        assert!(result.timed_out);
+        assert_eq!(result.plaintext.len(), plaintext.len());
+
+        // too short to guess a keylength of
+        let ctx = PipelineContext {
+            deadline: None,
+            ..ctx
+        };
+        let result = Pipeline::default().run(&ctx, "abc");
+        assert!(result.plaintext.is_empty());
+
+        // nothing to rank without a rank stage
+        let result = Pipeline::default().without("rank").run(&ctx, &ciphertext);
+        assert!(result.plaintext.is_empty());
+    }
+
+    #[test]
+    fn partials() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let bytes_dict = BytesDictionary::from_dict(&dict);
+        let stats = DictionaryStats::from_dict(&dict);
+
+        let plaintext = Generator::with_dict(&dict).generate_words(60);
+        let encryptor = Encryptor::new(vec![4, 8, 15, 16, 23], RepeatingKey, Rng::default());
+        let ciphertext = encryptor.encrypt(&plaintext);
+
+        let partials = Mutex::new(Vec::new());
+        let on_partial = |partial: &Partial| partials.lock().unwrap().push(partial.clone());
+        let ctx = PipelineContext {
+            stats: &stats,
+            bytes_dict: &bytes_dict,
+            deadline: None,
+            on_partial: Some(&on_partial),
+        };
+        let result = Pipeline::default().run(&ctx, &ciphertext);
+        let partials = partials.into_inner().unwrap();
+
+        // every stage that goes candidate by candidate reports each of them, in stage order
+        let stages: Vec<&str> = partials.iter().map(|partial| partial.stage).collect();
+        let mut order = stages.clone();
+        order.dedup();
+        assert_eq!(order, ["crack", "boundaries", "columns", "spellcheck"]);
+        for stage in order {
+            let reported: Vec<&Partial> = partials.iter().filter(|p| p.stage == stage).collect();
+            let finished: Vec<usize> = reported.iter().map(|p| p.finished).collect();
+            assert_eq!(finished, (1..=reported[0].candidates).collect::<Vec<_>>());
+        }
+
+        // the first crack is an improvement on nothing, and the last best is the final crack
+        assert!(partials[0].improved);
+        let (_, best) = partials.last().unwrap().best.clone().unwrap();
+        assert_eq!(best.plaintext, result.plaintext);
+        assert_eq!(bytes_to_str(&best.plaintext), plaintext);
+    }
+}
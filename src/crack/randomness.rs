@@ -0,0 +1,217 @@
+//! Module for checking how random a sequence of symbols looks.
+//!
+//! A good scheduler leaves a ciphertext that looks like noise, and so should the random characters
+//! it inserts. A key that repeats too soon leaves the letter frequencies of the plaintext showing
+//! through, which a chi-squared test against uniform symbols picks up, and a weak source of random
+//! characters tends to clump or alternate, which a runs test picks up.
+//!
+//! Both tests give a p-value: the chance of a truly random sequence looking at least as far off.
+
+use crate::crack::keylength::p_value;
+use crate::utils::ALPHABET;
+
+use std::fmt;
+
+/// Randomness checks of a sequence of symbols of the [`ALPHABET`], from [`Randomness::of`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Randomness {
+    /// Number of symbols checked
+    pub symbols: usize,
+    /// Shannon entropy of the symbol counts, in bits per symbol. Uniform symbols have about
+    /// `log2(27)`, 4.75 bits.
+    pub entropy: f64,
+    /// Chi-squared statistic of the symbol counts against uniform counts
+    pub chi_squared: f64,
+    /// Chance of uniform symbols giving at least [`chi_squared`][`Self::chi_squared`]
+    pub chi_squared_p: f64,
+    /// Number of runs of symbols in the low half of the alphabet (`a` to `m`) or the high half
+    pub runs: usize,
+    /// Standard score of [`runs`][`Self::runs`] against the runs expected of independent symbols
+    pub runs_z: f64,
+    /// Chance of independent symbols giving at least as many or as few runs, either way
+    pub runs_p: f64,
+}
+
+impl Randomness {
+    /// Check the symbols of `text`.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::crack::randomness::Randomness;
+    /// # use one_team_pad_cipher_cracker::utils::str_to_bytes;
+    /// let text = str_to_bytes(&"the quick brown fox jumps over the lazy dog ".repeat(20));
+    /// assert!(!Randomness::of(&text).looks_random(0.001));
+    /// ```
+    pub fn of(text: &[u8]) -> Self {
+        let symbols = text.len();
+        let mut counts = [0usize; ALPHABET.len()];
+        for &c in text {
+            counts[c as usize] += 1;
+        }
+
+        let n = symbols as f64;
+        let entropy = counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / n;
+                -p * p.log2()
+            })
+            .sum();
+
+        let expected = n / ALPHABET.len() as f64;
+        let chi_squared = match symbols {
+            0 => 0.0,
+            _ => counts
+                .iter()
+                .map(|&count| (count as f64 - expected).powi(2) / expected)
+                .sum(),
+        };
+        let chi_squared_p = chi_squared_p_value(chi_squared, ALPHABET.len() - 1);
+
+        let (runs, runs_z) = runs_test(text);
+        let runs_p = (2.0 * p_value(runs_z.abs())).min(1.0);
+
+        Self {
+            symbols,
+            entropy,
+            chi_squared,
+            chi_squared_p,
+            runs,
+            runs_z,
+            runs_p,
+        }
+    }
+
+    /// Whether neither test rejects the symbols as random at significance `alpha`.
+    pub fn looks_random(&self, alpha: f64) -> bool {
+        self.chi_squared_p >= alpha && self.runs_p >= alpha
+    }
+}
+
+impl fmt::Display for Randomness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "symbols      {}", self.symbols)?;
+        writeln!(
+            f,
+            "entropy      {:.3} bits per symbol (uniform is {:.3})",
+            self.entropy,
+            (ALPHABET.len() as f64).log2()
+        )?;
+        writeln!(
+            f,
+            "chi-squared  {:.1} on {} degrees of freedom, p = {:.4}",
+            self.chi_squared,
+            ALPHABET.len() - 1,
+            self.chi_squared_p
+        )?;
+        writeln!(
+            f,
+            "runs         {} (z = {:.2}), p = {:.4}",
+            self.runs, self.runs_z, self.runs_p
+        )
+    }
+}
+
+/// Chance of a chi-squared variable with `degrees` degrees of freedom being at least `x`, from
+/// the Wilson-Hilferty approximation, which is close for a few degrees of freedom or more.
+fn chi_squared_p_value(x: f64, degrees: usize) -> f64 {
+    let k = degrees as f64;
+    let spread = 2.0 / (9.0 * k);
+    let z = ((x / k).cbrt() - (1.0 - spread)) / spread.sqrt();
+    p_value(z)
+}
+
+/// Wald-Wolfowitz runs test of `text`, split into symbols in the low and high half of the
+/// alphabet. Returns the number of runs and its standard score, which is 0.0 when only one half
+/// shows up.
+fn runs_test(text: &[u8]) -> (usize, f64) {
+    let half = (ALPHABET.len() / 2) as u8;
+    let runs = match text.first() {
+        None => 0,
+        Some(_) => {
+            1 + text
+                .windows(2)
+                .filter(|pair| (pair[0] < half) != (pair[1] < half))
+                .count()
+        }
+    };
+
+    let low = text.iter().filter(|&&c| c < half).count() as f64;
+    let high = text.len() as f64 - low;
+    let n = low + high;
+    if low == 0.0 || high == 0.0 {
+        return (runs, 0.0);
+    }
+    let mean = 2.0 * low * high / n + 1.0;
+    let variance = 2.0 * low * high * (2.0 * low * high - n) / (n * n * (n - 1.0));
+    if variance <= 0.0 {
+        return (runs, 0.0);
+    }
+    (runs, (runs as f64 - mean) / variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::ciphers::{Cipher, Encryptor};
+    use crate::rng::Rng;
+    use crate::utils::str_to_bytes;
+
+    #[test]
+    fn rng_output() {
+        for seed in 0..8 {
+            let mut rng = Rng::from_seed(seed);
+            let symbols: Vec<u8> = (0..10_000)
+                .map(|_| rng.gen_range(0..ALPHABET.len()) as u8)
+                .collect();
+            let randomness = Randomness::of(&symbols);
+            assert!(
+                randomness.looks_random(0.001),
+                "seed {}\n{}",
+                seed,
+                randomness
+            );
+            assert!(randomness.entropy > 4.7);
+        }
+    }
+
+    #[test]
+    fn weak_ciphertexts() {
+        let plaintext = "it was the best of times it was the worst of times ".repeat(40);
+
+        // a short repeating key leaves the letter frequencies showing through
+        let weak = Encryptor::new(vec![3, 1, 4], RepeatingKey, Rng::default());
+        let ciphertext = str_to_bytes(&weak.encrypt(&plaintext));
+        let randomness = Randomness::of(&ciphertext);
+        assert!(randomness.chi_squared_p < 1e-6, "{}", randomness);
+
+        // a key as long as the plaintext doesn't
+        let mut rng = Rng::from_seed(3);
+        let key: Vec<_> = (0..plaintext.len())
+            .map(|_| rng.gen_range(0..ALPHABET.len()) as i8)
+            .collect();
+        let pad = Encryptor::new(key, RepeatingKey, Rng::default());
+        let ciphertext = str_to_bytes(&pad.encrypt(&plaintext));
+        assert!(Randomness::of(&ciphertext).looks_random(0.001));
+    }
+
+    #[test]
+    fn runs() {
+        // strictly alternating halves have as many runs as symbols
+        let alternating: Vec<u8> = (0..1000).map(|i| if i % 2 == 0 { 0 } else { 20 }).collect();
+        let randomness = Randomness::of(&alternating);
+        assert_eq!(randomness.runs, 1000);
+        assert!(randomness.runs_z > 0.0 && randomness.runs_p < 1e-6);
+
+        // one long clump of each half has two
+        let clumped: Vec<u8> = (0..1000).map(|i| if i < 500 { 0 } else { 20 }).collect();
+        let randomness = Randomness::of(&clumped);
+        assert_eq!(randomness.runs, 2);
+        assert!(randomness.runs_z < 0.0 && randomness.runs_p < 1e-6);
+
+        let empty = Randomness::of(&[]);
+        assert_eq!(empty.runs, 0);
+        assert!(empty.looks_random(0.99));
+    }
+}
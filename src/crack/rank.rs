@@ -0,0 +1,167 @@
+//! Ranking cracks made at several keylengths.
+//!
+//! Cracks rank by [`CrackResult::cmp_confidence`], most confident first, then by keylength,
+//! shortest first, then by plaintext, so the same cracks rank the same whatever order they were
+//! made in. A key cracked at a multiple of its keylength usually gives the same plaintext at each
+//! multiple, which [`RankedResults::dedup`] keeps only the best of.
+
+use crate::crack::CrackResult;
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// Cracks paired with the keylength each was cracked at, best first, from [`rank`].
+#[derive(Clone, Default)]
+pub struct RankedResults {
+    results: Vec<(usize, CrackResult)>,
+}
+
+/// Rank cracks paired with the keylength each was cracked at.
+///
+/// ```
+/// # use one_team_pad_cipher_cracker::crack::{rank, CrackResult};
+/// let ranked = rank(vec![
+///     (6, CrackResult::new(vec![1, 2], 0.25)),
+///     (3, CrackResult::new(vec![1, 2], 0.25)),
+///     (4, CrackResult::new(vec![5], 0.5)),
+///     (2, CrackResult::new(vec![9], f64::NAN)),
+/// ]);
+/// assert_eq!(ranked.keylengths().collect::<Vec<_>>(), [3, 6, 4, 2]);
+///
+/// // the crack at 6 repeats the one at 3, and NaN is never under a threshold
+/// let ranked = ranked.dedup().below(1.0);
+/// assert_eq!(ranked.keylengths().collect::<Vec<_>>(), [3, 4]);
+/// ```
+pub fn rank<I>(results: I) -> RankedResults
+where
+    I: IntoIterator<Item = (usize, CrackResult)>,
+{
+    let mut results: Vec<_> = results.into_iter().collect();
+    results.sort_by(cmp_ranked);
+    RankedResults { results }
+}
+
+/// Order of two cracks paired with their keylengths, better first.
+pub(crate) fn cmp_ranked(
+    (a_len, a): &(usize, CrackResult),
+    (b_len, b): &(usize, CrackResult),
+) -> Ordering {
+    a.cmp_confidence(b)
+        .then_with(|| a_len.cmp(b_len))
+        .then_with(|| a.plaintext.cmp(&b.plaintext))
+}
+
+impl RankedResults {
+    /// Best crack, or `None` if there are none.
+    pub fn best(&self) -> Option<&CrackResult> {
+        self.results.first().map(|(_, res)| res)
+    }
+
+    /// Cracks and their keylengths, best first.
+    pub fn iter(&self) -> std::slice::Iter<'_, (usize, CrackResult)> {
+        self.results.iter()
+    }
+
+    /// Keylengths of the cracks, best first.
+    pub fn keylengths(&self) -> impl Iterator<Item = usize> + '_ {
+        self.results.iter().map(|&(keylen, _)| keylen)
+    }
+
+    /// Only the best crack of each plaintext.
+    pub fn dedup(mut self) -> Self {
+        let mut seen = HashSet::new();
+        self.results
+            .retain(|(_, res)| seen.insert(res.plaintext.clone()));
+        self
+    }
+
+    /// Only cracks with a confidence under `threshold`. NaN confidences never are.
+    pub fn below(mut self, threshold: f64) -> Self {
+        self.results.retain(|(_, res)| res.confidence < threshold);
+        self
+    }
+
+    /// Number of cracks.
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Whether there are no cracks.
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+}
+
+impl IntoIterator for RankedResults {
+    type Item = (usize, CrackResult);
+    type IntoIter = std::vec::IntoIter<(usize, CrackResult)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.results.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a RankedResults {
+    type Item = &'a (usize, CrackResult);
+    type IntoIter = std::slice::Iter<'a, (usize, CrackResult)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranking() {
+        let results = vec![
+            (8, CrackResult::new(vec![1, 2, 3], 0.125)),
+            (5, CrackResult::new(vec![7], f64::NAN)),
+            (4, CrackResult::new(vec![1, 2, 3], 0.125)),
+            (9, CrackResult::new(vec![4], 0.0)),
+            (4, CrackResult::new(vec![0, 2, 3], 0.125)),
+            (2, CrackResult::empty()),
+        ];
+
+        // the same order whatever order the cracks come in
+        let ranked = rank(results.clone());
+        let order = [(9, vec![4]), (4, vec![0, 2, 3]), (4, vec![1, 2, 3])];
+        let top: Vec<_> = ranked
+            .iter()
+            .take(3)
+            .map(|(keylen, res)| (*keylen, res.plaintext.clone()))
+            .collect();
+        assert_eq!(top, order);
+        assert_eq!(ranked.keylengths().skip(3).collect::<Vec<_>>(), [8, 2, 5]);
+        for rotation in 0..results.len() {
+            let mut shuffled = results.clone();
+            shuffled.rotate_left(rotation);
+            shuffled.reverse();
+            let keylens: Vec<_> = rank(shuffled).keylengths().collect();
+            assert_eq!(keylens, ranked.keylengths().collect::<Vec<_>>());
+        }
+        assert_eq!(ranked.best().unwrap().plaintext, vec![4]);
+
+        // repeated plaintexts keep their best keylength
+        let deduped = ranked.clone().dedup();
+        assert_eq!(deduped.keylengths().collect::<Vec<_>>(), [9, 4, 4, 2, 5]);
+
+        // thresholds drop infinite and NaN confidences, and everything at the threshold
+        let below = ranked.below(0.125);
+        assert_eq!(below.len(), 1);
+        let below = rank(results).below(f64::INFINITY).dedup();
+        assert_eq!(
+            below
+                .into_iter()
+                .map(|(keylen, _)| keylen)
+                .collect::<Vec<_>>(),
+            [9, 4, 4]
+        );
+
+        let empty = rank(Vec::new());
+        assert!(empty.is_empty());
+        assert!(empty.best().is_none());
+    }
+}
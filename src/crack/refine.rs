@@ -0,0 +1,489 @@
+//! Refining a crack by hand, pinning words and key symbols that are known to be right.
+//!
+//! A [`Refinement`] starts from the best crack of a ciphertext. Every pinned word fixes the
+//! plaintext at its position, which fixes the key symbol of each column it covers. Pinned key
+//! symbols are kept as they are, and only the remaining columns are cracked again.
+
+use crate::crack::{
+    crack_keylengths, crack_pinned, guesses, key_from_fragments, spellcheck_all, Correction,
+    CrackResult, Cracker, SpellChecker, WordCorrection, MAX_GUESSED_KEYLENGTH,
+};
+use crate::utils::{bytes_to_str, str_to_bytes, Key, ALPHABET};
+
+use anyhow::{anyhow, bail, Context};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// The space symbol, which separates words.
+const SPACE: u8 = 26;
+
+/// A correction to a [`Refinement`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// The word at this index (counting from 0) of the current crack is this word
+    Word { index: usize, word: Vec<u8> },
+    /// The key symbol of this column (counting from 0) is this shift
+    Key { column: usize, shift: i8 },
+    /// Crack with this keylength instead. Pinned key symbols are dropped, pinned words are kept.
+    Keylength(usize),
+    /// Drop every pin
+    Clear,
+}
+
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    /// Parse `word 7 is banana`, `key[3] = 12` (or `key 3 m`), `keylength 7`, or `clear`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let s = s.replace(['[', ']', '='], " ");
+        let tokens: Vec<&str> = s
+            .split_whitespace()
+            .filter(|&token| token != "is")
+            .collect();
+
+        let index = |token: &str| {
+            token
+                .parse::<usize>()
+                .with_context(|| format!("{:?} is not an index", token))
+        };
+
+        match tokens.as_slice() {
+            ["word", i, word] => {
+                if let Some(chr) = word.chars().find(|&chr| !chr.is_ascii_lowercase()) {
+                    bail!("{:?} is not a letter", chr);
+                }
+                Ok(Self::Word {
+                    index: index(i)?,
+                    word: str_to_bytes(word),
+                })
+            }
+            ["key", i, shift] => {
+                let shift = match shift.parse::<i8>() {
+                    Ok(shift) => shift.rem_euclid(ALPHABET.len() as i8),
+                    Err(_) => match shift.chars().collect::<Vec<_>>().as_slice() {
+                        [letter] if letter.is_ascii_lowercase() => (*letter as u8 - b'a') as i8,
+                        _ => bail!("{:?} is not a shift or a letter", shift),
+                    },
+                };
+                Ok(Self::Key {
+                    column: index(i)?,
+                    shift,
+                })
+            }
+            ["keylength", keylength] => match index(keylength)? {
+                0 => bail!("keylength must be at least 1"),
+                keylength if keylength > MAX_GUESSED_KEYLENGTH => {
+                    bail!("keylength must be at most {}", MAX_GUESSED_KEYLENGTH)
+                }
+                keylength => Ok(Self::Keylength(keylength)),
+            },
+            ["clear"] => Ok(Self::Clear),
+            _ => bail!("unknown command {:?}", s.trim()),
+        }
+    }
+}
+
+/// Fail unless `keylength` is at least 1 and at most `len`, the length of the ciphertext, so a
+/// mistyped keylength isn't allocated a pinned key symbol per column.
+fn check_keylength(keylength: usize, len: usize) -> anyhow::Result<()> {
+    if keylength == 0 {
+        bail!("keylength must be at least 1");
+    }
+    if keylength > len {
+        bail!(
+            "keylength {} is longer than the ciphertext, which has {} characters",
+            keylength,
+            len
+        );
+    }
+    Ok(())
+}
+
+/// A crack of one ciphertext, along with the corrections pinned so far.
+pub struct Refinement<'a> {
+    cracker: &'a Cracker,
+    ciphertext: Vec<u8>,
+    keylength: usize,
+    /// Pinned key symbol of each column
+    pinned_key: Vec<Option<i8>>,
+    /// Pinned plaintext symbol at each position, from pinned words
+    pinned_plaintext: BTreeMap<usize, u8>,
+    /// Crack under the pins, lined up with the ciphertext
+    crack: CrackResult,
+    /// The crack, spellchecked, and the words spellchecking changed
+    correction: Correction,
+}
+
+impl Cracker {
+    /// Start refining a crack of `ciphertext` (Test 2 only), at the keylength whose spellchecked
+    /// crack is best.
+    pub fn refine(&self, ciphertext: &str) -> Refinement<'_> {
+        let cipherbytes = str_to_bytes(ciphertext);
+        let mut keylen_guesses = Vec::new();
+        guesses(&cipherbytes, &mut keylen_guesses);
+
+        let cracks = crack_keylengths(&cipherbytes, &keylen_guesses, &self.stats.letters);
+        let spellchecked = spellcheck_all(&cracks, &self.bytes_dict);
+        let keylength = keylen_guesses
+            .iter()
+            .zip(&spellchecked)
+            .min_by(|(_, a), (_, b)| a.confidence.total_cmp(&b.confidence))
+            .map_or(1, |((keylength, _), _)| *keylength);
+
+        self.refine_at(ciphertext, keylength)
+    }
+
+    /// Start refining a crack of `ciphertext` at `keylength`. Fails if the keylength is 0 or
+    /// longer than the ciphertext.
+    pub fn refine_with_keylength(
+        &self,
+        ciphertext: &str,
+        keylength: usize,
+    ) -> anyhow::Result<Refinement<'_>> {
+        check_keylength(keylength, str_to_bytes(ciphertext).len())?;
+        Ok(self.refine_at(ciphertext, keylength))
+    }
+
+    fn refine_at(&self, ciphertext: &str, keylength: usize) -> Refinement<'_> {
+        let mut refinement = Refinement {
+            cracker: self,
+            ciphertext: str_to_bytes(ciphertext),
+            keylength: keylength.max(1),
+            pinned_key: vec![None; keylength.max(1)],
+            pinned_plaintext: BTreeMap::new(),
+            crack: CrackResult::new(Vec::new(), 0.0),
+            correction: Correction {
+                checked: CrackResult::new(Vec::new(), 0.0),
+                alignment: Vec::new(),
+                corrections: Vec::new(),
+            },
+        };
+        refinement
+            .solve()
+            .expect("nothing is pinned, so nothing conflicts");
+        refinement
+    }
+}
+
+impl Refinement<'_> {
+    /// Apply a correction and crack again. If the correction conflicts with an earlier one, or
+    /// doesn't fit the ciphertext, it is not applied.
+    pub fn apply(&mut self, command: &Command) -> anyhow::Result<()> {
+        let (pinned_key, pinned_plaintext) =
+            (self.pinned_key.clone(), self.pinned_plaintext.clone());
+        let keylength = self.keylength;
+
+        let applied = self.pin(command).and_then(|_| self.solve());
+        if applied.is_err() {
+            self.pinned_key = pinned_key;
+            self.pinned_plaintext = pinned_plaintext;
+            self.keylength = keylength;
+        }
+        applied
+    }
+
+    fn pin(&mut self, command: &Command) -> anyhow::Result<()> {
+        match command {
+            Command::Word { index, word } => {
+                let start = self
+                    .words()
+                    .nth(*index)
+                    .map(|(start, _)| start)
+                    .ok_or_else(|| anyhow!("there is no word {}", index))?;
+                let end = start + word.len();
+                if end > self.ciphertext.len() {
+                    bail!(
+                        "{:?} runs past the end of the ciphertext",
+                        bytes_to_str(word)
+                    );
+                }
+
+                // the word, along with the spaces around it
+                self.pinned_plaintext
+                    .extend((start..end).zip(word.iter().copied()));
+                if start > 0 {
+                    self.pinned_plaintext.insert(start - 1, SPACE);
+                }
+                if end < self.ciphertext.len() {
+                    self.pinned_plaintext.insert(end, SPACE);
+                }
+            }
+            Command::Key { column, shift } => {
+                if *column >= self.keylength {
+                    bail!(
+                        "key[{}] is past the end of a {} symbol key",
+                        column,
+                        self.keylength
+                    );
+                }
+                self.pinned_key[*column] = Some(*shift);
+            }
+            Command::Keylength(keylength) => {
+                check_keylength(*keylength, self.ciphertext.len())?;
+                self.keylength = *keylength;
+                self.pinned_key = vec![None; *keylength];
+            }
+            Command::Clear => {
+                self.pinned_key.fill(None);
+                self.pinned_plaintext.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// Crack again under the pins.
+    fn solve(&mut self) -> anyhow::Result<()> {
+        let key = self.pinned_key()?;
+        self.crack = crack_pinned(
+            &self.ciphertext,
+            self.keylength,
+            &self.cracker.stats.letters,
+            &key,
+        );
+        self.correction = SpellChecker::new(&self.cracker.bytes_dict).correct(&self.crack);
+        Ok(())
+    }
+
+    /// The pinned key symbols, along with the ones fixed by pinned words.
+    fn pinned_key(&self) -> anyhow::Result<Vec<Option<i8>>> {
+        let fragments: Vec<(usize, Vec<u8>)> = self
+            .pinned_plaintext
+            .iter()
+            .map(|(&position, &plain)| (position, vec![plain]))
+            .collect();
+        let mut key = key_from_fragments(&self.ciphertext, self.keylength, &fragments)?;
+
+        for (column, &pinned) in self.pinned_key.iter().enumerate() {
+            match (key[column], pinned) {
+                (Some(from_words), Some(pinned)) if from_words != pinned => bail!(
+                    "key[{}] would be both {} and {}",
+                    column,
+                    pinned,
+                    from_words
+                ),
+                (_, Some(pinned)) => key[column] = Some(pinned),
+                _ => {}
+            }
+        }
+        Ok(key)
+    }
+
+    /// The start and bytes of each word of the crack.
+    fn words(&self) -> impl Iterator<Item = (usize, &[u8])> {
+        let mut start = 0;
+        self.crack
+            .plaintext
+            .split(|&c| c == SPACE)
+            .map(move |word| {
+                let here = start;
+                start += word.len() + 1;
+                (here, word)
+            })
+    }
+
+    pub fn keylength(&self) -> usize {
+        self.keylength
+    }
+
+    /// The key of the crack, as the shift of each column.
+    pub fn key(&self) -> Key {
+        Key::between(&self.ciphertext, &self.crack.plaintext, self.keylength)
+    }
+
+    /// The crack under the pins, before spellchecking. Its words are the ones numbered by
+    /// [`Command::Word`].
+    pub fn crack(&self) -> &CrackResult {
+        &self.crack
+    }
+
+    /// The crack under the pins, spellchecked.
+    pub fn spellchecked(&self) -> &CrackResult {
+        &self.correction.checked
+    }
+
+    /// For every character of [`spellchecked`][`Self::spellchecked`], the position in the crack
+    /// (and ciphertext) it was corrected from, or `None` if spellchecking added it.
+    pub fn alignment(&self) -> &[Option<usize>] {
+        &self.correction.alignment
+    }
+
+    /// The words of the crack that spellchecking changed, with where they start in the crack.
+    pub fn corrections(&self) -> &[WordCorrection] {
+        &self.correction.corrections
+    }
+}
+
+impl fmt::Display for Refinement<'_> {
+    /// The keylength, the key with pinned symbols marked `*`, the numbered words of the crack,
+    /// the spellchecked crack with the characters spellchecking changed marked `^`, and the words
+    /// it changed.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pinned = self.pinned_key().unwrap_or_default();
+        writeln!(f, "keylength:    {}", self.keylength)?;
+        write!(f, "key:         ")?;
+        for (column, &shift) in self.key().iter().enumerate() {
+            let mark = if pinned.get(column).copied().flatten().is_some() {
+                "*"
+            } else {
+                ""
+            };
+            write!(f, " {}{}", shift, mark)?;
+        }
+        writeln!(f, " {:?}", self.key().as_letters())?;
+
+        write!(f, "words:       ")?;
+        for (index, (_, word)) in self.words().enumerate() {
+            write!(f, " {}:{}", index, bytes_to_str(word))?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "spellchecked: {}",
+            bytes_to_str(&self.spellchecked().plaintext)
+        )?;
+        let changed: String = self
+            .spellchecked()
+            .plaintext
+            .iter()
+            .zip(self.alignment())
+            .map(|(&c, aligned)| match aligned {
+                Some(j) if self.crack.plaintext.get(*j) == Some(&c) => ' ',
+                _ => '^',
+            })
+            .collect();
+        writeln!(f, "              {}", changed.trim_end())?;
+        if !self.corrections().is_empty() {
+            writeln!(f, "corrections:")?;
+            write!(f, "{}", self.correction)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::prelude::*;
+
+    const KEY: [i8; 9] = [3, 9, 14, 20, 25, 1, 7, 11, 0];
+
+    /// A plaintext too short to crack by frequencies alone, and its ciphertext
+    fn short() -> (String, String) {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let plaintext = Generator::with_dict(&dict).generate_words(6);
+        let encryptor = Encryptor::new(KEY.to_vec(), RepeatingKey, Rng::default());
+        (plaintext.clone(), encryptor.encrypt(&plaintext))
+    }
+
+    #[test]
+    fn parse() {
+        let word = |index, word: &str| Command::Word {
+            index,
+            word: str_to_bytes(word),
+        };
+        assert_eq!(
+            "word 7 is banana".parse::<Command>().unwrap(),
+            word(7, "banana")
+        );
+        assert_eq!("word 0 = a".parse::<Command>().unwrap(), word(0, "a"));
+        let key = |column, shift| Command::Key { column, shift };
+        assert_eq!("key[3] = 12".parse::<Command>().unwrap(), key(3, 12));
+        assert_eq!("key 3 m".parse::<Command>().unwrap(), key(3, 12));
+        assert_eq!("key[1]=-1".parse::<Command>().unwrap(), key(1, 26));
+        assert_eq!(
+            " keylength  5 ".parse::<Command>().unwrap(),
+            Command::Keylength(5)
+        );
+        assert_eq!("clear".parse::<Command>().unwrap(), Command::Clear);
+
+        for bad in [
+            "",
+            "word 7 is Banana",
+            "word x is a",
+            "key 3",
+            "keylength 0",
+            "keylength 100000000000",
+            "key 1 ab",
+        ] {
+            assert!(bad.parse::<Command>().is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn pinned_key() {
+        let (plaintext, ciphertext) = short();
+        let cracker = Cracker::default();
+        let mut refinement = cracker
+            .refine_with_keylength(&ciphertext, KEY.len())
+            .unwrap();
+
+        for (column, &shift) in KEY.iter().enumerate() {
+            refinement.apply(&Command::Key { column, shift }).unwrap();
+        }
+        assert_eq!(refinement.key().0, KEY);
+        assert_eq!(bytes_to_str(&refinement.crack().plaintext), plaintext);
+        assert_eq!(
+            bytes_to_str(&refinement.spellchecked().plaintext),
+            plaintext
+        );
+        // nothing needed correcting, so every character lines up with itself
+        let lined_up: Vec<Option<usize>> = (0..plaintext.len()).map(Some).collect();
+        assert_eq!(refinement.alignment(), lined_up);
+        assert!(!refinement.to_string().contains('^'));
+
+        // out of range columns are rejected, and leave the pins alone
+        assert!(refinement
+            .apply(&Command::Key {
+                column: 9,
+                shift: 1
+            })
+            .is_err());
+        assert_eq!(bytes_to_str(&refinement.crack().plaintext), plaintext);
+
+        // a new keylength drops the pinned key
+        refinement.apply(&Command::Keylength(4)).unwrap();
+        assert_eq!(refinement.keylength(), 4);
+        assert_eq!(refinement.key().len(), 4);
+
+        // keylengths longer than the ciphertext are rejected, from the REPL or to start with
+        let too_long = format!("keylength {}", ciphertext.len() + 1);
+        let command = too_long.parse::<Command>().unwrap();
+        assert!(refinement.apply(&command).is_err());
+        assert_eq!(refinement.keylength(), 4);
+        assert!(cracker
+            .refine_with_keylength(&ciphertext, ciphertext.len() + 1)
+            .is_err());
+        assert!(cracker.refine_with_keylength(&ciphertext, 0).is_err());
+    }
+
+    #[test]
+    fn pinned_words() {
+        let (plaintext, ciphertext) = short();
+        let cracker = Cracker::default();
+        let mut refinement = cracker
+            .refine_with_keylength(&ciphertext, KEY.len())
+            .unwrap();
+
+        // pinning words in order keeps the next word's start lined up with the plaintext
+        let words: Vec<&str> = plaintext.split(' ').collect();
+        for (index, word) in words.iter().enumerate() {
+            let word = str_to_bytes(word);
+            refinement.apply(&Command::Word { index, word }).unwrap();
+        }
+        assert_eq!(bytes_to_str(&refinement.crack().plaintext), plaintext);
+
+        // a key symbol that disagrees with a pinned word is rejected
+        let column = words[0].len() % KEY.len();
+        let shift = (KEY[column] + 1) % 27;
+        let conflict = refinement.apply(&Command::Key { column, shift });
+        assert!(conflict.is_err());
+        assert_eq!(bytes_to_str(&refinement.crack().plaintext), plaintext);
+
+        refinement.apply(&Command::Clear).unwrap();
+        refinement.apply(&Command::Key { column, shift }).unwrap();
+        assert!(refinement.to_string().contains(&format!(" {}*", shift)));
+    }
+}
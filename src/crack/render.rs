@@ -0,0 +1,364 @@
+//! Rendering a crack as a colored diff, to check it by eye.
+//!
+//! A [`DiffView`] shows one text against another: a crack against the real plaintext when
+//! evaluating, or a spellchecked crack against the crack it was corrected from. Characters that
+//! were added or removed on the way are highlighted, and words can be marked as doubtful, such as
+//! ones that aren't in the dictionary or that spellchecking had to change a lot. It renders with
+//! ANSI colors for a terminal, as HTML, or as plain text in the style of `git diff --word-diff`.
+
+use super::explain::align;
+use super::{Correction, CrackResult};
+use crate::dict::{BytesDictionary, EDIT_COST};
+use crate::utils::{CharToNum, NumToChar};
+
+use std::fmt::{self, Write};
+
+/// Corrected words that changed by more than this fraction of their characters, by
+/// [`shift_levenshtein`][`crate::dict::shift_levenshtein`], are marked as doubtful by
+/// [`DiffView::from_correction`].
+pub const DOUBTFUL_CHANGE: f64 = 1.0 / 3.0;
+
+/// How a [`DiffView`] is rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderFormat {
+    /// `[-removed-]{+added+}`, with doubtful words in `(?...?)`
+    Plain,
+    /// Removed characters struck through in red, added ones in green, and doubtful words
+    /// underlined
+    Ansi,
+    /// A `<pre>` with `<del>`, `<ins>`, and doubtful words in `<span class="doubtful">`, styled
+    /// inline
+    Html,
+}
+
+/// What a run of a [`DiffView`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// In both texts
+    Same,
+    /// Only in the shown text
+    Added,
+    /// Only in the text it's compared with
+    Removed,
+}
+
+/// One text shown against another, with the characters that differ between them.
+#[derive(Clone, Debug)]
+pub struct DiffView {
+    /// Runs of the diff in order, along with where each starts in the shown text (where it would
+    /// have been, for removed runs)
+    runs: Vec<(Kind, usize, Vec<u8>)>,
+    /// For every character of the shown text, whether it's part of a doubtful word
+    doubtful: Vec<bool>,
+}
+
+impl DiffView {
+    /// `shown` against `other`, lined up along a shortest edit path.
+    pub fn new(shown: &[u8], other: &[u8]) -> Self {
+        let alignment = align(shown.len(), other.len(), |i, j| shown[i] == other[j]);
+        Self::aligned(shown, other, &alignment)
+    }
+
+    /// `shown` against `other`, where `alignment` gives the index of the character of `other`
+    /// that each character of `shown` lines up with, in order, or `None` if it was added.
+    pub fn aligned(shown: &[u8], other: &[u8], alignment: &[Option<usize>]) -> Self {
+        // each character's kind, and the position in the shown text it comes before
+        let mut chars = Vec::with_capacity(shown.len());
+        let mut next = 0;
+        for (i, (&c, &aligned)) in shown.iter().zip(alignment).enumerate() {
+            match aligned {
+                Some(j) if j >= next => {
+                    chars.extend(other[next..j].iter().map(|&o| (Kind::Removed, i, o)));
+                    if c == other[j] {
+                        chars.push((Kind::Same, i, c));
+                    } else {
+                        chars.push((Kind::Removed, i, other[j]));
+                        chars.push((Kind::Added, i, c));
+                    }
+                    next = j + 1;
+                }
+                _ => chars.push((Kind::Added, i, c)),
+            }
+        }
+        chars.extend(
+            other[next..]
+                .iter()
+                .map(|&o| (Kind::Removed, shown.len(), o)),
+        );
+
+        // runs of unchanged characters, and runs of changes showing what was removed, then what
+        // was added
+        let mut runs = Vec::new();
+        let same = |&(kind, _, _): &(Kind, usize, u8)| kind == Kind::Same;
+        for chunk in chars.chunk_by(|a, b| same(a) == same(b)) {
+            for kind in [Kind::Same, Kind::Removed, Kind::Added] {
+                let mut run = chunk.iter().filter(|&&(k, _, _)| k == kind).peekable();
+                if let Some(&&(_, start, _)) = run.peek() {
+                    runs.push((kind, start, run.map(|&(_, _, c)| c).collect()));
+                }
+            }
+        }
+
+        Self {
+            runs,
+            doubtful: vec![false; shown.len()],
+        }
+    }
+
+    /// The spellchecked crack of `correction` against `cracked`, the crack it was corrected from.
+    /// Words that were corrected by more than [`DOUBTFUL_CHANGE`] of their characters are marked
+    /// as doubtful.
+    pub fn from_correction(cracked: &CrackResult, correction: &Correction) -> Self {
+        let shown = &correction.checked.plaintext;
+        let mut view = Self::aligned(shown, &cracked.plaintext, &correction.alignment);
+
+        // words that were kept are kept whole, so corrections start as far into the spellchecked
+        // crack as they do into the crack
+        let (mut position, mut start) = (0, 0);
+        for word in &correction.corrections {
+            start += word.position - position;
+            let end = start + word.replacement.len();
+            position = word.position + word.original.len();
+
+            let changed = word.distance as f64 / (EDIT_COST * word.replacement.len()) as f64;
+            if changed > DOUBTFUL_CHANGE && end <= shown.len() {
+                view.doubt(start..end);
+            }
+            start = end;
+        }
+        view
+    }
+
+    /// Mark the words of the shown text that aren't in `dict` as doubtful.
+    pub fn doubt_unknown_words(&mut self, dict: &BytesDictionary) {
+        let text = self.shown();
+        let space = ' '.to_num();
+        let mut start = 0;
+        for word in text.split(|&c| c == space) {
+            if !word.is_empty() && !dict.contains(word) {
+                self.doubt(start..start + word.len());
+            }
+            start += word.len() + 1;
+        }
+    }
+
+    /// Mark the characters of the shown text in `range` as doubtful.
+    fn doubt(&mut self, range: std::ops::Range<usize>) {
+        self.doubtful[range].iter_mut().for_each(|d| *d = true);
+    }
+
+    /// The shown text.
+    pub fn shown(&self) -> Vec<u8> {
+        self.runs
+            .iter()
+            .filter(|(kind, _, _)| *kind != Kind::Removed)
+            .flat_map(|(_, _, text)| text.iter().copied())
+            .collect()
+    }
+
+    /// Whether the two texts are the same.
+    pub fn is_same(&self) -> bool {
+        self.runs.iter().all(|(kind, _, _)| *kind == Kind::Same)
+    }
+
+    /// Number of characters removed and added, the edit distance between the texts if each
+    /// substitution counted twice.
+    pub fn changed(&self) -> usize {
+        self.runs
+            .iter()
+            .filter(|(kind, _, _)| *kind != Kind::Same)
+            .map(|(_, _, text)| text.len())
+            .sum()
+    }
+
+    /// Number of characters of the shown text that are part of doubtful words.
+    pub fn doubtful(&self) -> usize {
+        self.doubtful.iter().filter(|&&d| d).count()
+    }
+
+    /// The diff, rendered as `format`.
+    pub fn render(&self, format: RenderFormat) -> String {
+        let mut out = String::new();
+        if format == RenderFormat::Html {
+            out.push_str("<pre class=\"crack-diff\">");
+        }
+        let mut in_doubt = false;
+        for (kind, doubtful, text) in self.pieces() {
+            let text: String = text.iter().map(|&c| c.to_char()).collect();
+            match format {
+                RenderFormat::Plain => {
+                    // doubtful words are marked around the changes in them, not inside
+                    if doubtful != in_doubt {
+                        out.push_str(if doubtful { "(?" } else { "?)" });
+                        in_doubt = doubtful;
+                    }
+                    match kind {
+                        Kind::Same => out.push_str(&text),
+                        Kind::Added => write!(out, "{{+{}+}}", text).unwrap(),
+                        Kind::Removed => write!(out, "[-{}-]", text).unwrap(),
+                    }
+                }
+                RenderFormat::Ansi => {
+                    let mut codes = match kind {
+                        Kind::Same => vec![],
+                        Kind::Added => vec!["1", "32"],
+                        Kind::Removed => vec!["9", "31"],
+                    };
+                    if doubtful {
+                        codes.push("4");
+                        if kind == Kind::Same {
+                            codes.push("33");
+                        }
+                    }
+                    if codes.is_empty() {
+                        out.push_str(&text);
+                    } else {
+                        write!(out, "\x1b[{}m{}\x1b[0m", codes.join(";"), text).unwrap();
+                    }
+                }
+                RenderFormat::Html => {
+                    let text = if doubtful {
+                        format!(
+                            "<span class=\"doubtful\" style=\"text-decoration: underline wavy \
+                             #c60\">{}</span>",
+                            text
+                        )
+                    } else {
+                        text
+                    };
+                    match kind {
+                        Kind::Same => out.push_str(&text),
+                        Kind::Added => {
+                            write!(out, "<ins style=\"background: #cfc\">{}</ins>", text).unwrap()
+                        }
+                        Kind::Removed => {
+                            write!(out, "<del style=\"background: #fcc\">{}</del>", text).unwrap()
+                        }
+                    }
+                }
+            }
+        }
+        match format {
+            RenderFormat::Plain if in_doubt => out.push_str("?)"),
+            RenderFormat::Html => out.push_str("</pre>"),
+            _ => {}
+        }
+        out
+    }
+
+    /// The runs split wherever doubtful words start or end, along with whether each piece is
+    /// doubtful. Removed runs are doubtful if the character they were removed before is.
+    fn pieces(&self) -> Vec<(Kind, bool, &[u8])> {
+        let mut pieces = Vec::new();
+        for (kind, start, text) in &self.runs {
+            if *kind == Kind::Removed {
+                let doubtful = self.doubtful.get(*start).copied().unwrap_or(false);
+                pieces.push((*kind, doubtful, text.as_slice()));
+                continue;
+            }
+            let doubtful = &self.doubtful[*start..*start + text.len()];
+            let mut from = 0;
+            for to in 1..=text.len() {
+                if to == text.len() || doubtful[to] != doubtful[from] {
+                    pieces.push((*kind, doubtful[from], &text[from..to]));
+                    from = to;
+                }
+            }
+        }
+        pieces
+    }
+}
+
+impl fmt::Display for DiffView {
+    /// The diff as [plain text][`RenderFormat::Plain`].
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.render(RenderFormat::Plain))
+    }
+}
+
+/// A standalone HTML page of `views`, each under its heading.
+pub fn html_page(title: &str, views: &[(&str, &DiffView)]) -> String {
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n\
+         <body>\n",
+        title
+    );
+    for (heading, view) in views {
+        writeln!(page, "<h2>{}</h2>", heading).unwrap();
+        writeln!(page, "{}", view.render(RenderFormat::Html)).unwrap();
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crack::SpellChecker;
+    use crate::dict::Dictionary;
+    use crate::utils::{bytes_to_str, str_to_bytes};
+
+    #[test]
+    fn diff() {
+        let view = DiffView::new(&str_to_bytes("the cat sta"), &str_to_bytes("the bat sat"));
+        assert_eq!(bytes_to_str(&view.shown()), "the cat sta");
+        assert!(!view.is_same());
+        assert_eq!(view.to_string(), "the [-b-]{+c+}at s[-at-]{+ta+}");
+        assert_eq!(view.changed(), 6);
+
+        let same = DiffView::new(&str_to_bytes("cat"), &str_to_bytes("cat"));
+        assert!(same.is_same());
+        assert_eq!(same.render(RenderFormat::Ansi), "cat");
+        assert_eq!(
+            same.render(RenderFormat::Html),
+            "<pre class=\"crack-diff\">cat</pre>"
+        );
+
+        // texts that have nothing in common
+        let view = DiffView::new(&str_to_bytes("dog"), &str_to_bytes(""));
+        assert_eq!(view.to_string(), "{+dog+}");
+        let view = DiffView::new(&str_to_bytes(""), &str_to_bytes("dog"));
+        assert_eq!(view.to_string(), "[-dog-]");
+    }
+
+    #[test]
+    fn doubtful_words() {
+        let dict = BytesDictionary::from_dict(&Dictionary {
+            words: vec!["the", "cat", "sat"],
+        });
+        let mut view = DiffView::new(&str_to_bytes("the cxt sat"), &str_to_bytes("the cat sat"));
+        view.doubt_unknown_words(&dict);
+        assert_eq!(view.doubtful(), 3);
+        assert_eq!(view.to_string(), "the (?c[-a-]{+x+}t?) sat");
+
+        let ansi = view.render(RenderFormat::Ansi);
+        assert!(ansi.starts_with("the \x1b[4;33mc\x1b[0m\x1b[9;31;4ma\x1b[0m\x1b[1;32;4mx"));
+        let html = view.render(RenderFormat::Html);
+        assert!(html.contains("<ins style=\"background: #cfc\"><span class=\"doubtful\""));
+
+        let page = html_page("crack", &[("spellcheck", &view)]);
+        assert!(page.contains("<h2>spellcheck</h2>") && page.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn corrections() {
+        let dict = BytesDictionary::from_dict(&Dictionary {
+            words: vec!["the", "cat", "sat", "on", "hippo"],
+        });
+        let cracked = CrackResult::new(str_to_bytes("the cqt sat on hzqxo"), 1.0);
+        let correction = SpellChecker::new(&dict).correct(&cracked);
+        assert_eq!(
+            bytes_to_str(&correction.checked.plaintext),
+            "the cat sat on hippo"
+        );
+
+        // "cqt" is one letter off, but most of "hippo" had to be changed
+        let view = DiffView::from_correction(&cracked, &correction);
+        assert_eq!(bytes_to_str(&view.shown()), "the cat sat on hippo");
+        assert_eq!(
+            view.to_string(),
+            "the c[-q-]{+a+}t sat on (?h[-zqx-]{+ipp+}o?)"
+        );
+        assert_eq!(view.doubtful(), 5);
+    }
+}
@@ -0,0 +1,157 @@
+//! Recording worker trials to a file, and replaying them later.
+//!
+//! Trials are logged as newline delimited JSON (NDJSON), one [`TrialRecord`] per line. Every
+//! record has the ciphertext and the real plaintext, so a failed trial can be cracked again by id
+//! without needing the worker's random state.
+
+use crate::crack::worker::{TrialCracker, TrialRecord, TrialResult};
+
+use anyhow::{anyhow, Context};
+use serde::de::DeserializeOwned;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Writes [`TrialRecord`]s, or anything else that serializes, as NDJSON.
+pub struct Recorder<W: Write> {
+    out: W,
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Record to a new file at `path`, replacing it if it exists.
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Write one trial as a line of JSON.
+    pub fn record<T: serde::Serialize>(&mut self, trial: &T) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.out, trial)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Flush and return the underlying writer.
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Read every trial from NDJSON, skipping blank lines.
+pub fn read(input: impl BufRead) -> anyhow::Result<Vec<TrialRecord>> {
+    read_lines(input, "trial")
+}
+
+/// Read every trial from an NDJSON file.
+pub fn load(path: &Path) -> anyhow::Result<Vec<TrialRecord>> {
+    load_lines(path, "trial")
+}
+
+/// Read one `T` from every non-blank line of NDJSON. `what` names a `T` in errors.
+pub(crate) fn read_lines<T: DeserializeOwned>(
+    input: impl BufRead,
+    what: &str,
+) -> anyhow::Result<Vec<T>> {
+    let mut items = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item = serde_json::from_str(&line)
+            .with_context(|| format!("invalid {} on line {}", what, number + 1))?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// Same as [`read_lines`], from a file.
+pub(crate) fn load_lines<T: DeserializeOwned>(path: &Path, what: &str) -> anyhow::Result<Vec<T>> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    read_lines(BufReader::new(file), what)
+}
+
+/// Find the trial with the given id in an NDJSON file.
+pub fn find(path: &Path, id: u64) -> anyhow::Result<TrialRecord> {
+    load(path)?
+        .into_iter()
+        .find(|trial| trial.id == id)
+        .ok_or_else(|| anyhow!("no trial with id {} in {}", id, path.display()))
+}
+
+/// Crack a recorded trial again, the same way the worker loop did.
+pub fn replay(trial: TrialRecord) -> TrialResult {
+    TrialCracker::project().crack(trial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::{RandomScheduler, SchedulerComplexity};
+    use crate::prelude::*;
+
+    #[test]
+    fn record_and_replay() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let mut gen = Generator::with_dict(&dict);
+        let cracker = TrialCracker::project();
+
+        let mut rng = Rng::default();
+        let trials: Vec<TrialRecord> = (0..4)
+            .map(|id| {
+                let sched = RandomScheduler::with_complexity(&mut rng, SchedulerComplexity::Easy);
+                cracker.generate(id, rng.next(), sched, &mut gen)
+            })
+            .collect();
+
+        let mut recorder = Recorder::new(Vec::new());
+        for trial in &trials {
+            recorder.record(trial).unwrap();
+        }
+        let log = recorder.finish().unwrap();
+        assert_eq!(log.iter().filter(|&&b| b == b'\n').count(), 4);
+
+        let read_back = read(log.as_slice()).unwrap();
+        assert_eq!(read_back.len(), 4);
+        for (a, b) in trials.iter().zip(read_back.iter()) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.key, b.key);
+            assert_eq!(a.ciphertext, b.ciphertext);
+            assert_eq!(a.plaintext, b.plaintext);
+        }
+
+        // replaying gives the same outcome as cracking the original
+        let original = TrialCracker::project().crack(trials[2].clone());
+        let replayed = replay(read_back[2].clone());
+        assert_eq!(replayed.trial.id, 2);
+        assert_eq!(replayed.success, original.success);
+        assert_eq!(replayed.error, original.error);
+    }
+
+    #[test]
+    fn regenerated_from_seed() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let cracker = TrialCracker::project();
+        let sched = RandomScheduler::from_rng(&mut Rng::default());
+
+        let a = cracker.generate(0, 1234, sched, &mut Generator::with_dict(&dict));
+        let b = cracker.generate(0, 1234, sched, &mut Generator::with_dict(&dict));
+        assert_eq!(a.ciphertext, b.ciphertext);
+        assert_eq!(a.plaintext, b.plaintext);
+    }
+
+    #[test]
+    fn bad_lines() {
+        assert!(read("\n\n".as_bytes()).unwrap().is_empty());
+        assert!(read("{\"id\": 3}\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,19 @@
+//! Module for [`CrackScratch`].
+
+/// Reusable buffers for the steps of cracking.
+///
+/// Cracking many ciphertexts in a row (in a worker, or a batch) would otherwise allocate and free
+/// the same temporary buffers for every keylength of every ciphertext. Keep one `CrackScratch`
+/// per thread, and pass it to the `_with_scratch` versions of [`crack`][`super::crack`] and
+/// [`spellcheck`][`super::spellcheck`] instead.
+#[derive(Clone, Debug, Default)]
+pub struct CrackScratch {
+    /// Keylength guesses, for passing to [`guesses`][`super::guesses`]
+    pub keylen_guesses: Vec<(usize, f64)>,
+    /// Character counts of each ciphertext column
+    pub(super) histograms: Vec<[u32; 27]>,
+    /// Best shift of each ciphertext column
+    pub(super) shifts: Vec<i8>,
+    /// Row of edit distances for levenshtein while spellchecking
+    pub(super) levenshtein_row: Vec<usize>,
+}
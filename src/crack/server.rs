@@ -0,0 +1,478 @@
+//! A long running crack server, so many ciphertexts can be cracked without starting a process for
+//! each one.
+//!
+//! Clients connect over TCP or a Unix socket and send requests as newline delimited JSON, one
+//! [`Request`] per line. Every request is answered with a [`Response`] line when it is queued,
+//! another when a worker starts cracking it, one each time the best crack so far improves if the
+//! request asks for partial results, and a last one with the result. Requests are cracked
+//! concurrently, so responses to different requests can come back in any order, and carry the id
+//! of their request.
+
+use crate::crack::cracker::deadline_after;
+use crate::crack::worker::{Submitter, WorkerPool};
+use crate::crack::Cracker;
+use crate::utils::{bytes_to_str, check_alphabet};
+
+use crossbeam_channel::{unbounded, Sender};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info_span, warn};
+
+/// A ciphertext to crack.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Request {
+    /// Echoed back in every response. Defaults to the number of the request on its connection,
+    /// counting from 0.
+    pub id: Option<u64>,
+    pub ciphertext: String,
+    /// Seconds to crack for before answering with the best result so far
+    pub timeout: Option<f64>,
+    /// Whether to answer with the best crack so far each time it improves, before the result
+    #[serde(default)]
+    pub partial: bool,
+}
+
+/// One line of progress on a request.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    /// The request is waiting for a free worker
+    Queued { id: u64 },
+    /// A worker started cracking the request
+    Cracking { id: u64 },
+    /// The best plaintext guess so far, when the request asked for partial results
+    Partial {
+        id: u64,
+        keylength: usize,
+        plaintext: String,
+        confidence: f64,
+    },
+    /// The best plaintext guess
+    Done {
+        id: u64,
+        plaintext: String,
+        confidence: f64,
+        timed_out: bool,
+    },
+    /// The request could not be cracked, or not even parsed
+    Error { id: Option<u64>, message: String },
+}
+
+/// A request waiting for a worker, and where to send its responses.
+struct Job {
+    id: u64,
+    ciphertext: String,
+    timeout: Option<Duration>,
+    partial: bool,
+    responses: Sender<Response>,
+}
+
+impl Job {
+    /// Crack the request with `cracker`, answering on its responses channel.
+    fn crack(self, cracker: &Cracker) {
+        // the client may have hung up, and then nobody needs the result
+        if self
+            .responses
+            .send(Response::Cracking { id: self.id })
+            .is_err()
+        {
+            return;
+        }
+        let _span = info_span!("request", id = self.id).entered();
+        let deadline = self.timeout.and_then(deadline_after);
+
+        // one bad ciphertext shouldn't take a worker down with it
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            if !self.partial {
+                return cracker.crack_with_deadline(&self.ciphertext, deadline);
+            }
+            cracker.crack_with_deadline_partials(&self.ciphertext, deadline, |p| {
+                if let (true, Some((keylength, best))) = (p.improved, &p.best) {
+                    let _ = self.responses.send(Response::Partial {
+                        id: self.id,
+                        keylength: *keylength,
+                        plaintext: bytes_to_str(&best.plaintext),
+                        confidence: best.confidence,
+                    });
+                }
+            })
+        }));
+        let response = match result {
+            // empty cracks are infinitely unconfident, which JSON can't say
+            Ok(result) if result.plaintext.is_empty() => Response::Error {
+                id: Some(self.id),
+                message: "ciphertext too short to crack".to_string(),
+            },
+            Ok(result) => Response::Done {
+                id: self.id,
+                plaintext: bytes_to_str(&result.plaintext),
+                confidence: result.confidence,
+                timed_out: result.timed_out,
+            },
+            Err(_) => {
+                error!("cracking panicked");
+                Response::Error {
+                    id: Some(self.id),
+                    message: "cracking failed".to_string(),
+                }
+            }
+        };
+        let _ = self.responses.send(response);
+    }
+}
+
+/// A [`WorkerPool`], sharing one [`Cracker`], that every connection queues its requests on as
+/// tasks.
+pub struct Server {
+    pool: WorkerPool,
+    cracker: Arc<Cracker>,
+}
+
+impl Server {
+    /// Spawn `num_workers` cracking threads, each cracking one request at a time.
+    pub fn spawn(num_workers: usize) -> Self {
+        Self::spawn_with(Cracker::default(), num_workers)
+    }
+
+    /// Same as [`spawn`][`Self::spawn`], cracking with `cracker`.
+    pub fn spawn_with(cracker: Cracker, num_workers: usize) -> Self {
+        Self {
+            pool: WorkerPool::spawn(num_workers.max(1)),
+            cracker: Arc::new(cracker),
+        }
+    }
+
+    /// Accept TCP connections forever, serving each on its own thread.
+    pub fn serve_tcp(&self, listener: TcpListener) -> anyhow::Result<()> {
+        for stream in listener.incoming() {
+            // a connection that failed to open shouldn't stop the server from accepting others
+            let (stream, writer) = match stream.and_then(|stream| {
+                let writer = stream.try_clone()?;
+                Ok((stream, writer))
+            }) {
+                Ok(connection) => connection,
+                Err(err) => {
+                    warn!(%err, "could not accept a connection");
+                    continue;
+                }
+            };
+            self.spawn_connection(stream, writer);
+        }
+        Ok(())
+    }
+
+    /// Accept Unix socket connections forever, serving each on its own thread.
+    #[cfg(unix)]
+    pub fn serve_unix(&self, listener: std::os::unix::net::UnixListener) -> anyhow::Result<()> {
+        for stream in listener.incoming() {
+            // a connection that failed to open shouldn't stop the server from accepting others
+            let (stream, writer) = match stream.and_then(|stream| {
+                let writer = stream.try_clone()?;
+                Ok((stream, writer))
+            }) {
+                Ok(connection) => connection,
+                Err(err) => {
+                    warn!(%err, "could not accept a connection");
+                    continue;
+                }
+            };
+            self.spawn_connection(stream, writer);
+        }
+        Ok(())
+    }
+
+    fn spawn_connection<R, W>(&self, reader: R, writer: W)
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let tasks = self.tasks();
+        let cracker = Arc::clone(&self.cracker);
+        std::thread::spawn(move || {
+            let _ = serve_connection(&tasks, &cracker, BufReader::new(reader), writer);
+        });
+    }
+
+    /// Serve one connection until the client stops sending requests and every request it sent is
+    /// answered.
+    pub fn serve_connection(
+        &self,
+        reader: impl BufRead,
+        writer: impl Write + Send,
+    ) -> anyhow::Result<()> {
+        serve_connection(&self.tasks(), &self.cracker, reader, writer)
+    }
+
+    fn tasks(&self) -> Submitter {
+        self.pool
+            .sender()
+            .expect("the server never shuts its pool down")
+    }
+}
+
+fn serve_connection(
+    tasks: &Submitter,
+    cracker: &Arc<Cracker>,
+    reader: impl BufRead,
+    mut writer: impl Write + Send,
+) -> anyhow::Result<()> {
+    let (responses_in, responses_out) = unbounded();
+
+    std::thread::scope(|scope| {
+        // write responses as they come in. This ends once the reader below and every queued job
+        // of this connection have dropped their senders
+        let written = scope.spawn(move || -> anyhow::Result<()> {
+            for response in responses_out.iter() {
+                let mut line = serde_json::to_vec(&response)?;
+                line.push(b'\n');
+                writer.write_all(&line)?;
+                writer.flush()?;
+            }
+            Ok(())
+        });
+
+        let mut next_id = 0;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let request = match parse(&line) {
+                Ok(request) => request,
+                Err(message) => {
+                    let _ = responses_in.send(Response::Error { id: None, message });
+                    continue;
+                }
+            };
+            let id = request.id.unwrap_or(next_id);
+            next_id += 1;
+
+            let ciphertext = request.ciphertext.trim();
+            if let Err(err) = check_alphabet("ciphertext", ciphertext) {
+                let _ = responses_in.send(Response::Error {
+                    id: Some(id),
+                    message: err.to_string(),
+                });
+                continue;
+            }
+
+            let _ = responses_in.send(Response::Queued { id });
+            let job = Job {
+                id,
+                ciphertext: ciphertext.to_string(),
+                timeout: request.timeout.map(Duration::from_secs_f64),
+                partial: request.partial,
+                responses: responses_in.clone(),
+            };
+            let cracker = Arc::clone(cracker);
+            if tasks.execute(move || job.crack(&cracker)).is_err() {
+                let message = "the server is shutting down".to_string();
+                let _ = responses_in.send(Response::Error {
+                    id: Some(id),
+                    message,
+                });
+            }
+        }
+        drop(responses_in);
+
+        written.join().expect("response writer panicked")
+    })
+}
+
+/// Parse a request, describing what is wrong with it if it doesn't parse.
+fn parse(line: &str) -> Result<Request, String> {
+    let request: Request =
+        serde_json::from_str(line).map_err(|err| format!("invalid request: {}", err))?;
+    match request.timeout.map(Duration::try_from_secs_f64) {
+        Some(Err(err)) => Err(format!(
+            "invalid timeout {}: {}",
+            request.timeout.unwrap_or_default(),
+            err
+        )),
+        _ => Ok(request),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::prelude::*;
+
+    use std::io::BufReader;
+    use std::net::TcpStream;
+
+    fn responses(output: &[u8]) -> Vec<Response> {
+        output
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn connection() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let mut gen = Generator::with_dict(&dict);
+        let key = vec![1, 5, 9, 13, 17];
+        let plaintexts = [gen.generate_words(60), gen.generate_words(60)];
+        let ciphertexts: Vec<String> = plaintexts
+            .iter()
+            .map(|plaintext| {
+                Encryptor::new(key.clone(), RepeatingKey, Rng::default()).encrypt(plaintext)
+            })
+            .collect();
+
+        let input = format!(
+            "{{\"ciphertext\": \"{}\"}}\n\nnot json\n{{\"id\": 7, \"ciphertext\": \"{}\"}}\n{{\"ciphertext\": \"ABC\"}}\n",
+            ciphertexts[0], ciphertexts[1]
+        );
+        let mut output = Vec::new();
+        let server = Server::spawn(2);
+        server
+            .serve_connection(input.as_bytes(), &mut output)
+            .unwrap();
+        let responses = responses(&output);
+
+        // every request is queued, started and done, in that order, or turned away
+        for (id, plaintext) in [(0, &plaintexts[0]), (7, &plaintexts[1])] {
+            let progress: Vec<&Response> = responses
+                .iter()
+                .filter(|response| match response {
+                    Response::Queued { id: i }
+                    | Response::Cracking { id: i }
+                    | Response::Done { id: i, .. } => *i == id,
+                    Response::Partial { .. } | Response::Error { .. } => false,
+                })
+                .collect();
+            assert_eq!(progress.len(), 3, "{:?}", responses);
+            assert_eq!(progress[0], &Response::Queued { id });
+            assert_eq!(progress[1], &Response::Cracking { id });
+            match progress[2] {
+                Response::Done {
+                    plaintext: cracked,
+                    timed_out,
+                    ..
+                } => {
+                    assert_eq!(cracked, plaintext);
+                    assert!(!timed_out);
+                }
+                other => panic!("not done: {:?}", other),
+            }
+        }
+
+        let errors: Vec<&Response> = responses
+            .iter()
+            .filter(|response| matches!(response, Response::Error { .. }))
+            .collect();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors.contains(&&Response::Error {
+            id: Some(2),
+            message: "ciphertext has 3 characters not in the alphabet: 'A' at offset 0, \
+                      'B' at offset 1, 'C' at offset 2 (only lowercase letters and spaces are)"
+                .to_string()
+        }));
+    }
+
+    #[test]
+    fn partial() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let plaintext = Generator::with_dict(&dict).generate_words(60);
+        let encryptor = Encryptor::new(vec![2, 7, 1, 8, 2, 8], RepeatingKey, Rng::default());
+        let ciphertext = encryptor.encrypt(&plaintext);
+
+        let input = format!(
+            "{{\"ciphertext\": \"{}\", \"partial\": true}}\n",
+            ciphertext
+        );
+        let mut output = Vec::new();
+        Server::spawn(1)
+            .serve_connection(input.as_bytes(), &mut output)
+            .unwrap();
+        let responses = responses(&output);
+
+        // partial results come between starting and finishing, and end on the final crack
+        assert_eq!(responses[1], Response::Cracking { id: 0 });
+        let partials = &responses[2..responses.len() - 1];
+        assert!(!partials.is_empty(), "{:?}", responses);
+        for partial in partials {
+            assert!(
+                matches!(partial, Response::Partial { id: 0, .. }),
+                "{:?}",
+                partial
+            );
+        }
+        match (partials.last(), responses.last()) {
+            (
+                Some(Response::Partial { plaintext: p, .. }),
+                Some(Response::Done { plaintext, .. }),
+            ) => {
+                assert_eq!(p, plaintext)
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn long_timeouts() {
+        // too long for a Duration is a bad request, too long for an Instant is no deadline
+        let ciphertext = "the quick brown fox jumps over the lazy dog";
+        let input = format!(
+            "{{\"ciphertext\": \"{0}\", \"timeout\": 1e30}}\n{{\"ciphertext\": \"{0}\", \"timeout\": 1e18}}\n",
+            ciphertext
+        );
+        let mut output = Vec::new();
+        Server::spawn(1)
+            .serve_connection(input.as_bytes(), &mut output)
+            .unwrap();
+        let responses = responses(&output);
+        let rejected = responses.iter().any(|response| match response {
+            Response::Error { id, message } => id.is_none() && message.contains("invalid timeout"),
+            _ => false,
+        });
+        assert!(rejected, "{:?}", responses);
+        let done = responses.iter().any(|response| match response {
+            Response::Done { id, timed_out, .. } => *id == 0 && !*timed_out,
+            _ => false,
+        });
+        assert!(done, "{:?}", responses);
+    }
+
+    #[test]
+    fn tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || Server::spawn(1).serve_tcp(listener));
+
+        // the first ciphertext is too short to crack, and the worker carries on after it
+        let ciphertext = "the quick brown fox jumps over the lazy dog";
+        let input = format!(
+            "{{\"ciphertext\": \"hello\"}}\n{{\"ciphertext\": \"{}\", \"timeout\": 0}}\n",
+            ciphertext
+        );
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(input.as_bytes()).unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+
+        let mut output = Vec::new();
+        BufReader::new(stream).read_to_end(&mut output).unwrap();
+        let responses = responses(&output);
+        assert_eq!(responses.len(), 6, "{:?}", responses);
+        assert!(responses.contains(&Response::Error {
+            id: Some(0),
+            message: "ciphertext too short to crack".to_string()
+        }));
+        let done = responses.iter().any(|response| match response {
+            Response::Done { id, timed_out, .. } => *id == 1 && *timed_out,
+            _ => false,
+        });
+        assert!(done, "{:?}", responses);
+    }
+}
@@ -0,0 +1,233 @@
+//! Module for cracking ciphertexts too short for column frequencies.
+//!
+//! With only a handful of characters in each column, the shift with the best letter frequencies is
+//! often wrong. Instead, this searches over whole keys at once, scoring each by how likely the runs
+//! of four characters (tetragrams) of its plaintext are. Tetragrams span several columns, so they
+//! judge shifts by their neighbors too. The search is hill climbing from several starting keys,
+//! which stays cheap for the short keys and short texts it is meant for.
+
+use super::ngram::NgramModel;
+use super::{crack_pinned, CrackResult, Frequencies};
+use crate::dict::BytesDictionary;
+use crate::rng::Rng;
+use crate::utils::{CharToNum, Shift, ALPHABET};
+
+/// Longest keylength cracked by searching whole keys.
+pub const MAX_SHORT_KEYLENGTH: usize = 8;
+
+/// Starting keys to climb from, besides the frequency crack.
+const RESTARTS: usize = 24;
+
+/// Count added to every tetragram, so ones no word has are unlikely but not impossible.
+const SMOOTHING: f64 = 0.01;
+
+/// How likely every run of four characters is in plaintexts made of dictionary words.
+#[derive(Clone, Debug)]
+pub struct Tetragrams {
+    /// Smoothed model of every tetragram
+    model: NgramModel,
+}
+
+impl Tetragrams {
+    /// Count the tetragrams of plaintexts made of `dict`'s words in a random order, including the
+    /// ones spanning the space between two words.
+    pub fn from_bytes_dict(dict: &BytesDictionary) -> Self {
+        let len = ALPHABET.len();
+        let space = ' '.to_num();
+        let mut counts = vec![0.0; len.pow(4)];
+
+        // first and last characters of every word, with the spaces around it
+        let mut first = vec![0.0; len];
+        let mut first_two = vec![0.0; len * len];
+        let mut last = vec![0.0; len];
+        let mut last_two = vec![0.0; len * len];
+
+        // dictionary words already end in a space
+        for word in dict.words() {
+            let padded: Vec<u8> = Some(space)
+                .into_iter()
+                .chain(word.iter().copied())
+                .collect();
+            for window in padded.windows(4) {
+                counts[index(window)] += 1.0;
+            }
+
+            let n = padded.len();
+            if n >= 3 {
+                first[padded[1] as usize] += 1.0;
+                first_two[padded[1] as usize * len + padded[2] as usize] += 1.0;
+                last[padded[n - 2] as usize] += 1.0;
+                last_two[padded[n - 3] as usize * len + padded[n - 2] as usize] += 1.0;
+            }
+        }
+
+        // "yz a" and "z ab", for every pair of words next to each other
+        let words = dict.len().max(1) as f64;
+        for a in 0..len {
+            for yz in 0..len * len {
+                let (y, z) = (yz / len, yz % len);
+                counts[((y * len + z) * len + space as usize) * len + a] +=
+                    last_two[yz] * first[a] / words;
+                counts[((a * len + space as usize) * len + y) * len + z] +=
+                    last[a] * first_two[yz] / words;
+            }
+        }
+
+        let model = NgramModel::from_counts(4, len, &counts, SMOOTHING)
+            .expect("one count for every tetragram");
+        Self { model }
+    }
+
+    /// How unlike dictionary words `plaintext` is, by the surprise of every run of four
+    /// characters. Lower is better.
+    pub fn score(&self, plaintext: &[u8]) -> f64 {
+        self.model.score(plaintext)
+    }
+}
+
+fn index(window: &[u8]) -> usize {
+    window
+        .iter()
+        .fold(0, |index, &c| index * ALPHABET.len() + c as usize)
+}
+
+/// Crack `ciphertext` at a short `keylength` by hill climbing over whole keys, scored by
+/// `tetragrams`. Key symbols in `pinned` are kept as they are.
+///
+/// The climb starts from the frequency crack, and from a few random keys, changing one column's
+/// shift at a time while the score goes down. The best plaintext found is returned, with the
+/// frequency crack's confidence scaled by how much the tetragram score changed.
+pub fn crack_short(
+    ciphertext: &[u8],
+    keylength: usize,
+    baseline: &Frequencies,
+    tetragrams: &Tetragrams,
+    pinned: &[Option<i8>],
+) -> CrackResult {
+    let frequency_crack = crack_pinned(ciphertext, keylength, baseline, pinned);
+    if keylength == 0 || ciphertext.len() < 4 {
+        return frequency_crack;
+    }
+
+    let len = ALPHABET.len() as i8;
+    let free: Vec<usize> = (0..keylength.min(ciphertext.len()))
+        .filter(|&column| pinned.get(column).copied().flatten().is_none())
+        .collect();
+    let decrypt = |shifts: &[i8], plaintext: &mut Vec<u8>| {
+        plaintext.clear();
+        plaintext.extend(
+            ciphertext
+                .iter()
+                .enumerate()
+                .map(|(index, c)| c.shift(shifts[index % keylength])),
+        );
+    };
+
+    let start: Vec<i8> = (0..keylength)
+        .map(|column| match ciphertext.get(column) {
+            Some(&c) => (frequency_crack.plaintext[column] as i8 - c as i8).rem_euclid(len),
+            None => 0,
+        })
+        .collect();
+    let original = tetragrams.score(&frequency_crack.plaintext);
+    let mut best = (original, frequency_crack.plaintext.clone());
+
+    let mut rng = Rng::default();
+    let mut plaintext = Vec::with_capacity(ciphertext.len());
+    for restart in 0..=RESTARTS {
+        let mut shifts = start.clone();
+        if restart > 0 {
+            for &column in &free {
+                shifts[column] = rng.gen_range(0..ALPHABET.len()) as i8;
+            }
+        }
+        decrypt(&shifts, &mut plaintext);
+        let mut score = tetragrams.score(&plaintext);
+
+        // move one column at a time to its best shift, until no column moves
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for &column in &free {
+                let mut kept = shifts[column];
+                for shift in 0..len {
+                    if shift == kept {
+                        continue;
+                    }
+                    shifts[column] = shift;
+                    decrypt(&shifts, &mut plaintext);
+                    let trial = tetragrams.score(&plaintext);
+                    if trial < score {
+                        score = trial;
+                        kept = shift;
+                        improved = true;
+                    }
+                }
+                shifts[column] = kept;
+            }
+        }
+
+        if score < best.0 {
+            decrypt(&shifts, &mut plaintext);
+            best = (score, plaintext.clone());
+        }
+    }
+
+    CrackResult {
+        plaintext: best.1,
+        confidence: match original {
+            original if original > 0.0 => frequency_crack.confidence * best.0 / original,
+            _ => frequency_crack.confidence,
+        },
+        timed_out: false,
+    }
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::crack::DictionaryStats;
+    use crate::prelude::*;
+    use crate::utils::{bytes_to_str, str_to_bytes};
+
+    #[test]
+    fn tetragram_scores() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let tetragrams = Tetragrams::from_bytes_dict(&BytesDictionary::from_dict(&dict));
+
+        // words, and the spaces between them, score better than the same characters shuffled
+        let plaintext = str_to_bytes(&Generator::with_dict(&dict).generate_words(10));
+        let mut shuffled = plaintext.clone();
+        shuffled.rotate_left(3);
+        shuffled.reverse();
+        assert!(tetragrams.score(&plaintext) < tetragrams.score(&shuffled));
+    }
+
+    #[test]
+    fn cracks_short_ciphertext() {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let tetragrams = Tetragrams::from_bytes_dict(&BytesDictionary::from_dict(&dict));
+        let stats = DictionaryStats::from_dict(&dict);
+
+        // about eight characters a column, too few for frequencies alone
+        let mut plaintext = Generator::with_dict(&dict).generate_words(20);
+        plaintext.truncate(64);
+        let plaintext = plaintext[..plaintext.rfind(' ').unwrap()].to_string();
+        let key = vec![4, 20, 11, 0, 17, 9, 2, 25];
+        let ciphertext =
+            Encryptor::new(key.clone(), RepeatingKey, Rng::default()).encrypt(&plaintext);
+        let ciphertext = str_to_bytes(&ciphertext);
+
+        let result = crack_short(&ciphertext, key.len(), &stats.letters, &tetragrams, &[]);
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
+
+        // pinned key symbols are kept, even wrong ones
+        let mut pinned = vec![None; key.len()];
+        pinned[0] = Some(key[0] + 1);
+        let result = crack_short(&ciphertext, key.len(), &stats.letters, &tetragrams, &pinned);
+        assert_eq!(result.plaintext[0], ciphertext[0].shift(-key[0] - 1));
+    }
+}
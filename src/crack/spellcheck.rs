@@ -1,100 +1,463 @@
 //! Module for correcting nearly perfect plaintext, into a plausible plaintext that actually could
 //! have been generated from the source dictionary.
 
-use super::CrackResult;
-use crate::dict::{levenshtein, BytesDictionary};
+use super::explain::align;
+use super::{CrackResult, CrackScratch, DictionaryStats};
+use crate::dict::{shift_levenshtein_with_row, BytesDictionary};
+use crate::utils::{bytes_to_str, CharToNum};
 
 use std::cmp::min;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+/// How a [`SpellChecker`] weighs the words a run of characters could be split into. Each word is
+/// scored by
+///
+/// ```text
+/// characters used ^ length_weight / edit distance ^ edit_weight
+///     * exp(-freq_weight * how unlikely the word's length is)
+/// ```
+///
+/// and the best scoring word is picked next. How unlikely each length is comes from the
+/// [`DictionaryStats`] given with [`SpellChecker::with_stats`], without which `freq_weight` does
+/// nothing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SegmentationWeights {
+    /// How much longer words are preferred over splitting into several shorter ones
+    pub length_weight: f64,
+    /// How much closer words are preferred over longer ones
+    pub edit_weight: f64,
+    /// How much words of the lengths the dictionary often has are preferred
+    pub freq_weight: f64,
+}
+
+impl Default for SegmentationWeights {
+    /// Picked by [`eval`][`super::eval`]'s `segmentation_experiments`.
+    fn default() -> Self {
+        Self {
+            length_weight: 1.0,
+            edit_weight: 1.0,
+            freq_weight: 0.1,
+        }
+    }
+}
 
 struct Word<'a> {
     word: &'a [u8],
     score: usize,
     bytes_used: usize,
+    /// How unlikely the word's length is by the dictionary's word lengths, or 0.0 without them
+    length_surprise: f64,
 }
 
 impl<'a> Word<'a> {
     // higher score is better
     //
-    // prefer longer words and smaller edit-distance
-    fn score(&self) -> usize {
-        (self.bytes_used as f32 / self.score as f32 * 1000.0) as usize
+    // prefer longer words and smaller edit-distance, and words of likely lengths
+    fn score(&self, weights: &SegmentationWeights) -> usize {
+        let used = (self.bytes_used as f64).powf(weights.length_weight);
+        let edits = (self.score as f64).powf(weights.edit_weight);
+        (used / edits * 1000.0 * (-self.length_surprise * weights.freq_weight).exp()) as usize
+    }
+}
+
+/// Most slices a [`SpellChecker`] remembers the closest word to. Once full, the slices that were
+/// looked up the least recently are forgotten, half of them at a time.
+pub const SPELLCHECK_CACHE: usize = 4096;
+
+/// Spellchecks cracks against one dictionary, remembering the closest dictionary word to the
+/// slices it has looked up. The same slices come up over and over (every "the " of a crack, and
+/// the same crack again with a few columns moved), so reusing a spellchecker across cracks of the
+/// same dictionary skips most of the searches through it.
+pub struct SpellChecker<'d> {
+    dict: &'d BytesDictionary,
+    stats: Option<&'d DictionaryStats>,
+    weights: SegmentationWeights,
+    /// Closest word to each slice looked up recently
+    cache: HashMap<Vec<u8>, Lookup<'d>>,
+    /// Lookups so far, to tell how recently each slice was looked up
+    lookups: u64,
+    /// Row of edit distances for levenshtein
+    row: Vec<usize>,
+}
+
+/// The closest word to a slice, as remembered by a [`SpellChecker`].
+struct Lookup<'d> {
+    word: &'d [u8],
+    distance: usize,
+    /// Lookup this slice was last looked up at
+    used: u64,
+}
+
+impl<'d> SpellChecker<'d> {
+    pub fn new(dict: &'d BytesDictionary) -> Self {
+        Self {
+            dict,
+            stats: None,
+            weights: SegmentationWeights::default(),
+            cache: HashMap::new(),
+            lookups: 0,
+            row: Vec::new(),
+        }
+    }
+
+    /// Weigh words by how likely their length is, like [`spellcheck_aligned_with_stats`].
+    pub fn with_stats(mut self, stats: &'d DictionaryStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Weigh the words runs of characters could be split into by `weights`.
+    pub fn with_weights(mut self, weights: SegmentationWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Same as [`BytesDictionary::best_levenshtein`], remembering the result.
+    pub fn best_levenshtein(&mut self, word: &[u8]) -> (&'d [u8], usize) {
+        self.lookups += 1;
+        if let Some(lookup) = self.cache.get_mut(word) {
+            lookup.used = self.lookups;
+            return (lookup.word, lookup.distance);
+        }
+
+        if self.cache.len() >= SPELLCHECK_CACHE {
+            // at most half the cache was looked up in the last half a cache of lookups
+            let recent = self.lookups.saturating_sub(SPELLCHECK_CACHE as u64 / 2);
+            self.cache.retain(|_, lookup| lookup.used >= recent);
+        }
+
+        let (closest, distance) = self.dict.best_levenshtein_with_row(word, &mut self.row);
+        self.cache.insert(
+            word.to_vec(),
+            Lookup {
+                word: closest,
+                distance,
+                used: self.lookups,
+            },
+        );
+        (closest, distance)
+    }
+
+    /// Same as [`spellcheck`].
+    pub fn spellcheck(&mut self, cracked: &CrackResult) -> CrackResult {
+        self.spellcheck_aligned(cracked).0
+    }
+
+    /// Same as [`spellcheck_aligned`].
+    pub fn spellcheck_aligned(
+        &mut self,
+        cracked: &CrackResult,
+    ) -> (CrackResult, Vec<Option<usize>>) {
+        let correction = self.correct(cracked);
+        (correction.checked, correction.alignment)
+    }
+
+    /// Spellcheck `cracked` like [`spellcheck_aligned`], along with every word that was changed.
+    ///
+    /// Words of the crack that are already dictionary words are kept as they are, and only the runs
+    /// of words between them that aren't are split into the dictionary words that fit them best,
+    /// so a mostly right crack takes a few lookups instead of a search for every character.
+    pub fn correct(&mut self, cracked: &CrackResult) -> Correction {
+        let text = cracked.plaintext.as_slice();
+        let space = ' '.to_num();
+        let mut segmented = Segmented {
+            plaintext: Vec::with_capacity(text.len()),
+            alignment: Vec::with_capacity(text.len()),
+            corrections: Vec::new(),
+            last_corrected: false,
+        };
+
+        // start of the run of words that aren't dictionary words, up to the current word
+        let mut run = 0;
+        let mut start = 0;
+        for word in text.split(|&c| c == space) {
+            let end = start + word.len();
+            if self.dict.contains(word) {
+                self.segment(text, run..start, &mut segmented);
+
+                // the word as it is, with its space
+                segmented.plaintext.extend_from_slice(word);
+                segmented.plaintext.push(space);
+                segmented.alignment.extend((start..end).map(Some));
+                segmented.alignment.push((end < text.len()).then_some(end));
+                segmented.last_corrected = false;
+                run = (end + 1).min(text.len());
+            }
+            start = end + 1;
+        }
+        self.segment(text, run..text.len(), &mut segmented);
+
+        let Segmented {
+            mut plaintext,
+            mut alignment,
+            mut corrections,
+            last_corrected,
+        } = segmented;
+
+        // pop off the last space because all dictionary words come with a space
+        plaintext.pop();
+        alignment.pop();
+        if let Some(last) = corrections.last_mut().filter(|_| last_corrected) {
+            last.replacement.pop();
+            last.distance =
+                shift_levenshtein_with_row(&last.original, &last.replacement, &mut self.row);
+        }
+        corrections.retain(|correction| correction.original != correction.replacement);
+
+        let confidence = changed_fraction(text, &plaintext, &alignment);
+        Correction {
+            checked: CrackResult {
+                plaintext,
+                confidence,
+                timed_out: cracked.timed_out,
+            },
+            alignment,
+            corrections,
+        }
+    }
+
+    /// Split `text[region]` into the dictionary words that fit it best from the left, adding them
+    /// to `segmented`.
+    fn segment(&mut self, text: &[u8], region: Range<usize>, segmented: &mut Segmented) {
+        // one past the longest word in the dictionary given (with its space), as an exclusive bound
+        let longest_word = self.dict.longest_word() + 1;
+
+        // a slice where the start is always pointing to the next word to spell check, and the end
+        // goes all the way to the end of the region.
+        let mut next_slice = &text[region.clone()];
+
+        while next_slice.len() > 1 {
+            // farthest right to try to match, up to all of the rest, so the last word isn't cut
+            // short
+            let rbound = min(longest_word, next_slice.len() + 1);
+
+            // find the next possible words, keeping the best (the last one, on ties)
+            let mut best: Option<Word> = None;
+            for bytes_used in 1..rbound {
+                let (word, score) = self.best_levenshtein(&next_slice[..bytes_used]);
+                // dictionary words come with their space
+                let length_surprise = self
+                    .stats
+                    .and_then(|stats| stats.word_length_surprise(word.len() - 1))
+                    .unwrap_or(0.0);
+                let word = Word {
+                    word,
+                    score,
+                    bytes_used,
+                    length_surprise,
+                };
+                let weights = &self.weights;
+                if best
+                    .as_ref()
+                    .is_none_or(|best| word.score(weights) >= best.score(weights))
+                {
+                    best = Some(word);
+                }
+            }
+            let best = best.unwrap();
+
+            // add the best word to the plaintext, lined up with the characters it was corrected
+            // from
+            let used = &next_slice[..best.bytes_used];
+            let start = region.end - next_slice.len();
+            segmented.plaintext.extend_from_slice(best.word);
+            segmented.alignment.extend(
+                align(best.word.len(), used.len(), |i, j| best.word[i] == used[j])
+                    .into_iter()
+                    .map(|j| j.map(|j| start + j)),
+            );
+            segmented.corrections.push(WordCorrection {
+                position: start,
+                original: used.to_vec(),
+                replacement: best.word.to_vec(),
+                distance: best.score,
+            });
+            segmented.last_corrected = true;
+
+            // advance to the next word by however many characters we read
+            next_slice = &next_slice[best.bytes_used..];
+        }
+    }
+}
+
+/// The spellchecked plaintext so far, while [`SpellChecker::correct`] builds it.
+struct Segmented {
+    plaintext: Vec<u8>,
+    alignment: Vec<Option<usize>>,
+    /// Every word split out of the runs of words that weren't dictionary words, changed or not
+    corrections: Vec<WordCorrection>,
+    /// Whether the last word came from `corrections`
+    last_corrected: bool,
+}
+
+/// A crack spellchecked by [`SpellChecker::correct`], and what was changed to get there.
+#[derive(Clone)]
+pub struct Correction {
+    /// The spellchecked crack, as [`spellcheck`] gives it
+    pub checked: CrackResult,
+    /// For every character of the spellchecked plaintext, the index of the character of the crack
+    /// it was corrected from, or `None` if spellchecking added it, as [`spellcheck_aligned`] gives
+    /// it
+    pub alignment: Vec<Option<usize>>,
+    /// Every word that was changed, in order
+    pub corrections: Vec<WordCorrection>,
+}
+
+/// One word of a crack that spellchecking changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WordCorrection {
+    /// Index of the first character of `original` in the crack
+    pub position: usize,
+    /// The characters of the crack that were read as one word, with the space after it if there
+    /// was one
+    pub original: Vec<u8>,
+    /// The dictionary word they were corrected to, with its space unless it ends the plaintext
+    pub replacement: Vec<u8>,
+    /// Edit distance from `original` to `replacement`, by
+    /// [`shift_levenshtein`][`crate::dict::shift_levenshtein`] in units of
+    /// [`EDIT_COST`][`crate::dict::EDIT_COST`]
+    pub distance: usize,
+}
+
+impl fmt::Display for Correction {
+    /// One line for every word changed, with its position in the crack and its edit distance.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for correction in &self.corrections {
+            writeln!(
+                f,
+                "{:>6} {:?} -> {:?} ({})",
+                correction.position,
+                bytes_to_str(&correction.original),
+                bytes_to_str(&correction.replacement),
+                correction.distance
+            )?;
+        }
+        Ok(())
     }
 }
 
 /// This function exploits the fact that we know the source dictionary (or can guess between a
 /// small number of dictionaries), and uses spell checking strategies to fix up any incorrectly
 /// guessed shift values from the previous step.
+///
+/// The confidence of the result is the fraction of characters changed by spellchecking: the
+/// corrected characters, the added ones and the dropped ones, out of the longer of the two
+/// plaintexts. It doesn't depend on the confidence of `cracked`, so spellchecked cracks at
+/// different keylengths compare fairly with [`best_crack`][`super::best_crack`].
+///
+/// To spellcheck many cracks against the same dictionary, use a [`SpellChecker`].
 #[allow(dead_code)]
 pub fn spellcheck(cracked: &CrackResult, dict: &BytesDictionary) -> CrackResult {
-    //the string we will correct
-    let mut plaintext: Vec<u8> = Vec::with_capacity(cracked.plaintext.len());
+    SpellChecker::new(dict).spellcheck(cracked)
+}
 
-    // the longest word in the dictionary given
-    //
-    // I don't know why it needs a +1 ...
-    let longest_word = dict.words.iter().map(|w| w.len()).max().unwrap() + 1;
-
-    // a slice where the start is always pointing to the next word to spell check, and the end goes
-    // all the way to the end of the given plaintext.
-    let mut next_slice = cracked.plaintext.as_slice();
-
-    // temporary vec to hold scores for scanned words
-    let mut next_words: Vec<Word> = Vec::new();
-
-    while next_slice.len() > 1 {
-        // farthest right to try to match
-        let rbound = min(longest_word, next_slice.len());
-
-        // find the next possible words
-        for bytes_used in 1..rbound {
-            let (word, score) = dict.best_levenshtein(&next_slice[..bytes_used]);
-            let word = Word {
-                word,
-                score,
-                bytes_used,
-            };
-            next_words.push(word);
-        }
+/// Same as [`spellcheck`], reusing the buffers in `scratch`.
+pub fn spellcheck_with_scratch(
+    cracked: &CrackResult,
+    dict: &BytesDictionary,
+    scratch: &mut CrackScratch,
+) -> CrackResult {
+    spellcheck_aligned_with_scratch(cracked, dict, scratch).0
+}
 
-        // pick the best word from next_words
-        let best = next_words.iter().max_by_key(|word| word.score()).unwrap();
+/// Same as [`spellcheck`], along with where each character of the corrected plaintext came from:
+/// the index of the character of `cracked` it was corrected from, or `None` if spellchecking
+/// added it.
+///
+/// Cracked plaintexts line up with their ciphertext, so for Test 2 these are ciphertext positions
+/// too, and ciphertext positions that nothing maps to are the characters spellchecking dropped.
+pub fn spellcheck_aligned(
+    cracked: &CrackResult,
+    dict: &BytesDictionary,
+) -> (CrackResult, Vec<Option<usize>>) {
+    SpellChecker::new(dict).spellcheck_aligned(cracked)
+}
 
-        // add the best word to the plaintext
-        plaintext.extend_from_slice(best.word);
+/// Same as [`spellcheck_aligned`], reusing the buffers in `scratch`.
+pub fn spellcheck_aligned_with_scratch(
+    cracked: &CrackResult,
+    dict: &BytesDictionary,
+    scratch: &mut CrackScratch,
+) -> (CrackResult, Vec<Option<usize>>) {
+    let mut checker = SpellChecker::new(dict);
+    std::mem::swap(&mut checker.row, &mut scratch.levenshtein_row);
+    let checked = checker.spellcheck_aligned(cracked);
+    std::mem::swap(&mut checker.row, &mut scratch.levenshtein_row);
+    checked
+}
 
-        // advance to the next word by however many characters we read
-        next_slice = &next_slice[best.bytes_used..];
+/// Same as [`spellcheck_aligned`], but of words about as good a fit, the one with the likelier
+/// length by the dictionary's word lengths in `stats` wins. So a run of characters isn't as easily
+/// split into a few short words, or merged into a long one, that the dictionary rarely has.
+pub fn spellcheck_aligned_with_stats(
+    cracked: &CrackResult,
+    dict: &BytesDictionary,
+    stats: &DictionaryStats,
+) -> (CrackResult, Vec<Option<usize>>) {
+    SpellChecker::new(dict)
+        .with_stats(stats)
+        .spellcheck_aligned(cracked)
+}
 
-        // clear the next_words vec
-        next_words.clear();
+/// Fraction of characters changed from `original` to `corrected`, lined up by `alignment`.
+fn changed_fraction(original: &[u8], corrected: &[u8], alignment: &[Option<usize>]) -> f64 {
+    let len = original.len().max(corrected.len());
+    if len == 0 {
+        return 0.0;
     }
 
-    // pop off the last space because all dictionary words come with a space
-    plaintext.pop();
+    // corrected characters that aren't the one they line up with, or don't line up with any
+    let replaced = corrected
+        .iter()
+        .zip(alignment)
+        .filter(|(&c, aligned)| aligned.map(|j| original[j]) != Some(c))
+        .count();
+    let kept = alignment.iter().flatten().count();
+    let dropped = original.len() - kept;
 
-    // overall confidence is levenshtein edit distance from what we recovered to the given
-    // near-plaintext. (Not sure how useful this is...)
-    let confidence = levenshtein(&plaintext, &cracked.plaintext) as f64;
+    ((replaced + dropped) as f64 / len as f64).min(1.0)
+}
+
+/// Spellcheck every candidate crack, returning the corrected results in the same order.
+///
+/// Spellchecking is the slowest step of cracking, so with the `parallel` feature the candidates
+/// are spellchecked concurrently on the rayon thread pool. Each thread reuses one
+/// [`SpellChecker`] for the candidates it spellchecks.
+pub fn spellcheck_all(cracked: &[CrackResult], dict: &BytesDictionary) -> Vec<CrackResult> {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        cracked
+            .par_iter()
+            .map_init(
+                || SpellChecker::new(dict),
+                |checker, c| checker.spellcheck(c),
+            )
+            .collect()
+    }
 
-    CrackResult {
-        plaintext,
-        confidence: confidence * cracked.confidence,
+    #[cfg(not(feature = "parallel"))]
+    {
+        let mut checker = SpellChecker::new(dict);
+        cracked.iter().map(|c| checker.spellcheck(c)).collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::dict::Dictionary;
+    use crate::crack::best_crack;
+    use crate::dict::{Dictionary, EDIT_COST};
     use crate::utils::*;
 
     #[test]
     fn testing() {
         let newstring=String::from("wordss wishes this pig the quics brown fox jumpede over the lazy dog cat lion seal fish canary sf f a fish carp sharks");
-        let cracked = CrackResult {
-            plaintext: str_to_bytes(&newstring),
-            confidence: 4000.0,
-        };
+        let cracked = CrackResult::new(str_to_bytes(&newstring), 4000.0);
 
         let targetplaintext=String::from("words wishes that pig the quick brown fox jumped over the lazy dog cat lion seal fish canary sf f a fish carp shark");
         let bytestarget = str_to_bytes(&targetplaintext);
@@ -123,4 +486,223 @@ mod tests {
 
         assert_eq!(&errorcorrect.plaintext, &bytestarget);
     }
+
+    #[test]
+    fn all_in_order() {
+        let dict = BytesDictionary::from_dict(&Dictionary {
+            words: vec!["cat", "dog", "fish", "bird"],
+        });
+
+        let cracked: Vec<CrackResult> = ["cat dgo fish", "bird bird cot", "fsh dog"]
+            .iter()
+            .map(|s| CrackResult::new(str_to_bytes(s), 1.0))
+            .collect();
+
+        let all = spellcheck_all(&cracked, &dict);
+        assert_eq!(all.len(), cracked.len());
+        for (corrected, original) in all.iter().zip(cracked.iter()) {
+            assert_eq!(corrected.plaintext, spellcheck(original, &dict).plaintext);
+        }
+        assert_eq!(bytes_to_str(&all[0].plaintext), "cat dog fish");
+    }
+
+    #[test]
+    fn confidence() {
+        let dict = BytesDictionary::from_dict(&Dictionary {
+            words: vec!["cat", "dog", "fish", "bird"],
+        });
+        let check = |text: &str, confidence| {
+            spellcheck(&CrackResult::new(str_to_bytes(text), confidence), &dict)
+        };
+
+        // the fraction of characters changed, whatever the confidence before
+        assert_eq!(check("cat dog fish bird", 50.0).confidence, 0.0);
+        assert_eq!(check("cat dgo fish bird", 50.0).confidence, 2.0 / 17.0);
+        assert_eq!(check("cat dog fsh bird", 0.5).confidence, 1.0 / 17.0);
+
+        // a pool of cracks at different keylengths ranks by how much needed correcting, even when
+        // the frequency confidences are on very different scales
+        let pool: Vec<CrackResult> = [
+            ("cxt dxg fxsh bxrd", 0.01),
+            ("cat dgo fish bird", 5.0),
+            ("qzvkq fmwp zzuo", 0.001),
+            ("bat dog fish bird", 800.0),
+        ]
+        .iter()
+        .map(|&(text, confidence)| CrackResult::new(str_to_bytes(text), confidence))
+        .collect();
+        let checked = spellcheck_all(&pool, &dict);
+        let mut ranked: Vec<usize> = (0..pool.len()).collect();
+        ranked.sort_by(|&a, &b| checked[a].confidence.total_cmp(&checked[b].confidence));
+        assert_eq!(ranked, [3, 1, 0, 2]);
+        assert_eq!(
+            bytes_to_str(&best_crack(&checked).plaintext),
+            "cat dog fish bird"
+        );
+        assert!(checked.iter().all(|c| (0.0..=1.0).contains(&c.confidence)));
+    }
+
+    #[test]
+    fn aligned() {
+        let dict = BytesDictionary::from_dict(&Dictionary {
+            words: vec!["cat", "dog", "fish", "bird"],
+        });
+
+        let cracked = CrackResult::new(str_to_bytes("cat dgo fsh birxd dog"), 1.0);
+        let (checked, alignment) = spellcheck_aligned(&cracked, &dict);
+        assert_eq!(bytes_to_str(&checked.plaintext), "cat dog fish bird dog");
+        assert_eq!(alignment.len(), checked.plaintext.len());
+
+        // "fsh" needs an "i" added
+        let fish = &alignment[8..13];
+        assert_eq!(fish, [Some(8), None, Some(9), Some(10), Some(11)]);
+
+        // characters stay in order, up to the end of the last word
+        let mapped: Vec<usize> = alignment.iter().flatten().copied().collect();
+        assert!(mapped.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(alignment.last(), Some(&Some(cracked.plaintext.len() - 1)));
+    }
+
+    #[test]
+    fn word_lengths() {
+        let dict = Dictionary {
+            words: vec![
+                "bird", "fish", "frog", "newt", "crab", "moth", "wasp", "mole", "hare", "lynx",
+                "toad", "seal", "birds",
+            ],
+        };
+        let stats = DictionaryStats::from_dict(&dict);
+        let dict = BytesDictionary::from_dict(&dict);
+
+        // without the space, "birds" fits as well as "bird", but there's only one five letter word
+        let cracked = CrackResult::new(str_to_bytes("birdsfish"), 1.0);
+        assert_eq!(
+            bytes_to_str(&spellcheck(&cracked, &dict).plaintext),
+            "birds fish"
+        );
+        let (checked, _) = spellcheck_aligned_with_stats(&cracked, &dict, &stats);
+        assert_eq!(bytes_to_str(&checked.plaintext), "bird fish");
+
+        // words that fit exactly are kept whatever their length
+        let cracked = CrackResult::new(str_to_bytes("birds fish"), 1.0);
+        let (checked, _) = spellcheck_aligned_with_stats(&cracked, &dict, &stats);
+        assert_eq!(checked.confidence, 0.0);
+    }
+
+    #[test]
+    fn segmentation_weights() {
+        let dict = Dictionary {
+            words: vec![
+                "bird", "fish", "frog", "newt", "crab", "moth", "wasp", "mole", "hare", "lynx",
+                "toad", "seal", "birds",
+            ],
+        };
+        let stats = DictionaryStats::from_dict(&dict);
+        let dict = BytesDictionary::from_dict(&dict);
+        let cracked = CrackResult::new(str_to_bytes("birdsfish"), 1.0);
+        let spellcheck_with = |weights| {
+            let mut checker = SpellChecker::new(&dict)
+                .with_stats(&stats)
+                .with_weights(weights);
+            bytes_to_str(&checker.spellcheck(&cracked).plaintext)
+        };
+
+        // the default weights are the ones spellcheck_aligned_with_stats uses
+        let default = SegmentationWeights::default();
+        assert_eq!(spellcheck_with(default), "bird fish");
+
+        // without weighing word lengths, the stats don't matter
+        let weights = SegmentationWeights {
+            freq_weight: 0.0,
+            ..default
+        };
+        assert_eq!(spellcheck_with(weights), "birds fish");
+
+        // weighing the characters used enough, the longer word wins despite its unlikely length
+        let weights = SegmentationWeights {
+            length_weight: 2.0,
+            ..default
+        };
+        assert_eq!(spellcheck_with(weights), "birds fish");
+    }
+
+    #[test]
+    fn spell_checker() {
+        let dict = BytesDictionary::from_dict(&Dictionary {
+            words: vec!["cat", "dog", "fish", "bird"],
+        });
+        let cracked = CrackResult::new(str_to_bytes("cta dgo fsh birxd dgo cta"), 1.0);
+
+        // the same as spellchecking without remembering anything, however often it is reused
+        let mut checker = SpellChecker::new(&dict);
+        let (expected, expected_alignment) = spellcheck_aligned(&cracked, &dict);
+        for _ in 0..3 {
+            let (checked, alignment) = checker.spellcheck_aligned(&cracked);
+            assert_eq!(checked.plaintext, expected.plaintext);
+            assert_eq!(checked.confidence, expected.confidence);
+            assert_eq!(alignment, expected_alignment);
+        }
+        assert!(checker.cache.len() < checker.lookups as usize / 3);
+
+        // the least recently looked up slices are forgotten once full
+        let mut checker = SpellChecker::new(&dict);
+        for i in 0..SPELLCHECK_CACHE + 1 {
+            let slice = [i % 27, i / 27 % 27, i / 729].map(|digit| digit as u8);
+            checker.best_levenshtein(&slice);
+        }
+        assert!(checker.cache.len() <= SPELLCHECK_CACHE / 2 + 1);
+        assert!(checker.cache.contains_key(&[19, 16, 5][..]));
+    }
+
+    #[test]
+    fn corrections() {
+        let dict = BytesDictionary::from_dict(&Dictionary {
+            words: vec!["cat", "dog", "fish", "bird"],
+        });
+        let cracked = CrackResult::new(str_to_bytes("cat dgo fsh bird cat"), 1.0);
+        let correction = SpellChecker::new(&dict).correct(&cracked);
+        assert_eq!(
+            bytes_to_str(&correction.checked.plaintext),
+            "cat dog fish bird cat"
+        );
+
+        let changed: Vec<(usize, String, String)> = correction
+            .corrections
+            .iter()
+            .map(|c| {
+                let (original, replacement) = (&c.original, &c.replacement);
+                assert_eq!(
+                    c.distance,
+                    shift_levenshtein_with_row(original, replacement, &mut Vec::new())
+                );
+                (
+                    c.position,
+                    bytes_to_str(original),
+                    bytes_to_str(replacement),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                (4, "dgo ".to_string(), "dog ".to_string()),
+                (8, "fsh ".to_string(), "fish ".to_string())
+            ]
+        );
+
+        // dictionary words are kept without searching for them
+        let mut checker = SpellChecker::new(&dict);
+        let correction = checker.correct(&CrackResult::new(str_to_bytes("cat dog fish"), 1.0));
+        assert_eq!(bytes_to_str(&correction.checked.plaintext), "cat dog fish");
+        assert_eq!(correction.alignment, (0..12).map(Some).collect::<Vec<_>>());
+        assert_eq!(checker.lookups, 0);
+
+        // the last word has no space to correct
+        let cracked = CrackResult::new(str_to_bytes("cat fsh"), 1.0);
+        let correction = SpellChecker::new(&dict).correct(&cracked);
+        assert_eq!(correction.corrections.len(), 1);
+        assert_eq!(correction.corrections[0].replacement, str_to_bytes("fish"));
+        assert_eq!(correction.corrections[0].distance, EDIT_COST);
+        assert_eq!(correction.to_string(), "     4 \"fsh\" -> \"fish\" (4)\n");
+    }
 }
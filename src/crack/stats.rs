@@ -0,0 +1,214 @@
+//! Module for [`DictionaryStats`].
+
+use crate::crack::Frequencies;
+use crate::dict::Dictionary;
+use crate::utils::{str_to_bytes, CharToNum};
+
+use std::sync::OnceLock;
+
+/// Statistics about a dictionary that cracking compares candidate plaintexts against.
+///
+/// These take a pass over every character of every word to compute, so they are computed once per
+/// dictionary and passed around, instead of being recomputed wherever they are needed.
+#[derive(Clone, Debug)]
+pub struct DictionaryStats {
+    /// Letter (and space) frequencies, assuming every word is followed by a space
+    pub letters: Frequencies,
+    /// `bigrams[a][b]` is the fraction of adjacent character pairs that are `a` followed by `b`,
+    /// with the same 0-26 numbering as [`Frequencies`]
+    pub bigrams: Box<[[f32; 27]; 27]>,
+    /// `word_lengths[n]` is the number of words with `n` letters
+    pub word_lengths: Vec<usize>,
+    /// Number of letters in the longest word
+    pub longest_word: usize,
+    /// `bigram_surprise[a][b]` is `-ln` of the add-one smoothed probability of `a` followed by `b`
+    bigram_surprise: Box<[[f64; 27]; 27]>,
+    /// `word_length_surprise[n]` is how much less likely (in nats) an `n` letter word is than a
+    /// word of the most common length, for word lengths 0 through the longest
+    word_length_surprise: Vec<f64>,
+}
+
+/// Penalty for each space pattern that can't come from dictionary words, in
+/// [`DictionaryStats::word_boundary_penalty`].
+pub const IMPOSSIBLE_BOUNDARY: f64 = 4.0;
+
+/// Weight of how unlikely each word's length is, in
+/// [`DictionaryStats::word_boundary_penalty`].
+pub const WORD_LENGTH_WEIGHT: f64 = 0.1;
+
+impl DictionaryStats {
+    /// Compute all statistics for the given dictionary.
+    pub fn from_dict(dict: &Dictionary) -> Self {
+        let space = ' '.to_num();
+
+        let mut bigrams = Box::new([[0.0; 27]; 27]);
+        let mut word_lengths = Vec::new();
+        let mut pairs = 0;
+
+        for word in &dict.words {
+            // count the space before the word and the space after it too, since words are space
+            // separated in plaintexts
+            let mut prev = space;
+            for b in str_to_bytes(word).into_iter().chain(Some(space)) {
+                bigrams[prev as usize][b as usize] += 1.0;
+                prev = b;
+                pairs += 1;
+            }
+
+            if word.len() >= word_lengths.len() {
+                word_lengths.resize(word.len() + 1, 0);
+            }
+            word_lengths[word.len()] += 1;
+        }
+
+        // smoothed with a count of 1 for every pair, so pairs no word has aren't infinitely unlikely
+        let mut bigram_surprise = Box::new([[0.0; 27]; 27]);
+        let smoothed_pairs = (pairs + 27 * 27) as f64;
+        for (surprise, counts) in bigram_surprise.iter_mut().zip(bigrams.iter()) {
+            for (surprise, &count) in surprise.iter_mut().zip(counts) {
+                *surprise = -((count as f64 + 1.0) / smoothed_pairs).ln();
+            }
+        }
+
+        // divide each pair count by the total to get a fraction
+        for row in bigrams.iter_mut() {
+            for v in row.iter_mut() {
+                *v /= pairs.max(1) as f32;
+            }
+        }
+
+        // -ln p(n) of each length, less that of the most common length. Lengths no word has still
+        // get a count of 1, since any length up to the longest word is not impossible
+        let total = (dict.words.len() + word_lengths.len()) as f64;
+        let nll: Vec<f64> = word_lengths
+            .iter()
+            .map(|&count| -((count + 1) as f64 / total).ln())
+            .collect();
+        let likeliest = nll.iter().copied().fold(f64::INFINITY, f64::min);
+        let word_length_surprise = nll.iter().map(|nll| nll - likeliest).collect();
+
+        Self {
+            letters: Frequencies::from_dict(dict),
+            bigrams,
+            bigram_surprise,
+            longest_word: word_lengths.len().saturating_sub(1),
+            word_lengths,
+            word_length_surprise,
+        }
+    }
+
+    /// How unlikely `a` followed by `b` is, as `-ln` of its smoothed probability.
+    pub fn bigram_surprise(&self, a: u8, b: u8) -> f64 {
+        self.bigram_surprise[a as usize][b as usize]
+    }
+
+    /// How much less likely (in nats) a word of `letters` letters is than a word of the most common
+    /// length. `None` for words longer than any in the dictionary.
+    pub fn word_length_surprise(&self, letters: usize) -> Option<f64> {
+        self.word_length_surprise.get(letters).copied()
+    }
+
+    /// How unlike dictionary words `plaintext` is, by the surprise of every adjacent pair of
+    /// characters. Lower is better.
+    pub fn bigram_score(&self, plaintext: &[u8]) -> f64 {
+        plaintext
+            .windows(2)
+            .map(|pair| self.bigram_surprise(pair[0], pair[1]))
+            .sum()
+    }
+
+    /// How unlike a sequence of dictionary words the spaces of `plaintext` are. Lower is better.
+    ///
+    /// Every impossible pattern costs [`IMPOSSIBLE_BOUNDARY`]: two spaces in a row, a space at the
+    /// start or end, or a word longer than any in the dictionary. On top of that, every word adds
+    /// how much less likely its length is than the most common length, by the dictionary's word
+    /// lengths, times [`WORD_LENGTH_WEIGHT`].
+    pub fn word_boundary_penalty(&self, plaintext: &[u8]) -> f64 {
+        let space = ' '.to_num();
+        let mut penalty = 0.0;
+
+        for end in [plaintext.first(), plaintext.last()] {
+            if end == Some(&space) {
+                penalty += IMPOSSIBLE_BOUNDARY;
+            }
+        }
+
+        for word in plaintext.split(|&b| b == space) {
+            match self.word_length_surprise(word.len()) {
+                // empty words come from the spaces counted above and below
+                _ if word.is_empty() => {}
+                Some(surprise) => penalty += surprise * WORD_LENGTH_WEIGHT,
+                None => penalty += IMPOSSIBLE_BOUNDARY,
+            }
+        }
+
+        // every two spaces in a row
+        penalty
+            + plaintext
+                .windows(2)
+                .filter(|pair| pair[0] == space && pair[1] == space)
+                .count() as f64
+                * IMPOSSIBLE_BOUNDARY
+    }
+
+    /// Statistics of the project dictionary, computed on first use.
+    pub fn project() -> &'static Self {
+        static STATS: OnceLock<DictionaryStats> = OnceLock::new();
+        STATS.get_or_init(|| {
+            let mut words = include_str!("../../words/default.txt").to_string();
+            Self::from_dict(&Dictionary::from_string(&mut words))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_dict() {
+        let dict = Dictionary {
+            words: vec!["ab", "b", "abc"],
+        };
+        let stats = DictionaryStats::from_dict(&dict);
+
+        assert_eq!(stats.word_lengths, vec![0, 1, 1, 1]);
+        assert_eq!(stats.longest_word, 3);
+
+        // " ab " " b " " abc " has 3 + 2 + 4 = 9 pairs
+        let a = 'a'.to_num() as usize;
+        let b = 'b'.to_num() as usize;
+        let space = ' '.to_num() as usize;
+        assert_eq!(stats.bigrams[a][b], 2.0 / 9.0);
+        assert_eq!(stats.bigrams[space][a], 2.0 / 9.0);
+        assert_eq!(stats.bigrams[b][space], 2.0 / 9.0);
+        let total: f32 = stats.bigrams.iter().flatten().sum();
+        assert!((total - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn word_boundaries() {
+        let dict = Dictionary {
+            words: vec!["ab", "b", "abc", "cab"],
+        };
+        let stats = DictionaryStats::from_dict(&dict);
+        let penalty = |plaintext: &str| stats.word_boundary_penalty(&str_to_bytes(plaintext));
+
+        // three letter words are the most common, and cost nothing
+        assert_eq!(penalty("abc cab"), 0.0);
+        assert!(penalty("ab b") > 0.0);
+        assert!(penalty("ab b") < IMPOSSIBLE_BOUNDARY);
+
+        assert_eq!(penalty("abc  cab"), IMPOSSIBLE_BOUNDARY);
+        assert_eq!(penalty(" abc cab "), 2.0 * IMPOSSIBLE_BOUNDARY);
+        assert_eq!(penalty("abcab"), IMPOSSIBLE_BOUNDARY);
+    }
+
+    #[test]
+    fn project_is_memoized() {
+        assert!(std::ptr::eq(
+            DictionaryStats::project(),
+            DictionaryStats::project()
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+//! Module for picking how to crack a ciphertext by its length.
+//!
+//! No one [`Pipeline`] suits every ciphertext. A short ciphertext has too few characters in each
+//! column for keylength guessing or letter frequencies, so searching small keys whole works
+//! better. A long one has plenty, and is worth refining and spellchecking. In between, column
+//! frequencies alone are a quick answer. A [`Dispatch`] picks the [`Strategy`] for a ciphertext,
+//! and notes whether known plaintexts are checked first, so the decision can be shown along with
+//! the crack.
+
+use crate::crack::pipeline::{normalize, Pipeline};
+use crate::crack::MAX_SHORT_KEYLENGTH;
+
+use std::fmt;
+
+/// Ciphertexts shorter than this, in characters of the alphabet, are cracked with
+/// [`Strategy::ShortKeySearch`].
+pub const SHORT_TEXT: usize = 64;
+
+/// Ciphertexts at least this long are cracked with [`Strategy::HillClimbSpellcheck`].
+pub const LONG_TEXT: usize = 200;
+
+/// How a ciphertext of dictionary words is cracked, each a [`Pipeline`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Search every key up to [`MAX_SHORT_KEYLENGTH`] symbols whole by tetragrams, then
+    /// spellcheck
+    ShortKeySearch,
+    /// Guess keylengths and crack each column by letter frequencies, fixing word boundaries
+    ColumnFrequency,
+    /// Every stage: column frequencies, refined by hill climbing on neighbouring columns,
+    /// spellchecked and verified
+    HillClimbSpellcheck,
+}
+
+impl Strategy {
+    /// The strategy for a ciphertext of `length` characters of the alphabet.
+    pub fn for_length(length: usize) -> Self {
+        if length < SHORT_TEXT {
+            Self::ShortKeySearch
+        } else if length < LONG_TEXT {
+            Self::ColumnFrequency
+        } else {
+            Self::HillClimbSpellcheck
+        }
+    }
+
+    /// Name of the strategy, for printing in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ShortKeySearch => "short key search",
+            Self::ColumnFrequency => "column frequency",
+            Self::HillClimbSpellcheck => "hill climb and spellcheck",
+        }
+    }
+
+    /// The stages the strategy cracks with.
+    pub fn pipeline(&self) -> Pipeline {
+        let names: &[&str] = match self {
+            Self::ShortKeySearch => &["normalize", "short", "spellcheck", "rank"],
+            Self::ColumnFrequency => &["normalize", "keylength", "crack", "boundaries", "rank"],
+            Self::HillClimbSpellcheck => return Pipeline::default(),
+        };
+        Pipeline::from_names(names.iter().copied()).expect("every stage name exists")
+    }
+}
+
+/// Which strategy a ciphertext is cracked with, and why.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dispatch {
+    /// Characters of the alphabet in the ciphertext
+    pub length: usize,
+    /// Known plaintexts checked before cracking (Test 1). With none, the ciphertext is only
+    /// cracked as dictionary words.
+    pub known_plaintexts: usize,
+    /// How the ciphertext is cracked if it isn't a known plaintext
+    pub strategy: Strategy,
+}
+
+impl Dispatch {
+    /// Pick the strategy for `ciphertext`, with `known_plaintexts` candidates to check first.
+    pub fn new(ciphertext: &str, known_plaintexts: usize) -> Self {
+        let length = normalize(ciphertext).len();
+        Self {
+            length,
+            known_plaintexts,
+            strategy: Strategy::for_length(length),
+        }
+    }
+
+    /// Whether the known plaintext classifier runs before the strategy.
+    pub fn checks_known(&self) -> bool {
+        self.known_plaintexts > 0
+    }
+}
+
+impl fmt::Display for Dispatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let why = match self.strategy {
+            Strategy::ShortKeySearch => format!(
+                "shorter than {}, keys up to {}",
+                SHORT_TEXT, MAX_SHORT_KEYLENGTH
+            ),
+            Strategy::ColumnFrequency => format!("{} to {}", SHORT_TEXT, LONG_TEXT - 1),
+            Strategy::HillClimbSpellcheck => format!("at least {}", LONG_TEXT),
+        };
+        writeln!(
+            f,
+            "strategy:  {}, for {} characters ({})",
+            self.strategy.name(),
+            self.length,
+            why
+        )?;
+        if self.checks_known() {
+            writeln!(
+                f,
+                "           after the known plaintext classifier, over {} candidates",
+                self.known_plaintexts
+            )?;
+        }
+        writeln!(
+            f,
+            "pipeline:  {}",
+            self.strategy.pipeline().names().join(" ")
+        )
+    }
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::prelude::*;
+    use crate::utils::bytes_to_str;
+
+    #[test]
+    fn by_length() {
+        assert_eq!(Strategy::for_length(0), Strategy::ShortKeySearch);
+        assert_eq!(Strategy::for_length(SHORT_TEXT), Strategy::ColumnFrequency);
+        assert_eq!(
+            Strategy::for_length(LONG_TEXT - 1),
+            Strategy::ColumnFrequency
+        );
+        assert_eq!(
+            Strategy::for_length(LONG_TEXT),
+            Strategy::HillClimbSpellcheck
+        );
+
+        // characters outside the alphabet don't count
+        let dispatch = Dispatch::new(&"a!".repeat(SHORT_TEXT), 0);
+        assert_eq!(dispatch.length, SHORT_TEXT);
+        assert_eq!(dispatch.strategy, Strategy::ColumnFrequency);
+        assert!(!dispatch.checks_known());
+
+        let text = Dispatch::new("hello", 5).to_string();
+        assert!(
+            text.contains("short key search, for 5 characters"),
+            "{}",
+            text
+        );
+        assert!(text.contains("over 5 candidates"), "{}", text);
+        assert!(
+            text.contains("pipeline:  normalize short spellcheck rank"),
+            "{}",
+            text
+        );
+        assert_eq!(
+            Strategy::HillClimbSpellcheck.pipeline().names(),
+            Pipeline::default().names()
+        );
+    }
+
+    #[test]
+    fn adaptive_cracker() {
+        let cracker = Cracker::default().with_adaptive_strategy();
+        let encrypt = |plaintext: &str, key: Vec<i8>| {
+            Encryptor::new(key, RepeatingKey, Rng::default()).encrypt(plaintext)
+        };
+
+        // too short for column frequencies, about ten characters a column
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let mut gen = Generator::with_dict(&dict);
+        let mut plaintext = gen.generate_words(20);
+        plaintext.truncate(SHORT_TEXT - 1);
+        let plaintext = &plaintext[..plaintext.rfind(' ').unwrap()];
+        let ciphertext = encrypt(plaintext, vec![4, 20, 11, 0, 17, 9]);
+        let dispatch = cracker.dispatch(&ciphertext);
+        assert_eq!(dispatch.strategy, Strategy::ShortKeySearch);
+        assert!(dispatch.checks_known());
+        let result = cracker.crack(&ciphertext);
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
+
+        // long enough for every stage
+        let plaintext = gen.generate_words(60);
+        let ciphertext = encrypt(&plaintext, vec![2, 7, 1, 8, 2, 8]);
+        assert_eq!(
+            cracker.dispatch(&ciphertext).strategy,
+            Strategy::HillClimbSpellcheck
+        );
+        let result = cracker.crack(&ciphertext);
+        assert_eq!(bytes_to_str(&result.plaintext), plaintext);
+    }
+}
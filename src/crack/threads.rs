@@ -0,0 +1,141 @@
+//! Module for how many threads cracking runs on, and which cores they run on.
+//!
+//! Every pool of threads in the crate starts [`threads`] threads unless told otherwise: the
+//! worker pool, campaigns, the crack server, and [`CrackOptions`][`crate::crack::CrackOptions`].
+//! That is the number of cores, unless [`configure`] said otherwise. With the `parallel` feature,
+//! the keylength and spellchecking stages of a single crack run on rayon's global pool, which
+//! [`configure`] sizes too, and [`install`] only starts another pool for cracks that ask for a
+//! different number of threads. So one setting controls every thread the process starts.
+//!
+//! With the `affinity` feature, threads can also be pinned to a core each, so a benchmark or a
+//! long campaign isn't moved between cores by the scheduler.
+
+use anyhow::bail;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Number of threads set by [`configure`], or 0 if it wasn't called.
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether [`configure`] asked for threads to be pinned to cores.
+static PIN: AtomicBool = AtomicBool::new(false);
+
+/// How many threads the process cracks with, and whether they are pinned to cores.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ThreadConfig {
+    /// Number of threads for every pool. `None` is one per core, from [`available_threads`].
+    pub threads: Option<usize>,
+    /// Pin every pool's `n`th thread to the `n`th core, wrapping around. Needs the `affinity`
+    /// feature.
+    pub pin: bool,
+}
+
+/// Number of threads the system can run at once, or 1 if it can't tell.
+pub fn available_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Number of threads pools start when not told how many: as [`configure`]d, or one per core.
+pub fn threads() -> usize {
+    match THREADS.load(Ordering::Relaxed) {
+        0 => available_threads(),
+        n => n,
+    }
+}
+
+/// Set the number of threads for every pool and whether they are pinned to cores, and with the
+/// `parallel` feature, build rayon's global pool to match. Call this before anything cracks, since
+/// rayon's global pool can only be built once; fails if it was already built.
+pub fn configure(config: ThreadConfig) -> anyhow::Result<()> {
+    if config.threads == Some(0) {
+        bail!("can't crack with 0 threads");
+    }
+    if config.pin && !cfg!(feature = "affinity") {
+        bail!("pinning threads to cores needs the affinity feature");
+    }
+
+    THREADS.store(config.threads.unwrap_or(0), Ordering::Relaxed);
+    PIN.store(config.pin, Ordering::Relaxed);
+
+    #[cfg(feature = "parallel")]
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads())
+        .start_handler(pin_current)
+        .build_global()?;
+
+    Ok(())
+}
+
+/// Pin the calling thread to core `index`, wrapping around the cores, if [`configure`] asked for
+/// pinning. Every pool calls this from its `index`th thread as the thread starts.
+pub fn pin_current(index: usize) {
+    if !PIN.load(Ordering::Relaxed) {
+        return;
+    }
+
+    #[cfg(feature = "affinity")]
+    if let Some(cores) = core_affinity::get_core_ids().filter(|cores| !cores.is_empty()) {
+        let core = cores[index % cores.len()];
+        if !core_affinity::set_for_current(core) {
+            tracing::warn!(index, core = core.id, "could not pin thread to core");
+        }
+    }
+
+    #[cfg(not(feature = "affinity"))]
+    let _ = index;
+}
+
+/// Run `f` on `num_threads` threads: on rayon's global pool if it has that many, or on a pool
+/// started just for `f` otherwise, pinned like the global pool. Without the `parallel` feature,
+/// `f` just runs on the calling thread.
+pub fn install<R: Send>(num_threads: usize, f: impl FnOnce() -> R + Send) -> R {
+    #[cfg(feature = "parallel")]
+    {
+        let num_threads = num_threads.max(1);
+        if num_threads == rayon::current_num_threads() {
+            return f();
+        }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .start_handler(pin_current)
+            .build();
+        match pool {
+            Ok(pool) => pool.install(f),
+            Err(err) => {
+                tracing::debug!(%err, "could not start threads, running on the global pool");
+                f()
+            }
+        }
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        let _ = num_threads;
+        f()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_and_install() {
+        assert!(available_threads() >= 1);
+        assert!(threads() >= 1);
+
+        // the closure runs whatever the pool, with as many threads as asked for
+        #[cfg(feature = "parallel")]
+        {
+            assert_eq!(install(3, rayon::current_num_threads), 3);
+            let global = rayon::current_num_threads();
+            assert_eq!(install(global, rayon::current_num_threads), global);
+        }
+        assert_eq!(install(1, || 7), 7);
+
+        assert!(configure(ThreadConfig {
+            threads: Some(0),
+            pin: false
+        })
+        .is_err());
+    }
+}
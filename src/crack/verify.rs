@@ -0,0 +1,123 @@
+//! Checking a guess at how a crack was encrypted, by encrypting the crack again.
+//!
+//! [`verify`] re-encrypts a plaintext under a key and scheduler, and compares every character the
+//! scheduler shifts with the key to the ciphertext. Random characters could have been anything, so
+//! they match whatever is there.
+
+use crate::ciphers::schedulers::NextKey;
+use crate::ciphers::KeySchedule;
+use crate::utils::{Key, Shift};
+
+/// How well a plaintext re-encrypts to a ciphertext under a key and scheduler, from [`verify`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VerificationReport {
+    /// Ciphertext characters the scheduler shifts a plaintext character into
+    pub checked: usize,
+    /// Of those, the ones the plaintext character re-encrypts to
+    pub matches: usize,
+    /// Ciphertext characters the scheduler makes random, which match anything
+    pub wildcards: usize,
+    /// Plaintext characters left over once the ciphertext ran out
+    pub leftover: usize,
+}
+
+impl VerificationReport {
+    /// The fraction of the checked and left over characters that re-encrypt, or 1 if there were
+    /// none.
+    pub fn match_fraction(&self) -> f64 {
+        match self.checked + self.leftover {
+            0 => 1.0,
+            total => self.matches as f64 / total as f64,
+        }
+    }
+
+    /// Whether the whole plaintext re-encrypts to the ciphertext, up to the random characters.
+    pub fn is_exact(&self) -> bool {
+        self.matches == self.checked && self.leftover == 0
+    }
+}
+
+/// Re-encrypt `plaintext` with `key` and `scheduler`, comparing it to `ciphertext` everywhere but
+/// the random characters. All three are bytes, and the key is picked from by the scheduler as it
+/// would be for a plaintext of this length.
+///
+/// ```
+/// # use one_team_pad_cipher_cracker::ciphers::schedulers::PeriodicRand;
+/// # use one_team_pad_cipher_cracker::crack::verify;
+/// # use one_team_pad_cipher_cracker::utils::{str_to_bytes, Key};
+/// // every other character is random
+/// let sched = PeriodicRand { period: 2, start: 1, overwrite: false };
+/// let key = Key::from(vec![1]);
+/// let report = verify(&str_to_bytes("bxcxd"), &key, &sched, &str_to_bytes("abc"));
+/// assert!(report.is_exact());
+/// assert_eq!(report.wildcards, 2);
+///
+/// let report = verify(&str_to_bytes("bxcxd"), &key, &sched, &str_to_bytes("abd"));
+/// assert_eq!(report.match_fraction(), 2.0 / 3.0);
+/// ```
+pub fn verify<K: KeySchedule + ?Sized>(
+    ciphertext: &[u8],
+    key: &Key,
+    scheduler: &K,
+    plaintext: &[u8],
+) -> VerificationReport {
+    let mut report = VerificationReport::default();
+    let mut plain = plaintext.iter();
+    for (index, &cipher) in ciphertext.iter().enumerate() {
+        match scheduler.schedule(index, key.len(), plaintext.len()) {
+            NextKey::Rand => report.wildcards += 1,
+            NextKey::KeyIndex(key_index) => {
+                report.checked += 1;
+                let encrypted = plain
+                    .next()
+                    .zip(key.get(key_index))
+                    .and_then(|(&plain, &shift)| plain.checked_shift(shift));
+                report.matches += (encrypted == Some(cipher)) as usize;
+            }
+        }
+    }
+    report.leftover = plain.count();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::{PeriodicRand, RepeatingKey};
+    use crate::ciphers::{Cipher, Encryptor};
+    use crate::rng::Rng;
+    use crate::utils::str_to_bytes;
+
+    #[test]
+    fn reports() {
+        let key = Key::from(vec![3, 1, 4, 1, 5]);
+        let sched = PeriodicRand {
+            period: 7,
+            start: 2,
+            overwrite: true,
+        };
+        let plaintext = "the quick brown fox jumps over the lazy dog";
+        let ciphertext = Encryptor::new(key.clone(), sched, Rng::from_seed(3)).encrypt(plaintext);
+        let (cipherbytes, mut plainbytes) = (str_to_bytes(&ciphertext), str_to_bytes(plaintext));
+
+        // the real plaintext, key and scheduler re-encrypt exactly
+        let report = verify(&cipherbytes, &key, &sched, &plainbytes);
+        assert!(report.is_exact());
+        assert_eq!(report.wildcards, 7);
+        assert_eq!(report.checked + report.wildcards, cipherbytes.len());
+
+        // without the random characters, the plaintext is out of step from the first one on
+        let report = verify(&cipherbytes, &key, &RepeatingKey, &plainbytes);
+        assert_eq!(report.checked, cipherbytes.len());
+        assert!(report.match_fraction() < 0.5, "{:?}", report);
+
+        // a wrong character, or one too many
+        plainbytes[0] = (plainbytes[0] + 1) % 27;
+        let report = verify(&cipherbytes, &key, &sched, &plainbytes);
+        assert_eq!(report.checked - report.matches, 1);
+        plainbytes.push(0);
+        assert_eq!(verify(&cipherbytes, &key, &sched, &plainbytes).leftover, 1);
+
+        assert_eq!(verify(&[], &key, &sched, &[]).match_fraction(), 1.0);
+    }
+}
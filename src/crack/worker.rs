@@ -1,164 +1,935 @@
 #![allow(dead_code)]
 
-use crate::ciphers::schedulers::RandomScheduler;
+use crate::ciphers::schedulers::{RandomScheduler, SchedulerKind};
 use crate::ciphers::{Cipher, Encryptor};
-use crate::crack::{best_crack, crack, guesses, spellcheck, Frequencies};
-use crate::dict::{BytesDictionary, Dictionary};
+use crate::crack::cracker::{KnownPlaintexts, TEST1_THRESHOLD};
+use crate::crack::pipeline::{Pipeline, PipelineContext, PipelineState, Spellcheck};
+use crate::crack::threads;
+use crate::crack::{
+    guesses, CrackResult, CrackScratch, DictionaryStats, SegmentationWeights, WordCorrection,
+    MAX_GUESSED_KEYLENGTH,
+};
+use crate::dict::{normalized_levenshtein, BytesDictionary, Dictionary};
 use crate::gen::Generator;
-use crate::rng::{FromRng, Rng};
+use crate::rng::{random_key, splitmix64, FromRng, Rng, KEY_LENGTHS};
 use crate::utils::*;
 
-use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tracing::{debug, debug_span};
 
 pub struct CrackWorker {
-    // recv RandomSchedulers
-    schedulers: Receiver<RandomScheduler>,
-    // send back the RandomScheduler, keylen, and success
-    results: Sender<(u8, u8, RandomScheduler, usize, f32)>,
+    // recv RandomSchedulers, along with the id of their trial and the lengths its key may have,
+    // and other tasks to run
+    schedulers: Receiver<Job>,
+    // send back the outcome of cracking each one, waiting for room or dropping it if the
+    // channel is full
+    results: ResultsSender<TrialResult>,
+    // seed of the whole pool, which every trial seed is derived from
+    seed: u64,
 }
 
-pub type WorkerComms = (
-    Sender<RandomScheduler>,
-    Receiver<(u8, u8, RandomScheduler, usize, f32)>,
-    Vec<std::thread::JoinHandle<()>>,
-);
+/// One generated trial of the worker loop, with everything needed to crack it again later.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrialRecord {
+    /// Unique within one worker pool, in the order schedulers were submitted
+    pub id: u64,
+    /// Seed the key, plaintext and encryptor randomness of this trial were generated from
+    pub seed: u64,
+    pub key: Key,
+    /// Scheduler the plaintext was encrypted with
+    pub scheduler: RandomScheduler,
+    /// Which known plaintext was encrypted (Test 1), or `None` for dictionary words (Test 2)
+    pub known_plaintext: Option<usize>,
+    pub plaintext: String,
+    pub ciphertext: String,
+}
+
+impl TrialRecord {
+    /// Which test the plaintext came from: 1 for a known plaintext, 2 for dictionary words
+    pub fn actual_test(&self) -> u8 {
+        if self.known_plaintext.is_some() {
+            1
+        } else {
+            2
+        }
+    }
+}
 
-pub fn spawn_workers(num_workers: usize) -> WorkerComms {
-    let (sched_in, sched_out) = bounded(128);
-    let (results_in, results_out) = unbounded();
-    let mut rng = Rng::default();
+/// The outcome of one trial of the worker loop: encrypting a plaintext with a scheduler and
+/// trying to crack it again.
+#[derive(Clone, Debug)]
+pub struct TrialResult {
+    /// The trial that was cracked
+    pub trial: TrialRecord,
+    /// Which test the cracker decided the ciphertext came from
+    pub guessed_test: u8,
+    /// The cracked plaintext
+    pub cracked: String,
+    /// Levenshtein distance between the cracked and real plaintext, divided by the plaintext
+    /// length
+    pub error: f64,
+    /// Whether the plaintext was recovered exactly
+    pub success: bool,
+    /// The repeating key that turns the cracked plaintext into the ciphertext, if there is one
+    pub recovered_key: Option<Key>,
+    /// Words spellchecking corrected to get the cracked plaintext, 0 for Test 1
+    pub corrections: usize,
+}
 
-    let mut handles = Vec::new();
+/// What a worker does with a result when the results channel is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WhenFull {
+    /// Wait until there is room, so no result is lost, but cracking stalls behind a slow reader
+    Block,
+    /// Throw the result away and move on to the next trial
+    Drop,
+}
+
+/// How many results a [`WorkerPool`] holds on to before anyone receives them, and what happens to
+/// results beyond that.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResultsChannel {
+    /// Results the channel holds at most
+    pub capacity: usize,
+    pub when_full: WhenFull,
+}
+
+impl Default for ResultsChannel {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            when_full: WhenFull::Block,
+        }
+    }
+}
 
-    for _ in 0..num_workers {
-        let worker = CrackWorker {
-            schedulers: sched_out.clone(),
-            results: results_in.clone(),
+impl ResultsChannel {
+    /// A channel this bounded, and the counters of what its senders did with their results.
+    pub(crate) fn open<T>(&self) -> (ResultsSender<T>, Receiver<T>, Arc<ResultsCounters>) {
+        let (sender, receiver) = bounded(self.capacity.max(1));
+        let counters = Arc::new(ResultsCounters::default());
+        let sender = ResultsSender {
+            sender,
+            when_full: self.when_full,
+            counters: Arc::clone(&counters),
         };
+        (sender, receiver, counters)
+    }
+}
+
+/// The sending side of a channel opened by a [`ResultsChannel`].
+pub(crate) struct ResultsSender<T> {
+    sender: Sender<T>,
+    when_full: WhenFull,
+    counters: Arc<ResultsCounters>,
+}
 
-        let seed = rng.next();
-        let handle = std::thread::spawn(move || worker.crack_loop(seed));
-        handles.push(handle);
+impl<T> Clone for ResultsSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            when_full: self.when_full,
+            counters: Arc::clone(&self.counters),
+        }
     }
+}
 
-    (sched_in, results_out, handles)
+impl<T> ResultsSender<T> {
+    /// Send `result`, waiting for room or dropping it if the channel is full, as the channel was
+    /// opened to. Returns false once nobody is receiving anymore.
+    pub(crate) fn send(&self, result: T) -> bool {
+        let sent = match self.sender.try_send(result) {
+            Ok(()) => true,
+            Err(TrySendError::Full(result)) => match self.when_full {
+                WhenFull::Block => {
+                    self.counters.blocked.fetch_add(1, Ordering::Relaxed);
+                    self.sender.send(result).is_ok()
+                }
+                WhenFull::Drop => {
+                    self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                    debug!("results channel full, dropping result");
+                    return true;
+                }
+            },
+            Err(TrySendError::Disconnected(_)) => false,
+        };
+        if sent {
+            self.counters.sent.fetch_add(1, Ordering::Relaxed);
+        }
+        sent
+    }
 }
 
-impl CrackWorker {
-    pub fn crack_loop(&self, seed: u64) {
-        // SETUP
-        let mut words = include_str!("../../words/default.txt").to_string();
-        let dict = Dictionary::from_string(&mut words);
-        let bytes_dict = BytesDictionary::from_dict(&dict);
-        let baseline_freqs = Frequencies::from_dict(&dict);
-
-        // Get strings for Test 1
-        let test1_str = include_str!("../../words/test1_plaintext.txt");
-        let test1_known_plaintexts: Vec<(String, Frequencies)> = test1_str
-            .lines()
-            .map(|s| {
-                let string = s.to_string();
-                let freqs = Frequencies::from_str(s);
-                (string, freqs)
-            })
-            .collect();
+/// Counts of what happened to a [`WorkerPool`]'s results, from
+/// [`results_stats`][`WorkerPool::results_stats`], or to a campaign's, in its
+/// [`CampaignReport`][`crate::crack::campaign::CampaignReport`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResultsStats {
+    /// Results that made it into the channel
+    pub sent: u64,
+    /// Times a worker found the channel full and waited for room
+    pub blocked: u64,
+    /// Results thrown away because the channel was full
+    pub dropped: u64,
+}
 
-        let mut gen = Generator::with_dict(&dict);
-        let mut rng = Rng::with_seed(seed, seed);
+impl fmt::Display for ResultsStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} results sent, blocked {} times, {} dropped",
+            self.sent, self.blocked, self.dropped
+        )
+    }
+}
 
-        let mut keylen_guesses = Vec::new();
-        let mut crack_results = Vec::new();
-        let mut spell_checked = Vec::new();
+/// [`ResultsStats`] shared by every sender of a results channel as they happen.
+#[derive(Debug, Default)]
+pub(crate) struct ResultsCounters {
+    sent: AtomicU64,
+    blocked: AtomicU64,
+    dropped: AtomicU64,
+}
 
-        'cracking: loop {
-            // clear these vectors
-            crack_results.clear();
-            spell_checked.clear();
+impl ResultsCounters {
+    pub(crate) fn stats(&self) -> ResultsStats {
+        ResultsStats {
+            sent: self.sent.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A pool of [`CrackWorker`] threads. Schedulers are sent in with [`submit`][`WorkerPool::submit`]
+/// and [`TrialResult`]s come back out of [`results`][`WorkerPool::results`]. Other work, like the
+/// keylength tasks of [`crack_parallel`][`crate::crack::distributed::crack_parallel`] or the requests
+/// of the crack server, can be queued up in between with [`execute`][`WorkerPool::execute`].
+///
+/// Workers keep cracking until the pool is shut down and every scheduler already submitted has
+/// been cracked, then exit on their own.
+///
+/// The results channel is bounded by a [`ResultsChannel`], so a reader that can't keep up makes
+/// the workers wait, or drop results, instead of piling them up in memory.
+/// [`spawn_aggregator`][`WorkerPool::spawn_aggregator`] reads them as fast as they come on a
+/// thread of its own.
+pub struct WorkerPool {
+    /// Sending side of the scheduler channel. Taken (dropped) on shutdown, which closes the
+    /// channel once every other clone of it is gone too.
+    schedulers: Option<Submitter>,
+    results: Receiver<TrialResult>,
+    counters: Arc<ResultsCounters>,
+    handles: Vec<std::thread::JoinHandle<()>>,
+}
+
+/// A trial id, its scheduler, and the lengths its key may have, or `None` for any of
+/// [`KEY_LENGTHS`].
+type Submission = (u64, RandomScheduler, Option<Range<usize>>);
 
-            // get the next scheduler to try to crack
-            let sched = self.schedulers.recv().unwrap();
+/// Work for a [`WorkerPool`] besides trials, run on whichever worker takes it.
+pub type Task = Box<dyn FnOnce() + Send>;
 
-            // generate a key
-            let key = Key::from_rng(&mut rng);
-            let keylen = key.len();
+/// What the workers of a [`WorkerPool`] receive.
+enum Job {
+    Trial(Submission),
+    Task(Task),
+}
 
-            // compile the encryptor
-            let encryptor = Encryptor::new(key, sched, Rng::from_rng(&mut rng));
+/// A handle for submitting schedulers to a [`WorkerPool`]. Every scheduler is numbered with its
+/// trial id as it is submitted.
+#[derive(Clone)]
+pub struct Submitter {
+    schedulers: Sender<Job>,
+    // id for the next trial, shared by every handle to the pool
+    next_id: Arc<AtomicU64>,
+}
 
-            // generate plaintext
-            let testtype = if *rng.choose(&[true, false]) { 1 } else { 2 };
+impl Submitter {
+    /// Queue up a scheduler to be cracked. Blocks if the queue is full. Returns the scheduler back
+    /// as an error if every worker has exited.
+    pub fn submit(&self, sched: RandomScheduler) -> Result<(), RandomScheduler> {
+        self.send(sched, None)
+    }
+
+    /// Same as [`submit`][`Self::submit`], with a key of a length in `key_lengths`.
+    pub fn submit_with_key_lengths(
+        &self,
+        sched: RandomScheduler,
+        key_lengths: Range<usize>,
+    ) -> Result<(), RandomScheduler> {
+        self.send(sched, Some(key_lengths))
+    }
 
-            let plaintext = match testtype {
-                1 => rng.choose(&test1_known_plaintexts).0.clone(),
-                2 => gen.generate_words(200),
-                _ => unreachable!(),
+    fn send(
+        &self,
+        sched: RandomScheduler,
+        key_lengths: Option<Range<usize>>,
+    ) -> Result<(), RandomScheduler> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        match self.schedulers.send(Job::Trial((id, sched, key_lengths))) {
+            Ok(()) => Ok(()),
+            Err(e) => match e.into_inner() {
+                Job::Trial((_, sched, _)) => Err(sched),
+                Job::Task(_) => unreachable!("sent a trial"),
+            },
+        }
+    }
+
+    /// Queue up `task` to run on one of the workers, in between trials. Blocks if the queue is
+    /// full. Returns the task back as an error if every worker has exited.
+    pub fn execute(&self, task: impl FnOnce() + Send + 'static) -> Result<(), Task> {
+        match self.schedulers.send(Job::Task(Box::new(task))) {
+            Ok(()) => Ok(()),
+            Err(e) => match e.into_inner() {
+                Job::Task(task) => Err(task),
+                Job::Trial(_) => unreachable!("sent a task"),
+            },
+        }
+    }
+}
+
+impl WorkerPool {
+    /// Seed for pools spawned without one
+    pub const DEFAULT_SEED: u64 = 0x5eed_c4ac;
+
+    /// Spawn `num_workers` threads, with the default seed.
+    pub fn spawn(num_workers: usize) -> Self {
+        Self::spawn_with_seed(num_workers, Self::DEFAULT_SEED)
+    }
+
+    /// Spawn `num_workers` threads. Every trial is generated from `seed` and the trial's id, so
+    /// pools with the same seed generate the same trials for the same schedulers, no matter how
+    /// many workers there are or which worker picks up which trial.
+    pub fn spawn_with_seed(num_workers: usize, seed: u64) -> Self {
+        Self::spawn_with(num_workers, seed, ResultsChannel::default())
+    }
+
+    /// Same as [`spawn_with_seed`][`Self::spawn_with_seed`], with the results channel bounded by
+    /// `channel`.
+    pub fn spawn_with(num_workers: usize, seed: u64, channel: ResultsChannel) -> Self {
+        let (sched_in, sched_out) = bounded(128);
+        let (results_in, results_out, counters) = channel.open();
+
+        let mut handles = Vec::new();
+
+        for index in 0..num_workers {
+            let worker = CrackWorker {
+                schedulers: sched_out.clone(),
+                results: results_in.clone(),
+                seed,
             };
 
-            // generate ciphertext
-            let ciphertext = encryptor.encrypt(&plaintext);
-            let cipherbytes = str_to_bytes(&ciphertext);
+            let handle = std::thread::spawn(move || {
+                threads::pin_current(index);
+                worker.crack_loop()
+            });
+            handles.push(handle);
+        }
 
-            // KEYLENGTH GUESSING
-            guesses(&cipherbytes, &mut keylen_guesses);
+        Self {
+            schedulers: Some(Submitter {
+                schedulers: sched_in,
+                next_id: Arc::new(AtomicU64::new(0)),
+            }),
+            results: results_out,
+            counters,
+            handles,
+        }
+    }
 
-            // ===============   TEST 1   ===================== //
+    /// Queue up a scheduler to be cracked. Blocks if the queue is full. Returns the scheduler back
+    /// as an error if the pool was already shut down.
+    pub fn submit(&self, sched: RandomScheduler) -> Result<(), RandomScheduler> {
+        match &self.schedulers {
+            Some(schedulers) => schedulers.submit(sched),
+            None => Err(sched),
+        }
+    }
 
-            let mut best_test1_score = f32::MAX;
+    /// Queue up `task` to run on one of the workers, in between trials. Blocks if the queue is
+    /// full. Returns the task back as an error if the pool was already shut down.
+    pub fn execute(&self, task: impl FnOnce() + Send + 'static) -> Result<(), Task> {
+        match &self.schedulers {
+            Some(schedulers) => schedulers.execute(task),
+            None => Err(Box::new(task)),
+        }
+    }
 
-            for (known_pt, freqs) in test1_known_plaintexts.iter() {
-                let mut best_score = f32::MAX;
+    /// Get another handle for submitting schedulers, for example to feed the pool from a separate
+    /// thread. The pool doesn't finish shutting down until every such handle is dropped.
+    pub fn sender(&self) -> Option<Submitter> {
+        self.schedulers.clone()
+    }
 
-                for crack in (3..120_usize).map(|keylen| crack(&cipherbytes, keylen, &freqs)) {
-                    let crackstr = bytes_to_str(&crack.plaintext);
-                    let score =
-                        strsim::levenshtein(&crackstr, &known_pt) as f32 / plaintext.len() as f32;
+    /// Results from the workers. Once the pool is shut down and all workers have exited, iterating
+    /// over this receiver ends.
+    pub fn results(&self) -> &Receiver<TrialResult> {
+        &self.results
+    }
 
-                    // update the best score for this plaintext
-                    if score < best_score {
-                        best_score = score;
-                    }
-                }
+    /// What happened to the results so far.
+    pub fn results_stats(&self) -> ResultsStats {
+        self.counters.stats()
+    }
 
-                if best_score < best_test1_score {
-                    best_test1_score = best_score;
-                }
+    /// Receive every result on a thread of its own, as soon as the workers send it, so they never
+    /// wait on a slow reader. Each result is passed to `on_result`, then added to the
+    /// [`Aggregator`] the thread returns once every worker has exited. If `on_result` fails, the
+    /// thread stops receiving and returns the error.
+    pub fn spawn_aggregator<F>(&self, mut on_result: F) -> JoinHandle<anyhow::Result<Aggregator>>
+    where
+        F: FnMut(&TrialResult) -> anyhow::Result<()> + Send + 'static,
+    {
+        let results = self.results.clone();
+        std::thread::spawn(move || {
+            let mut aggregator = Aggregator::default();
+            for result in results.iter() {
+                on_result(&result)?;
+                aggregator.add(&result);
             }
+            Ok(aggregator)
+        })
+    }
 
-            if best_test1_score < 0.8 {
-                // it was probably test1, send back results
-                self.results
-                    .send((testtype, 1, encryptor.keyschedule, keylen, best_test1_score))
-                    .unwrap();
+    /// Stop accepting new schedulers. Workers finish what is already queued, then exit.
+    pub fn shutdown(&mut self) {
+        self.schedulers.take();
+    }
+
+    /// Shut down and wait for every worker to exit. Results that were not received yet are
+    /// dropped. Unless an [`aggregator`][`Self::spawn_aggregator`] is still receiving, workers
+    /// stop at their next result instead of waiting for room in the channel.
+    pub fn join(self) -> std::thread::Result<()> {
+        let Self {
+            schedulers,
+            results,
+            handles,
+            ..
+        } = self;
+        // no one can read the results after this, so don't let a full channel keep workers
+        // waiting for a reader
+        drop(schedulers);
+        drop(results);
+        for handle in handles {
+            handle.join()?;
+        }
+        Ok(())
+    }
+}
+
+/// Seed of trial `id` of a pool seeded with `seed`. The pool seed is scrambled before the id is
+/// mixed in, so pools with different seeds, even neighbouring ones, don't share trials.
+pub fn trial_seed(seed: u64, id: u64) -> u64 {
+    Rng::from_seed(splitmix64(seed) ^ id).next()
+}
+
+/// Spawn a [`WorkerPool`] of `num_workers` threads.
+pub fn spawn_workers(num_workers: usize) -> WorkerPool {
+    WorkerPool::spawn(num_workers)
+}
+
+/// Spawn a [`WorkerPool`] of as many threads as [`threads::threads`] says.
+pub fn spawn_default_workers() -> WorkerPool {
+    WorkerPool::spawn(threads::threads())
+}
+
+/// Crack ciphertext made up of dictionary words (Test 2), running every [`Pipeline`] stage after
+/// keylength guessing on each of `keylen_guesses`, and return the best corrected result, along
+/// with the words spellchecking corrected to get it. Spellchecking weighs words by `weights`.
+pub fn crack_test2(
+    cipherbytes: &[u8],
+    keylen_guesses: &[(usize, f64)],
+    stats: &DictionaryStats,
+    bytes_dict: &BytesDictionary,
+    weights: SegmentationWeights,
+) -> (CrackResult, Vec<WordCorrection>) {
+    let ctx = PipelineContext {
+        stats,
+        bytes_dict,
+        deadline: None,
+        on_partial: None,
+    };
+    let mut state = PipelineState::with_guesses(cipherbytes, keylen_guesses);
+    Pipeline::default()
+        .without("normalize")
+        .without("keylength")
+        .replace(Spellcheck { weights })
+        .run_state(&ctx, &mut state);
+    let best = state.best.expect("the pipeline ranks the candidates");
+
+    let corrections = state
+        .candidates
+        .into_iter()
+        .find(|candidate| {
+            let checked = candidate.checked.as_ref();
+            checked.is_some_and(|checked| checked.plaintext == best.plaintext)
+        })
+        .map(|candidate| candidate.corrections)
+        .unwrap_or_default();
+    (best, corrections)
+}
+
+/// Generates trials, and cracks them the same way every worker does.
+pub struct TrialCracker {
+    bytes_dict: BytesDictionary,
+    /// Candidate plaintexts for Test 1
+    known_plaintexts: KnownPlaintexts,
+    /// buffers reused for every ciphertext
+    scratch: CrackScratch,
+    /// How Test 2 cracks are spellchecked
+    weights: SegmentationWeights,
+}
 
-                // continue main cracking loop
-                continue 'cracking;
+impl TrialCracker {
+    /// A TrialCracker using the project's Test 2 dictionary and Test 1 plaintexts.
+    pub fn project() -> Self {
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+
+        Self {
+            bytes_dict: BytesDictionary::from_dict(&dict),
+            known_plaintexts: KnownPlaintexts::project(),
+            scratch: CrackScratch::default(),
+            weights: SegmentationWeights::default(),
+        }
+    }
+
+    /// Spellcheck Test 2 cracks weighing words by `weights`.
+    pub fn with_weights(mut self, weights: SegmentationWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Generate a trial from `seed`: a random key, and either a known plaintext or words from
+    /// `gen`, encrypted with `sched`.
+    pub fn generate(
+        &self,
+        id: u64,
+        seed: u64,
+        sched: RandomScheduler,
+        gen: &mut Generator,
+    ) -> TrialRecord {
+        self.generate_with_key_lengths(id, seed, sched, KEY_LENGTHS, gen)
+    }
+
+    /// Same as [`generate`][`Self::generate`], with a key of a length in `key_lengths`.
+    pub fn generate_with_key_lengths(
+        &self,
+        id: u64,
+        seed: u64,
+        sched: RandomScheduler,
+        key_lengths: Range<usize>,
+        gen: &mut Generator,
+    ) -> TrialRecord {
+        let mut rng = Rng::from_seed(seed);
+
+        // generate a key
+        let key = random_key(&mut rng, key_lengths);
+
+        // compile the encryptor
+        let encryptor = Encryptor::new(key.clone(), sched, Rng::from_rng(&mut rng));
+
+        // generate plaintext
+        let known_plaintext = if rng.gen_bool(0.5) {
+            Some(rng.gen_range(0..self.known_plaintexts.len()))
+        } else {
+            None
+        };
+
+        let plaintext = match known_plaintext {
+            Some(index) => bytes_to_str(self.known_plaintexts.get(index)),
+            None => {
+                gen.rng = Rng::from_rng(&mut rng);
+                gen.generate_words(200)
             }
+        };
+
+        // generate ciphertext
+        let ciphertext = encryptor.encrypt(&plaintext);
+
+        TrialRecord {
+            id,
+            seed,
+            key,
+            scheduler: sched,
+            known_plaintext,
+            plaintext,
+            ciphertext,
+        }
+    }
 
+    /// Keylength guesses of the last cracked trial, best first.
+    pub fn keylen_guesses(&self) -> &[(usize, f64)] {
+        &self.scratch.keylen_guesses
+    }
+
+    /// Crack the ciphertext of a trial, and compare against its real plaintext.
+    pub fn crack(&mut self, trial: TrialRecord) -> TrialResult {
+        let scratch = &mut self.scratch;
+        let cipherbytes = str_to_bytes(&trial.ciphertext);
+        let plainbytes = str_to_bytes(&trial.plaintext);
+        let _span = debug_span!("trial", id = trial.id).entered();
+
+        // KEYLENGTH GUESSING
+        debug_span!("keylength_guessing").in_scope(|| {
+            guesses(&cipherbytes, &mut scratch.keylen_guesses);
+        });
+
+        // ===============   TEST 1   ===================== //
+        let test1 = self.known_plaintexts.find(
+            &cipherbytes,
+            TEST1_THRESHOLD,
+            MAX_GUESSED_KEYLENGTH,
+            None,
+            scratch,
+        );
+
+        let (guessed_test, cracked, corrections) = if let Some(known) = test1 {
+            // it was probably test1
+            (1, known.plaintext, 0)
+        } else {
             // ===============   TEST 2   ===================== //
+            let _span = debug_span!("test2").entered();
+            let (best_after_spellcheck, corrections) = crack_test2(
+                &cipherbytes,
+                &scratch.keylen_guesses,
+                DictionaryStats::project(),
+                &self.bytes_dict,
+                self.weights,
+            );
+            (2, best_after_spellcheck.plaintext, corrections.len())
+        };
+
+        let error = normalized_levenshtein(&cracked, &plainbytes);
+        let success = cracked == plainbytes;
+        let recovered_key = Key::fit(&cipherbytes, &cracked, MAX_KEYLENGTH);
+        debug!(
+            guessed_test,
+            error,
+            success,
+            ?recovered_key,
+            corrections,
+            "cracked trial"
+        );
+
+        TrialResult {
+            trial,
+            guessed_test,
+            cracked: bytes_to_str(&cracked),
+            error,
+            success,
+            recovered_key,
+            corrections,
+        }
+    }
+}
+
+impl CrackWorker {
+    /// Encrypt and crack a random plaintext for every scheduler received, sending back a
+    /// [`TrialResult`] for each, and run every other task received. Returns once the scheduler
+    /// channel is closed and drained, or nobody is receiving results anymore.
+    pub fn crack_loop(&self) {
+        // SETUP, the cracker only once there is a trial to crack, since a pool may only run tasks
+        let mut words = include_str!("../../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let mut gen = Generator::with_dict(&dict);
+        let mut cracker = None;
+
+        // get the next scheduler to try to crack, until the pool shuts down
+        while let Ok(job) = self.schedulers.recv() {
+            let (id, sched, key_lengths) = match job {
+                Job::Trial(submission) => submission,
+                Job::Task(task) => {
+                    task();
+                    continue;
+                }
+            };
+            let cracker = cracker.get_or_insert_with(TrialCracker::project);
+
+            // every trial gets its own seed, so it can be generated again on its own
+            let seed = trial_seed(self.seed, id);
+            let key_lengths = key_lengths.unwrap_or(KEY_LENGTHS);
+            let trial = cracker.generate_with_key_lengths(id, seed, sched, key_lengths, &mut gen);
+
+            // send back the results, waiting for room or dropping them if the channel is full
+            if !self.results.send(cracker.crack(trial)) {
+                // nobody is listening for results anymore
+                return;
+            }
+        }
+    }
+}
+
+/// Running totals over a group of [`TrialResult`]s.
+#[derive(Clone, Debug, Default)]
+pub struct Tally {
+    pub trials: usize,
+    pub successes: usize,
+    pub total_error: f64,
+}
+
+impl Tally {
+    pub(crate) fn add(&mut self, result: &TrialResult) {
+        self.trials += 1;
+        self.successes += result.success as usize;
+        self.total_error += result.error;
+    }
+
+    pub fn success_rate(&self) -> f64 {
+        self.successes as f64 / self.trials.max(1) as f64
+    }
 
-            // CRACKING SLICES
-            for (keylen, keylen_confidence) in keylen_guesses.iter() {
-                let mut res = crack(&cipherbytes, *keylen, &baseline_freqs);
-                res.confidence *= keylen_confidence;
-                crack_results.push(res);
+    pub fn mean_error(&self) -> f64 {
+        self.total_error / self.trials.max(1) as f64
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>5} trials {:>6.1}% cracked  mean error {:.4}",
+            self.trials,
+            self.success_rate() * 100.0,
+            self.mean_error()
+        )
+    }
+}
+
+/// Collects [`TrialResult`]s from the workers into summary statistics, overall and per scheduler.
+#[derive(Clone, Debug, Default)]
+pub struct Aggregator {
+    pub overall: Tally,
+    /// Results grouped by the base scheduler family
+    pub per_scheduler: HashMap<SchedulerKind, Tally>,
+    /// Results grouped by the number of PeriodicRand layers
+    pub per_layers: [Tally; 4],
+    /// Number of times the cracker guessed the wrong test
+    pub wrong_test: usize,
+}
+
+impl Aggregator {
+    pub fn add(&mut self, result: &TrialResult) {
+        self.overall.add(result);
+        self.per_scheduler
+            .entry(result.trial.scheduler.base().kind())
+            .or_default()
+            .add(result);
+        self.per_layers[result.trial.scheduler.layers()].add(result);
+        if result.trial.actual_test() != result.guessed_test {
+            self.wrong_test += 1;
+        }
+    }
+}
+
+impl fmt::Display for Aggregator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<16} {}", "overall", self.overall)?;
+        writeln!(f, "{:<16} {}", "wrong test", self.wrong_test)?;
+
+        for kind in SchedulerKind::ALL.iter() {
+            if let Some(tally) = self.per_scheduler.get(kind) {
+                writeln!(f, "{:<16} {}", kind.name(), tally)?;
+            }
+        }
+
+        for (layers, tally) in self.per_layers.iter().enumerate() {
+            if tally.trials > 0 {
+                writeln!(f, "{:<16} {}", format!("{} rand layers", layers), tally)?;
             }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::SchedulerComplexity;
+    use std::collections::HashSet;
+
+    #[test]
+    fn aggregate() {
+        use crate::ciphers::schedulers::{Aab, RandomBaseScheduler, RepeatingKey};
+
+        let result = |base, success| TrialResult {
+            trial: TrialRecord {
+                id: 0,
+                seed: 1,
+                key: Key::from(vec![1; 7]),
+                scheduler: RandomScheduler::Zero(base),
+                known_plaintext: None,
+                plaintext: String::new(),
+                ciphertext: String::new(),
+            },
+            guessed_test: 2,
+            cracked: String::new(),
+            error: if success { 0.0 } else { 0.5 },
+            success,
+            recovered_key: None,
+            corrections: 0,
+        };
+        let aab = RandomBaseScheduler::Aab(Aab {
+            num_chars: 1,
+            num_reps: 1,
+            offset: 0,
+        });
+        let rk = RandomBaseScheduler::RepeatingKey(RepeatingKey);
+
+        let mut aggregator = Aggregator::default();
+        aggregator.add(&result(aab, true));
+        aggregator.add(&result(aab, false));
+        aggregator.add(&result(rk, true));
+        aggregator.add(&result(rk, true));
+
+        assert_eq!(aggregator.overall.trials, 4);
+        assert_eq!(aggregator.overall.success_rate(), 0.75);
+        assert_eq!(aggregator.overall.mean_error(), 0.125);
+        assert_eq!(aggregator.per_scheduler[&SchedulerKind::Aab].successes, 1);
+        assert_eq!(
+            aggregator.per_scheduler[&SchedulerKind::RepeatingKey].successes,
+            2
+        );
+        assert_eq!(aggregator.per_layers[0].trials, 4);
+
+        let summary = aggregator.to_string();
+        assert!(summary.contains("Aab"));
+        assert!(summary.contains("RepeatingKey"));
+        assert!(!summary.contains("LengthMod"));
+    }
+
+    #[test]
+    fn shutdown_idle_pool() {
+        let pool = spawn_workers(2);
+        pool.join().expect("worker panicked");
+    }
+
+    #[test]
+    fn submit_after_shutdown() {
+        use crate::ciphers::schedulers::{RandomBaseScheduler, RepeatingKey};
+
+        let mut pool = spawn_workers(1);
+        pool.shutdown();
+
+        let sched = RandomScheduler::Zero(RandomBaseScheduler::RepeatingKey(RepeatingKey));
+        assert!(pool.submit(sched).is_err());
+        assert!(pool.results().iter().next().is_none());
+        pool.join().expect("worker panicked");
+    }
+
+    #[test]
+    fn tasks() {
+        let mut pool = spawn_workers(2);
+        let (done_in, done_out) = crossbeam_channel::unbounded();
+        for n in 0..10 {
+            let done = done_in.clone();
+            assert!(pool.execute(move || done.send(n).unwrap()).is_ok());
+        }
+        drop(done_in);
+        let mut done: Vec<i32> = done_out.iter().collect();
+        done.sort_unstable();
+        assert_eq!(done, (0..10).collect::<Vec<_>>());
+
+        // tasks come back once the pool is shut down
+        pool.shutdown();
+        assert!(pool.execute(|| ()).is_err());
+        pool.join().expect("worker panicked");
+    }
 
-            // SPELL CHECKING
-            for crack in &crack_results {
-                spell_checked.push(spellcheck(crack, &bytes_dict));
+    #[test]
+    fn bounded_results() {
+        let submit = |pool: &mut WorkerPool| {
+            let mut rng = Rng::from_seed(7);
+            for _ in 0..3 {
+                let sched = RandomScheduler::with_complexity(&mut rng, SchedulerComplexity::Easy);
+                pool.submit(sched).unwrap();
             }
+            pool.shutdown();
+        };
+
+        // nobody reads until every trial is done, so all but one result is dropped
+        let channel = ResultsChannel {
+            capacity: 1,
+            when_full: WhenFull::Drop,
+        };
+        let mut pool = WorkerPool::spawn_with(1, 7, channel);
+        submit(&mut pool);
+        while pool.results_stats().sent + pool.results_stats().dropped < 3 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(pool.results().try_iter().count(), 1);
+        let stats = pool.results_stats();
+        assert_eq!((stats.sent, stats.blocked, stats.dropped), (1, 0, 2));
+        pool.join().unwrap();
+
+        // waiting for room loses nothing, and the aggregator thread gets every result
+        let channel = ResultsChannel {
+            capacity: 1,
+            when_full: WhenFull::Block,
+        };
+        let mut pool = WorkerPool::spawn_with(2, 7, channel);
+        let aggregator = pool.spawn_aggregator(|_| Ok(()));
+        submit(&mut pool);
+        let aggregator = aggregator.join().unwrap().unwrap();
+        assert_eq!(aggregator.overall.trials, 3);
+        let stats = pool.results_stats();
+        assert_eq!((stats.sent, stats.dropped), (3, 0));
+        assert!(stats.to_string().starts_with("3 results sent"));
+        pool.join().unwrap();
+
+        // joining with more unread results than fit doesn't wait for a reader that never comes
+        let mut pool = WorkerPool::spawn_with(2, 7, channel);
+        submit(&mut pool);
+        let (joined_in, joined) = crossbeam_channel::bounded(1);
+        std::thread::spawn(move || joined_in.send(pool.join().is_ok()));
+        let timeout = std::time::Duration::from_secs(60);
+        assert_eq!(joined.recv_timeout(timeout), Ok(true));
+    }
 
-            let best_after_spellcheck = best_crack(&spell_checked);
+    #[test]
+    fn neighbouring_seeds_share_no_trials() {
+        let seeds = |seed| (0..1000).map(move |id| trial_seed(seed, id));
+        let first: HashSet<u64> = seeds(42).collect();
+        assert_eq!(first.len(), 1000);
+        assert!(seeds(43).all(|seed| !first.contains(&seed)));
+        assert!(seeds(0).chain(seeds(1)).all(|seed| !first.contains(&seed)));
+    }
 
-            let success =
-                strsim::levenshtein(&bytes_to_str(&best_after_spellcheck.plaintext), &plaintext)
-                    as f32
-                    / plaintext.len() as f32;
+    /// the trials generated only depend on the pool seed and the submitted schedulers
+    #[test]
+    fn same_seed_same_trials() {
+        let trials = |num_workers, seed| {
+            let pool = WorkerPool::spawn_with_seed(num_workers, seed);
+            let mut rng = Rng::from_seed(seed);
+            for _ in 0..3 {
+                let sched = RandomScheduler::with_complexity(&mut rng, SchedulerComplexity::Easy);
+                pool.submit(sched).unwrap();
+            }
+            let mut pool = pool;
+            pool.shutdown();
+
+            let mut trials: Vec<TrialRecord> = pool.results().iter().map(|r| r.trial).collect();
+            pool.join().unwrap();
+            trials.sort_by_key(|t| t.id);
+            trials
+        };
 
-            // send back the results
-            self.results
-                .send((testtype, 2, encryptor.keyschedule, keylen, success))
-                .unwrap();
+        let a = trials(1, 42);
+        let b = trials(3, 42);
+        let c = trials(1, 43);
+        assert_eq!(a.len(), 3);
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_eq!(a.ciphertext, b.ciphertext);
+            assert_eq!(a.key, b.key);
         }
+        assert_ne!(a[0].ciphertext, c[0].ciphertext);
     }
 }
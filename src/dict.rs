@@ -1,6 +1,21 @@
 //! Module for [`Dictionary`].
 
-use crate::utils::str_to_bytes;
+use crate::rng::Rng;
+use crate::utils::{str_to_bytes, ALPHABET};
+
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Dictionaries with more words than this are searched in parallel shards by
+/// [`BytesDictionary::best_levenshtein`], when the `parallel` feature is enabled. Smaller ones
+/// take less time to search than to hand out to threads.
+pub const PARALLEL_WORDS: usize = 8192;
+
+/// Words in each shard of a dictionary searched in parallel.
+#[cfg(feature = "parallel")]
+const SHARD_WORDS: usize = 2048;
 
 /// A dictionary will hold an alphabetized wordlist. Each word only consists of lowercase ASCII
 /// alphabetic characters.
@@ -28,9 +43,9 @@ impl<'a> Dictionary<'a> {
             .split_ascii_whitespace()
             // make sure the word is only a-zA-Z
             .filter(|word| {
-                let alphabetic = word.chars().all(|chr| chr.is_alphabetic());
+                let alphabetic = word.chars().all(|chr| chr.is_ascii_lowercase());
                 if !alphabetic {
-                    eprintln!("word \"{}\" is non-alphabetic", word);
+                    tracing::warn!(word, "skipping non-alphabetic word");
                 }
                 alphabetic
             })
@@ -43,43 +58,208 @@ impl<'a> Dictionary<'a> {
         // return the dictionary
         Self { words }
     }
+
+    /// A smaller dictionary of `n` of these words picked at random, still alphabetized. Every
+    /// word if there are no more than `n`. `n` can't be zero, since nothing can be generated from
+    /// an empty dictionary.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::dict::Dictionary;
+    /// # use one_team_pad_cipher_cracker::rng::Rng;
+    /// # use std::num::NonZeroUsize;
+    /// let mut words = String::from("delta alpha echo charlie bravo");
+    /// let dict = Dictionary::from_string(&mut words);
+    /// let three = NonZeroUsize::new(3).unwrap();
+    /// let sample = dict.sample(three, &mut Rng::default());
+    /// assert_eq!(sample.words.len(), 3);
+    /// assert!(sample.words.windows(2).all(|pair| pair[0] < pair[1]));
+    /// assert!(sample.words.iter().all(|word| dict.words.contains(word)));
+    /// ```
+    pub fn sample(&self, n: NonZeroUsize, rng: &mut Rng) -> Self {
+        let mut words = self.words.clone();
+        rng.shuffle(&mut words);
+        words.truncate(n.get());
+        words.sort_unstable();
+        Self { words }
+    }
 }
 
+/// The words of a [`Dictionary`] as bytes, each followed by a space, for spellchecking.
+///
+/// The words are stored back to back in one buffer, shortest first, so scanning every word is a
+/// single pass over contiguous memory, and words too long or short to be the closest can be
+/// skipped.
 pub struct BytesDictionary {
-    pub words: Vec<Vec<u8>>,
+    /// Every word with its space, back to back
+    bytes: Vec<u8>,
+    /// Where each word starts in `bytes`, and its length with its space, sorted by length. Words
+    /// of the same length stay in alphabetical order.
+    entries: Vec<(u32, u32)>,
+    /// Every word without its space, for looking up whole words
+    exact: HashSet<Box<[u8]>>,
 }
 
 impl BytesDictionary {
     pub fn from_dict(dict: &Dictionary) -> Self {
         use crate::utils::CharToNum;
 
-        let words = dict
-            .words
+        let mut words = dict.words.clone();
+        words.sort_by_key(|w| w.len());
+
+        let mut bytes = Vec::with_capacity(words.iter().map(|w| w.len() + 1).sum());
+        let entries = words
             .iter()
             .map(|w| {
-                let mut w = str_to_bytes(w);
-                w.push(' '.to_num());
-                w
+                let offset = bytes.len() as u32;
+                bytes.extend(str_to_bytes(w));
+                bytes.push(' '.to_num());
+                (offset, w.len() as u32 + 1)
             })
             .collect();
+        let exact = words.iter().map(|w| str_to_bytes(w).into()).collect();
 
-        Self { words }
+        Self {
+            bytes,
+            entries,
+            exact,
+        }
     }
 
-    /// Find the closest word by Levenshtein distance.
+    /// Number of words.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no words at all.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The word at `index`, with its space. Words are numbered shortest first.
+    pub fn word(&self, index: usize) -> &[u8] {
+        let (offset, len) = self.entries[index];
+        &self.bytes[offset as usize..(offset + len) as usize]
+    }
+
+    /// Every word with its space, shortest first.
+    pub fn words(&self) -> impl ExactSizeIterator<Item = &[u8]> + '_ {
+        (0..self.len()).map(move |index| self.word(index))
+    }
+
+    /// Whether `word`, without a space, is one of the words.
+    pub fn contains(&self, word: &[u8]) -> bool {
+        self.exact.contains(word)
+    }
+
+    /// Length of the longest word, with its space. 0 if there are no words.
+    pub fn longest_word(&self) -> usize {
+        self.entries.last().map_or(0, |&(_, len)| len as usize)
+    }
+
+    /// Find the closest word by [`shift_levenshtein`] distance, so of equally distant words the
+    /// one a slightly wrong key byte would turn into `word` wins.
     ///
-    /// Returns (dictionary_word, edit_distance)
+    /// Returns (dictionary_word, edit_distance), with the distance in units of
+    /// [`EDIT_COST`].
     ///
     /// The lower the score, the fewer edits needed to match the dictionary word.
     pub fn best_levenshtein<'a>(&'a self, word: &[u8]) -> (&'a [u8], usize) {
-        // iterate over words in dictionary
-        self.words
-            .iter()
-            // create tuples of &str and the respective levenshtein distance
-            .map(|s| (s.as_slice(), levenshtein(word, s)))
-            // return the best word-score tuple
-            .min_by_key(|x| x.1)
-            .expect("spell correct with an empty Dictionary")
+        self.best_levenshtein_with_row(word, &mut Vec::new())
+    }
+
+    /// Same as [`best_levenshtein`][`Self::best_levenshtein`], reusing `row` as the buffer for
+    /// [`shift_levenshtein_with_row`].
+    ///
+    /// Words are compared outward from the length of `word`, since every character of difference
+    /// in length costs an insertion or deletion, and stop once that alone is more than the best
+    /// distance so far. Of equally distant words, the one closest in length wins, then the one
+    /// numbered first.
+    ///
+    /// With the `parallel` feature, dictionaries of more than [`PARALLEL_WORDS`] words are split
+    /// into shards of words that are compared concurrently on the rayon thread pool. The shards
+    /// share the best distance so far, so each skips the words the others have already beaten.
+    pub fn best_levenshtein_with_row<'a>(
+        &'a self,
+        word: &[u8],
+        row: &mut Vec<usize>,
+    ) -> (&'a [u8], usize) {
+        let shared = AtomicUsize::new(usize::MAX);
+
+        #[cfg(feature = "parallel")]
+        let best = if self.len() > PARALLEL_WORDS {
+            use rayon::prelude::*;
+            (0..self.len())
+                .into_par_iter()
+                .step_by(SHARD_WORDS)
+                .map_init(Vec::new, |row, start| {
+                    let shard = start..(start + SHARD_WORDS).min(self.len());
+                    self.closest_in(word, shard, &shared, row)
+                })
+                .flatten()
+                .min()
+        } else {
+            self.closest_in(word, 0..self.len(), &shared, row)
+        };
+
+        #[cfg(not(feature = "parallel"))]
+        let best = self.closest_in(word, 0..self.len(), &shared, row);
+
+        let (distance, _, index) = best.expect("spell correct with an empty Dictionary");
+        (self.word(index), distance)
+    }
+
+    /// The closest word to `word` among the words at `indices`, as its distance, difference in
+    /// length, and index, so the smallest is the best of any words. Words are compared outward
+    /// from the length of `word`, skipping those whose difference in length alone is more than
+    /// `shared`, the best distance found in any shard so far, which is lowered as better words
+    /// are found.
+    ///
+    /// Words that could at best tie `shared` are still compared, so the same word wins whether
+    /// the dictionary is searched in one pass or in shards, whichever shard finishes first.
+    fn closest_in(
+        &self,
+        word: &[u8],
+        indices: Range<usize>,
+        shared: &AtomicUsize,
+        row: &mut Vec<usize>,
+    ) -> Option<(usize, usize, usize)> {
+        let bound =
+            |index: usize| (self.entries[index].1 as usize).abs_diff(word.len()) * EDIT_COST;
+
+        // shorter words go down from `below`, and longer ones (or as long) up from `above`
+        let mut above = indices.start
+            + self.entries[indices.clone()]
+                .partition_point(|&(_, len)| (len as usize) < word.len());
+        let mut below = above;
+        let mut best: Option<(usize, usize, usize)> = None;
+        loop {
+            let down = (below > indices.start).then(|| below - 1);
+            let up = (above < indices.end).then_some(above);
+            let next = match (down, up) {
+                (Some(down), Some(up)) if bound(down) < bound(up) => down,
+                (_, Some(up)) => up,
+                (Some(down), None) => down,
+                (None, None) => break,
+            };
+            let so_far = shared.load(Ordering::Relaxed);
+            if bound(next) > so_far {
+                // every word left differs in length at least as much
+                break;
+            }
+            if up == Some(next) {
+                above += 1;
+            } else {
+                below -= 1;
+            }
+
+            let distance = shift_levenshtein_with_row(word, self.word(next), row);
+            let candidate = (distance, bound(next), next);
+            if best.is_none_or(|best| candidate < best) {
+                best = Some(candidate);
+                shared.fetch_min(distance, Ordering::Relaxed);
+            }
+        }
+        best
     }
 }
 
@@ -87,6 +267,21 @@ pub fn levenshtein<'a, 'b, Iter1: ?Sized, Iter2: ?Sized, Elem1, Elem2>(
     a: &'a Iter1,
     b: &'b Iter2,
 ) -> usize
+where
+    &'a Iter1: IntoIterator<Item = Elem1>,
+    &'b Iter2: IntoIterator<Item = Elem2>,
+    Elem1: PartialEq<Elem2>,
+{
+    levenshtein_with_row(a, b, &mut Vec::new())
+}
+
+/// Same as [`levenshtein`], using `cache` as the buffer for one row of edit distances instead of
+/// allocating a new one.
+pub fn levenshtein_with_row<'a, 'b, Iter1: ?Sized, Iter2: ?Sized, Elem1, Elem2>(
+    a: &'a Iter1,
+    b: &'b Iter2,
+    cache: &mut Vec<usize>,
+) -> usize
 where
     &'a Iter1: IntoIterator<Item = Elem1>,
     &'b Iter2: IntoIterator<Item = Elem2>,
@@ -100,7 +295,8 @@ where
         return b_len;
     }
 
-    let mut cache: Vec<usize> = (1..b_len + 1).collect();
+    cache.clear();
+    cache.extend(1..b_len + 1);
 
     let mut result = 0;
 
@@ -120,10 +316,219 @@ where
     result
 }
 
+/// Cost of inserting, deleting, or substituting an unrelated character in [`shift_levenshtein`].
+pub const EDIT_COST: usize = 4;
+
+/// Cost of substituting `b` for `a` in [`shift_levenshtein`], out of [`EDIT_COST`].
+///
+/// A column cracked with a key byte off by a little decrypts every one of its characters shifted
+/// by that little, so characters a small cyclic shift apart are cheap to swap: half an edit for a
+/// shift of one, three quarters for a shift of two, and a whole edit from there on.
+pub fn substitution_cost(a: u8, b: u8) -> usize {
+    let len = ALPHABET.len();
+    let diff = (a as usize).abs_diff(b as usize) % len;
+    match diff.min(len - diff) {
+        0 => 0,
+        shift => (shift + 1).min(EDIT_COST),
+    }
+}
+
+/// Edit distance between `a` and `b`, both as bytes, where substitutions cost
+/// [`substitution_cost`] and insertions and deletions cost [`EDIT_COST`].
+///
+/// ```
+/// # use one_team_pad_cipher_cracker::dict::{shift_levenshtein, EDIT_COST};
+/// # use one_team_pad_cipher_cracker::utils::str_to_bytes;
+/// let shift = |s| shift_levenshtein(&str_to_bytes("cat"), &str_to_bytes(s));
+/// assert_eq!(shift("cat"), 0);
+/// assert_eq!(shift("cbt"), EDIT_COST / 2);
+/// assert_eq!(shift("cot"), EDIT_COST);
+/// assert_eq!(shift("at"), EDIT_COST);
+/// ```
+pub fn shift_levenshtein(a: &[u8], b: &[u8]) -> usize {
+    shift_levenshtein_with_row(a, b, &mut Vec::new())
+}
+
+/// Same as [`shift_levenshtein`], using `row` as the buffer for one row of edit distances.
+pub fn shift_levenshtein_with_row(a: &[u8], b: &[u8], row: &mut Vec<usize>) -> usize {
+    row.clear();
+    row.extend((0..=b.len()).map(|j| j * EDIT_COST));
+
+    for (i, &a_elem) in a.iter().enumerate() {
+        // the cell above and to the left, before it is overwritten
+        let mut diagonal = row[0];
+        row[0] = (i + 1) * EDIT_COST;
+        for (j, &b_elem) in b.iter().enumerate() {
+            let substitute = diagonal + substitution_cost(a_elem, b_elem);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute
+                .min(row[j + 1] + EDIT_COST)
+                .min(row[j] + EDIT_COST);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Same as [`levenshtein`], but gives up as soon as the distance is known to be more than `max`,
+/// returning `None`. Only the band of the edit distance table within `max` of the diagonal is
+/// filled in, so comparing long slices against a tight `max` is far cheaper than the full
+/// distance. `row` is reused as the buffer for one row of the table.
+pub fn levenshtein_within<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    max: usize,
+    row: &mut Vec<usize>,
+) -> Option<usize> {
+    // the distance is at least the difference in length, and at most the longer length
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let max = max.min(a.len().max(b.len()));
+
+    // cells outside of the band are never read as anything less than `max + 1`
+    let outside = max + 1;
+    row.clear();
+    row.extend((0..=b.len()).map(|j| j.min(outside)));
+
+    for (i, a_elem) in a.iter().enumerate() {
+        let i = i + 1;
+        let lo = i.saturating_sub(max).max(1);
+        let hi = (i + max).min(b.len());
+
+        let mut diagonal = row[lo - 1];
+        let mut left = if lo == 1 { i.min(outside) } else { outside };
+        row[lo - 1] = left;
+        let mut row_min = left;
+
+        for (cell, b_elem) in row[lo..=hi].iter_mut().zip(&b[lo - 1..hi]) {
+            let cost = (a_elem != b_elem) as usize;
+            let above = *cell;
+            left = (diagonal + cost).min(above + 1).min(left + 1).min(outside);
+            diagonal = above;
+            *cell = left;
+            row_min = row_min.min(left);
+        }
+
+        // the smallest distance of a row never decreases in the rows below it
+        if row_min > max {
+            return None;
+        }
+    }
+
+    Some(row[b.len()]).filter(|&distance| distance <= max)
+}
+
+/// Edit distance between two slices, scaled by the length of the longer one. The result is
+/// symmetric, and ranges from 0.0 for equal slices to 1.0 for slices with nothing in common. Two
+/// empty slices are equal.
+///
+/// ```
+/// # use one_team_pad_cipher_cracker::dict::normalized_levenshtein;
+/// assert_eq!(normalized_levenshtein(b"kitten", b"kitten"), 0.0);
+/// assert_eq!(normalized_levenshtein(b"abcd", b"abxy"), 0.5);
+/// assert_eq!(normalized_levenshtein(b"", b"abc"), 1.0);
+/// ```
+pub fn normalized_levenshtein<T: PartialEq>(a: &[T], b: &[T]) -> f64 {
+    let len = a.len().max(b.len());
+    if len == 0 {
+        return 0.0;
+    }
+    levenshtein(a, b) as f64 / len as f64
+}
+
 // Tests for the Dictionary type. These get run with `cargo test`
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[test]
+    fn sample() {
+        let mut words = include_str!("../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+
+        // the same seed samples the same words, and a different one other words
+        let sample = |n, seed| {
+            let n = NonZeroUsize::new(n).unwrap();
+            dict.sample(n, &mut Rng::from_seed(seed)).words
+        };
+        assert_eq!(sample(10, 1), sample(10, 1));
+        assert_ne!(sample(10, 1), sample(10, 2));
+
+        // asking for every word or more keeps the whole dictionary
+        assert_eq!(sample(dict.words.len(), 3), dict.words);
+        assert_eq!(sample(usize::MAX, 3), dict.words);
+        assert_eq!(sample(1, 3).len(), 1);
+    }
+
+    #[test]
+    fn normalized() {
+        assert_eq!(normalized_levenshtein(b"", b""), 0.0);
+        assert_eq!(normalized_levenshtein(b"abc", b""), 1.0);
+        assert_eq!(normalized_levenshtein(b"abc", b"xyz"), 1.0);
+        assert_eq!(normalized_levenshtein(b"abc", b"abcdef"), 0.5);
+        assert_eq!(normalized_levenshtein(b"abcdef", b"abc"), 0.5);
+    }
+
+    #[test]
+    fn within() {
+        use crate::rng::Rng;
+
+        let mut rng = Rng::default();
+        let mut row = Vec::new();
+        for _ in 0..500 {
+            // a small alphabet, so the slices have plenty in common
+            let a: Vec<u8> = (0..rng.gen_range(0..30))
+                .map(|_| rng.gen_range(0..3) as u8)
+                .collect();
+            let b: Vec<u8> = (0..rng.gen_range(0..30))
+                .map(|_| rng.gen_range(0..3) as u8)
+                .collect();
+            let distance = levenshtein(&a, &b);
+
+            for max in 0..35 {
+                let expected = Some(distance).filter(|&d| d <= max);
+                assert_eq!(levenshtein_within(&a, &b, max, &mut row), expected);
+            }
+            assert_eq!(
+                levenshtein_within(&a, &b, usize::MAX, &mut row),
+                Some(distance)
+            );
+        }
+    }
+
+    #[test]
+    fn shift_weighted() {
+        use crate::rng::Rng;
+
+        // shifts wrap around the alphabet, through the space
+        assert_eq!(substitution_cost(0, 26), EDIT_COST / 2);
+        assert_eq!(substitution_cost(3, 5), 3);
+        assert_eq!(substitution_cost(3, 20), EDIT_COST);
+
+        // never more than unit costs, and never less than half of them
+        let mut rng = Rng::default();
+        let mut row = Vec::new();
+        for _ in 0..500 {
+            let a: Vec<u8> = (0..rng.gen_range(0..12))
+                .map(|_| rng.gen_range(0..27) as u8)
+                .collect();
+            let b: Vec<u8> = (0..rng.gen_range(0..12))
+                .map(|_| rng.gen_range(0..27) as u8)
+                .collect();
+            let weighted = shift_levenshtein_with_row(&a, &b, &mut row);
+            assert!(weighted <= levenshtein(&a, &b) * EDIT_COST);
+            assert!(weighted * 2 >= levenshtein(&a, &b) * EDIT_COST);
+        }
+
+        // of two words one substitution away, the one a shift of one away is closer
+        let dict = BytesDictionary::from_dict(&Dictionary {
+            words: vec!["cot", "cat"],
+        });
+        let (word, distance) = dict.best_levenshtein(&str_to_bytes("cbt "));
+        assert_eq!(word, str_to_bytes("cat "));
+        assert_eq!(distance, EDIT_COST / 2);
+    }
+
     #[test]
     fn create() {
         let mut s = String::from("abc def ghi jkl");
@@ -179,4 +584,53 @@ mod tests {
         assert_eq!(d.words[2], "ghi");
         assert_eq!(d.words[3], "jkl");
     }
+
+    #[test]
+    fn bytes_dict() {
+        let mut words = include_str!("../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let bytes_dict = BytesDictionary::from_dict(&dict);
+
+        assert_eq!(bytes_dict.len(), dict.words.len());
+        assert!(bytes_dict.words().all(|w| w.last() == Some(&26)));
+        let lengths: Vec<usize> = bytes_dict.words().map(<[u8]>::len).collect();
+        assert!(lengths.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(bytes_dict.longest_word(), *lengths.last().unwrap());
+
+        // skipping words by length still finds the closest
+        let mut rng = crate::rng::Rng::default();
+        let mut row = Vec::new();
+        for _ in 0..200 {
+            let len = rng.gen_range(1..20);
+            let word: Vec<u8> = (0..len).map(|_| rng.gen_range(0..27) as u8).collect();
+            let closest = bytes_dict
+                .words()
+                .map(|w| shift_levenshtein_with_row(&word, w, &mut row))
+                .min();
+            assert_eq!(Some(bytes_dict.best_levenshtein(&word).1), closest);
+        }
+    }
+
+    #[test]
+    fn large_bytes_dict() {
+        let mut words = include_str!("../words/words_alpha_40k.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let bytes_dict = BytesDictionary::from_dict(&dict);
+        assert!(bytes_dict.len() > PARALLEL_WORDS);
+
+        // searched in shards, but the same closest word as the whole dictionary in one pass
+        let mut rng = crate::rng::Rng::default();
+        let mut row = Vec::new();
+        for _ in 0..20 {
+            let len = rng.gen_range(1..16);
+            let word: Vec<u8> = (0..len).map(|_| rng.gen_range(0..27) as u8).collect();
+            let shared = AtomicUsize::new(usize::MAX);
+            let (distance, _, index) = bytes_dict
+                .closest_in(&word, 0..bytes_dict.len(), &shared, &mut row)
+                .unwrap();
+            let (closest, best) = bytes_dict.best_levenshtein(&word);
+            assert_eq!(best, distance);
+            assert_eq!(closest, bytes_dict.word(index));
+        }
+    }
 }
@@ -1,15 +1,191 @@
 //! Module for [`Generator`].
 
+#[cfg(feature = "crack")]
+use crate::crack::DictionaryStats;
 use crate::dict::Dictionary;
 use crate::rng::Rng;
 
+use std::cell::OnceCell;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// How long a generated message is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MessageLength {
+    /// A number of words, picked from the range
+    Words(Range<usize>),
+    /// A target number of characters, picked from the range. Words are added until the message
+    /// reaches the target, so the last word can run past it.
+    Chars(Range<usize>),
+}
+
+/// How the words of a generated message are picked.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WordLengths {
+    /// Any dictionary word is as likely as any other, so word lengths follow the dictionary's own
+    /// distribution
+    Dictionary,
+    /// Pick a word length by weight, then a dictionary word of that length. Lengths without any
+    /// dictionary words are never picked. If no length with a positive weight has any, words are
+    /// picked like [`Dictionary`][`Self::Dictionary`] does.
+    Weighted(Vec<(usize, f64)>),
+}
+
+/// How the words of a generated message are separated. The message alphabet has no punctuation,
+/// so spaces are all there is to work with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Spacing {
+    /// A single space between words
+    Single,
+    /// Words run together without spaces
+    None,
+    /// A single space between words, and a double space between sentences with a number of words
+    /// picked from the range. The double space stands in for the end of sentence punctuation.
+    Sentences(Range<usize>),
+}
+
+/// The shape of messages made by [`Generator::generate_message`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageShape {
+    pub length: MessageLength,
+    pub word_lengths: WordLengths,
+    pub spacing: Spacing,
+}
+
+#[cfg(feature = "crack")]
+impl WordLengths {
+    /// Word lengths weighted like the words of the dictionary `stats` was computed from, so a
+    /// generator with a different wordbank still makes messages with its word lengths.
+    pub fn from_stats(stats: &DictionaryStats) -> Self {
+        Self::Weighted(
+            stats
+                .word_lengths
+                .iter()
+                .enumerate()
+                .filter(|(_, &count)| count > 0)
+                .map(|(len, &count)| (len, count as f64))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(feature = "crack")]
+impl MessageShape {
+    /// Messages of about `words` words of the dictionary `stats` was computed from, separated by
+    /// single spaces. The length is in characters, by the average word length, so like the
+    /// project's fixed length plaintexts, messages end wherever the characters run out.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::crack::DictionaryStats;
+    /// # use one_team_pad_cipher_cracker::gen::{MessageLength, MessageShape};
+    /// let shape = MessageShape::from_stats(DictionaryStats::project(), 100..200);
+    /// assert!(matches!(shape.length, MessageLength::Chars(chars) if chars.start > 100));
+    /// ```
+    pub fn from_stats(stats: &DictionaryStats, words: Range<usize>) -> Self {
+        let count: usize = stats.word_lengths.iter().sum();
+        let letters: usize = stats
+            .word_lengths
+            .iter()
+            .enumerate()
+            .map(|(len, &count)| len * count)
+            .sum();
+        // each word comes with a space, but the last one
+        let chars_per_word = letters as f64 / count.max(1) as f64 + 1.0;
+        let chars = |words: usize| (words as f64 * chars_per_word).round() as usize;
+
+        Self {
+            length: MessageLength::Chars(chars(words.start).saturating_sub(1)..chars(words.end)),
+            word_lengths: WordLengths::from_stats(stats),
+            spacing: Spacing::Single,
+        }
+    }
+}
+
+impl Default for MessageShape {
+    /// Between 400 and 1600 characters of dictionary words, separated by single spaces.
+    fn default() -> Self {
+        Self {
+            length: MessageLength::Chars(400..1600),
+            word_lengths: WordLengths::Dictionary,
+            spacing: Spacing::Single,
+        }
+    }
+}
+
 /// A deterministic plaintext generator. The purpose is to be able to quickly generate known
 /// plaintexts so that we can encipher them, and then attempt to crack the ciphertext. Since we
 /// generated the plaintext ourself, we can simply compare our cracking results to verify.
 #[derive(Clone, Debug)]
 pub struct Generator<'d> {
-    dictionary: &'d Dictionary<'d>,
+    source: Source<'d>,
     pub rng: Rng,
+    /// Dictionary words grouped by length, built the first time they are needed
+    by_length: OnceCell<Vec<Vec<&'d str>>>,
+}
+
+/// Where a [`Generator`] gets its words from.
+#[derive(Clone, Debug)]
+enum Source<'d> {
+    /// Independent words picked from a wordbank
+    Dictionary(&'d Dictionary<'d>),
+    /// Contiguous passages of real text
+    Corpus(Arc<Corpus>),
+}
+
+/// Natural language text mapped into the message alphabet, so passages can be sampled from it.
+struct Corpus {
+    /// The words of the corpus, lowercase a-z only
+    words: Vec<String>,
+    /// Whether each word ends a sentence in the original text
+    sentence_ends: Vec<bool>,
+}
+
+impl Corpus {
+    /// Split raw text into words of the message alphabet. Letters are lowercased, apostrophes are
+    /// dropped so contractions stay one word, and anything else separates words. Words with letters
+    /// outside of a-z are left out, since they have no spelling in the message alphabet.
+    fn new(text: &str) -> Self {
+        let mut corpus = Self {
+            words: Vec::new(),
+            sentence_ends: Vec::new(),
+        };
+        let mut word = String::new();
+        let mut foreign = false;
+
+        for chr in text.chars().chain(std::iter::once(' ')) {
+            match chr {
+                'a'..='z' | 'A'..='Z' => word.push(chr.to_ascii_lowercase()),
+                '\'' | '\u{2019}' => {}
+                chr if chr.is_alphabetic() => foreign = true,
+                chr => {
+                    if !word.is_empty() && !foreign {
+                        corpus.words.push(std::mem::take(&mut word));
+                        corpus.sentence_ends.push(false);
+                    }
+                    word.clear();
+                    foreign = false;
+
+                    // sentence ending punctuation marks the word before it
+                    if matches!(chr, '.' | '!' | '?') {
+                        if let Some(end) = corpus.sentence_ends.last_mut() {
+                            *end = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        corpus
+    }
+}
+
+impl std::fmt::Debug for Corpus {
+    // a corpus is far too long to print in full
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Corpus")
+            .field("words", &self.words.len())
+            .finish()
+    }
 }
 
 impl<'d> Generator<'d> {
@@ -18,12 +194,58 @@ impl<'d> Generator<'d> {
     pub fn with_dict(dictionary: &'d Dictionary<'d>) -> Self {
         Self {
             rng: Rng::default(),
-            dictionary,
+            source: Source::Dictionary(dictionary),
+            by_length: OnceCell::new(),
+        }
+    }
+
+    /// Same as [`with_dict`][`Self::with_dict`], but seeding the generator with
+    /// [`Rng::from_seed`], so different seeds give different, reproducible streams of messages.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::{dict::Dictionary, gen::Generator};
+    /// let mut words = String::from("abc def ghi jkl");
+    /// let dict = Dictionary::from_string(&mut words);
+    ///
+    /// let a: Vec<String> = Generator::with_seed(&dict, 7).messages(3).take(2).collect();
+    /// let b: Vec<String> = Generator::with_seed(&dict, 7).messages(3).take(2).collect();
+    /// assert_eq!(a, b);
+    /// ```
+    pub fn with_seed(dictionary: &'d Dictionary<'d>, seed: u64) -> Self {
+        Self {
+            rng: Rng::from_seed(seed),
+            ..Self::with_dict(dictionary)
+        }
+    }
+
+    /// Instantiate a generator that samples contiguous passages from real text, rather than
+    /// picking independent words, so plaintexts have natural letter and word statistics.
+    ///
+    /// The text is mapped into the message alphabet first: letters are lowercased, apostrophes
+    /// are dropped, and everything else separates words. Passages start at a random word and wrap
+    /// around to the beginning of the corpus if they run past the end.
+    ///
+    /// Panics if the corpus has no words.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::gen::Generator;
+    /// let mut gen = Generator::from_corpus("It was the best of times, it was the worst of times.");
+    /// let passage = gen.generate_words(4);
+    /// assert!("it was the best of times it was the worst of times it was the"
+    ///     .contains(&passage));
+    /// ```
+    pub fn from_corpus(corpus: &str) -> Self {
+        let corpus = Corpus::new(corpus);
+        assert!(!corpus.words.is_empty(), "corpus has no words");
+        Self {
+            rng: Rng::default(),
+            source: Source::Corpus(Arc::new(corpus)),
+            by_length: OnceCell::new(),
         }
     }
 
     /// Pick `num_words` number of words from the wordbank, join them together with a single space,
-    /// then return as a String.
+    /// then return as a String. A corpus generator takes a passage of `num_words` words instead.
     pub fn generate_words(&mut self, num_words: usize) -> String {
         let mut sentence = String::new();
         self.generate_words_into(num_words, &mut sentence);
@@ -38,9 +260,17 @@ impl<'d> Generator<'d> {
             dest.push(' ');
         }
 
+        let mut cursor = self.passage_start();
         for _ in 0..num_words {
-            // choose a word at random
-            let word = *self.rng.choose(&self.dictionary.words);
+            // choose a word at random, or the next word of the passage
+            let word = match &self.source {
+                Source::Dictionary(dictionary) => *self.rng.choose(&dictionary.words),
+                Source::Corpus(corpus) => {
+                    let word = &corpus.words[cursor];
+                    cursor = (cursor + 1) % corpus.words.len();
+                    word
+                }
+            };
 
             // append the &str to the String
             dest.push_str(word);
@@ -54,6 +284,237 @@ impl<'d> Generator<'d> {
             dest.pop();
         }
     }
+
+    /// Generate a message of exactly `chars` characters, like the fixed length plaintexts of the
+    /// project tests.
+    ///
+    /// Dictionary words are picked so the message ends on a word boundary whenever the dictionary
+    /// has words of the right lengths to fill it. Otherwise, and for corpus passages, the last word
+    /// is truncated, or if only the space after a word fits, the message is padded with that space.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::{dict::Dictionary, gen::Generator};
+    /// let mut words = String::from("abc defg hi");
+    /// let dict = Dictionary::from_string(&mut words);
+    /// assert_eq!(Generator::with_dict(&dict).generate_exact_len(500).len(), 500);
+    /// ```
+    pub fn generate_exact_len(&mut self, chars: usize) -> String {
+        let mut message = String::with_capacity(chars);
+        let mut cursor = self.passage_start();
+
+        // which amounts of room can be filled exactly with whole dictionary words
+        let fillable = match &self.source {
+            Source::Dictionary(dictionary) => {
+                let by_length = Self::by_length(&self.by_length, dictionary);
+                let mut fillable = vec![false; chars + 1];
+                for room in 1..=chars {
+                    fillable[room] = by_length.iter().enumerate().any(|(len, words)| {
+                        !words.is_empty()
+                            && (len == room || (len + 1 < room && fillable[room - len - 1]))
+                    });
+                }
+                fillable
+            }
+            Source::Corpus(_) => Vec::new(),
+        };
+
+        while message.len() < chars {
+            let separator = if message.is_empty() { 0 } else { 1 };
+            if chars - message.len() == separator {
+                message.push(' ');
+                break;
+            }
+            let room = chars - message.len() - separator;
+
+            let word = match &self.source {
+                Source::Dictionary(dictionary) => {
+                    // lengths of words that leave a fillable amount of room after them, weighted by
+                    // how many words have that length
+                    let by_length = Self::by_length(&self.by_length, dictionary);
+                    let lengths: Vec<(usize, f64)> = by_length
+                        .iter()
+                        .enumerate()
+                        .filter(|(len, words)| {
+                            !words.is_empty()
+                                && (*len == room || (len + 1 < room && fillable[room - len - 1]))
+                        })
+                        .map(|(len, words)| (len, words.len() as f64))
+                        .collect();
+
+                    if lengths.is_empty() {
+                        // no whole words can fill the rest, so any word will be cut off or padded
+                        *self.rng.choose(&dictionary.words)
+                    } else {
+                        let len = *self.rng.choose_weighted(&lengths);
+                        *self.rng.choose(&by_length[len])
+                    }
+                }
+                Source::Corpus(corpus) => {
+                    let word = corpus.words[cursor].as_str();
+                    cursor = (cursor + 1) % corpus.words.len();
+                    word
+                }
+            };
+
+            if separator == 1 {
+                message.push(' ');
+            }
+            message.push_str(&word[..word.len().min(room)]);
+        }
+
+        message
+    }
+
+    /// An endless iterator of messages of `num_words` words each, as made by
+    /// [`generate_words`][`Self::generate_words`].
+    pub fn messages(&mut self, num_words: usize) -> Messages<'_, 'd> {
+        Messages {
+            gen: self,
+            num_words,
+        }
+    }
+
+    /// Generate a message with the length, words, and spacing described by `shape`.
+    ///
+    /// A corpus generator ignores `shape.word_lengths`, since it takes passages as they are, and
+    /// [`Spacing::Sentences`] follows the sentences of the corpus rather than the given range.
+    pub fn generate_message(&mut self, shape: &MessageShape) -> String {
+        let mut message = String::new();
+        self.generate_message_into(shape, &mut message);
+        message
+    }
+
+    /// Same as [`generate_message`][`Self::generate_message`] but appends to a String rather than
+    /// returning a String.
+    pub fn generate_message_into(&mut self, shape: &MessageShape, dest: &mut String) {
+        // separate from an already existing message, unless words run together anyway
+        if !dest.is_empty() && !dest.ends_with(' ') && shape.spacing != Spacing::None {
+            dest.push(' ');
+        }
+        let start = dest.len();
+
+        let (num_words, num_chars) = match &shape.length {
+            MessageLength::Words(range) => (Some(self.rng.gen_range(range.clone())), None),
+            MessageLength::Chars(range) => (None, Some(self.rng.gen_range(range.clone()))),
+        };
+
+        // only keep the weights of lengths that can be picked and have words to pick from
+        let length_weights: Vec<(usize, f64)> = match (&shape.word_lengths, &self.source) {
+            (WordLengths::Weighted(weights), Source::Dictionary(dictionary)) => {
+                let by_length = Self::by_length(&self.by_length, dictionary);
+                weights
+                    .iter()
+                    .filter(|(len, weight)| {
+                        *weight > 0.0 && by_length.get(*len).is_some_and(|w| !w.is_empty())
+                    })
+                    .copied()
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        let mut cursor = self.passage_start();
+        let mut words = 0;
+        let mut sentence_left = self.sentence_length(&shape.spacing);
+        loop {
+            let done = match (num_words, num_chars) {
+                (Some(num_words), _) => words >= num_words,
+                (_, Some(num_chars)) => dest.len() - start >= num_chars,
+                _ => unreachable!(),
+            };
+            if done {
+                break;
+            }
+
+            // separate from the previous word
+            if words > 0 {
+                let end_of_sentence = match &self.source {
+                    Source::Dictionary(_) => sentence_left == 0,
+                    Source::Corpus(corpus) => {
+                        let previous = (cursor + corpus.words.len() - 1) % corpus.words.len();
+                        corpus.sentence_ends[previous]
+                    }
+                };
+                match shape.spacing {
+                    Spacing::Single => dest.push(' '),
+                    Spacing::None => {}
+                    Spacing::Sentences(_) if end_of_sentence => {
+                        dest.push_str("  ");
+                        sentence_left = self.sentence_length(&shape.spacing);
+                    }
+                    Spacing::Sentences(_) => dest.push(' '),
+                }
+            }
+
+            let word = match (&self.source, &shape.word_lengths) {
+                (Source::Corpus(corpus), _) => {
+                    let word = &corpus.words[cursor];
+                    cursor = (cursor + 1) % corpus.words.len();
+                    word
+                }
+                (Source::Dictionary(dictionary), WordLengths::Weighted(_))
+                    if !length_weights.is_empty() =>
+                {
+                    let len = *self.rng.choose_weighted(&length_weights);
+                    let by_length = Self::by_length(&self.by_length, dictionary);
+                    *self.rng.choose(&by_length[len])
+                }
+                (Source::Dictionary(dictionary), _) => *self.rng.choose(&dictionary.words),
+            };
+            dest.push_str(word);
+            words += 1;
+            sentence_left = sentence_left.saturating_sub(1);
+        }
+    }
+
+    /// Index of the first word of a corpus passage. Dictionary generators don't use passages, and
+    /// don't use up a random number for one.
+    fn passage_start(&mut self) -> usize {
+        match &self.source {
+            Source::Dictionary(_) => 0,
+            Source::Corpus(corpus) => self.rng.gen_range(0..corpus.words.len()),
+        }
+    }
+
+    /// Number of words in the next sentence, when sentences are separated at all.
+    fn sentence_length(&mut self, spacing: &Spacing) -> usize {
+        match spacing {
+            Spacing::Sentences(range) => self.rng.gen_range(range.clone()),
+            _ => 0,
+        }
+    }
+
+    /// Dictionary words grouped by their length. This only borrows the fields it needs, so the
+    /// words can be chosen from with `self.rng`.
+    fn by_length<'a>(
+        by_length: &'a OnceCell<Vec<Vec<&'d str>>>,
+        dictionary: &Dictionary<'d>,
+    ) -> &'a [Vec<&'d str>] {
+        by_length.get_or_init(|| {
+            let longest = dictionary.words.iter().map(|w| w.len()).max();
+            let mut by_length = vec![Vec::new(); longest.map_or(0, |len| len + 1)];
+            for word in &dictionary.words {
+                by_length[word.len()].push(*word);
+            }
+            by_length
+        })
+    }
+}
+
+/// Iterator over generated messages, created with [`Generator::messages`]. It never ends, so use
+/// [`Iterator::take`] or [`Iterator::zip`] to stop it.
+#[derive(Debug)]
+pub struct Messages<'g, 'd> {
+    gen: &'g mut Generator<'d>,
+    num_words: usize,
+}
+
+impl Iterator for Messages<'_, '_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        Some(self.gen.generate_words(self.num_words))
+    }
 }
 
 // Tests for the Generator type. These get run with `cargo test`
@@ -66,12 +527,12 @@ mod tests {
         let d = Dictionary::from_string(&mut s);
 
         let mut g = Generator::with_dict(&d);
-        assert_eq!("jkl", g.generate_words(1));
-        assert_eq!("ghi", g.generate_words(1));
+        assert_eq!("def", g.generate_words(1));
         assert_eq!("ghi", g.generate_words(1));
         assert_eq!("abc", g.generate_words(1));
-        assert_eq!("abc", g.generate_words(1));
-        assert_eq!("abc", g.generate_words(1));
+        assert_eq!("def", g.generate_words(1));
+        assert_eq!("ghi", g.generate_words(1));
+        assert_eq!("def", g.generate_words(1));
         assert_eq!("def", g.generate_words(1));
     }
 
@@ -81,7 +542,231 @@ mod tests {
         let d = Dictionary::from_string(&mut s);
 
         let mut g = Generator::with_dict(&d);
-        assert_eq!("jkl ghi ghi abc abc abc def", g.generate_words(7));
+        assert_eq!("def ghi abc def ghi def def", g.generate_words(7));
+    }
+
+    #[test]
+    fn message_length() {
+        let mut s = String::from("abc defg hi jklmn");
+        let d = Dictionary::from_string(&mut s);
+        let mut g = Generator::with_dict(&d);
+
+        for _ in 0..100 {
+            let shape = MessageShape {
+                length: MessageLength::Words(3..6),
+                ..MessageShape::default()
+            };
+            let words = g.generate_message(&shape).split(' ').count();
+            assert!((3..6).contains(&words));
+
+            // character targets are reached, overshooting by less than a word
+            let shape = MessageShape {
+                length: MessageLength::Chars(20..40),
+                ..MessageShape::default()
+            };
+            let message = g.generate_message(&shape);
+            assert!((20..45).contains(&message.len()), "{:?}", message);
+            assert!(message.split(' ').all(|word| d.words.contains(&word)));
+        }
+
+        let empty = MessageShape {
+            length: MessageLength::Words(0..1),
+            ..MessageShape::default()
+        };
+        assert_eq!(g.generate_message(&empty), "");
+    }
+
+    #[test]
+    fn weighted_word_lengths() {
+        let mut s = String::from("abc defg hi jklmn op");
+        let d = Dictionary::from_string(&mut s);
+        let mut g = Generator::with_dict(&d);
+
+        // only two letter words, since there are no six letter words to pick
+        let shape = MessageShape {
+            length: MessageLength::Words(50..51),
+            word_lengths: WordLengths::Weighted(vec![(2, 1.0), (6, 10.0)]),
+            spacing: Spacing::Single,
+        };
+        let message = g.generate_message(&shape);
+        assert!(message.split(' ').all(|word| word == "hi" || word == "op"));
+        assert!(message.contains("hi") && message.contains("op"));
+    }
+
+    #[test]
+    fn weighted_word_lengths_without_words() {
+        let mut s = String::from("abc defg hi jklmn op");
+        let d = Dictionary::from_string(&mut s);
+        let mut g = Generator::with_dict(&d);
+
+        // no words of any weighted length, or only of lengths never picked, so any word goes
+        for weights in [vec![], vec![(6, 1.0)], vec![(2, 0.0), (7, 3.0)]] {
+            let shape = MessageShape {
+                length: MessageLength::Words(20..21),
+                word_lengths: WordLengths::Weighted(weights),
+                spacing: Spacing::Single,
+            };
+            let message = g.generate_message(&shape);
+            assert_eq!(message.split(' ').count(), 20);
+            assert!(message.split(' ').all(|word| d.words.contains(&word)));
+        }
+    }
+
+    #[cfg(feature = "crack")]
+    #[test]
+    fn shape_from_stats() {
+        let mut s = String::from("abc defg hi jklmn op");
+        let d = Dictionary::from_string(&mut s);
+        let mut other = String::from("xy zw uvwxy");
+        let stats = DictionaryStats::from_dict(&Dictionary::from_string(&mut other));
+
+        // two letter words twice as often as five letter words, and no others
+        let shape = MessageShape::from_stats(&stats, 100..101);
+        assert_eq!(
+            shape.word_lengths,
+            WordLengths::Weighted(vec![(2, 2.0), (5, 1.0)])
+        );
+        assert_eq!(shape.length, MessageLength::Chars(399..404));
+
+        let message = Generator::with_dict(&d).generate_message(&shape);
+        assert!(message
+            .split(' ')
+            .all(|word| word.len() == 2 || word.len() == 5));
+        assert!((399..409).contains(&message.len()));
+    }
+
+    #[test]
+    fn spacing() {
+        let mut s = String::from("abc def ghi jkl");
+        let d = Dictionary::from_string(&mut s);
+        let mut g = Generator::with_dict(&d);
+
+        let shape = MessageShape {
+            length: MessageLength::Words(10..11),
+            word_lengths: WordLengths::Dictionary,
+            spacing: Spacing::None,
+        };
+        let message = g.generate_message(&shape);
+        assert_eq!(message.len(), 30);
+        assert!(!message.contains(' '));
+
+        // sentences of 2 or 3 words are separated by a double space
+        let shape = MessageShape {
+            spacing: Spacing::Sentences(2..4),
+            ..shape
+        };
+        let message = g.generate_message(&shape);
+        assert!(!message.starts_with(' ') && !message.ends_with(' '));
+        let sentences: Vec<_> = message.split("  ").collect();
+        assert!(sentences.len() >= 3, "{:?}", message);
+        for sentence in &sentences[..sentences.len() - 1] {
+            let words = sentence.split(' ').count();
+            assert!((2..4).contains(&words), "{:?}", message);
+        }
+    }
+
+    #[test]
+    fn corpus_words() {
+        let corpus = Corpus::new("Don't panic!  It's 42... a naïve café-like\nplace, the end");
+        assert_eq!(
+            corpus.words,
+            ["dont", "panic", "its", "a", "like", "place", "the", "end"]
+        );
+        assert_eq!(
+            corpus.sentence_ends,
+            [false, true, true, false, false, false, false, false]
+        );
+    }
+
+    #[test]
+    fn corpus_passages() {
+        let text = "One two three. Four five six. Seven eight nine.";
+        let mut g = Generator::from_corpus(text);
+        let circular = "one two three four five six seven eight nine ".repeat(2);
+
+        // passages are contiguous, wrapping around the end of the corpus
+        for _ in 0..50 {
+            let passage = g.generate_words(5);
+            assert_eq!(passage.split(' ').count(), 5);
+            assert!(circular.contains(&passage), "{:?}", passage);
+        }
+
+        // sentences come from the corpus punctuation, and word lengths are ignored
+        let shape = MessageShape {
+            length: MessageLength::Chars(40..41),
+            word_lengths: WordLengths::Weighted(vec![(1, 1.0)]),
+            spacing: Spacing::Sentences(1..2),
+        };
+        let circular = "one two three. four five six. seven eight nine. ".repeat(3);
+        for _ in 0..50 {
+            let message = g.generate_message(&shape);
+            let sentences = message.replace("  ", ". ");
+            assert!(circular.contains(&sentences), "{:?}", message);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn empty_corpus() {
+        let _ = Generator::from_corpus("123 ... !!!");
+    }
+
+    #[test]
+    fn exact_len() {
+        let mut s = String::from("abc defg hi jklmn op");
+        let d = Dictionary::from_string(&mut s);
+        let mut g = Generator::with_dict(&d);
+
+        for chars in 0..300 {
+            let message = g.generate_exact_len(chars);
+            assert_eq!(message.len(), chars);
+            assert!(!message.starts_with(' ') && !message.contains("  "));
+
+            // words 2 through 5 letters long can fill any message with whole words
+            if chars > 1 {
+                assert!(
+                    message.split(' ').all(|word| d.words.contains(&word)),
+                    "{:?}",
+                    message
+                );
+            }
+        }
+
+        // with only three letter words, the last word is cut off or padded with a space
+        let mut s = String::from("abc def");
+        let d = Dictionary::from_string(&mut s);
+        let mut g = Generator::with_dict(&d);
+        for chars in 0..50 {
+            let message = g.generate_exact_len(chars);
+            assert_eq!(message.len(), chars);
+            let words: Vec<_> = message.split(' ').collect();
+            let (last, rest) = words.split_last().unwrap();
+            assert!(rest.iter().all(|word| word.len() == 3));
+            assert!(last.len() <= 3);
+            assert!(last.is_empty() == (chars % 4 == 0));
+        }
+
+        let mut g = Generator::from_corpus("one two three four five");
+        let passage = g.generate_exact_len(100);
+        assert_eq!(passage.len(), 100);
+        assert!("one two three four five ".repeat(6).contains(&passage));
+    }
+
+    #[test]
+    fn seeded_messages() {
+        let mut s = String::from("abc def ghi jkl");
+        let d = Dictionary::from_string(&mut s);
+
+        // the iterator is the same stream as calling generate_words
+        let mut g = Generator::with_seed(&d, 1);
+        let messages: Vec<String> = g.messages(4).take(3).collect();
+        let mut g = Generator::with_seed(&d, 1);
+        for message in &messages {
+            assert_eq!(message, &g.generate_words(4));
+        }
+
+        let other: Vec<String> = Generator::with_seed(&d, 2).messages(4).take(3).collect();
+        assert_ne!(messages, other);
     }
 
     #[test]
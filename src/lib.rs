@@ -0,0 +1,33 @@
+//! Cracking ciphertexts encrypted with the project's one-time-pad-like cipher.
+//!
+//! The command line program in `main.rs` is a thin wrapper around this library, which is also
+//! used by the benchmarks in `benches/`.
+//!
+//! Only the random number generator, the dictionary, and the character helpers in `utils` are
+//! always built. The rest is split into features, all on by default, so a program that only
+//! encrypts doesn't pull in the cracker's dependencies:
+//!
+//!  - `cipher`: `ciphers`, the encryptor and the key schedulers
+//!  - `gen`: `gen`, plaintext generation
+//!  - `crack`: `crack`, cracking a single ciphertext. Needs `cipher`.
+//!  - `workers`: the worker pool and what's built on it, like campaigns and the crack server.
+//!    Needs `crack` and `gen`.
+
+// these "mod" statements bring in ciphers/mod.rs, crack/mod.rs, dict.rs, gen.rs, prelude.rs,
+// rng.rs, and utils.rs files
+#[cfg(feature = "cipher")]
+pub mod ciphers;
+#[cfg(feature = "crack")]
+pub mod crack;
+pub mod dict;
+#[cfg(feature = "gen")]
+pub mod gen;
+pub mod prelude;
+pub mod rng;
+#[cfg(all(feature = "cipher", any(test, feature = "testing")))]
+pub mod testing;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -1,14 +1,870 @@
-// these "mod" statements bring in ciphers/mod.rs, dict.rs, gen.rs, and utils.rs files
-mod ciphers;
-mod crack;
-mod dict;
-mod gen;
-mod rng;
-mod utils;
+mod cli;
 
-use crack::crack_single_ciphertext;
+use anyhow::{anyhow, bail, Context};
+use cli::Args;
+use one_team_pad_cipher_cracker::ciphers::schedulers::{
+    PeriodicRand, RandomScheduler, SchedulerComplexity, SchedulerKind,
+};
+use one_team_pad_cipher_cracker::ciphers::{Cipher, Encryptor, KeySchedule};
+use one_team_pad_cipher_cracker::crack::bench::Bench;
+use one_team_pad_cipher_cracker::crack::columns::ColumnDiagnostics;
+use one_team_pad_cipher_cracker::crack::depth::read_depth;
+use one_team_pad_cipher_cracker::crack::explain::Explanation;
+use one_team_pad_cipher_cracker::crack::hypotheses::{HypothesisFeed, HypothesisScheduler, Policy};
+use one_team_pad_cipher_cracker::crack::randomness::Randomness;
+use one_team_pad_cipher_cracker::crack::refine::Command;
+use one_team_pad_cipher_cracker::crack::render::{html_page, DiffView, RenderFormat};
+use one_team_pad_cipher_cracker::crack::threads::{self, ThreadConfig};
+use one_team_pad_cipher_cracker::crack::worker::{ResultsChannel, WhenFull, WorkerPool};
+use one_team_pad_cipher_cracker::crack::{
+    CrackOptions, CrackResult, Cracker, DictionaryStats, SpellChecker,
+};
+use one_team_pad_cipher_cracker::dict::{BytesDictionary, Dictionary};
+use one_team_pad_cipher_cracker::gen::Generator;
+use one_team_pad_cipher_cracker::rng::Rng;
+use one_team_pad_cipher_cracker::utils::{
+    bytes_to_str, check_alphabet, str_to_bytes, Key, ALPHABET, MAX_KEYLENGTH,
+};
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 fn main() -> anyhow::Result<()> {
+    init_logging();
+
+    // 0. parse command line arguments, and pick the subcommand
+    let mut args = Args::from_env(&[
+        "project-mode",
+        "partial",
+        "pin-threads",
+        "drop-results",
+        "adaptive",
+    ])?;
+
+    // every subcommand takes --threads and --pin-threads, which set up every thread pool up front
+    threads::configure(ThreadConfig {
+        threads: args.take("threads")?,
+        pin: args.take::<String>("pin-threads")?.is_some(),
+    })?;
+
+    // every subcommand takes --seed, so any run that generates random trials can be repeated
+    let seed = args.value("seed")?;
+
+    match args.positional.first().map(String::as_str) {
+        None => crack_stdin(&args),
+        Some("bench") => bench(&args, seed),
+        Some("campaign") => campaign(&args, seed),
+        #[cfg(feature = "serde")]
+        Some("dataset") => dataset(&args, seed),
+        Some("depth") => depth(&args),
+        Some("encrypt") => encrypt(&args, seed),
+        Some("explain") => explain(&args),
+        Some("gen") => gen(&args, seed),
+        Some("refine") => refine(&args),
+        Some("selftest") => selftest(&args, seed),
+        Some("stats") => stats(&args, seed),
+        #[cfg(feature = "tui")]
+        Some("tui") => tui(&args),
+        #[cfg(feature = "serde")]
+        Some("serve") => serve(&args),
+        #[cfg(feature = "serde")]
+        Some("replay") => replay(&args),
+        Some(other) => bail!("unknown subcommand {:?}", other),
+    }
+}
+
+/// Log to stderr, filtered by `RUST_LOG` (warnings and errors only by default), and as JSON lines
+/// if `RUST_LOG_FORMAT=json`.
+fn init_logging() {
+    use tracing_subscriber::EnvFilter;
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    let logger = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    if std::env::var("RUST_LOG_FORMAT").is_ok_and(|format| format == "json") {
+        logger.json().init();
+    } else {
+        logger.init();
+    }
+}
+
+/// Benchmark the cracker on generated ciphertexts, and print the throughput and timings.
+fn bench(args: &Args, seed: Option<u64>) -> anyhow::Result<()> {
+    args.reject_unknown(&["count", "words", "schedulers", "seed"])?;
+
+    let mut bench = Bench::default();
+    if let Some(count) = args.value("count")? {
+        bench.count = count;
+    }
+    if let Some(words) = args.list("words")? {
+        bench.plaintext_words = words;
+    }
+    if let Some(seed) = seed {
+        bench.seed = seed;
+    }
+    if let Some(names) = args.list::<String>("schedulers")? {
+        bench.schedulers = names
+            .iter()
+            .map(|name| {
+                SchedulerKind::from_name(name)
+                    .ok_or_else(|| anyhow!("unknown scheduler {:?}", name))
+            })
+            .collect::<anyhow::Result<_>>()?;
+    }
+
+    println!("{}", bench.run());
+    Ok(())
+}
+
+/// Build a dataset of encrypted trials as NDJSON, on stdout or to `--out FILE`. With
+/// `--eval FILE`, crack a dataset instead and print its accuracy, also writing the report as JSON
+/// to `--report FILE`.
+#[cfg(feature = "serde")]
+fn dataset(args: &Args, seed: Option<u64>) -> anyhow::Result<()> {
+    use one_team_pad_cipher_cracker::crack::dataset::{self, Dataset};
+    use one_team_pad_cipher_cracker::crack::eval::evaluate_dataset;
+
+    args.reject_unknown(&["count", "complexity", "out", "eval", "report", "seed"])?;
+
+    if let Some(path) = args.value::<PathBuf>("eval")? {
+        let report = evaluate_dataset(dataset::load(&path)?);
+        print!("{}", report);
+        if let Some(path) = args.value::<PathBuf>("report")? {
+            let json = serde_json::to_string_pretty(&report)?;
+            std::fs::write(&path, json + "\n")
+                .with_context(|| format!("could not write {}", path.display()))?;
+        }
+        return Ok(());
+    }
+
+    let mut dataset = Dataset::default();
+    if let Some(count) = args.value("count")? {
+        dataset.count = count;
+    }
+    if let Some(seed) = seed {
+        dataset.seed = seed;
+    }
+    eprintln!("seed {}", dataset.seed);
+    if let Some(names) = args.list::<String>("complexity")? {
+        dataset.complexities = names
+            .iter()
+            .map(|name| {
+                SchedulerComplexity::from_name(name)
+                    .ok_or_else(|| anyhow!("unknown complexity {:?}", name))
+            })
+            .collect::<anyhow::Result<_>>()?;
+    }
+
+    match args.value::<PathBuf>("out")? {
+        Some(path) => {
+            let file = std::fs::File::create(&path)
+                .with_context(|| format!("could not create {}", path.display()))?;
+            dataset.write(std::io::BufWriter::new(file))
+        }
+        None => dataset.write(std::io::stdout().lock()),
+    }
+}
+
+/// Read two messages in depth from stdin: two ciphertexts of the same length, one per line,
+/// encrypted under the same keystream. Both are decoded together into words of the project
+/// dictionary, and their plaintexts printed one per line.
+fn depth(args: &Args) -> anyhow::Result<()> {
+    args.reject_unknown(&["seed"])?;
+
+    eprintln!("Enter the two ciphertexts, each followed by a newline:");
+    let stdin = std::io::stdin();
+    let read = |name| -> anyhow::Result<Vec<u8>> {
+        let mut line = String::new();
+        stdin.read_line(&mut line)?;
+        let line = line.trim();
+        check_alphabet(name, line)?;
+        Ok(str_to_bytes(line))
+    };
+    let first = read("first ciphertext")?;
+    let second = read("second ciphertext")?;
+    if first.len() != second.len() {
+        bail!(
+            "the ciphertexts are {} and {} characters long, but messages in depth are the same length",
+            first.len(),
+            second.len()
+        );
+    }
+
+    let mut words = include_str!("../words/default.txt").to_string();
+    let bytes_dict = BytesDictionary::from_dict(&Dictionary::from_string(&mut words));
+    let cracked = read_depth(&first, &second, &bytes_dict)
+        .ok_or_else(|| anyhow!("no words of the dictionary fit both ciphertexts"))?;
+    eprintln!(
+        "{:.1}% of characters don't fit the difference of the ciphertexts",
+        cracked.confidence * 100.0
+    );
+    println!("{}", bytes_to_str(&cracked.first));
+    println!("{}", bytes_to_str(&cracked.second));
+    Ok(())
+}
+
+/// Encrypt the plaintext on stdin with `--key`, as letters or a list of shifts (or a key derived
+/// from `--passphrase`), and a scheduler of the family named `--scheduler` (`RepeatingKey` by
+/// default). `--rand PERIODS` stacks a layer of random characters every so many characters on top
+/// of it for each period, outermost first. The scheduler's parameters and the random characters
+/// come from the seed.
+fn encrypt(args: &Args, seed: Option<u64>) -> anyhow::Result<()> {
+    args.reject_unknown(&["key", "passphrase", "scheduler", "rand", "seed"])?;
+
+    let key = match (
+        args.value::<Key>("key")?,
+        args.value::<String>("passphrase")?,
+    ) {
+        (Some(_), Some(_)) => bail!("--key and --passphrase can't be used together"),
+        (Some(key), None) => key,
+        (None, Some(passphrase)) => Key::from_passphrase(&passphrase),
+        (None, None) => bail!("--key or --passphrase is required"),
+    };
+    if key.len() > MAX_KEYLENGTH {
+        bail!("--key must be 1 to {} shifts long", MAX_KEYLENGTH);
+    }
+    eprintln!("key: {} ({:#})", key, key);
+    let name = args
+        .value::<String>("scheduler")?
+        .unwrap_or_else(|| SchedulerKind::RepeatingKey.name().to_string());
+    let kind =
+        SchedulerKind::from_name(&name).ok_or_else(|| anyhow!("unknown scheduler {:?}", name))?;
+    let periods: Vec<usize> = args.list("rand")?.unwrap_or_default();
+    if periods.contains(&0) {
+        bail!("--rand periods must be at least 1");
+    }
+
+    // the scheduler is only known now, so it's boxed
+    let mut rng = Rng::from_seed(seed.unwrap_or(0));
+    let mut sched: Box<dyn KeySchedule> = Box::new(kind.generate(&mut rng));
+    for &period in periods.iter().rev() {
+        let rand = PeriodicRand {
+            period,
+            start: period,
+            overwrite: false,
+        };
+        sched = Box::new((rand, sched));
+    }
+    eprintln!("scheduler: {:?}", sched);
+
+    eprintln!("Enter the plaintext followed by a newline:");
+    let mut plaintext = String::new();
+    std::io::stdin().read_line(&mut plaintext)?;
+    let plaintext = plaintext.trim();
+    check_alphabet("plaintext", plaintext)?;
+
+    println!("{}", Encryptor::new(key, sched, rng).encrypt(plaintext));
+    Ok(())
+}
+
+/// Annotate a crack of the ciphertext on stdin, or in `--ciphertext FILE`: the key that fits, the
+/// random characters, a guess at the scheduler, and the texts side by side, then the words that
+/// spellchecking against the project dictionary would change, and the crack and its spellchecked
+/// version as a diff (also written as HTML to `--html FILE`). The plaintext is cracked first,
+/// unless a crack to explain is given with `--crack FILE`. First of all comes the strategy an
+/// adaptive crack (`--adaptive`) would pick for the ciphertext's length.
+///
+/// Between them goes the frequency attack on each column of the ciphertext, as a histogram with
+/// the best and runner-up shifts, marking the columns it is unsure of. The keylength is the one
+/// that fits the crack best, unless it is given with `--keylength N`, and the columns are also
+/// written as CSV to `--columns-csv FILE`.
+fn explain(args: &Args) -> anyhow::Result<()> {
+    args.reject_unknown(&[
+        "ciphertext",
+        "crack",
+        "width",
+        "keylength",
+        "columns-csv",
+        "html",
+        "seed",
+    ])?;
+
+    let read = |path: &PathBuf| {
+        std::fs::read_to_string(path)
+            .map(|text| text.trim().to_string())
+            .with_context(|| format!("could not read {}", path.display()))
+    };
+
+    let ciphertext = match args.value::<PathBuf>("ciphertext")? {
+        Some(path) => read(&path)?,
+        None => {
+            eprintln!("Enter the ciphertext followed by a newline:");
+            let mut ciphertext = String::new();
+            std::io::stdin().read_line(&mut ciphertext)?;
+            ciphertext.trim().to_string()
+        }
+    };
+    check_alphabet("ciphertext", &ciphertext)?;
+    let cracker = Cracker::default();
+    let plaintext = match args.value::<PathBuf>("crack")? {
+        Some(path) => read(&path)?,
+        None => bytes_to_str(&cracker.crack(&ciphertext).plaintext),
+    };
+    check_alphabet("crack", &plaintext)?;
+
+    let cipherbytes = str_to_bytes(&ciphertext);
+    let explanation = match args.value("keylength")? {
+        Some(0) => bail!("--keylength must be at least 1"),
+        Some(keylength) if keylength > cipherbytes.len() => bail!(
+            "--keylength {} is longer than the ciphertext, which has {} characters",
+            keylength,
+            cipherbytes.len()
+        ),
+        Some(keylength) => {
+            Explanation::at_keylength(&cipherbytes, &str_to_bytes(&plaintext), keylength)
+        }
+        None => Explanation::new(&cipherbytes, &str_to_bytes(&plaintext)),
+    };
+    let width = args.value("width")?.unwrap_or(64);
+    // what an adaptive crack would pick for this ciphertext
+    print!("{}", cracker.dispatch(&ciphertext));
+    println!();
+    print!("{:1$}", explanation, width);
+
+    let columns = ColumnDiagnostics::new(
+        &cipherbytes,
+        explanation.keylength,
+        &DictionaryStats::project().letters,
+    );
+    println!();
+    print!("{}", columns);
+    if let Some(path) = args.value::<PathBuf>("columns-csv")? {
+        std::fs::write(&path, columns.to_csv())
+            .with_context(|| format!("could not write {}", path.display()))?;
+    }
+
+    let mut words = include_str!("../words/default.txt").to_string();
+    let bytes_dict = BytesDictionary::from_dict(&Dictionary::from_string(&mut words));
+    let crack = CrackResult::new(str_to_bytes(&plaintext), 0.0);
+    let correction = SpellChecker::new(&bytes_dict).correct(&crack);
+    println!();
+    println!(
+        "spellcheck: {} words corrected",
+        correction.corrections.len()
+    );
+    print!("{}", correction);
+
+    let view = DiffView::from_correction(&crack, &correction);
+    println!();
+    println!("spellcheck diff:");
+    print_diff(&view);
+    write_html(args, "crack explanation", &[("spellcheck", &view)])
+}
+
+/// Print `view`, in color if stdout is a terminal.
+fn print_diff(view: &DiffView) {
+    use std::io::IsTerminal;
+
+    let format = if std::io::stdout().is_terminal() {
+        RenderFormat::Ansi
+    } else {
+        RenderFormat::Plain
+    };
+    println!("{}", view.render(format));
+}
+
+/// Write `views` as an HTML page to `--html FILE`, if it was given.
+fn write_html(args: &Args, title: &str, views: &[(&str, &DiffView)]) -> anyhow::Result<()> {
+    if let Some(path) = args.value::<PathBuf>("html")? {
+        std::fs::write(&path, html_page(title, views))
+            .with_context(|| format!("could not write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Generate plaintexts, one per line on stdout, or one per file with `--out DIR`. Words come from
+/// the project dictionary, another dictionary with `--dict`, or passages of a text with `--corpus`.
+fn gen(args: &Args, seed: Option<u64>) -> anyhow::Result<()> {
+    args.reject_unknown(&["words", "chars", "count", "dict", "corpus", "out", "seed"])?;
+
+    let seed = seed.unwrap_or(0);
+    eprintln!("seed {}", seed);
+
+    let count: usize = args.value("count")?.unwrap_or(1);
+    let words: Option<usize> = args.value("words")?;
+    let chars: Option<usize> = args.value("chars")?;
+    if words.is_some() && chars.is_some() {
+        bail!("--words and --chars can't be used together");
+    }
+
+    let dict_path: Option<PathBuf> = args.value("dict")?;
+    let corpus_path: Option<PathBuf> = args.value("corpus")?;
+    let mut source = match (&dict_path, &corpus_path) {
+        (Some(_), Some(_)) => bail!("--dict and --corpus can't be used together"),
+        (Some(path), None) | (None, Some(path)) => std::fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?,
+        (None, None) => include_str!("../words/default.txt").to_string(),
+    };
+
+    let dict;
+    let mut gen = match corpus_path {
+        Some(_) => {
+            if !source.chars().any(|chr| chr.is_ascii_alphabetic()) {
+                bail!("corpus has no words");
+            }
+            let mut gen = Generator::from_corpus(&source);
+            gen.rng = Rng::from_seed(seed);
+            gen
+        }
+        None => {
+            dict = Dictionary::from_string(&mut source);
+            if dict.words.is_empty() {
+                bail!("dictionary has no words");
+            }
+            Generator::with_seed(&dict, seed)
+        }
+    };
+
+    let out: Option<PathBuf> = args.value("out")?;
+    if let Some(dir) = &out {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("could not create {}", dir.display()))?;
+    }
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for i in 0..count {
+        let plaintext = match chars {
+            Some(chars) => gen.generate_exact_len(chars),
+            None => gen.generate_words(words.unwrap_or(200)),
+        };
+
+        match &out {
+            Some(dir) => {
+                let path = dir.join(format!("plaintext_{:04}.txt", i));
+                std::fs::write(&path, plaintext + "\n")
+                    .with_context(|| format!("could not write {}", path.display()))?;
+            }
+            None => writeln!(stdout, "{}", plaintext)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Crack the ciphertext in `--ciphertext FILE`, or the first line of stdin, then read corrections
+/// from stdin one per line and crack again under them, until `done` or the end of input.
+fn refine(args: &Args) -> anyhow::Result<()> {
+    args.reject_unknown(&["ciphertext", "keylength", "seed"])?;
+
+    let stdin = std::io::stdin();
+    let ciphertext = match args.value::<PathBuf>("ciphertext")? {
+        Some(path) => std::fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", path.display()))?,
+        None => {
+            eprintln!("Enter the ciphertext followed by a newline:");
+            let mut ciphertext = String::new();
+            stdin.read_line(&mut ciphertext)?;
+            ciphertext
+        }
+    };
+    let ciphertext = ciphertext.trim();
+    check_alphabet("ciphertext", ciphertext)?;
+
+    let cracker = Cracker::default();
+    let mut refinement = match args.value("keylength")? {
+        Some(0) => bail!("--keylength must be at least 1"),
+        Some(keylength) => cracker.refine_with_keylength(ciphertext, keylength)?,
+        None => cracker.refine(ciphertext),
+    };
+
+    const HELP: &str = "\
+commands:
+  word N is WORD   the Nth word (from 0) of the crack is WORD
+  key[N] = S       key symbol N (from 0) is shift S, or a letter
+  keylength N      crack with keylength N, dropping pinned key symbols
+  clear            drop every pin
+  show             print the crack again
+  done             print the spellchecked crack on stdout and quit";
+
+    eprint!("{}", refinement);
+    eprintln!("{}", HELP);
+    loop {
+        eprint!("> ");
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            break;
+        }
+        match line.trim() {
+            "done" | "quit" => break,
+            "" | "show" => eprint!("{}", refinement),
+            "help" => eprintln!("{}", HELP),
+            line => match line
+                .parse::<Command>()
+                .and_then(|command| refinement.apply(&command))
+            {
+                Ok(()) => eprint!("{}", refinement),
+                Err(err) => eprintln!("{:#}", err),
+            },
+        }
+    }
+
+    println!("{}", bytes_to_str(&refinement.spellchecked().plaintext));
+    Ok(())
+}
+
+/// Check how random the ciphertext on stdin, or in `--ciphertext FILE`, looks: the entropy of its
+/// symbols, a chi-squared test of their counts, and a runs test of their order. A ciphertext that
+/// fails them was likely made by a weak scheduler. With `--rng COUNT`, the same checks are made of
+/// COUNT random symbols from the internal rng instead, seeded by `--seed`.
+fn stats(args: &Args, seed: Option<u64>) -> anyhow::Result<()> {
+    args.reject_unknown(&["ciphertext", "rng", "seed"])?;
+
+    let symbols = match args.value::<usize>("rng")? {
+        Some(count) => {
+            let mut rng = Rng::from_seed(seed.unwrap_or(0));
+            (0..count)
+                .map(|_| rng.gen_range(0..ALPHABET.len()) as u8)
+                .collect()
+        }
+        None => {
+            let ciphertext = match args.value::<PathBuf>("ciphertext")? {
+                Some(path) => std::fs::read_to_string(&path)
+                    .with_context(|| format!("could not read {}", path.display()))?,
+                None => {
+                    eprintln!("Enter the ciphertext followed by a newline:");
+                    let mut ciphertext = String::new();
+                    std::io::stdin().read_line(&mut ciphertext)?;
+                    ciphertext
+                }
+            };
+            let ciphertext = ciphertext.trim();
+            check_alphabet("ciphertext", ciphertext)?;
+            str_to_bytes(ciphertext)
+        }
+    };
+
+    let randomness = Randomness::of(&symbols);
+    print!("{}", randomness);
+    println!(
+        "{}",
+        match randomness.looks_random(0.001) {
+            true => "looks random",
+            false => "doesn't look random",
+        }
+    );
+    Ok(())
+}
+
+/// Sweep the default campaign of schedulers, key lengths, plaintext lengths and rand periods, with
+/// `--trials N` trials per cell, on `--workers N` threads, and with `--dictionary-words N` words of
+/// the dictionary for a quicker sweep. The report goes to `--report FILE`, as JSON if it ends in
+/// `.json` and CSV otherwise, or as CSV to stdout. With `--checkpoint FILE`, the finished trials
+/// are saved as it goes, and `--resume FILE` picks up from them, saving to the same file unless
+/// `--checkpoint` says otherwise. Finished trials wait to be counted in a channel of
+/// `--results-capacity N`, and workers wait for room beyond that.
+fn campaign(args: &Args, seed: Option<u64>) -> anyhow::Result<()> {
+    use one_team_pad_cipher_cracker::crack::campaign::{Campaign, Checkpoint};
+
+    if args.value::<String>("drop-results")?.is_some() {
+        bail!("campaign can't --drop-results, since dropped trials are left out of the report");
+    }
+    args.reject_unknown(&[
+        "trials",
+        "workers",
+        "dictionary-words",
+        "report",
+        "checkpoint",
+        "resume",
+        "results-capacity",
+        "seed",
+    ])?;
+
+    let defaults = Campaign::default();
+    let campaign = Campaign {
+        trials: args.value("trials")?.unwrap_or(defaults.trials),
+        num_workers: args.value("workers")?.unwrap_or(defaults.num_workers),
+        dictionary_words: args.value("dictionary-words")?,
+        seed: seed.unwrap_or(defaults.seed),
+        results_capacity: args
+            .value("results-capacity")?
+            .unwrap_or(defaults.results_capacity),
+        ..defaults
+    };
+
+    let resume = args.value::<PathBuf>("resume")?;
+    let checkpoint = args.value::<PathBuf>("checkpoint")?.or(resume.clone());
+    let report = match checkpoint {
+        Some(path) => {
+            let resume = resume.map(|path| Checkpoint::read(&path)).transpose()?;
+            if let Some(resume) = &resume {
+                eprintln!("resuming after {} finished trials", resume.finished());
+            }
+            campaign.run_checkpointed(resume, &path)?
+        }
+        None => campaign.run(),
+    };
+    eprintln!("{}", report.results);
+
+    match args.value::<PathBuf>("report")? {
+        Some(path) => report.write(&path)?,
+        None => print!("{}", report.to_csv()),
+    }
+    Ok(())
+}
+
+/// Encrypt and crack random trials on the worker pool, and print the success rates. With
+/// `--record`, every trial is also logged so it can be replayed later. Results wait for the
+/// reader in a channel of `--results-capacity N`, and with `--drop-results`, results that don't
+/// fit are dropped instead of making the workers wait. With `--hypotheses round-robin` or
+/// `--hypotheses bandit`, trials test every scheduler family and band of key lengths in turn, or
+/// mostly the ones that have been cracking, and how each did is printed too.
+fn selftest(args: &Args, seed: Option<u64>) -> anyhow::Result<()> {
+    args.reject_unknown(&[
+        "trials",
+        "complexity",
+        "workers",
+        "record",
+        "results-capacity",
+        "drop-results",
+        "hypotheses",
+        "seed",
+    ])?;
+
+    let seed = seed.unwrap_or(WorkerPool::DEFAULT_SEED);
+    eprintln!("seed {}", seed);
+
+    let trials: usize = args.value("trials")?.unwrap_or(100);
+    let workers = match args.value("workers")? {
+        // a pool without workers would never crack a trial, and wait on them forever
+        Some(0) => bail!("--workers must be at least 1"),
+        Some(workers) => workers,
+        None => threads::threads(),
+    };
+    let complexity = match args.value::<String>("complexity")? {
+        None => SchedulerComplexity::Hard,
+        Some(name) => SchedulerComplexity::from_name(&name)
+            .ok_or_else(|| anyhow!("unknown complexity {:?}", name))?,
+    };
+    let channel = results_channel(args)?;
+
+    // shared with the aggregator thread, and taken back to finish once it is done
+    #[cfg(feature = "serde")]
+    let recorder = Arc::new(std::sync::Mutex::new(
+        match args.value::<PathBuf>("record")? {
+            Some(path) => {
+                Some(one_team_pad_cipher_cracker::crack::replay::Recorder::create(&path)?)
+            }
+            None => None,
+        },
+    ));
+    #[cfg(not(feature = "serde"))]
+    if args.value::<String>("record")?.is_some() {
+        bail!("--record needs the serde feature");
+    }
+
+    let mut pool = WorkerPool::spawn_with(workers, seed, channel);
+
+    // a couple of trials per worker in flight keeps every worker busy, while the bandit still
+    // learns from most results before picking the next hypothesis
+    let feed = match args.value::<String>("hypotheses")? {
+        Some(name) => {
+            let policy = Policy::from_name(&name)
+                .ok_or_else(|| anyhow!("unknown hypothesis policy {:?}", name))?;
+            let scheduler = HypothesisScheduler::new(policy, complexity, seed);
+            Some(Arc::new(HypothesisFeed::new(scheduler, 2 * workers)))
+        }
+        None => None,
+    };
+
+    // results are recorded and aggregated on a thread of their own, so the workers never wait on
+    // this one
+    #[cfg(feature = "serde")]
+    let recording = Arc::clone(&recorder);
+    let feeding = feed.clone();
+    let aggregator = pool.spawn_aggregator(move |result| {
+        #[cfg(feature = "serde")]
+        if let Some(recorder) = recording.lock().unwrap().as_mut() {
+            recorder.record(&result.trial)?;
+        }
+        if let Some(feed) = &feeding {
+            feed.record(result);
+        }
+        if !result.success {
+            eprintln!(
+                "trial {} failed: error {:.4}, guessed test {}",
+                result.trial.id, result.error, result.guessed_test
+            );
+        }
+        Ok(())
+    });
+
+    // feed schedulers from another thread so the bounded channel can't block us
+    let schedulers = pool.sender().expect("pool was just spawned");
+    let feeding = feed.clone();
+    std::thread::spawn(move || {
+        if let Some(feed) = feeding {
+            return feed.submit(&schedulers, trials);
+        }
+        let mut rng = Rng::from_seed(seed);
+        for _ in 0..trials {
+            let sched = RandomScheduler::with_complexity(&mut rng, complexity);
+            if schedulers.submit(sched).is_err() {
+                return;
+            }
+        }
+    });
+    pool.shutdown();
+
+    let aggregator = aggregator
+        .join()
+        .map_err(|_| anyhow!("aggregator panicked"))??;
+    let stats = pool.results_stats();
+    pool.join().map_err(|_| anyhow!("worker panicked"))?;
+
+    #[cfg(feature = "serde")]
+    if let Some(recorder) = recorder.lock().unwrap().take() {
+        recorder.finish()?;
+    }
+
+    eprintln!("{}", stats);
+    print!("{}", aggregator);
+    if let Some(feed) = feed {
+        println!();
+        print!("{}", feed.scheduler());
+    }
+    Ok(())
+}
+
+/// A results channel of `--results-capacity N`, dropping results that don't fit with
+/// `--drop-results` instead of making the workers wait.
+fn results_channel(args: &Args) -> anyhow::Result<ResultsChannel> {
+    let defaults = ResultsChannel::default();
+    Ok(ResultsChannel {
+        capacity: args.value("results-capacity")?.unwrap_or(defaults.capacity),
+        when_full: match args.value::<String>("drop-results")? {
+            Some(_) => WhenFull::Drop,
+            None => WhenFull::Block,
+        },
+    })
+}
+
+/// Crack a trial from a `selftest --record` log again, and show the crack against the real
+/// plaintext as a diff (also written as HTML to `--html FILE`).
+#[cfg(feature = "serde")]
+fn replay(args: &Args) -> anyhow::Result<()> {
+    use one_team_pad_cipher_cracker::crack::replay;
+
+    args.reject_unknown(&["log", "id", "html", "seed"])?;
+    let log: PathBuf = args
+        .value("log")?
+        .ok_or_else(|| anyhow!("--log is required"))?;
+    let id = args
+        .value("id")?
+        .ok_or_else(|| anyhow!("--id is required"))?;
+
+    let trial = replay::find(&log, id)?;
+    println!("scheduler: {:?}", trial.scheduler);
+    println!("key:       {:#} {:?}", trial.key, trial.key.to_string());
+    println!("test:      {}", trial.actual_test());
+
+    let result = replay::replay(trial);
+    println!("guessed:   test {}", result.guessed_test);
+    match &result.recovered_key {
+        Some(key) => println!("recovered: {:#} {:?}", key, key.to_string()),
+        None => println!("recovered: no key fits"),
+    }
+    println!("error:     {:.4}", result.error);
+    println!("success:   {}", result.success);
+
+    // the crack against the real plaintext, doubting the words that aren't in the dictionary
+    let mut words = include_str!("../words/default.txt").to_string();
+    let bytes_dict = BytesDictionary::from_dict(&Dictionary::from_string(&mut words));
+    let mut view = DiffView::new(
+        &str_to_bytes(&result.cracked),
+        &str_to_bytes(&result.trial.plaintext),
+    );
+    view.doubt_unknown_words(&bytes_dict);
+    println!("diff:");
+    print_diff(&view);
+    write_html(args, &format!("trial {}", id), &[("crack", &view)])
+}
+
+/// Crack the ciphertext on stdin, or in `--ciphertext FILE`, as dictionary words, watching the
+/// keylengths get cracked in the terminal, then print the best crack on stdout once the terminal
+/// is closed.
+#[cfg(feature = "tui")]
+fn tui(args: &Args) -> anyhow::Result<()> {
+    args.reject_unknown(&["ciphertext", "seed"])?;
+
+    let ciphertext = match args.value::<PathBuf>("ciphertext")? {
+        Some(path) => std::fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", path.display()))?,
+        None => {
+            eprintln!("Enter the ciphertext followed by a newline:");
+            let mut ciphertext = String::new();
+            std::io::stdin().read_line(&mut ciphertext)?;
+            ciphertext
+        }
+    };
+    let ciphertext = ciphertext.trim();
+    check_alphabet("ciphertext", ciphertext)?;
+
+    if let Some(best) = one_team_pad_cipher_cracker::tui::run(ciphertext)? {
+        println!("{}", bytes_to_str(&best.plaintext));
+    }
+    Ok(())
+}
+
+/// Crack ciphertexts sent as NDJSON requests, over TCP on `--listen ADDR` or on a Unix socket at
+/// `--unix PATH`, until killed.
+#[cfg(feature = "serde")]
+fn serve(args: &Args) -> anyhow::Result<()> {
+    use one_team_pad_cipher_cracker::crack::server::Server;
+
+    args.reject_unknown(&["listen", "unix", "workers", "seed"])?;
+    let workers = args.value("workers")?.unwrap_or_else(threads::threads);
+    let server = Server::spawn(workers);
+
+    match (
+        args.value::<String>("listen")?,
+        args.value::<PathBuf>("unix")?,
+    ) {
+        (Some(_), Some(_)) => bail!("--listen and --unix can't be used together"),
+        (None, None) => bail!("--listen or --unix is required"),
+        (Some(addr), None) => {
+            let listener = std::net::TcpListener::bind(&addr)
+                .with_context(|| format!("could not listen on {}", addr))?;
+            eprintln!("listening on {}", listener.local_addr()?);
+            server.serve_tcp(listener)
+        }
+        #[cfg(unix)]
+        (None, Some(path)) => {
+            let listener = std::os::unix::net::UnixListener::bind(&path)
+                .with_context(|| format!("could not listen on {}", path.display()))?;
+            eprintln!("listening on {}", path.display());
+            server.serve_unix(listener)
+        }
+        #[cfg(not(unix))]
+        (None, Some(_)) => bail!("--unix needs a unix system"),
+    }
+}
+
+/// Crack one line of ciphertext from stdin, and print the plaintext guess. With `--partial`, the
+/// best guess so far is also printed to stderr each time it improves, so a crack cut short by
+/// `--timeout` shows how far it got. With `--adaptive`, the strategy is picked by the length of
+/// the ciphertext, and the pick is printed to stderr.
+fn crack_stdin(args: &Args) -> anyhow::Result<()> {
+    args.reject_unknown(&[
+        "timeout",
+        "project-mode",
+        "partial",
+        "adaptive",
+        "workers",
+        "keylengths",
+        "options",
+        "seed",
+    ])?;
+    let opts = crack_options(args)?;
+    let adaptive = args.value::<String>("adaptive")?.is_some();
+    let cracker = match adaptive {
+        true => Cracker::default().with_adaptive_strategy(),
+        false => Cracker::default(),
+    };
+
     // 1. get ciphertext from stdin
     eprintln!("Enter the ciphertext followed by a newline:");
 
@@ -17,15 +873,35 @@ fn main() -> anyhow::Result<()> {
     let mut ciphertext = String::new();
     stdin.read_line(&mut ciphertext)?;
     ciphertext = ciphertext.trim().to_string();
+    check_alphabet("ciphertext", &ciphertext)?;
 
     eprintln!();
     eprintln!("we read as ciphertext:");
     eprintln!("--------");
     eprintln!("{}", ciphertext);
     eprintln!("--------");
+    if adaptive {
+        eprint!("{}", cracker.dispatch(&ciphertext));
+    }
 
-    // 2. crack ciphertext with crack_single_ciphertext()
-    let plaintext = crack_single_ciphertext(&ciphertext);
+    // 2. crack ciphertext with the Cracker
+    let result = match args.value::<String>("partial")? {
+        Some(_) => cracker.crack_with_partials(&ciphertext, &opts, |partial| {
+            if let (true, Some((keylength, best))) = (partial.improved, &partial.best) {
+                eprintln!(
+                    "best so far ({}, keylength {}): {}",
+                    partial.stage,
+                    keylength,
+                    bytes_to_str(&best.plaintext)
+                );
+            }
+        }),
+        None => cracker.crack_with(&ciphertext, &opts),
+    };
+    if result.timed_out {
+        eprintln!("ran out of time, using the best plaintext found so far");
+    }
+    let plaintext = bytes_to_str(&result.plaintext);
 
     // 3. print our plaintext guess on stdout
     eprintln!("Resulting plaintext is:");
@@ -35,3 +911,37 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Cracking options read from the JSON file `--options FILE` (with the `serde` feature), with
+/// `--timeout SECS`, `--workers N`, `--keylengths N` (the most keylength guesses to crack) and
+/// `--project-mode` (only guess keylengths the project's keys can have) on top.
+fn crack_options(args: &Args) -> anyhow::Result<CrackOptions> {
+    let mut opts = match args.value::<PathBuf>("options")? {
+        #[cfg(feature = "serde")]
+        Some(path) => {
+            let json = std::fs::read_to_string(&path)
+                .with_context(|| format!("could not read {}", path.display()))?;
+            serde_json::from_str(&json)
+                .with_context(|| format!("invalid options in {}", path.display()))?
+        }
+        #[cfg(not(feature = "serde"))]
+        Some(_) => bail!("--options needs the serde feature"),
+        None => CrackOptions::default(),
+    };
+
+    if let Some(secs) = args.value::<f64>("timeout")? {
+        let timeout = Duration::try_from_secs_f64(secs)
+            .with_context(|| format!("invalid --timeout {}", secs))?;
+        opts = opts.with_timeout(timeout);
+    }
+    if let Some(workers) = args.value("workers")? {
+        opts = opts.with_workers(workers);
+    }
+    if let Some(max) = args.value("keylengths")? {
+        opts = opts.with_max_keylengths(max);
+    }
+    if args.value::<String>("project-mode")?.is_some() {
+        opts.keylength_scoring.max_keylength = MAX_KEYLENGTH;
+    }
+    Ok(opts)
+}
@@ -0,0 +1,30 @@
+//! The types nearly every use of the crate needs, in one import. Each is only here with the
+//! feature its module needs.
+//!
+//! ```
+//! # #[cfg(all(feature = "gen", feature = "crack"))] {
+//! use one_team_pad_cipher_cracker::prelude::*;
+//! use one_team_pad_cipher_cracker::ciphers::schedulers::RepeatingKey;
+//!
+//! let mut words = include_str!("../words/default.txt").to_string();
+//! let dict = Dictionary::from_string(&mut words);
+//! let plaintext = Generator::with_dict(&dict).generate_words(80);
+//!
+//! let key = Key::from_rng(&mut Rng::from_seed(7));
+//! let encryptor = Encryptor::new(key, RepeatingKey, Rng::default());
+//! let ciphertext = encryptor.encrypt(&plaintext);
+//!
+//! let result: CrackResult = Cracker::default().crack(&ciphertext);
+//! assert_eq!(result.plaintext.len(), plaintext.len());
+//! # }
+//! ```
+
+#[cfg(feature = "cipher")]
+pub use crate::ciphers::{Cipher, Encryptor, KeySchedule};
+#[cfg(feature = "crack")]
+pub use crate::crack::{CrackResult, Cracker};
+pub use crate::dict::Dictionary;
+#[cfg(feature = "gen")]
+pub use crate::gen::Generator;
+pub use crate::rng::{FromRng, Rng};
+pub use crate::utils::Key;
@@ -1,6 +1,9 @@
 //! Module for random number generation.
 
-use crate::utils::Key;
+use crate::utils::{Key, ALPHABET};
+
+use std::convert::TryFrom;
+use std::ops::Range;
 
 /// This is [RomuDuo]
 ///
@@ -36,6 +39,7 @@ impl Rng {
     ///
     /// Basic usage:
     /// ```
+    /// # use one_team_pad_cipher_cracker::rng::Rng;
     /// let mut rng = Rng::default();
     /// println!("random u64: {}", rng.next());
     ///
@@ -45,6 +49,7 @@ impl Rng {
     /// // truncate a u64 to a single byte
     /// println!("random u8: {}", rng.next() as u8);
     /// ```
+    #[allow(clippy::should_implement_trait)] // an Rng never runs out, so it is not an Iterator
     pub fn next(&mut self) -> u64 {
         // the reason this doesn't look exactly like the C implementation of RomuDuo is because
         // Rust will panic (safely halt) if any arithmetic overflows in a debug build, such as
@@ -76,9 +81,78 @@ impl Rng {
         rng
     }
 
+    /// Initialize the Rng from a single seed, such as one given on the command line. Any value is
+    /// fine, including zero, and different seeds (even neighboring ones) give unrelated streams.
+    ///
+    /// The seed is expanded into the two starting states with
+    /// [SplitMix64](https://prng.di.unimi.it/splitmix64.c), then passed to
+    /// [`with_seed`][`Rng::with_seed`].
+    pub fn from_seed(seed: u64) -> Self {
+        let mut state = seed;
+        let mut splitmix = || {
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            splitmix64(state)
+        };
+
+        // the low bit is forced on so neither state can be zero
+        let x = splitmix() | 1;
+        let y = splitmix() | 1;
+        Self::with_seed(x, y)
+    }
+
+    /// Returns a random `usize` in `range`, without the bias of `rng.next() as usize % n`.
+    ///
+    /// Panics if the range is empty.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::rng::Rng;
+    /// let mut rng = Rng::default();
+    /// let keylen = rng.gen_range(5..24);
+    /// assert!((5..24).contains(&keylen));
+    /// ```
+    pub fn gen_range(&mut self, range: Range<usize>) -> usize {
+        assert!(range.start < range.end, "cannot sample an empty range");
+        let span = (range.end - range.start) as u64;
+
+        // Lemire's multiply-and-shift: the high half of `next() * span` is in `0..span`. The low
+        // half says whether this draw landed in one of the few over-represented slots, in which
+        // case it is thrown out and redrawn
+        let threshold = span.wrapping_neg() % span;
+        loop {
+            let wide = u128::from(self.next()) * u128::from(span);
+            if (wide as u64) >= threshold {
+                return range.start + (wide >> 64) as usize;
+            }
+        }
+    }
+
+    /// Returns `true` with probability `p`.
+    ///
+    /// Panics if `p` is not between 0.0 and 1.0.
+    pub fn gen_bool(&mut self, p: f64) -> bool {
+        assert!((0.0..=1.0).contains(&p), "probability {} out of range", p);
+
+        self.gen_unit() < p
+    }
+
+    /// Returns a uniform float in `[0, 1)`.
+    fn gen_unit(&mut self) -> f64 {
+        // the top 53 bits fill the mantissa of an f64 exactly
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Fill `dest` with random bytes.
+    pub fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
     /// Choose an item from a slice of items.
     ///
     /// ```
+    /// # use one_team_pad_cipher_cracker::rng::Rng;
     /// let mut rng = Rng::default();
     /// let choices = [1, 1, 1, 2, 3, 4, 4, 8];
     ///
@@ -87,9 +161,121 @@ impl Rng {
     /// ```
     pub fn choose<'a, T>(&mut self, choices: &'a [T]) -> &'a T {
         // generate a random but valid index
-        let index = self.next() as usize % choices.len();
+        let index = self.gen_range(0..choices.len());
         &choices[index]
     }
+
+    /// Generate `Some` random value with probability `p`, otherwise `None`.
+    pub fn gen_option<T: FromRng>(&mut self, p: f64) -> Option<T> {
+        if self.gen_bool(p) {
+            Some(T::from_rng(self))
+        } else {
+            None
+        }
+    }
+
+    /// Generate a `Vec` of random values, with a random length in `len`.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::rng::{Rng, Ranged};
+    /// let mut rng = Rng::default();
+    ///
+    /// // between 5 and 23 dice rolls
+    /// let rolls: Vec<Ranged<u8, 1, 7>> = rng.gen_vec(5..24);
+    /// assert!(rolls.iter().all(|roll| (1..7).contains(&roll.0)));
+    /// ```
+    pub fn gen_vec<T: FromRng>(&mut self, len: Range<usize>) -> Vec<T> {
+        let len = self.gen_range(len);
+        (0..len).map(|_| T::from_rng(self)).collect()
+    }
+
+    /// Choose an item from a slice of `(item, weight)` pairs, where each item is picked with
+    /// probability proportional to its weight.
+    ///
+    /// Panics if there are no items, any weight is negative or not finite, or every weight is zero.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::rng::Rng;
+    /// let mut rng = Rng::default();
+    ///
+    /// // "common" is picked about 9 times as often as "rare"
+    /// let choices = [("common", 9.0), ("rare", 1.0)];
+    /// println!("picked {}", rng.choose_weighted(&choices));
+    /// ```
+    pub fn choose_weighted<'a, T>(&mut self, choices: &'a [(T, f64)]) -> &'a T {
+        assert!(
+            choices.iter().all(|(_, w)| w.is_finite() && *w >= 0.0),
+            "weights must be finite and not negative"
+        );
+        let total: f64 = choices.iter().map(|(_, w)| w).sum();
+        assert!(total > 0.0, "at least one weight must be positive");
+
+        // walk the weights until the target falls inside one
+        let mut target = self.gen_unit() * total;
+        for (item, weight) in choices {
+            if target < *weight {
+                return item;
+            }
+            target -= weight;
+        }
+
+        // rounding can step past the end, so fall back to the last item that can be picked
+        let (item, _) = choices.iter().rev().find(|(_, w)| *w > 0.0).unwrap();
+        item
+    }
+
+    /// Shuffle a slice in place, with every order equally likely.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        // Fisher-Yates: swap each position with a random one at or before it
+        for ii in (1..items.len()).rev() {
+            let jj = self.gen_range(0..ii + 1);
+            items.swap(ii, jj);
+        }
+    }
+}
+
+/// With the `rand_core` feature, the crate's [`Rng`] plugs into distributions and shuffles from the
+/// rand ecosystem.
+#[cfg(feature = "rand_core")]
+impl rand_core::RngCore for Rng {
+    fn next_u32(&mut self) -> u32 {
+        // the high bits of RomuDuo output are the better ones
+        (self.next() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.next()
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        Rng::fill_bytes(self, dst)
+    }
+}
+
+#[cfg(feature = "rand_core")]
+impl rand_core::SeedableRng for Rng {
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Rng::from_seed(u64::from_le_bytes(seed))
+    }
+
+    fn seed_from_u64(seed: u64) -> Self {
+        Rng::from_seed(seed)
+    }
+}
+
+#[cfg(feature = "rand_core")]
+impl Rng {
+    /// Seed a new Rng from any external [`RngCore`][`rand_core::RngCore`], for use wherever the
+    /// crate expects its own Rng. The external generator is only used for the seed, so cracking
+    /// code keeps running on the fast RomuDuo generator.
+    pub fn from_rng_core<R: rand_core::RngCore + ?Sized>(rng: &mut R) -> Self {
+        // the low bit is forced on so neither state can be zero
+        let x = rng.next_u64() | 1;
+        let y = rng.next_u64() | 1;
+        Self::with_seed(x, y)
+    }
 }
 
 /// Types that can be generated pseudo-randomly implement `FromRng`.
@@ -123,41 +309,76 @@ impl FromRng for Rng {
     }
 }
 
-impl FromRng for Key {
-    fn from_rng(rng: &mut Rng) -> Self {
-        let mut x = 0;
-        loop {
-            if x == 128 {
-                return vec![1, 2, 3, 4];
-            }
-            x += 1;
+/// The [SplitMix64](https://prng.di.unimi.it/splitmix64.c) output function: scrambles `z` so
+/// that inputs differing in a single bit give unrelated outputs. Used for deriving seeds from
+/// other seeds.
+pub fn splitmix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
 
-            // generate a keylength between 5 and 19
-            let keylen = rng.next() as usize % 19 + 5;
+/// One shift of a key, already reduced to the alphabet.
+type KeyShift = Ranged<i8, 0, { ALPHABET.len() as i64 }>;
 
-            // generate and fill the key values with random values
-            let mut key = Vec::with_capacity(keylen);
-            for _ in 0..keylen {
-                key.push(rng.next() as i8);
-            }
+/// Lengths of the keys [`Key::from_rng`] generates: between 5 and 23 shifts.
+pub const KEY_LENGTHS: Range<usize> = 5..24;
 
-            // make sure key is friendly
-            crate::utils::reduce_key(&mut key);
+/// Generate a random key with a random length in `lengths`, which must not be empty or only
+/// allow empty keys.
+pub fn random_key(rng: &mut Rng, lengths: Range<usize>) -> Key {
+    loop {
+        let shifts: Vec<KeyShift> = rng.gen_vec(lengths.clone());
 
-            // return key if any are not zero
-            if key.iter().any(|k| k != &0) {
-                return key;
-            }
+        // return key if any are not zero. an all zero key is rare enough to just try again
+        if shifts.iter().any(|shift| shift.0 != 0) {
+            return shifts.into_iter().map(|shift| shift.0).collect();
         }
     }
 }
 
+impl FromRng for Key {
+    fn from_rng(rng: &mut Rng) -> Self {
+        random_key(rng, KEY_LENGTHS)
+    }
+}
+
 impl<A: FromRng, B: FromRng> FromRng for (A, B) {
     fn from_rng(rng: &mut Rng) -> Self {
         (A::from_rng(rng), B::from_rng(rng))
     }
 }
 
+/// `Some` random value half of the time. Use [`Rng::gen_option`] for other probabilities.
+impl<T: FromRng> FromRng for Option<T> {
+    fn from_rng(rng: &mut Rng) -> Self {
+        rng.gen_option(0.5)
+    }
+}
+
+/// An integer generated uniformly in `LO..HI`, for when a type's bounds are known up front.
+///
+/// ```
+/// # use one_team_pad_cipher_cracker::rng::{FromRng, Ranged, Rng};
+/// let mut rng = Rng::default();
+/// let Ranged(offset) = Ranged::<usize, 0, 17>::from_rng(&mut rng);
+/// assert!(offset < 17);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ranged<T, const LO: i64, const HI: i64>(pub T);
+
+impl<T, const LO: i64, const HI: i64> FromRng for Ranged<T, LO, HI>
+where
+    T: TryFrom<i64>,
+    T::Error: std::fmt::Debug,
+{
+    fn from_rng(rng: &mut Rng) -> Self {
+        assert!(LO < HI, "cannot sample an empty range");
+        let offset = rng.gen_range(0..(HI - LO) as usize) as i64;
+        Ranged(T::try_from(LO + offset).expect("range does not fit in the type"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,6 +405,177 @@ mod tests {
         }
     }
 
+    #[test]
+    fn from_seed() {
+        let first = |seed| Rng::from_seed(seed).next();
+        assert_eq!(first(0), first(0));
+        assert_ne!(first(0), first(1));
+        assert_ne!(first(2), first(3));
+        assert_ne!(first(u64::MAX), first(u64::MAX - 1));
+    }
+
+    #[test]
+    fn gen_range() {
+        let mut rng = Rng::default();
+
+        // every value in a small range shows up, and nothing outside it
+        let mut counts = [0; 27];
+        for _ in 0..27000 {
+            counts[rng.gen_range(0..27)] += 1;
+        }
+        assert!(
+            counts.iter().all(|&c| (800..1200).contains(&c)),
+            "{:?}",
+            counts
+        );
+
+        for _ in 0..1000 {
+            assert!((5..24).contains(&rng.gen_range(5..24)));
+        }
+        assert_eq!(rng.gen_range(7..8), 7);
+        let _ = rng.gen_range(0..usize::MAX);
+    }
+
+    #[test]
+    #[should_panic]
+    fn gen_range_empty() {
+        let _ = Rng::default().gen_range(3..3);
+    }
+
+    #[test]
+    fn gen_bool() {
+        let mut rng = Rng::default();
+        assert!((0..1000).all(|_| !rng.gen_bool(0.0)));
+        assert!((0..1000).all(|_| rng.gen_bool(1.0)));
+
+        let heads = (0..10000).filter(|_| rng.gen_bool(0.25)).count();
+        assert!((2300..2700).contains(&heads), "{}", heads);
+    }
+
+    #[test]
+    fn fill_bytes() {
+        // filling is the same stream of bytes however the buffer is split up
+        let mut whole = [0; 21];
+        Rng::default().fill_bytes(&mut whole);
+
+        let mut expected = Vec::new();
+        let mut rng = Rng::default();
+        for _ in 0..3 {
+            expected.extend_from_slice(&rng.next().to_le_bytes());
+        }
+        assert_eq!(&whole[..], &expected[..21]);
+    }
+
+    #[cfg(feature = "rand_core")]
+    #[test]
+    fn rand_core() {
+        use rand_core::{RngCore, SeedableRng};
+
+        // RngCore is the same stream as the inherent methods
+        let mut a = Rng::default();
+        let mut b = Rng::default();
+        assert_eq!(a.next_u64(), b.next());
+        assert_eq!(a.next_u32(), (b.next() >> 32) as u32);
+        let (mut x, mut y) = ([0; 13], [0; 13]);
+        RngCore::fill_bytes(&mut a, &mut x);
+        b.fill_bytes(&mut y);
+        assert_eq!(x, y);
+
+        // seeding through rand_core matches from_seed
+        assert_eq!(Rng::seed_from_u64(7).next(), Rng::from_seed(7).next());
+        let mut seeded = <Rng as SeedableRng>::from_seed(7u64.to_le_bytes());
+        assert_eq!(seeded.next(), Rng::from_seed(7).next());
+
+        // an external generator can seed the crate's Rng, even a trait object
+        let external: &mut dyn RngCore = &mut Rng::from_seed(1);
+        let mut wrapped = Rng::from_rng_core(external);
+        let mut again = Rng::from_rng_core(&mut Rng::from_seed(1));
+        assert_eq!(wrapped.next(), again.next());
+    }
+
+    #[test]
+    fn key_from_rng() {
+        let mut rng = Rng::default();
+        for _ in 0..1000 {
+            let key = Key::from_rng(&mut rng);
+            assert!((5..24).contains(&key.len()));
+            assert!(key.iter().all(|k| (0..27).contains(k)));
+            assert!(key.iter().any(|k| k != &0));
+        }
+    }
+
+    #[test]
+    fn option_and_vec() {
+        let mut rng = Rng::default();
+        assert!((0..100).all(|_| rng.gen_option::<Rng>(0.0).is_none()));
+        assert!((0..100).all(|_| rng.gen_option::<Rng>(1.0).is_some()));
+        let some = (0..1000)
+            .filter(|_| Option::<Rng>::from_rng(&mut rng).is_some())
+            .count();
+        assert!((400..600).contains(&some), "{}", some);
+
+        let mut lengths = std::collections::HashSet::new();
+        for _ in 0..100 {
+            let v: Vec<Ranged<u8, 0, 1>> = rng.gen_vec(2..5);
+            assert!(v.iter().all(|x| x.0 == 0));
+            lengths.insert(v.len());
+        }
+        assert_eq!(lengths.len(), 3);
+    }
+
+    #[test]
+    fn ranged() {
+        let mut rng = Rng::default();
+        let mut seen = [false; 7];
+        for _ in 0..1000 {
+            let Ranged(x) = Ranged::<i8, -3, 4>::from_rng(&mut rng);
+            seen[(x + 3) as usize] = true;
+        }
+        assert!(seen.iter().all(|&x| x));
+    }
+
+    #[test]
+    fn choose_weighted() {
+        let mut rng = Rng::default();
+        let choices = [(0, 1.0), (1, 0.0), (2, 3.0)];
+
+        let mut counts = [0; 3];
+        for _ in 0..10000 {
+            counts[*rng.choose_weighted(&choices)] += 1;
+        }
+        assert_eq!(counts[1], 0);
+        assert!((2300..2700).contains(&counts[0]), "{:?}", counts);
+        assert!((7300..7700).contains(&counts[2]), "{:?}", counts);
+    }
+
+    #[test]
+    #[should_panic]
+    fn choose_weighted_all_zero() {
+        let _ = Rng::default().choose_weighted(&[(0, 0.0), (1, 0.0)]);
+    }
+
+    #[test]
+    fn shuffle() {
+        let mut rng = Rng::default();
+
+        // every order of 3 items shows up
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..100 {
+            let mut items = [1, 2, 3];
+            rng.shuffle(&mut items);
+            seen.insert(items);
+        }
+        assert_eq!(seen.len(), 6);
+
+        // shuffling keeps every item
+        let mut items: Vec<usize> = (0..100).collect();
+        rng.shuffle(&mut items);
+        items.sort_unstable();
+        assert!(items.iter().copied().eq(0..100));
+
+        rng.shuffle::<u8>(&mut []);
+    }
+
     #[test]
     fn choose() {
         let choices = [0, 1, 2, 3, 4, 5];
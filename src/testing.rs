@@ -0,0 +1,179 @@
+//! Property testing helpers, with the `testing` feature.
+//!
+//! [`proptest`] strategies for keys, schedulers, plaintexts, and encryptors, mostly built on the
+//! [`FromRng`] impls the rest of the crate generates them with, and properties every [`Cipher`]
+//! and [`KeySchedule`] should have. The ciphers and schedulers of this crate are tested with them,
+//! and ones defined elsewhere can be too:
+//!
+//! ```
+//! use one_team_pad_cipher_cracker::ciphers::{schedulers::Aab, Encryptor};
+//! use one_team_pad_cipher_cracker::testing::{from_rng, plaintext, round_trip};
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     fn aab_round_trips(encryptor in from_rng::<Encryptor<Aab>>(), plaintext in plaintext()) {
+//!         round_trip(&encryptor, &plaintext)?;
+//!     }
+//! }
+//! # aab_round_trips();
+//! ```
+
+use crate::ciphers::schedulers::{
+    NextKey, RandomBaseScheduler, RandomScheduler, SchedulerComplexity, SchedulerKind,
+};
+use crate::ciphers::{Cipher, Encryptor, KeySchedule};
+use crate::rng::{FromRng, Rng};
+use crate::utils::{Key, ALPHABET, MAX_KEYLENGTH};
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+use proptest::test_runner::TestCaseError;
+use std::fmt::Debug;
+use std::ops::Range;
+
+/// Values of `T` generated by its [`FromRng`] impl, from a random seed. These shrink to the values
+/// of other seeds, not to simpler values.
+pub fn from_rng<T: FromRng + Debug>() -> impl Strategy<Value = T> {
+    any::<u64>().prop_map(|seed| T::from_rng(&mut Rng::from_seed(seed)))
+}
+
+/// Keys of 1 to [`MAX_KEYLENGTH`] shifts, already reduced. These shrink to shorter keys of smaller
+/// shifts, down to a key of one shift of 0 that doesn't encrypt at all.
+pub fn key() -> impl Strategy<Value = Key> {
+    vec(0..ALPHABET.len() as i8, 1..=MAX_KEYLENGTH).prop_map(Key::from)
+}
+
+/// Base schedulers of every family, with random parameters.
+pub fn base_scheduler() -> impl Strategy<Value = RandomBaseScheduler> {
+    (select(SchedulerKind::ALL.to_vec()), any::<u64>())
+        .prop_map(|(kind, seed)| kind.generate(&mut Rng::from_seed(seed)))
+}
+
+/// Schedulers with as many [`PeriodicRand`][`crate::ciphers::schedulers::PeriodicRand`] layers as
+/// `complexity` allows, the same way trials generate them.
+pub fn scheduler(complexity: SchedulerComplexity) -> impl Strategy<Value = RandomScheduler> {
+    any::<u64>().prop_map(move |seed| {
+        RandomScheduler::with_complexity(&mut Rng::from_seed(seed), complexity)
+    })
+}
+
+/// Plaintexts of 1 to 149 words of the project dictionary.
+pub fn plaintext() -> impl Strategy<Value = String> {
+    plaintext_words(1..150)
+}
+
+/// Plaintexts of a number of words of the project dictionary in `words`. These shrink to fewer
+/// words, earlier in the dictionary.
+pub fn plaintext_words(words: Range<usize>) -> impl Strategy<Value = String> {
+    let dict: Vec<&str> = include_str!("../words/default.txt")
+        .split_whitespace()
+        .collect();
+    vec(select(dict), words).prop_map(|words| words.join(" "))
+}
+
+/// Encryptors of a [`key`], a scheduler from `schedulers`, and an [`Rng`] from a random seed for
+/// the random characters.
+pub fn encryptor<K: KeySchedule>(
+    schedulers: impl Strategy<Value = K>,
+) -> impl Strategy<Value = Encryptor<K>> {
+    (key(), schedulers, any::<u64>())
+        .prop_map(|(key, sched, seed)| Encryptor::new(key, sched, Rng::from_seed(seed)))
+}
+
+/// Decrypting the encryption of `plaintext` gives `plaintext` back. Returns the ciphertext.
+pub fn round_trip<C: Cipher + ?Sized>(
+    cipher: &C,
+    plaintext: &str,
+) -> Result<String, TestCaseError> {
+    let ciphertext = cipher.encrypt(plaintext);
+    prop_assert!(
+        ciphertext.len() >= plaintext.len(),
+        "ciphertext {:?} is shorter than the plaintext",
+        ciphertext
+    );
+    prop_assert_eq!(cipher.decrypt(&ciphertext), plaintext);
+    Ok(ciphertext)
+}
+
+/// Scheduling a plaintext of `plaintext_length` characters with a key of `key_length` shifts
+/// only picks shifts of the key, and gets to the end of the plaintext with at most as many random
+/// characters as plaintext characters.
+pub fn schedules_in_bounds<K: KeySchedule + ?Sized>(
+    sched: &K,
+    key_length: usize,
+    plaintext_length: usize,
+) -> Result<(), TestCaseError> {
+    let mut encrypted = 0;
+    let mut index = 0;
+    while encrypted < plaintext_length {
+        prop_assert!(
+            index <= 2 * plaintext_length,
+            "{:?} inserted more random characters than plaintext characters",
+            sched
+        );
+        match sched.schedule(index, key_length, plaintext_length) {
+            NextKey::KeyIndex(key_index) => {
+                prop_assert!(
+                    key_index < key_length,
+                    "{:?} picked shift {} of a {} shift key at {}",
+                    sched,
+                    key_index,
+                    key_length,
+                    index
+                );
+                encrypted += 1;
+            }
+            NextKey::Rand => {}
+        }
+        index += 1;
+    }
+    Ok(())
+}
+
+/// Doing `f` to `value` twice gives the same as doing it once.
+pub fn idempotent<T: PartialEq + Debug>(
+    value: &T,
+    f: impl Fn(&T) -> T,
+) -> Result<(), TestCaseError> {
+    let once = f(value);
+    let twice = f(&once);
+    prop_assert_eq!(once, twice);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+
+    proptest! {
+        #[test]
+        fn every_scheduler_round_trips(
+            encryptor in encryptor(scheduler(SchedulerComplexity::Hard)),
+            plaintext in plaintext(),
+        ) {
+            round_trip(&encryptor, &plaintext)?;
+        }
+
+        #[test]
+        fn every_scheduler_in_bounds(
+            sched in scheduler(SchedulerComplexity::Hard),
+            key_length in 1..=MAX_KEYLENGTH,
+            plaintext_length in 0..1000_usize,
+        ) {
+            schedules_in_bounds(&sched, key_length, plaintext_length)?;
+        }
+
+        #[test]
+        fn canonical_keys(key in key(), plaintext in plaintext_words(1..20)) {
+            idempotent(&key, Key::canonical)?;
+
+            // a key cut down to its period encrypts the same
+            let encrypt = |key: &Key| {
+                Encryptor::new(key.clone(), RepeatingKey, Rng::default()).encrypt(&plaintext)
+            };
+            prop_assert_eq!(encrypt(&key), encrypt(&key.canonical()));
+        }
+    }
+}
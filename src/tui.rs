@@ -0,0 +1,335 @@
+//! Module for watching a crack in the terminal.
+//!
+//! [`run`] cracks a ciphertext with the default [`Cracker`] on a background thread, which sends
+//! an [`Event`] each time a stage of the crack finishes a keylength candidate, and draws a
+//! [`Session`] of the events so far: how far the current stage is, the best plaintext yet, how
+//! the keylengths rank, and how sure the frequency attack is of each column of the best one.
+
+use crate::crack::columns::ColumnDiagnostics;
+use crate::crack::pipeline::{normalize, Candidate, Partial};
+use crate::crack::{rank, CrackResult, Cracker, DictionaryStats};
+use crate::utils::{bytes_to_str, NumToChar};
+
+use crossbeam_channel::{unbounded, Sender};
+use ratatui::crossterm::event::{self, Event as TermEvent, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Bar, BarChart, BarGroup, Block, Gauge, Paragraph, Row, Table, Wrap};
+use ratatui::Frame;
+use std::time::{Duration, Instant};
+
+/// What the background crack has done, in the order it happened.
+#[derive(Clone)]
+pub enum Event {
+    /// A stage finished a keylength candidate
+    Partial(Box<Partial>),
+    /// The crack is done, with its result
+    Done(CrackResult),
+}
+
+/// The events of a crack so far, as the terminal shows them.
+#[derive(Clone)]
+pub struct Session {
+    /// Ciphertext being cracked, as bytes
+    ciphertext: Vec<u8>,
+    started: Instant,
+    /// Time the crack took, once it is done
+    finished: Option<Duration>,
+    /// Stage that last finished a candidate, how many it has finished, and out of how many
+    stage: Option<(&'static str, usize, usize)>,
+    /// Every keylength candidate reported so far, as the last stage to finish it left it
+    candidates: Vec<Candidate>,
+    /// Best crack so far and its keylength
+    best: Option<(usize, CrackResult)>,
+    /// The frequency attack on each column at the best keylength so far
+    columns: Option<ColumnDiagnostics>,
+    /// Result of the crack, once it is done
+    result: Option<CrackResult>,
+}
+
+impl Session {
+    /// A session cracking `ciphertext`, before any events.
+    pub fn new(ciphertext: &str) -> Self {
+        Self {
+            ciphertext: normalize(ciphertext),
+            started: Instant::now(),
+            finished: None,
+            stage: None,
+            candidates: Vec::new(),
+            best: None,
+            columns: None,
+            result: None,
+        }
+    }
+
+    /// Take in the next event of the crack.
+    pub fn apply(&mut self, event: Event) {
+        match event {
+            Event::Partial(partial) => {
+                let partial = *partial;
+                self.stage = Some((partial.stage, partial.finished, partial.candidates));
+                let keylength = partial.candidate.keylength;
+                match self
+                    .candidates
+                    .iter_mut()
+                    .find(|candidate| candidate.keylength == keylength)
+                {
+                    Some(candidate) => *candidate = partial.candidate,
+                    None => self.candidates.push(partial.candidate),
+                }
+
+                if let Some((keylength, _)) = &partial.best {
+                    if self.columns.as_ref().map(|c| c.keylength) != Some(*keylength) {
+                        let baseline = &DictionaryStats::project().letters;
+                        self.columns = Some(ColumnDiagnostics::new(
+                            &self.ciphertext,
+                            *keylength,
+                            baseline,
+                        ));
+                    }
+                }
+                self.best = partial.best;
+            }
+            Event::Done(result) => {
+                self.finished = Some(self.started.elapsed());
+                self.result = Some(result);
+            }
+        }
+    }
+
+    /// Whether the crack is done.
+    pub fn is_done(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// The result of the crack once it is done, or the best crack so far, or `None` before the
+    /// first keylength is cracked.
+    pub fn best(&self) -> Option<&CrackResult> {
+        self.result
+            .as_ref()
+            .or(self.best.as_ref().map(|(_, best)| best))
+    }
+
+    /// Draw the session over the whole of `frame`.
+    pub fn render(&self, frame: &mut Frame) {
+        let [progress, middle, plaintext] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(8),
+            Constraint::Length(8),
+        ])
+        .areas(frame.area());
+        let [ranking, columns] =
+            Layout::horizontal([Constraint::Length(48), Constraint::Min(20)]).areas(middle);
+
+        // progress through the current stage
+        let elapsed = self.finished.unwrap_or_else(|| self.started.elapsed());
+        let (label, ratio) = match (self.stage, self.is_done()) {
+            (_, true) => ("done (q to quit)".to_string(), 1.0),
+            (None, false) => ("guessing keylengths".to_string(), 0.0),
+            (Some((stage, finished, candidates)), false) => (
+                format!("{}: {} of {} keylengths", stage, finished, candidates),
+                finished as f64 / candidates.max(1) as f64,
+            ),
+        };
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::bordered().title(format!(" progress {:.1?} ", elapsed)))
+                .gauge_style(Style::default().fg(Color::Green))
+                .ratio(ratio.min(1.0))
+                .label(label),
+            progress,
+        );
+
+        // keylengths by how their cracks rank, spellchecked ones first, like the cracker ranks
+        let best_keylength = self.best.as_ref().map(|(keylength, _)| *keylength);
+        let checked = rank(
+            self.candidates
+                .iter()
+                .filter_map(|candidate| Some((candidate.keylength, candidate.checked.clone()?))),
+        );
+        let unchecked = rank(
+            self.candidates
+                .iter()
+                .filter(|candidate| candidate.checked.is_none())
+                .filter_map(|candidate| Some((candidate.keylength, candidate.crack.clone()?))),
+        );
+        let keylengths = checked.keylengths().chain(unchecked.keylengths());
+        let rows = keylengths.filter_map(|keylength| {
+            let candidate = self.candidates.iter().find(|c| c.keylength == keylength)?;
+            let confidence = |crack: &Option<CrackResult>| {
+                crack
+                    .as_ref()
+                    .map_or_else(String::new, |crack| format!("{:.4}", crack.confidence))
+            };
+            let row = Row::new(vec![
+                keylength.to_string(),
+                format!("{:.4}", candidate.keylength_confidence),
+                confidence(&candidate.crack),
+                confidence(&candidate.checked),
+            ]);
+            Some(match Some(keylength) == best_keylength {
+                true => row.bold().green(),
+                false => row,
+            })
+        });
+        frame.render_widget(
+            Table::new(
+                rows,
+                [
+                    Constraint::Length(9),
+                    Constraint::Length(11),
+                    Constraint::Length(11),
+                    Constraint::Length(11),
+                ],
+            )
+            .header(Row::new(vec!["keylength", "guess", "crack", "checked"]).underlined())
+            .block(Block::bordered().title(" keylengths ")),
+            ranking,
+        );
+
+        // how far the best shift of each column is ahead of the runner-up
+        let (title, bars) = match &self.columns {
+            Some(columns) => (
+                format!(
+                    " columns at keylength {}, {} unsure ",
+                    columns.keylength,
+                    columns.unsure_columns().len()
+                ),
+                column_bars(columns),
+            ),
+            None => (" columns ".to_string(), Vec::new()),
+        };
+        frame.render_widget(
+            BarChart::default()
+                .block(Block::bordered().title(title))
+                .data(BarGroup::default().bars(&bars))
+                .bar_width(3)
+                .bar_gap(1),
+            columns,
+        );
+
+        // the best plaintext so far
+        let text = self
+            .best()
+            .map_or_else(String::new, |best| bytes_to_str(&best.plaintext));
+        frame.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title(" best plaintext ")),
+            plaintext,
+        );
+    }
+}
+
+/// A bar per column, as tall as the margin of its best shift over the runner-up in thousandths,
+/// labelled with the key symbol it found. Columns the attack is unsure of are red.
+fn column_bars(columns: &ColumnDiagnostics) -> Vec<Bar<'static>> {
+    columns
+        .columns
+        .iter()
+        .map(|column| {
+            let color = match column.is_unsure() {
+                true => Color::Red,
+                false => Color::Green,
+            };
+            let key = match (column.key() as u8).to_char() {
+                ' ' => '_',
+                c => c,
+            };
+            Bar::default()
+                .value((column.margin().max(0.0) * 1000.0) as u64)
+                .label(Line::from(key.to_string()))
+                .style(Style::default().fg(color))
+        })
+        .collect()
+}
+
+/// Crack `ciphertext` with the default [`Cracker`], sending an [`Event`] to `events` each time a
+/// stage finishes a keylength candidate, and once more with the result.
+pub fn crack_session(ciphertext: &str, events: Sender<Event>) {
+    // a closed session doesn't need the events, but the crack is left to finish
+    let result = Cracker::default().crack_with_deadline_partials(ciphertext, None, |partial| {
+        let _ = events.send(Event::Partial(Box::new(partial.clone())));
+    });
+    let _ = events.send(Event::Done(result));
+}
+
+/// Crack `ciphertext` on a background thread, showing its progress in the terminal until it is
+/// done and `q` or escape is pressed, or they are pressed early. Returns the best crack by then,
+/// if any keylength was cracked.
+pub fn run(ciphertext: &str) -> anyhow::Result<Option<CrackResult>> {
+    let (events, received) = unbounded();
+    let ciphertext = ciphertext.to_string();
+    let cracking = ciphertext.clone();
+    std::thread::spawn(move || crack_session(&cracking, events));
+
+    let mut terminal = ratatui::try_init()?;
+    let mut session = Session::new(&ciphertext);
+    let result = (|| -> anyhow::Result<()> {
+        loop {
+            for event in received.try_iter() {
+                session.apply(event);
+            }
+            terminal.draw(|frame| session.render(frame))?;
+
+            if event::poll(Duration::from_millis(100))? {
+                if let TermEvent::Key(key) = event::read()? {
+                    let quit = matches!(key.code, KeyCode::Char('q') | KeyCode::Esc);
+                    if key.kind == KeyEventKind::Press && quit {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    })();
+    ratatui::restore();
+    result?;
+
+    Ok(session.best().cloned())
+}
+
+#[cfg(all(test, feature = "gen"))]
+mod tests {
+    use super::*;
+    use crate::ciphers::schedulers::RepeatingKey;
+    use crate::prelude::*;
+
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    #[test]
+    fn session() {
+        let mut words = include_str!("../words/default.txt").to_string();
+        let dict = Dictionary::from_string(&mut words);
+        let plaintext = Generator::with_dict(&dict).generate_words(80);
+        let encryptor = Encryptor::new(vec![3, 1, 4, 1, 5, 9], RepeatingKey, Rng::default());
+        let ciphertext = encryptor.encrypt(&plaintext);
+
+        let (events, received) = unbounded();
+        crack_session(&ciphertext, events);
+        let events: Vec<Event> = received.iter().collect();
+        assert!(matches!(events.first(), Some(Event::Partial(_))));
+        assert!(matches!(events.last(), Some(Event::Done(_))));
+
+        let mut session = Session::new(&ciphertext);
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        for event in events {
+            session.apply(event);
+            terminal.draw(|frame| session.render(frame)).unwrap();
+        }
+        assert!(session.is_done());
+        assert_eq!(bytes_to_str(&session.best().unwrap().plaintext), plaintext);
+        assert_eq!(session.columns.as_ref().unwrap().keylength % 6, 0);
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("done (q to quit)"), "{}", screen);
+        assert!(screen.contains(&plaintext[..40]), "{}", screen);
+    }
+}
@@ -1,79 +1,423 @@
 //! Module for utilities used throughout the cracking tool.
 
+use crate::rng::Rng;
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+
 /// The alphabet in the message space
 pub const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz ";
 
-/// Extension trait for `char` to be converted to a `i8` according to the encoding scheme
-/// 0 => 'a', 1 => 'b', 2 => 'c', ..., 25 => 'z', 26 => ' '. Only the lowercase characters 'a'
-/// through 'z' and space ' ' are supported.  converted.
+/// Extension trait for `char` to be converted to a `u8` according to the encoding scheme
+/// 'a' => 0, 'b' => 1, 'c' => 2, ..., 'z' => 25, ' ' => 26. Only the lowercase characters 'a'
+/// through 'z' and space ' ' are in the alphabet.
 pub trait CharToNum {
-    fn to_num(&self) -> u8;
-}
+    /// The alphabet index of the character, or `None` if it isn't in the alphabet.
+    fn try_to_num(&self) -> Option<u8>;
 
-impl CharToNum for char {
+    /// The alphabet index of the character.
+    ///
+    /// # Panics
+    ///
+    /// If the character isn't in the alphabet. Text from outside the crate should be checked with
+    /// [`validate_ciphertext`] or [`check_alphabet`] first.
     fn to_num(&self) -> u8 {
-        // Assert that the character is within our defined set ('a-z<space>') for debug builds.
-        // This is not asserted when built with `cargo build --release`.
-        debug_assert!(self == &' ' || ('a'..='z').contains(&self));
+        self.try_to_num()
+            .expect("only characters in the alphabet have a number")
+    }
+}
 
+impl CharToNum for char {
+    fn try_to_num(&self) -> Option<u8> {
         match *self {
-            ' ' => 26,
-            c => c as u8 - b'a',
+            ' ' => Some(26),
+            c if c.is_ascii_lowercase() => Some(c as u8 - b'a'),
+            _ => None,
         }
     }
 }
 
-/// Extension trait for integer types to be converted to a `char` according to the encoding scheme
-/// 0 => 'a', 1 => 'b', 2 => 'c', ..., 25 => 'z', 26 => ' '. All other numbers are invalid to be
-/// converted.
+/// Extension trait for alphabet indices to be converted to a `char` according to the encoding
+/// scheme 0 => 'a', 1 => 'b', 2 => 'c', ..., 25 => 'z', 26 => ' '. All other numbers are invalid
+/// to be converted.
 pub trait NumToChar {
-    fn to_char(&self) -> char;
+    /// The character at this alphabet index, or `None` past the end of the alphabet.
+    fn try_to_char(&self) -> Option<char>;
+
+    /// The character at this alphabet index.
+    ///
+    /// # Panics
+    ///
+    /// If the number is past the end of the alphabet.
+    fn to_char(&self) -> char {
+        self.try_to_char()
+            .expect("only numbers in the alphabet have a character")
+    }
 }
 
 impl NumToChar for u8 {
-    fn to_char(&self) -> char {
+    fn try_to_char(&self) -> Option<char> {
+        match *self {
+            26 => Some(' '),
+            n if n < 26 => Some((b'a' + n) as char),
+            _ => None,
+        }
+    }
+}
+
+/// An extension trait to shift alphabet characters or indices by some amount, using modulo to
+/// wrap around if needed.
+pub trait Shift: Sized {
+    /// Shift by `amount`, or `None` if `self` isn't in the alphabet.
+    fn checked_shift(self, amount: i8) -> Option<Self>;
+
+    /// Shift by `amount`.
+    ///
+    /// # Panics
+    ///
+    /// If `self` isn't in the alphabet.
+    fn shift(self, amount: i8) -> Self {
+        self.checked_shift(amount)
+            .expect("only the alphabet can be shifted")
+    }
+}
+
+impl Shift for char {
+    fn checked_shift(self, amount: i8) -> Option<Self> {
+        self.try_to_num()?.checked_shift(amount)?.try_to_char()
+    }
+}
+
+impl Shift for u8 {
+    fn checked_shift(self, amount: i8) -> Option<Self> {
         const ALPHALEN: u8 = ALPHABET.len() as u8;
 
-        // reduce self to positive integer within ALPHALEN
-        let num = self.rem_euclid(ALPHALEN);
+        if self >= ALPHALEN {
+            return None;
+        }
+
+        // wrap the shift amount to within one alphabet length, so the sum can't overflow
+        let amount = amount.rem_euclid(ALPHALEN as i8) as u8;
+        Some((self + amount) % ALPHALEN)
+    }
+}
+
+/// Longest key the project encrypts with, called `t` in the description.
+pub const MAX_KEYLENGTH: usize = 24;
+
+/// The key type defines what format various functions expect the key to be in.
+///
+/// The shift amount of each key symbol, which may be positive or negative. Keys compare equal when
+/// they encrypt the same way: with every shift [`reduce`][`Key::reduce`]d, and cut down to their
+/// [`minimal_period`][`Key::minimal_period`], so a key recovered at a multiple of the real
+/// keylength still equals the real key.
+#[derive(Clone, Default, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Key(pub Vec<i8>);
+
+impl Key {
+    /// Normalizes a key with arbitrary shift amounts the smallest positive shift amounts.
+    pub fn reduce(&mut self) {
+        for k in self.0.iter_mut() {
+            *k = k.rem_euclid(ALPHABET.len() as i8);
+        }
+    }
+
+    /// Length of the shortest repeating unit of the reduced key, like the real keylength of a key
+    /// cracked at a multiple of it. A key that doesn't repeat evenly is its own period.
+    pub fn minimal_period(&self) -> usize {
+        let mut key = self.clone();
+        key.reduce();
+        let len = key.len();
+        (1..len)
+            .find(|&period| len.is_multiple_of(period) && key[period..] == key[..len - period])
+            .unwrap_or(len)
+    }
+
+    /// The reduced key, cut down to its minimal period.
+    pub fn canonical(&self) -> Self {
+        let mut key = self.clone();
+        key.reduce();
+        key.0.truncate(self.minimal_period());
+        key
+    }
+
+    /// The key as letters, where a shift of 0 is `a` and a shift of 26 is a space.
+    pub fn as_letters(&self) -> String {
+        self.iter()
+            .map(|&shift| (shift.rem_euclid(ALPHABET.len() as i8) as u8).to_char())
+            .collect()
+    }
+
+    /// Derive a [`MAX_KEYLENGTH`] shift key from a human passphrase, the same way every time.
+    ///
+    /// Each letter or space of the passphrase (ignoring case) is the shift of its place in the
+    /// alphabet, and the rest of the key is padded with shifts from a hash of the whole passphrase,
+    /// so other characters still change the key. Letters past the end of the key wrap around and
+    /// add to the shifts already there.
+    ///
+    /// ```
+    /// # use one_team_pad_cipher_cracker::utils::{Key, MAX_KEYLENGTH};
+    /// let key = Key::from_passphrase("hunter two");
+    /// assert_eq!(key.len(), MAX_KEYLENGTH);
+    /// assert_eq!(key.as_letters()[..10], *"hunter two");
+    /// assert_eq!(key, Key::from_passphrase("Hunter Two"));
+    /// assert_ne!(key, Key::from_passphrase("hunter two!"));
+    /// ```
+    pub fn from_passphrase(passphrase: &str) -> Self {
+        // FNV-1a, which unlike the std hasher is the same on every platform and release
+        let hash = passphrase
+            .to_lowercase()
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+            });
+        let mut rng = Rng::from_seed(hash);
+        let mut key: Self = (0..MAX_KEYLENGTH)
+            .map(|_| rng.gen_range(0..ALPHABET.len()) as i8)
+            .collect();
 
-        match num {
-            26 => ' ',
-            n => (b'a' + n) as char,
+        let letters = passphrase
+            .chars()
+            .filter_map(|chr| chr.to_ascii_lowercase().try_to_num());
+        for (i, letter) in letters.enumerate() {
+            let shift = &mut key[i % MAX_KEYLENGTH];
+            *shift = match i < MAX_KEYLENGTH {
+                true => letter as i8,
+                false => (*shift + letter as i8).rem_euclid(ALPHABET.len() as i8),
+            };
         }
+        key
+    }
+
+    /// The `keylength` symbol key that encrypts the start of `plaintext` to the start of
+    /// `ciphertext`, both as bytes.
+    pub fn between(ciphertext: &[u8], plaintext: &[u8], keylength: usize) -> Self {
+        ciphertext
+            .iter()
+            .zip(plaintext)
+            .take(keylength)
+            .map(|(&c, &p)| (c as i8 - p as i8).rem_euclid(ALPHABET.len() as i8))
+            .collect()
+    }
+
+    /// The shortest repeating key, of at most `max_keylength` symbols, that encrypts every
+    /// character of `plaintext` to the same position of `ciphertext`. `None` if there is none, or
+    /// the two aren't the same length, like when random characters were inserted.
+    pub fn fit(ciphertext: &[u8], plaintext: &[u8], max_keylength: usize) -> Option<Self> {
+        if ciphertext.len() != plaintext.len() {
+            return None;
+        }
+        (1..=max_keylength.min(ciphertext.len())).find_map(|keylength| {
+            let key = Self::between(ciphertext, plaintext, keylength);
+            let fits = ciphertext
+                .iter()
+                .zip(plaintext)
+                .enumerate()
+                .all(|(i, (&c, &p))| p.shift(key[i % keylength]) == c);
+            fits.then_some(key)
+        })
     }
 }
 
-/// An extension trait to shift by some amount, using modulo to wrap around if needed.
-pub trait Shift {
-    fn shift(self, amount: i8) -> Self;
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical().0 == other.canonical().0
+    }
 }
 
-impl Shift for char {
-    fn shift(self, amount: i8) -> Self {
-        const ALPHALEN: i8 = ALPHABET.len() as i8;
+impl fmt::Display for Key {
+    /// The key as letters, like `headcrab`, or with the alternate flag (`{:#}`) as comma separated
+    /// shifts, like `7,4,0,3`. Both forms parse back with [`FromStr`].
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !f.alternate() {
+            return f.write_str(&self.as_letters());
+        }
+        for (i, shift) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", shift)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a [`Key`] couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseKeyError {
+    Empty,
+    /// A part of a numeric key that isn't a shift
+    Shift(String),
+    /// A character of a letter key that isn't in the alphabet
+    Letter(char),
+}
+
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("the key is empty"),
+            Self::Shift(shift) => write!(f, "{:?} is not a shift", shift),
+            Self::Letter(chr) => write!(f, "{:?} is not a letter or space", chr),
+        }
+    }
+}
 
-        // wrap the shift amount to within one alphabet length
-        let amount = amount.rem_euclid(ALPHALEN) as u8;
+impl std::error::Error for ParseKeyError {}
 
-        // get numerical value of this char
-        let num = self.to_num();
+impl FromStr for Key {
+    type Err = ParseKeyError;
 
-        // add the shift amount, and return as char
-        (num + amount).to_char()
+    /// Parse comma separated shifts, like `7,4,0,3` or `-1, 2`, or letters in either case, like
+    /// `HEADCRAB`, where `a` is a shift of 0 and a space is a shift of 26.
+    fn from_str(s: &str) -> Result<Self, ParseKeyError> {
+        if s.is_empty() {
+            return Err(ParseKeyError::Empty);
+        }
+        if s.chars()
+            .any(|chr| chr.is_ascii_digit() || chr == ',' || chr == '-')
+        {
+            return s
+                .split(',')
+                .map(|shift| {
+                    let shift = shift.trim();
+                    shift
+                        .parse()
+                        .map_err(|_| ParseKeyError::Shift(shift.to_string()))
+                })
+                .collect();
+        }
+        s.chars()
+            .map(|chr| match chr.to_ascii_lowercase().try_to_num() {
+                Some(num) => Ok(num as i8),
+                None => Err(ParseKeyError::Letter(chr)),
+            })
+            .collect()
     }
 }
 
-impl Shift for u8 {
-    fn shift(self, amount: i8) -> Self {
-        const ALPHALEN: u8 = ALPHABET.len() as u8;
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
-        // wrap the shift amount to within one alphabet length
-        let amount = amount.rem_euclid(ALPHALEN as i8) as u8;
+impl Deref for Key {
+    type Target = [i8];
+
+    fn deref(&self) -> &[i8] {
+        &self.0
+    }
+}
+
+impl DerefMut for Key {
+    fn deref_mut(&mut self) -> &mut [i8] {
+        &mut self.0
+    }
+}
+
+impl From<Vec<i8>> for Key {
+    fn from(shifts: Vec<i8>) -> Self {
+        Self(shifts)
+    }
+}
+
+impl FromIterator<i8> for Key {
+    fn from_iter<I: IntoIterator<Item = i8>>(shifts: I) -> Self {
+        Self(shifts.into_iter().collect())
+    }
+}
+
+/// Translate an entire &str to a Vec of bytes to more easily perform math.
+///
+/// # Panics
+///
+/// If `s` has a character outside the alphabet. See [`validate_ciphertext`] for text from outside
+/// the crate.
+#[allow(dead_code)]
+pub fn str_to_bytes(s: &str) -> Vec<u8> {
+    s.chars().map(|c| c.to_num()).collect()
+}
+
+/// Every character of a text that isn't in the alphabet, from [`validate_ciphertext`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputReport {
+    /// The character offset and character of each invalid symbol, in order
+    pub invalid: Vec<(usize, char)>,
+}
+
+impl InputReport {
+    /// How many invalid symbols are listed in messages, before the rest are only counted.
+    const LISTED: usize = 8;
+}
+
+impl fmt::Display for InputReport {
+    /// `'A' at offset 3, which is not in the alphabet`, or for more than one symbol,
+    /// `3 characters not in the alphabet: 'A' at offset 3, ...`, to follow "ciphertext has".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let [(offset, chr)] = self.invalid.as_slice() {
+            return write!(
+                f,
+                "{:?} at offset {}, which is not in the alphabet",
+                chr, offset
+            );
+        }
+        write!(f, "{} characters not in the alphabet: ", self.invalid.len())?;
+        for (i, (offset, chr)) in self.invalid.iter().take(Self::LISTED).enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{:?} at offset {}", chr, offset)?;
+        }
+        if self.invalid.len() > Self::LISTED {
+            write!(f, ", and {} more", self.invalid.len() - Self::LISTED)?;
+        }
+        if self
+            .invalid
+            .iter()
+            .all(|(_, chr)| chr.is_ascii_alphabetic())
+        {
+            f.write_str(" (only lowercase letters and spaces are)")?;
+        }
+        Ok(())
+    }
+}
 
-        // add the shift amount, and mod if needed
-        (self + amount).rem_euclid(ALPHALEN)
+impl std::error::Error for InputReport {}
+
+/// Translate text from outside the crate, like a pasted ciphertext, to bytes, or report every
+/// character that isn't in the alphabet and where it is.
+pub fn validate_ciphertext(s: &str) -> Result<Vec<u8>, InputReport> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut invalid = Vec::new();
+    for (offset, chr) in s.chars().enumerate() {
+        match chr.try_to_num() {
+            Some(num) => bytes.push(num),
+            None => invalid.push((offset, chr)),
+        }
     }
+    match invalid.is_empty() {
+        true => Ok(bytes),
+        false => Err(InputReport { invalid }),
+    }
+}
+
+/// Check that text from outside the crate, called `name` in the error, only has characters in the
+/// alphabet, before handing it to anything that converts it to bytes.
+pub fn check_alphabet(name: &str, text: &str) -> anyhow::Result<()> {
+    validate_ciphertext(text)
+        .map(drop)
+        .map_err(|report| anyhow::anyhow!("{} has {}", name, report))
+}
+
+/// Translate a slice of bytes back to a &str for presentation. For example, printing the recovered
+/// plaintext as a String.
+#[allow(dead_code)]
+pub fn bytes_to_str(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b.to_char()).collect()
 }
 
 #[cfg(test)]
@@ -96,30 +440,115 @@ mod tests {
         assert_eq!('a'.shift(-14 - 27 * 3), 'n');
         assert_eq!('a'.shift(-14 - 27 * 4), 'n');
         assert_eq!('a'.shift(-14 - 27 * 4), 'n');
+
+        // the whole range of shifts, from both ends of the alphabet
+        assert_eq!('a'.shift(i8::MIN), 'a'.shift(i8::MIN % 27));
+        assert_eq!(' '.shift(i8::MAX), ' '.shift(i8::MAX % 27));
+        assert_eq!(26u8.shift(i8::MAX), 26u8.shift(i8::MAX % 27));
     }
-}
 
-/// The key type defines what format various functions expect the key to be in.
-///
-/// A [`Vec`] of `i8` representing shift amounts that may be positive or negative.
-pub type Key = Vec<i8>;
+    #[test]
+    fn outside_the_alphabet() {
+        // bytes past the alphabet used to overflow or wrap into it
+        assert_eq!(230u8.checked_shift(26), None);
+        assert_eq!(27u8.checked_shift(0), None);
+        assert_eq!('A'.checked_shift(1), None);
+        assert_eq!(27u8.try_to_char(), None);
+        assert_eq!('\u{e9}'.try_to_num(), None);
+        assert_eq!(26u8.try_to_char(), Some(' '));
+    }
 
-/// Normalizes a key with arbitrary shift amounts the smallest positive shift amounts.
-pub fn reduce_key(key: &mut Key) {
-    for k in key.iter_mut() {
-        *k = k.rem_euclid(ALPHABET.len() as i8);
+    #[test]
+    fn validation() {
+        assert_eq!(validate_ciphertext("ab z"), Ok(vec![0, 1, 26, 25]));
+        assert_eq!(
+            validate_ciphertext("caf\u{e9} au lait\n")
+                .unwrap_err()
+                .invalid,
+            [(3, '\u{e9}'), (12, '\n')]
+        );
+        assert_eq!(
+            check_alphabet("ciphertext", "hi!").unwrap_err().to_string(),
+            "ciphertext has '!' at offset 2, which is not in the alphabet"
+        );
+        assert_eq!(
+            check_alphabet("ciphertext", "Hello World")
+                .unwrap_err()
+                .to_string(),
+            "ciphertext has 2 characters not in the alphabet: 'H' at offset 0, 'W' at offset 6 \
+             (only lowercase letters and spaces are)"
+        );
+        let report = validate_ciphertext("0123456789").unwrap_err();
+        assert_eq!(
+            report.to_string(),
+            "10 characters not in the alphabet: '0' at offset 0, '1' at offset 1, \
+             '2' at offset 2, '3' at offset 3, '4' at offset 4, '5' at offset 5, \
+             '6' at offset 6, '7' at offset 7, and 2 more"
+        );
     }
-}
 
-/// Translate an entire &str to a Vec of bytes to more easily perform math.
-#[allow(dead_code)]
-pub fn str_to_bytes(s: &str) -> Vec<u8> {
-    s.chars().map(|c| c.to_num()).collect()
-}
+    #[test]
+    fn keys() {
+        let key = Key::from(vec![1, -25, 1, 2]);
+        assert_eq!(key.minimal_period(), 2);
+        assert_eq!(key.canonical().0, [1, 2]);
+        assert_eq!(key, Key::from(vec![28, 2]));
+        assert_ne!(key, Key::from(vec![1, 2, 1]));
+        assert_eq!(Key::from(vec![0, 1, 26, -1]).as_letters(), "ab  ");
+        assert_eq!(Key::default().minimal_period(), 0);
 
-/// Translate a slice of bytes back to a &str for presentation. For example, printing the recovered
-/// plaintext as a String.
-#[allow(dead_code)]
-pub fn bytes_to_str(bytes: &[u8]) -> String {
-    bytes.iter().map(|&b| b.to_char()).collect()
+        let plaintext = str_to_bytes("the quick brown fox");
+        let ciphertext: Vec<u8> = (0..plaintext.len())
+            .map(|i| plaintext[i].shift(key[i % key.len()]))
+            .collect();
+        assert_eq!(Key::between(&ciphertext, &plaintext, 4), key);
+        assert_eq!(Key::fit(&ciphertext, &plaintext, 24).unwrap().0, [1, 2]);
+        assert_eq!(Key::fit(&ciphertext, &plaintext[1..], 24), None);
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let key: Key = "HEADCRAB".parse().unwrap();
+        assert_eq!(key.0, [7, 4, 0, 3, 2, 17, 0, 1]);
+        assert_eq!(key.to_string(), "headcrab");
+        assert_eq!(format!("{:#}", key), "7,4,0,3,2,17,0,1");
+
+        // both forms parse back, and spaces in letter keys are shifts
+        let key = Key::from(vec![7, -4, 26, 30]);
+        assert_eq!(key.to_string(), "hx d");
+        assert_eq!(key.to_string().parse::<Key>().unwrap(), key);
+        assert_eq!(format!("{:#}", key), "7,-4,26,30");
+        assert_eq!(format!("{:#}", key).parse::<Key>().unwrap().0, key.0);
+        assert_eq!("7, 4 ,0".parse::<Key>().unwrap().0, [7, 4, 0]);
+
+        assert_eq!("".parse::<Key>(), Err(ParseKeyError::Empty));
+        assert_eq!(
+            "7,,3".parse::<Key>(),
+            Err(ParseKeyError::Shift(String::new()))
+        );
+        assert_eq!("7,x".parse::<Key>(), Err(ParseKeyError::Shift("x".into())));
+        assert_eq!("head!".parse::<Key>(), Err(ParseKeyError::Letter('!')));
+    }
+
+    #[test]
+    fn passphrases() {
+        // the same passphrase always derives the same key
+        let key = Key::from_passphrase("correct horse battery staple");
+        assert_eq!(key, Key::from_passphrase("correct horse battery staple"));
+        assert_eq!(key.as_letters()[4..], *"ect horse battery st");
+        assert!(key.iter().all(|&shift| (0..27).contains(&shift)));
+
+        // letters past the end of the key still change it
+        let longer = Key::from_passphrase("correct horse battery staplers");
+        assert_eq!(longer[..4], key[..4]);
+        assert_ne!(longer[4..6], key[4..6]);
+        assert_eq!(longer[6..], key[6..]);
+
+        // and so does everything else
+        assert_ne!(
+            Key::from_passphrase("hunter2").0,
+            Key::from_passphrase("hunter3").0
+        );
+        assert_eq!(Key::from_passphrase("").len(), MAX_KEYLENGTH);
+    }
 }
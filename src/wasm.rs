@@ -0,0 +1,89 @@
+//! JavaScript bindings, for running the cracker in a browser.
+//!
+//! Build with `wasm-pack build --target web --no-default-features --features wasm`. Without the
+//! `parallel` feature nothing here starts a thread, and the dictionaries are compiled in, so the
+//! crack path needs no file system either.
+
+use crate::ciphers::schedulers::SchedulerKind;
+use crate::ciphers::{Cipher, Encryptor};
+use crate::crack::{CrackResult, Cracker};
+use crate::dict::Dictionary;
+use crate::rng::Rng;
+use crate::utils::{self, bytes_to_str, MAX_KEYLENGTH};
+
+use std::sync::OnceLock;
+use wasm_bindgen::prelude::*;
+
+/// A crack result as JavaScript sees it, with the plaintext as a string.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsCrackResult {
+    plaintext: String,
+    confidence: f64,
+    timed_out: bool,
+}
+
+impl From<CrackResult> for JsCrackResult {
+    fn from(result: CrackResult) -> Self {
+        Self {
+            plaintext: bytes_to_str(&result.plaintext),
+            confidence: result.confidence,
+            timed_out: result.timed_out,
+        }
+    }
+}
+
+fn to_js(result: CrackResult) -> Result<JsValue, JsError> {
+    serde_wasm_bindgen::to_value(&JsCrackResult::from(result)).map_err(JsError::from)
+}
+
+fn check_alphabet(name: &str, text: &str) -> Result<(), JsError> {
+    utils::check_alphabet(name, text).map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// Crack a ciphertext with the project dictionaries, returning
+/// `{ plaintext, confidence, timedOut }`.
+#[wasm_bindgen]
+pub fn crack(ciphertext: &str) -> Result<JsValue, JsError> {
+    // building the cracker takes longer than most cracks, so it is only done once
+    static CRACKER: OnceLock<Cracker> = OnceLock::new();
+
+    let ciphertext = ciphertext.trim();
+    check_alphabet("ciphertext", ciphertext)?;
+    to_js(CRACKER.get_or_init(Cracker::default).crack(ciphertext))
+}
+
+/// Crack a ciphertext made of words from `dictionary`, the bytes of a whitespace separated word
+/// list, returning `{ plaintext, confidence, timedOut }`.
+#[wasm_bindgen(js_name = crackWithDictionary)]
+pub fn crack_with_dictionary(ciphertext: &str, dictionary: &[u8]) -> Result<JsValue, JsError> {
+    let ciphertext = ciphertext.trim();
+    check_alphabet("ciphertext", ciphertext)?;
+
+    let mut words = String::from_utf8(dictionary.to_vec()).map_err(JsError::from)?;
+    let dict = Dictionary::from_string(&mut words);
+    if dict.words.is_empty() {
+        return Err(JsError::new("dictionary has no words"));
+    }
+    to_js(Cracker::new(&dict, std::iter::empty()).crack(ciphertext))
+}
+
+/// Encrypt a plaintext with `key`, a list of shifts, and a scheduler of the family named
+/// `scheduler` (like `"RepeatingKey"` or `"Aab"`). The scheduler's parameters and the random
+/// characters come from `seed`, so the same arguments always give the same ciphertext.
+#[wasm_bindgen]
+pub fn encrypt(plaintext: &str, key: &[i8], scheduler: &str, seed: u32) -> Result<String, JsError> {
+    check_alphabet("plaintext", plaintext)?;
+    if key.is_empty() || key.len() > MAX_KEYLENGTH {
+        return Err(JsError::new(&format!(
+            "key must be 1 to {} shifts long",
+            MAX_KEYLENGTH
+        )));
+    }
+    let kind = SchedulerKind::from_name(scheduler)
+        .ok_or_else(|| JsError::new(&format!("unknown scheduler {:?}", scheduler)))?;
+
+    let mut rng = Rng::from_seed(seed as u64);
+    let sched = kind.generate(&mut rng);
+    Ok(Encryptor::new(key.to_vec(), sched, rng).encrypt(plaintext))
+}